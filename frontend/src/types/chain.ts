@@ -12,6 +12,7 @@ export interface ChainStatusResponse {
 
 export interface BlockInfo {
   height: number;
+  version: number;
   hash: string;
   prev_hash: string;
   merkle_root: string;
@@ -19,6 +20,7 @@ export interface BlockInfo {
   timestamp: string;
   transactions: TransactionViewResponse[];
   size_bytes: number;
+  chainwork: string;
 }
 
 export interface ChainShowResponse {
@@ -40,3 +42,43 @@ export interface UtxosResponse {
   utxos: UtxoInfo[];
   total_value: number;
 }
+
+export interface DataSearchParams {
+  /** Hex prefix; empty matches every data output. */
+  prefix?: string;
+  limit?: number;
+}
+
+export interface DataOutputInfo {
+  height: number;
+  block_hash: string;
+  tx_id: string;
+  index: number;
+  data: string; // hex
+}
+
+export interface DataSearchResponse {
+  outputs: DataOutputInfo[];
+}
+
+export interface DeploymentInfo {
+  name: string;
+  bit: number;
+  start_time: number;
+  timeout: number;
+  state: 'defined' | 'started' | 'locked_in' | 'active' | 'failed';
+  /** First height of the period in which the current state began. */
+  since_height: number;
+  elapsed: number;
+  signalled: number;
+}
+
+export interface DeploymentsResponse {
+  /** Height of the next block, which the states apply to. */
+  height: number;
+  block_version: number;
+  window: number;
+  threshold: number;
+  period_start: number;
+  deployments: DeploymentInfo[];
+}
@@ -24,3 +24,12 @@ export interface MiningInfoResponse {
 export interface KeepMiningResponse {
   success: boolean;
 }
+
+export interface GenerateToAddressParams {
+  blocks: number;
+  address: string;
+}
+
+export interface GenerateToAddressResponse {
+  block_hashes: string[];
+}
@@ -8,17 +8,22 @@ export interface NodeStatusResponse {
   peers_connected: number;
   block_height: number;
   top_block_hash: string;
-  /** Hash of the local genesis block; identifies the network. Empty (or all-zero)
-   *  when the chain is empty. */
+  /** Hash of the network's hard-coded genesis block. */
   genesis_hash: string;
+  network: string; // main | test | regtest
 }
 
 export interface MempoolEntry {
   tx: TransactionViewResponse;
+  fee: number;
+  fee_per_byte: number;
 }
 
 export interface MempoolResponse {
   count: number;
+  size_bytes: number;
+  max_size_bytes: number;
+  min_fee_rate: number;
   transactions: MempoolEntry[];
 }
 
@@ -26,3 +31,19 @@ export interface NewPeerConnectionResponse {
   success: boolean;
   fail_message: string | null;
 }
+
+export interface EstimateFeeParams {
+  /** Blocks within which the transaction should confirm (1 to 25). */
+  target_blocks: number;
+}
+
+export interface EstimateFeeResponse {
+  target_blocks: number;
+  /** Fee per byte, or null until the node has seen enough blocks. */
+  fee_rate: number | null;
+}
+
+export interface SetMockTimeParams {
+  /** Unix time the node clock is frozen at; 0 restores the system clock. */
+  timestamp: number;
+}
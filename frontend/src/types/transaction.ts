@@ -4,13 +4,19 @@
 export interface TxInputInfo {
   prev_tx_id: string;
   output_index: number;
+  relative_lock: number;
+  sighash_type: string;
+  replaceable: boolean;
   signature: string;
   public_key: string;
+  unlock_script: string;
 }
 
 export interface TxOutputInfo {
   value: number;
   address: string;
+  script: string;
+  script_type: string;
 }
 
 export interface TransactionViewParams {
@@ -19,10 +25,58 @@ export interface TransactionViewParams {
 
 export interface TransactionViewResponse {
   id: string;
+  /** Hash including signatures and unlocking scripts; `id` leaves them out. */
+  wtxid: string;
   date: string;
   message?: string;
   inputs: TxInputInfo[];
   outputs: TxOutputInfo[];
   is_coinbase: boolean;
   size: number;
+  lock_time: number;
+}
+
+export interface HtlcPreimageParams {
+  tx_id: string;
+}
+
+export interface HtlcPreimageResponse {
+  preimage: string;
+  hash: string;
+}
+
+export interface RawTxInputParams {
+  tx_id: string;
+  index: number;
+  relative_lock?: number;
+  replaceable?: boolean;
+}
+
+export interface RawTxOutputParams {
+  address: string;
+  amount: number;
+}
+
+export interface TransactionCreateRawParams {
+  /** Hex raw transaction to extend, keeping its date, message and lock time. */
+  base?: string;
+  inputs?: RawTxInputParams[];
+  outputs?: RawTxOutputParams[];
+  message?: string;
+  lock_time?: number;
+}
+
+export interface TransactionCombineRawParams {
+  txs: string[];
+}
+
+export interface TransactionSendRawParams {
+  hex: string;
+}
+
+export interface RawTransactionResponse {
+  hex: string;
+  tx_id: string;
+  unsigned_inputs: number[];
+  complete: boolean;
 }
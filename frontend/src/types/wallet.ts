@@ -46,14 +46,41 @@ export interface WalletBalanceResponse {
   balance: number;
   utxo_count: number;
   utxos: UtxoInfo[];
+  immature_balance: number;
+  immature_utxos: UtxoInfo[];
 }
 
 export interface WalletSendParams {
   from: WalletAccessParams;
   to: string;
   amount: number;
+  /** Absolute fee; without it the fee follows fee_rate or conf_target. */
   fee?: number;
+  /** Fee per byte of the signed transaction. */
+  fee_rate?: number;
+  /** Blocks within which to confirm, priced by the fee estimator (default 6). */
+  conf_target?: number;
   message?: string;
+  lock_time?: number;
+  sighash?: string;
+  /** Defaults to true. */
+  replaceable?: boolean;
+}
+
+export interface WalletBumpFeeParams {
+  wallet: WalletAccessParams;
+  tx_id: string;
+  /** New total fee, taken from the transaction's change. */
+  fee: number;
+}
+
+export interface WalletSendDataParams {
+  from: WalletAccessParams;
+  /** Hex payload, at most 80 bytes. */
+  data: string;
+  fee?: number;
+  fee_rate?: number;
+  conf_target?: number;
 }
 
 export interface WalletSendResponse {
@@ -76,3 +103,96 @@ export interface GeneratedKey {
 export interface WalletGenerateKeysResponse {
   keys: GeneratedKey[];
 }
+
+export interface WalletMultisigCreateParams {
+  required: number;
+  public_keys: string[];
+}
+
+export interface WalletMultisigCreateResponse {
+  address: string;
+  redeem_script: string;
+  required: number;
+  public_keys: string[];
+}
+
+// Pending multisig spend; passed back to `wallet_multisig_sign` as returned.
+export type PartialTransaction = Record<string, unknown>;
+
+export interface WalletMultisigSpendParams {
+  wallet: WalletAccessParams;
+  redeem_script: string;
+  to: string;
+  amount: number;
+  /** Absolute fee; defaults to the mempool's minimum fee rate for the signed size. */
+  fee?: number;
+  message?: string;
+  sighash?: string;
+}
+
+export interface WalletMultisigSignParams {
+  wallet: WalletAccessParams;
+  pending: PartialTransaction;
+  broadcast?: boolean;
+}
+
+export interface WalletSignRawParams {
+  wallet: WalletAccessParams;
+  hex: string;
+  sighash?: string;
+}
+
+export interface WalletSignRawResponse {
+  hex: string;
+  tx_id: string;
+  signed_inputs: number[];
+  unsigned_inputs: number[];
+  complete: boolean;
+}
+
+export interface WalletMultisigSignResponse {
+  success: boolean;
+  pending?: PartialTransaction;
+  signatures_added: number;
+  missing_signatures: number;
+  complete: boolean;
+  tx_id?: string;
+  error?: string;
+}
+
+export interface WalletHtlcFundParams {
+  from: WalletAccessParams;
+  recipient: string;
+  amount: number;
+  hash: string; // hex SHA-256 of the secret
+  timeout: number; // block height, or Unix time from 500000000 on
+  /** Absolute fee; defaults to the mempool's minimum fee rate for the signed size. */
+  fee?: number;
+}
+
+export interface WalletHtlcFundResponse {
+  success: boolean;
+  tx_id?: string;
+  index?: number;
+  script?: string;
+  error?: string;
+}
+
+export interface WalletHtlcClaimParams {
+  wallet: WalletAccessParams;
+  tx_id: string;
+  index: number;
+  preimage: string;
+  to?: string;
+  /** Absolute fee; defaults to the mempool's minimum fee rate for the signed size. */
+  fee?: number;
+}
+
+export interface WalletHtlcRefundParams {
+  wallet: WalletAccessParams;
+  tx_id: string;
+  index: number;
+  to?: string;
+  /** Absolute fee; defaults to the mempool's minimum fee rate for the signed size. */
+  fee?: number;
+}
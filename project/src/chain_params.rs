@@ -0,0 +1,210 @@
+// Named network profiles: the chain identity and defaults of main, test and regtest.
+//
+// Every profile hard-codes its genesis block, so two nodes agree on the chain
+// they follow before any block is mined, and carries a network magic that
+// peers exchange in the version handshake. The active profile is chosen with
+// the `NETWORK` environment variable (see `globals::NETWORK`).
+use chrono::DateTime;
+use primitive_types::U256;
+
+use crate::globals::COIN;
+use crate::model::versionbits::Deployment;
+use crate::model::{Block, Script, Transaction, TxOutput, block::BlockHeader};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    Main,
+    Test,
+    Regtest,
+}
+
+impl Network {
+    pub const ALL: [Network; 3] = [Network::Main, Network::Test, Network::Regtest];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Network::Main => "main",
+            Network::Test => "test",
+            Network::Regtest => "regtest",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Network> {
+        Network::ALL
+            .into_iter()
+            .find(|network| network.name() == name.trim().to_lowercase())
+    }
+}
+
+/// Deployment without rules of its own, for exercising the version-bits
+/// machinery end to end.
+fn testdummy(start_time: i64, timeout: i64) -> Deployment {
+    Deployment::new("testdummy", 28, start_time, timeout).expect("valid deployment")
+}
+
+#[derive(Debug, Clone)]
+pub struct NetworkParams {
+    pub network: Network,
+    /// Sent in the version handshake; peers with a different magic are refused.
+    pub magic: [u8; 4],
+    /// Unix time of the genesis block.
+    pub genesis_timestamp: i64,
+    /// Proof-of-work nonce of the genesis block, found once and hard-coded.
+    pub genesis_nonce: u32,
+    /// Text carried by the genesis coinbase's `OP_RETURN` output.
+    pub genesis_message: &'static str,
+    /// Hex header hash of the genesis block, checked when the chain is loaded.
+    pub genesis_hash: &'static str,
+    /// Heights and hex hashes of blocks every chain of the network must contain.
+    pub checkpoints: Vec<(u64, &'static str)>,
+    /// Height and hex hash of the block below which scripts are not verified.
+    pub assume_valid: Option<(u64, &'static str)>,
+    pub initial_target: U256,
+    pub initial_block_reward: i64,
+    pub halving_interval: u64,
+    pub target_block_time_secs: u64,
+    /// Keep every block at `initial_target` instead of retargeting with LWMA.
+    pub pow_no_retargeting: bool,
+    /// Length of the periods over which version-bits signalling is counted.
+    pub version_bits_window: usize,
+    /// Signalling blocks within one window needed to lock a deployment in.
+    pub version_bits_threshold: usize,
+    pub deployments: Vec<Deployment>,
+    /// First two bytes of pay-to-pubkey-hash and pay-to-script-hash addresses.
+    pub pubkey_hash_prefix: [u8; 2],
+    pub script_hash_prefix: [u8; 2],
+    pub default_p2p_port: u16,
+    pub default_rpc_port: u16,
+    pub default_http_port: u16,
+    /// Directory for the chain, mempool, database and logs.
+    pub data_dir: &'static str,
+}
+
+impl NetworkParams {
+    pub fn for_network(network: Network) -> Self {
+        match network {
+            Network::Main => NetworkParams {
+                network,
+                magic: [0xca, 0x7a, 0x3e, 0xd9],
+                genesis_timestamp: 1_767_225_600,
+                genesis_nonce: 13_584,
+                genesis_message: "Caramuru main network genesis",
+                genesis_hash: "00001f4c61f7646752f966e6d40e29c5dbf503fe30f7a60b65d8530d12540095",
+                checkpoints: vec![(
+                    0,
+                    "00001f4c61f7646752f966e6d40e29c5dbf503fe30f7a60b65d8530d12540095",
+                )],
+                assume_valid: None,
+                // 12 leading zero bits.
+                initial_target: U256::MAX >> 19u32,
+                initial_block_reward: COIN,
+                halving_interval: 210_000,
+                target_block_time_secs: 10,
+                pow_no_retargeting: false,
+                version_bits_window: 2016,
+                version_bits_threshold: 1916,
+                deployments: vec![testdummy(1_798_761_600, 1_830_297_600)],
+                pubkey_hash_prefix: [0x00, 0x00],
+                script_hash_prefix: [0x00, 0x05],
+                default_p2p_port: 6000,
+                default_rpc_port: 7000,
+                default_http_port: 7001,
+                data_dir: "saved_files",
+            },
+            Network::Test => NetworkParams {
+                network,
+                magic: [0x0b, 0x11, 0x09, 0x07],
+                genesis_timestamp: 1_767_225_600,
+                genesis_nonce: 12_577,
+                genesis_message: "Caramuru test network genesis",
+                genesis_hash: "00000ad688e2c10c754a33097249ce53d54c7ee7a0f50cec3c7ab48279604719",
+                checkpoints: vec![(
+                    0,
+                    "00000ad688e2c10c754a33097249ce53d54c7ee7a0f50cec3c7ab48279604719",
+                )],
+                assume_valid: None,
+                initial_target: U256::MAX >> 19u32,
+                initial_block_reward: COIN,
+                halving_interval: 210_000,
+                target_block_time_secs: 10,
+                pow_no_retargeting: false,
+                version_bits_window: 2016,
+                version_bits_threshold: 1512,
+                deployments: vec![testdummy(1_767_225_600, 1_830_297_600)],
+                pubkey_hash_prefix: [0x00, 0x6f],
+                script_hash_prefix: [0x00, 0xc4],
+                default_p2p_port: 16000,
+                default_rpc_port: 17000,
+                default_http_port: 17001,
+                data_dir: "saved_files/testnet",
+            },
+            Network::Regtest => NetworkParams {
+                network,
+                magic: [0xfa, 0xbf, 0xb5, 0xda],
+                genesis_timestamp: 1_767_225_600,
+                genesis_nonce: 2,
+                genesis_message: "Caramuru regression test genesis",
+                genesis_hash: "2719c59f5800ac338389ea3635b03ff9343c40172d0dd64476b61c0536c1cb93",
+                checkpoints: Vec::new(),
+                assume_valid: None,
+                // Half of all hashes qualify, so blocks are found almost instantly.
+                initial_target: U256::MAX >> 1u32,
+                initial_block_reward: COIN,
+                halving_interval: 150,
+                target_block_time_secs: 10,
+                pow_no_retargeting: true,
+                version_bits_window: 144,
+                version_bits_threshold: 108,
+                deployments: vec![testdummy(0, i64::MAX)],
+                pubkey_hash_prefix: [0x00, 0x6f],
+                script_hash_prefix: [0x00, 0xc4],
+                default_p2p_port: 26000,
+                default_rpc_port: 27000,
+                default_http_port: 27001,
+                data_dir: "saved_files/regtest",
+            },
+        }
+    }
+
+    /// The network's first block. Its coinbase pays nothing and only carries
+    /// `genesis_message`, so the block depends on the profile alone.
+    pub fn genesis_block(&self) -> Block {
+        let timestamp = DateTime::from_timestamp(self.genesis_timestamp, 0)
+            .expect("genesis timestamp out of range")
+            .naive_utc();
+        let coinbase = Transaction {
+            inputs: Vec::new(),
+            outputs: vec![TxOutput::with_script(
+                0,
+                Script::new_data(self.genesis_message.as_bytes()),
+            )],
+            date: timestamp,
+            message: None,
+            lock_time: 0,
+        };
+        let mut block = Block {
+            header: BlockHeader {
+                version: BlockHeader::INITIAL_VERSION,
+                prev_block_hash: [0; 32],
+                merkle_root: [0; 32],
+                nonce: self.genesis_nonce,
+                timestamp,
+                target: self.initial_target,
+            },
+            transactions: vec![coinbase],
+        };
+        block.evaluate_merkle_root();
+        block
+    }
+
+    /// Regtest enables on-demand block generation and mock time.
+    pub fn is_regtest(&self) -> bool {
+        self.network == Network::Regtest
+    }
+
+    pub fn genesis_hash(&self) -> [u8; 32] {
+        let mut hash = [0; 32];
+        hex::decode_to_slice(self.genesis_hash, &mut hash).expect("invalid genesis hash");
+        hash
+    }
+}
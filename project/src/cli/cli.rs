@@ -54,6 +54,14 @@ pub enum NodeCommands {
 pub enum MineCommands {
     /// Mine a new block with pending transactions
     Block,
+
+    /// Instantly mine blocks paying to an address (regtest only)
+    Generate {
+        #[arg(short, long)]
+        blocks: u32,
+        #[arg(short, long)]
+        address: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -72,6 +80,16 @@ pub enum ChainCommands {
         #[arg(short, long, default_value_t = 20)]
         limit: u32,
     },
+
+    /// Show the block subsidy schedule and coin supply
+    Emission {
+        /// Height to report on (defaults to the chain tip)
+        #[arg(long)]
+        height: Option<u64>,
+    },
+
+    /// Show the version-bits state of every soft-fork deployment
+    Deployments,
 }
 
 #[derive(Subcommand)]
@@ -136,6 +154,10 @@ pub enum WalletCommands {
         /// Optional message
         #[arg(short, long)]
         message: Option<String>,
+
+        /// Block height (or Unix time from 500000000) before which it cannot be mined
+        #[arg(long)]
+        lock_time: Option<u64>,
     },
 
     /// Generate n keys from the miner's wallet
@@ -89,10 +89,16 @@ async fn startup_helper(client: &RpcClient) {
         }
     };
 
-    if response.block_height == 0 {
-        println!("⚠  Blockchain is empty. Use 'mine block' to create the genesis block.");
+    if response.block_height <= 1 {
+        println!(
+            "⚠  Blockchain only holds the {} network genesis block. Use 'mine block' to extend it.",
+            response.network
+        );
     } else {
-        println!("✓  Loaded blockchain with {} blocks", response.block_height);
+        println!(
+            "✓  Loaded {} network blockchain with {} blocks",
+            response.network, response.block_height
+        );
     }
 }
 
@@ -125,12 +131,16 @@ fn print_help() {
 
     println!("\n⛏  Mining:");
     println!("  mine block                 - Mine a new block with pending transactions");
+    println!("  mine generate --blocks <n> --address <address>");
+    println!("      - Instantly mine <n> blocks paying to <address> (regtest only)");
 
     println!("\n🔗 Blockchain:");
     println!("  chain show                 - Display the entire blockchain");
     println!("  chain status               - Show blockchain status");
     println!("  chain validate             - Validate blockchain integrity");
     println!("  chain utxos [--limit <n>]  - Show at most <n> UTXOs");
+    println!("  chain emission [--height <n>] - Show block subsidy and coin supply");
+    println!("  chain deployments          - Show soft-fork deployment states");
     println!("    - Limit is optional, default is 10");
 
     println!("\n💰 Wallet:");
@@ -157,9 +167,12 @@ fn print_help() {
     println!("    - Defaults to miner's wallet if no name is provided");
 
     println!(
-        "\n  wallet send [--from <name>] --to <addr> --amount <n> [--fee <fee>] [--message <msg>]"
+        "\n  wallet send [--from <name>] --to <addr> --amount <n> [--fee <fee>] [--message <msg>] [--lock-time <n>]"
     );
     println!("    - Send transaction (miner's wallet will send by default)");
+    println!(
+        "    - --lock-time: block height, or Unix time from 500000000 on, before which it cannot be mined"
+    );
 
     println!("\n  wallet generate-keys [--count <n>] [--name <name>] [--type <0|1>]");
     println!("    - Generate n keys (default: 5). ");
@@ -210,10 +223,21 @@ fn parse_command(input: &str) -> Result<Commands, String> {
 
         "mine" => {
             if parts.len() < 2 {
-                return Err("Usage: mine block".to_string());
+                return Err("Usage: mine <block|generate>".to_string());
             }
             match parts[1] {
                 "block" => Ok(Commands::Mine(MineCommands::Block)),
+                "generate" => {
+                    let blocks = parse_flag_value(&parts, "--blocks")
+                        .map_err(|_| "Number of blocks must be provided (--blocks)".to_string())?
+                        .parse::<u32>()
+                        .map_err(|_| {
+                            "Invalid blocks format. Must be a positive number".to_string()
+                        })?;
+                    let address = parse_flag_value(&parts, "--address")
+                        .map_err(|_| "Address must be provided (--address)".to_string())?;
+                    Ok(Commands::Mine(MineCommands::Generate { blocks, address }))
+                }
                 _ => Err(format!("Unknown mine command: {}", parts[1])),
             }
         }
@@ -237,6 +261,17 @@ fn parse_command(input: &str) -> Result<Commands, String> {
 
                     Ok(Commands::Chain(ChainCommands::Utxos { limit }))
                 }
+                "emission" => {
+                    let height = match parse_flag_value(&parts, "--height") {
+                        Ok(height_str) => Some(height_str.parse::<u64>().map_err(|_| {
+                            "Invalid height format. Must be a non-negative number".to_string()
+                        })?),
+                        Err(_) => None,
+                    };
+
+                    Ok(Commands::Chain(ChainCommands::Emission { height }))
+                }
+                "deployments" => Ok(Commands::Chain(ChainCommands::Deployments)),
                 _ => Err(format!("Unknown chain command: {}", parts[1])),
             }
         }
@@ -347,12 +382,19 @@ fn parse_command(input: &str) -> Result<Commands, String> {
                         Err(_) => None,
                     };
                     let message = parse_flag_value(&parts, "--message").ok();
+                    let lock_time = match parse_flag_value(&parts, "--lock-time") {
+                        Ok(lock_str) => Some(lock_str.parse::<u64>().map_err(|_| {
+                            "Invalid lock time format. Must be a positive number".to_string()
+                        })?),
+                        Err(_) => None,
+                    };
                     Ok(Commands::Wallet(WalletCommands::Send {
                         from,
                         to,
                         amount,
                         fee,
                         message,
+                        lock_time,
                     }))
                 }
 
@@ -558,6 +600,7 @@ async fn handle_node(command: NodeCommands, client: &RpcClient) {
             };
             println!("\n=== Node Status ===");
             println!("  Version: {}", status_response.version);
+            println!("  Network: {}", status_response.network);
             println!("  Peers Connected: {}", status_response.peers_connected);
             println!("  Advertised Address: {}", status_response.advertised_addr);
             println!("  Current Block Height: {}", status_response.block_height);
@@ -604,6 +647,19 @@ async fn handle_mine(command: MineCommands, client: &RpcClient) {
             println!("  Next Target: {:?}", mine_response.next_target);
             println!("  Next Difficulty: {:?}", mine_response.next_difficulty);
         }
+        MineCommands::Generate { blocks, address } => {
+            let response = match client.generate_to_address(blocks, &address).await {
+                Ok(res) => res,
+                Err(e) => {
+                    println!("✗ Block generation failed: {}", e);
+                    return;
+                }
+            };
+            println!("✓ Generated {} block(s):", response.block_hashes.len());
+            for hash in response.block_hashes {
+                println!("  {}", hash);
+            }
+        }
     }
 }
 
@@ -626,6 +682,7 @@ async fn handle_chain(command: ChainCommands, client: &RpcClient) {
             println!("\n=== Blockchain ===\n");
             for (i, block) in chain_show_response.blocks.iter().enumerate() {
                 println!("Block #{} Size: {} bytes", i, block.size_bytes);
+                println!("  Version: {:#010x}", block.version);
                 println!("  Hash: {}", block.hash);
                 println!("  Previous Hash: {}", block.prev_hash);
                 println!("  Merkle Root: {}", block.merkle_root);
@@ -762,6 +819,73 @@ async fn handle_chain(command: ChainCommands, client: &RpcClient) {
             );
             println!();
         }
+
+        ChainCommands::Emission { height } => {
+            let emission = match client.chain_emission(height).await {
+                Ok(res) => res,
+                Err(e) => {
+                    println!("✗ Could not retrieve emission schedule: {}", e);
+                    return;
+                }
+            };
+
+            println!("\n=== Emission ===");
+            println!("  Height: {}", emission.height);
+            println!("  Block Subsidy: {}", emission.block_subsidy);
+            println!("  Total Supply: {}", emission.total_supply);
+            println!("  Max Supply: {}", emission.max_supply);
+            println!("  Halving Interval: {}", emission.halving_interval);
+            match emission.next_halving_height {
+                Some(next) => println!("  Next Halving: block {}", next),
+                None => println!("  Next Halving: none (subsidy exhausted)"),
+            }
+
+            println!("\n  Era │ Start Height │ Subsidy      │ Supply at End");
+            for era in &emission.eras {
+                println!(
+                    "  {:>3} │ {:>12} │ {:>12} │ {}",
+                    era.era, era.start_height, era.block_subsidy, era.supply_at_end
+                );
+            }
+            println!();
+        }
+
+        ChainCommands::Deployments => {
+            let response = match client.chain_deployments().await {
+                Ok(res) => res,
+                Err(e) => {
+                    println!("✗ Could not retrieve deployments: {}", e);
+                    return;
+                }
+            };
+
+            println!("\n=== Deployments ===");
+            println!("  Next Block Height: {}", response.height);
+            println!("  Next Block Version: {:#010x}", response.block_version);
+            println!(
+                "  Period: starts at {}, {} of {} blocks needed",
+                response.period_start, response.threshold, response.window
+            );
+            if response.deployments.is_empty() {
+                println!("\n  No deployments configured");
+            }
+            for deployment in &response.deployments {
+                println!("\n  {} (bit {})", deployment.name, deployment.bit);
+                println!(
+                    "    State: {} since block {}",
+                    deployment.state, deployment.since_height
+                );
+                println!(
+                    "    Start Time: {}  Timeout: {}",
+                    deployment.start_time, deployment.timeout
+                );
+                println!(
+                    "    Signalled: {} of {} blocks this period",
+                    deployment.signalled, deployment.elapsed
+                );
+            }
+            println!();
+        }
     }
 }
 
@@ -905,6 +1029,13 @@ async fn handle_wallet(
             println!("\n=== Wallet Balance ===");
             println!("  UTXOs: {}", balance_response.utxos.len());
             println!("  Total Balance: {} coins", total);
+            if balance_response.immature_balance > 0 {
+                println!(
+                    "  Immature Balance: {} coins ({} coinbase UTXOs awaiting maturity)",
+                    balance_response.immature_balance,
+                    balance_response.immature_utxos.len()
+                );
+            }
 
             if !balance_response.utxos.is_empty() {
                 println!("\n  Details:");
@@ -921,6 +1052,7 @@ async fn handle_wallet(
             amount,
             fee,
             message,
+            lock_time,
         } => {
             let wallet = match resolve_wallet_by_name(from, loaded_wallets) {
                 Ok(w) => w,
@@ -930,7 +1062,10 @@ async fn handle_wallet(
                 }
             };
 
-            let send_response = match client.wallet_send(wallet, &to, amount, fee, message).await {
+            let send_response = match client
+                .wallet_send(wallet, &to, amount, fee, message, lock_time)
+                .await
+            {
                 Ok(res) => res,
                 Err(e) => {
                     println!("✗ Could not prepare transaction: {}", e);
@@ -3,11 +3,12 @@ use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
 
 use crate::daemon::types::{
-    ChainShowResponse, ChainStatusResponse, MempoolResponse, MineBlockResponse,
-    NewPeerConnectionResponse, NodeInitResponse, NodeStatusResponse, PeerDisconnectResponse,
-    PeersListResponse, RpcRequest, RpcResponse, SimpleSuccessResponse, TransactionViewResponse,
-    UtxosResponse, WalletAccessParams, WalletAddressResponse, WalletBalanceResponse,
-    WalletGenerateKeysResponse, WalletNewResponse, WalletSendResponse,
+    ChainShowResponse, ChainStatusResponse, DeploymentsResponse, EmissionResponse,
+    GenerateToAddressResponse, MempoolResponse, MineBlockResponse, NewPeerConnectionResponse,
+    NodeInitResponse, NodeStatusResponse, PeerDisconnectResponse, PeersListResponse, RpcRequest,
+    RpcResponse, SimpleSuccessResponse, TransactionViewResponse, UtxosResponse, WalletAccessParams,
+    WalletAddressResponse, WalletBalanceResponse, WalletGenerateKeysResponse, WalletNewResponse,
+    WalletSendResponse,
 };
 use crate::utils::LogEntry;
 
@@ -130,6 +131,18 @@ impl RpcClient {
         self.call("mine_block", serde_json::json!({})).await
     }
 
+    pub async fn generate_to_address(
+        &self,
+        blocks: u32,
+        address: &str,
+    ) -> Result<GenerateToAddressResponse, String> {
+        self.call(
+            "generate_to_address",
+            serde_json::json!({ "blocks": blocks, "address": address }),
+        )
+        .await
+    }
+
     // ========================================================================
     // Chain Methods
     // ========================================================================
@@ -150,6 +163,15 @@ impl RpcClient {
             .await
     }
 
+    pub async fn chain_deployments(&self) -> Result<DeploymentsResponse, String> {
+        self.call("chain_deployments", serde_json::json!({})).await
+    }
+
+    pub async fn chain_emission(&self, height: Option<u64>) -> Result<EmissionResponse, String> {
+        self.call("chain_emission", serde_json::json!({ "height": height }))
+            .await
+    }
+
     // ========================================================================
     // Transaction Methods
     // ========================================================================
@@ -223,6 +245,7 @@ impl RpcClient {
         amount: i64,
         fee: Option<i64>,
         message: Option<String>,
+        lock_time: Option<u64>,
     ) -> Result<WalletSendResponse, String> {
         self.call(
             "wallet_send",
@@ -231,7 +254,8 @@ impl RpcClient {
                 "to": to,
                 "amount": amount,
                 "fee": fee,
-                "message": message
+                "message": message,
+                "lock_time": lock_time
             }),
         )
         .await
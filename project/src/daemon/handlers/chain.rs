@@ -1,13 +1,16 @@
 // Chain Handlers
-use crate::daemon::types::rpc::INTERNAL_ERROR;
+use crate::daemon::types::rpc::{INTERNAL_ERROR, INVALID_PARAMS};
 use crate::daemon::types::{
-    BlockInfo, ChainShowResponse, ChainStatusResponse, RpcResponse, UtxoInfo, UtxosParams,
-    UtxosResponse,
+    BlockInfo, ChainShowResponse, ChainStatusResponse, DataOutputInfo, DataSearchParams,
+    DataSearchResponse, DeploymentInfo, DeploymentsResponse, EmissionEra, EmissionParams,
+    EmissionResponse, RpcResponse, UtxoInfo, UtxosParams, UtxosResponse,
 };
 use crate::db::repository::LedgerRepository;
+use crate::globals::CONSENSUS_RULES;
 use crate::model::get_node;
 use crate::security_utils::bytes_to_hex_string;
 use crate::utils::{format_difficulty, format_target_hex, transaction_model_to_view};
+use primitive_types::U256;
 
 pub async fn handle_chain_status(id: Option<u64>) -> RpcResponse {
     let node = get_node().await;
@@ -37,26 +40,32 @@ pub async fn handle_chain_status(id: Option<u64>) -> RpcResponse {
 pub async fn handle_chain_show(id: Option<u64>) -> RpcResponse {
     let node = get_node().await;
 
+    let mut chainwork = U256::zero();
     let blocks: Vec<BlockInfo> = node
         .blockchain
         .chain
         .iter()
         .enumerate()
-        .map(|(i, block)| BlockInfo {
-            height: i,
-            hash: bytes_to_hex_string(&block.header_hash()),
-            prev_hash: bytes_to_hex_string(&block.header.prev_block_hash),
-            merkle_root: bytes_to_hex_string(&block.header.merkle_root),
-            nonce: block.header.nonce,
-            timestamp: block.header.timestamp.to_string(),
-            target: format_target_hex(block.header.target),
-            difficulty: format_difficulty(block.header.target),
-            transactions: block
-                .transactions
-                .iter()
-                .map(|tx| transaction_model_to_view(tx))
-                .collect(),
-            size_bytes: block.size(),
+        .map(|(i, block)| {
+            chainwork = chainwork.saturating_add(block.work());
+            BlockInfo {
+                height: i,
+                version: block.header.version,
+                hash: bytes_to_hex_string(&block.header_hash()),
+                prev_hash: bytes_to_hex_string(&block.header.prev_block_hash),
+                merkle_root: bytes_to_hex_string(&block.header.merkle_root),
+                nonce: block.header.nonce,
+                timestamp: block.header.timestamp.to_string(),
+                target: format_target_hex(block.header.target),
+                difficulty: format_difficulty(block.header.target),
+                transactions: block
+                    .transactions
+                    .iter()
+                    .map(|tx| transaction_model_to_view(tx))
+                    .collect(),
+                size_bytes: block.size(),
+                chainwork: format_target_hex(chainwork),
+            }
         })
         .collect();
 
@@ -122,3 +131,130 @@ pub async fn handle_chain_utxos(id: Option<u64>, params: serde_json::Value) -> R
 
     RpcResponse::success(id, serde_json::to_value(response).unwrap())
 }
+
+pub async fn handle_chain_find_data(id: Option<u64>, params: serde_json::Value) -> RpcResponse {
+    let params: DataSearchParams = match serde_json::from_value(params) {
+        Ok(p) => p,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e)),
+    };
+    let prefix = match hex::decode(&params.prefix) {
+        Ok(prefix) => prefix,
+        Err(e) => {
+            return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid prefix hex: {}", e));
+        }
+    };
+
+    let node = get_node().await;
+    let outputs = node
+        .blockchain
+        .find_data_outputs(&prefix, params.limit as usize)
+        .into_iter()
+        .map(|output| DataOutputInfo {
+            height: output.height,
+            block_hash: bytes_to_hex_string(&output.block_hash),
+            tx_id: bytes_to_hex_string(&output.tx_id),
+            index: output.index,
+            data: hex::encode(&output.data),
+        })
+        .collect();
+
+    let response = DataSearchResponse { outputs };
+    RpcResponse::success(id, serde_json::to_value(response).unwrap())
+}
+
+pub async fn handle_chain_emission(id: Option<u64>, params: serde_json::Value) -> RpcResponse {
+    let params: EmissionParams = if params.is_null() {
+        EmissionParams::default()
+    } else {
+        match serde_json::from_value(params) {
+            Ok(p) => p,
+            Err(e) => {
+                return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e));
+            }
+        }
+    };
+
+    let chain_height = get_node().await.blockchain.height() as u64;
+    let (height, total_supply) = match params.height {
+        Some(h) => (h, CONSENSUS_RULES.total_supply_at(h)),
+        None if chain_height > 0 => {
+            let tip = chain_height - 1;
+            (tip, CONSENSUS_RULES.total_supply_at(tip))
+        }
+        None => (0, 0),
+    };
+
+    let interval = CONSENSUS_RULES.halving_interval.max(1);
+    let mut eras = Vec::new();
+    let mut era: u64 = 0;
+    while let Some(start_height) = era.checked_mul(interval) {
+        let block_subsidy = CONSENSUS_RULES.block_subsidy(start_height);
+        if block_subsidy == 0 {
+            break;
+        }
+        let end_height = start_height.saturating_add(interval - 1);
+        eras.push(EmissionEra {
+            era,
+            start_height,
+            end_height,
+            block_subsidy,
+            supply_at_end: CONSENSUS_RULES.total_supply_at(end_height),
+        });
+        era += 1;
+    }
+
+    let block_subsidy = CONSENSUS_RULES.block_subsidy(height);
+    let next_halving_height = if block_subsidy > 0 {
+        (height / interval + 1).checked_mul(interval)
+    } else {
+        None
+    };
+
+    let response = EmissionResponse {
+        height,
+        block_subsidy,
+        total_supply,
+        max_supply: CONSENSUS_RULES.max_supply(),
+        initial_block_reward: CONSENSUS_RULES.initial_block_reward,
+        halving_interval: CONSENSUS_RULES.halving_interval,
+        next_halving_height,
+        eras,
+    };
+
+    RpcResponse::success(id, serde_json::to_value(response).unwrap())
+}
+
+pub async fn handle_chain_deployments(id: Option<u64>) -> RpcResponse {
+    let node = get_node().await;
+    let blockchain = &node.blockchain;
+    let height = blockchain.height() as u64;
+    let window = CONSENSUS_RULES.version_bits_window;
+
+    let deployments = CONSENSUS_RULES
+        .deployments
+        .iter()
+        .map(|deployment| {
+            let status = blockchain.deployment_status(deployment);
+            DeploymentInfo {
+                name: deployment.name.clone(),
+                bit: deployment.bit,
+                start_time: deployment.start_time,
+                timeout: deployment.timeout,
+                state: status.state.name().to_string(),
+                since_height: status.since_height,
+                elapsed: status.elapsed,
+                signalled: status.signalled,
+            }
+        })
+        .collect();
+
+    let response = DeploymentsResponse {
+        height,
+        block_version: blockchain.compute_block_version(),
+        window,
+        threshold: CONSENSUS_RULES.version_bits_threshold,
+        period_start: height - height % window as u64,
+        deployments,
+    };
+    RpcResponse::success(id, serde_json::to_value(response).unwrap())
+}
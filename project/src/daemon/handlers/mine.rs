@@ -1,8 +1,12 @@
 // Mining Handlers
 use crate::daemon::types::rpc::{INTERNAL_ERROR, INVALID_PARAMS};
-use crate::daemon::types::{KeepMiningParams, MineBlockResponse, RpcResponse};
-use crate::model::miner::{build_mined_block_response, mine, submit_block};
+use crate::daemon::types::{
+    GenerateToAddressParams, GenerateToAddressResponse, KeepMiningParams, MineBlockResponse,
+    RpcResponse,
+};
+use crate::model::miner::{build_mined_block_response, generate_to_address, mine, submit_block};
 use crate::model::{get_node, get_node_mut};
+use crate::security_utils::bytes_to_hex_string;
 
 pub async fn handle_mine_block(id: Option<u64>) -> RpcResponse {
     let mined_block = match mine().await {
@@ -53,3 +57,20 @@ pub async fn handle_keep_mining(id: Option<u64>, params: serde_json::Value) -> R
     });
     RpcResponse::success(id, serde_json::to_value(response).unwrap())
 }
+
+pub async fn handle_generate_to_address(id: Option<u64>, params: serde_json::Value) -> RpcResponse {
+    let params: GenerateToAddressParams = match serde_json::from_value(params) {
+        Ok(p) => p,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e)),
+    };
+
+    match generate_to_address(params.blocks, &params.address).await {
+        Ok(hashes) => {
+            let response = GenerateToAddressResponse {
+                block_hashes: hashes.iter().map(|h| bytes_to_hex_string(h)).collect(),
+            };
+            RpcResponse::success(id, serde_json::to_value(response).unwrap())
+        }
+        Err(e) => RpcResponse::error(id, INVALID_PARAMS, e),
+    }
+}
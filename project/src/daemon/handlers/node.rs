@@ -1,12 +1,15 @@
 // Node Handlers
 use crate::daemon::types::{
-    INVALID_PARAMS, MempoolEntry, MempoolResponse, NewPeerConnectionParams,
-    NewPeerConnectionResponse, NodeStatusResponse, RpcResponse,
+    EstimateFeeParams, EstimateFeeResponse, INVALID_PARAMS, MempoolEntry, MempoolResponse,
+    NewPeerConnectionParams, NewPeerConnectionResponse, NodeStatusResponse, RpcResponse,
+    SetMockTimeParams, SimpleSuccessResponse,
 };
+use crate::globals::NETWORK;
+use crate::model::fee_estimator::MAX_CONFIRM_TARGET;
 use crate::model::{get_node, get_node_mut, node::restart_node};
 use crate::network::server::connect_to_new_peer;
 use crate::security_utils::bytes_to_hex_string;
-use crate::utils::transaction_model_to_view;
+use crate::utils::{get_current_timestamp, set_mock_time, transaction_model_to_view};
 
 pub async fn handle_node_status(id: Option<u64>) -> RpcResponse {
     let state = get_node().await.get_node_state().await;
@@ -18,6 +21,7 @@ pub async fn handle_node_status(id: Option<u64>) -> RpcResponse {
         block_height: state.version.height as usize,
         top_block_hash: bytes_to_hex_string(&state.version.top_hash),
         genesis_hash: bytes_to_hex_string(&state.version.genesis_hash),
+        network: NETWORK.network.name().to_string(),
     };
 
     RpcResponse::success(id, serde_json::to_value(response).unwrap())
@@ -38,17 +42,22 @@ pub async fn handle_node_init(id: Option<u64>) -> RpcResponse {
 
 pub async fn handle_node_mempool(id: Option<u64>) -> RpcResponse {
     let node = get_node().await;
+    let mempool = node.get_mempool();
 
-    let transactions: Vec<MempoolEntry> = node
-        .get_mempool()
+    let transactions: Vec<MempoolEntry> = mempool
         .iter()
         .map(|mtx| MempoolEntry {
             tx: transaction_model_to_view(&mtx.tx),
+            fee: mtx.calculate_fee(),
+            fee_per_byte: mtx.calculate_fee_per_byte(),
         })
         .collect();
 
     let response = MempoolResponse {
         count: transactions.len(),
+        size_bytes: mempool.total_size(),
+        max_size_bytes: mempool.limits().max_size,
+        min_fee_rate: mempool.min_fee_rate(get_current_timestamp().and_utc().timestamp()),
         transactions,
     };
 
@@ -78,3 +87,49 @@ pub async fn handle_node_connect(id: Option<u64>, params: serde_json::Value) ->
     };
     RpcResponse::success(id, serde_json::json!(response))
 }
+
+/// Regtest only: freezes the clock used for block timestamps and validation.
+pub async fn handle_node_estimate_fee(id: Option<u64>, params: serde_json::Value) -> RpcResponse {
+    let params: EstimateFeeParams = match serde_json::from_value(params) {
+        Ok(p) => p,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e)),
+    };
+    if params.target_blocks == 0 || params.target_blocks > MAX_CONFIRM_TARGET {
+        return RpcResponse::error(
+            id,
+            INVALID_PARAMS,
+            format!("target_blocks must be between 1 and {}", MAX_CONFIRM_TARGET),
+        );
+    }
+
+    let response = EstimateFeeResponse {
+        target_blocks: params.target_blocks,
+        fee_rate: get_node().await.estimate_fee(params.target_blocks),
+    };
+    RpcResponse::success(id, serde_json::to_value(response).unwrap())
+}
+
+pub async fn handle_set_mock_time(id: Option<u64>, params: serde_json::Value) -> RpcResponse {
+    let params: SetMockTimeParams = match serde_json::from_value(params) {
+        Ok(p) => p,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e)),
+    };
+    if !NETWORK.is_regtest() {
+        return RpcResponse::error(
+            id,
+            INVALID_PARAMS,
+            "Mock time is only available on regtest".to_string(),
+        );
+    }
+    if params.timestamp < 0 {
+        return RpcResponse::error(
+            id,
+            INVALID_PARAMS,
+            "Timestamp must not be negative".to_string(),
+        );
+    }
+
+    set_mock_time(params.timestamp);
+    let response = SimpleSuccessResponse { success: true };
+    RpcResponse::success(id, serde_json::to_value(response).unwrap())
+}
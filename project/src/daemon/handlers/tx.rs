@@ -1,10 +1,16 @@
 // Transaction Handlers
+use crate::daemon::handlers::wallet::submit_transaction;
 use crate::daemon::types::rpc::INVALID_PARAMS;
 use crate::daemon::types::{
-    RpcResponse, TransactionViewParams, TransactionViewResponse, TxInputInfo, TxOutputInfo,
+    HtlcPreimageParams, HtlcPreimageResponse, RawTransactionResponse, RpcResponse,
+    TransactionCombineRawParams, TransactionCreateRawParams, TransactionSendRawParams,
+    TransactionViewParams, WalletSendResponse,
 };
 use crate::db::repository::LedgerRepository;
-use crate::security_utils::bytes_to_hex_string;
+use crate::model::htlc::extract_preimage;
+use crate::model::{HDKey, MempoolTx, Transaction, TxInput, TxOutput, get_node};
+use crate::security_utils::{bytes_to_hex_string, hash_from_hex, sha256};
+use crate::utils::transaction_model_to_view;
 
 pub async fn handle_transaction_view(id: Option<u64>, params: serde_json::Value) -> RpcResponse {
     let params: TransactionViewParams = match serde_json::from_value(params) {
@@ -32,36 +38,7 @@ pub async fn handle_transaction_view(id: Option<u64>, params: serde_json::Value)
     let repo = LedgerRepository::new();
     match repo.get_transaction(&tx_id_bytes) {
         Ok(Some(tx)) => {
-            let inputs = tx
-                .inputs
-                .iter()
-                .map(|i| TxInputInfo {
-                    prev_tx_id: bytes_to_hex_string(&i.prev_tx_id),
-                    output_index: i.output_index,
-                    signature: i.signature.clone(),
-                    public_key: i.public_key.clone(),
-                })
-                .collect();
-
-            let outputs = tx
-                .outputs
-                .iter()
-                .map(|o| TxOutputInfo {
-                    value: o.value,
-                    address: o.address.clone(),
-                })
-                .collect();
-
-            let response = TransactionViewResponse {
-                id: bytes_to_hex_string(&tx.id()),
-                date: tx.date.to_string(),
-                message: tx.message.clone(),
-                inputs,
-                outputs,
-                is_coinbase: tx.is_coinbase(),
-                size: tx.size(),
-            };
-
+            let response = transaction_model_to_view(&tx);
             RpcResponse::success(id, serde_json::to_value(response).unwrap())
         }
         Ok(None) => RpcResponse::error(
@@ -76,3 +53,168 @@ pub async fn handle_transaction_view(id: Option<u64>, params: serde_json::Value)
         ),
     }
 }
+
+pub async fn handle_htlc_extract_preimage(
+    id: Option<u64>,
+    params: serde_json::Value,
+) -> RpcResponse {
+    let params: HtlcPreimageParams = match serde_json::from_value(params) {
+        Ok(p) => p,
+        Err(e) => {
+            return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e));
+        }
+    };
+    let tx_id = match hash_from_hex(&params.tx_id) {
+        Ok(tx_id) => tx_id,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+    };
+
+    match extract_preimage(&tx_id) {
+        Ok(preimage) => {
+            let response = HtlcPreimageResponse {
+                hash: bytes_to_hex_string(&sha256(&preimage)),
+                preimage: bytes_to_hex_string(&preimage),
+            };
+            RpcResponse::success(id, serde_json::to_value(response).unwrap())
+        }
+        Err(e) => RpcResponse::error(id, INVALID_PARAMS, e),
+    }
+}
+
+fn raw_transaction_response(tx: &Transaction) -> RawTransactionResponse {
+    let unsigned_inputs = tx.unsigned_inputs();
+    RawTransactionResponse {
+        hex: tx.to_hex(),
+        tx_id: bytes_to_hex_string(&tx.id()),
+        complete: unsigned_inputs.is_empty(),
+        unsigned_inputs,
+    }
+}
+
+/// Builds an unsigned raw transaction, or adds inputs and outputs to `base`.
+pub async fn handle_transaction_create_raw(
+    id: Option<u64>,
+    params: serde_json::Value,
+) -> RpcResponse {
+    let params: TransactionCreateRawParams = match serde_json::from_value(params) {
+        Ok(p) => p,
+        Err(e) => {
+            return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e));
+        }
+    };
+
+    let mut tx = match &params.base {
+        Some(base) => {
+            if params.message.is_some() || params.lock_time.is_some() {
+                return RpcResponse::error(
+                    id,
+                    INVALID_PARAMS,
+                    "Message and lock time come from the base transaction".to_string(),
+                );
+            }
+            match Transaction::from_hex(base) {
+                Ok(tx) => tx,
+                Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+            }
+        }
+        None => {
+            let mut tx = Transaction::new(Vec::new(), Vec::new(), params.message);
+            tx.lock_time = params.lock_time.unwrap_or(0);
+            tx
+        }
+    };
+    for input in params.inputs {
+        let prev_tx_id = match hash_from_hex(&input.tx_id) {
+            Ok(tx_id) => tx_id,
+            Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+        };
+        tx.inputs.push(TxInput {
+            relative_lock: input.relative_lock.unwrap_or(0),
+            replaceable: input.replaceable.unwrap_or(false),
+            ..TxInput::new(prev_tx_id, input.index)
+        });
+    }
+    for output in params.outputs {
+        if !HDKey::validate_address(&output.address) {
+            return RpcResponse::error(
+                id,
+                INVALID_PARAMS,
+                format!("Invalid output address: {}", output.address),
+            );
+        }
+        tx.outputs
+            .push(TxOutput::new(output.amount, output.address));
+    }
+
+    let response = raw_transaction_response(&tx);
+    RpcResponse::success(id, serde_json::to_value(response).unwrap())
+}
+
+pub async fn handle_transaction_combine_raw(
+    id: Option<u64>,
+    params: serde_json::Value,
+) -> RpcResponse {
+    let params: TransactionCombineRawParams = match serde_json::from_value(params) {
+        Ok(p) => p,
+        Err(e) => {
+            return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e));
+        }
+    };
+
+    let mut combined: Option<Transaction> = None;
+    for raw in &params.txs {
+        let tx = match Transaction::from_hex(raw) {
+            Ok(tx) => tx,
+            Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+        };
+        match combined.as_mut() {
+            Some(combined) => {
+                if let Err(e) = combined.combine(&tx) {
+                    return RpcResponse::error(id, INVALID_PARAMS, e);
+                }
+            }
+            None => combined = Some(tx),
+        }
+    }
+    let Some(combined) = combined else {
+        return RpcResponse::error(id, INVALID_PARAMS, "No transactions to combine".to_string());
+    };
+
+    let response = raw_transaction_response(&combined);
+    RpcResponse::success(id, serde_json::to_value(response).unwrap())
+}
+
+pub async fn handle_transaction_send_raw(
+    id: Option<u64>,
+    params: serde_json::Value,
+) -> RpcResponse {
+    let params: TransactionSendRawParams = match serde_json::from_value(params) {
+        Ok(p) => p,
+        Err(e) => {
+            return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e));
+        }
+    };
+    let tx = match Transaction::from_hex(&params.hex) {
+        Ok(tx) => tx,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+    };
+
+    let utxos = get_node().await.resolve_spent_utxos(&tx);
+    let result = match utxos {
+        Ok(utxos) => submit_transaction(MempoolTx::new(tx, utxos)).await,
+        Err(e) => Err(e),
+    };
+    let response = match result {
+        Ok(tx_id) => WalletSendResponse {
+            success: true,
+            tx_id: Some(tx_id),
+            error: None,
+        },
+        Err(e) => WalletSendResponse {
+            success: false,
+            tx_id: None,
+            error: Some(e),
+        },
+    };
+    RpcResponse::success(id, serde_json::to_value(response).unwrap())
+}
@@ -1,16 +1,28 @@
 // Wallet Handlers
 use crate::daemon::types::rpc::INVALID_PARAMS;
 use crate::daemon::types::{
-    GeneratedKey, RpcResponse, UtxoInfo, WalletAddressParams, WalletAddressResponse,
-    WalletBalanceParams, WalletBalanceResponse, WalletGenerateKeysParams,
-    WalletGenerateKeysResponse, WalletImportParams, WalletNewParams, WalletNewResponse,
-    WalletSendParams, WalletSendResponse,
+    GeneratedKey, RpcResponse, UtxoInfo, WalletAccessParams, WalletAddressParams,
+    WalletAddressResponse, WalletBalanceParams, WalletBalanceResponse, WalletBumpFeeParams,
+    WalletGenerateKeysParams, WalletGenerateKeysResponse, WalletHtlcClaimParams,
+    WalletHtlcFundParams, WalletHtlcFundResponse, WalletHtlcRefundParams, WalletImportParams,
+    WalletMultisigCreateParams, WalletMultisigCreateResponse, WalletMultisigSignParams,
+    WalletMultisigSignResponse, WalletMultisigSpendParams, WalletNewParams, WalletNewResponse,
+    WalletSendDataParams, WalletSendParams, WalletSendResponse, WalletSignRawParams,
+    WalletSignRawResponse,
 };
+use crate::db::repository::LedgerRepository;
+use crate::model::fee_estimator::{DEFAULT_CONFIRM_TARGET, MAX_CONFIRM_TARGET};
+use crate::model::htlc::HtlcParams;
+use crate::model::io::{SIGHASH_ALL, parse_sighash_type};
+use crate::model::multisig::{MultisigAddress, PartialTransaction};
 use crate::model::wallet::DerivationType;
-use crate::model::{TxOutput, Wallet, get_node_mut};
+use crate::model::{
+    HDKey, MempoolTx, Script, Transaction, TxOutput, UTXO, Wallet, get_node, get_node_mut,
+};
 use crate::security_utils::Keystore;
-use crate::security_utils::bytes_to_hex_string;
 use crate::security_utils::resolve_keystore_path;
+use crate::security_utils::{bytes_to_hex_string, hash_from_hex, load_public_key_from_hex};
+use crate::utils::get_current_timestamp;
 
 fn sandboxed_path(raw: &str, id: Option<u64>) -> Result<String, RpcResponse> {
     match resolve_keystore_path(raw) {
@@ -23,6 +35,50 @@ fn sandboxed_path(raw: &str, id: Option<u64>) -> Result<String, RpcResponse> {
     }
 }
 
+/// Sighash type named by an optional `sighash` parameter, `SIGHASH_ALL` when absent.
+fn sighash_param(raw: Option<&str>) -> Result<u8, String> {
+    raw.map_or(Ok(SIGHASH_ALL), parse_sighash_type)
+}
+
+/// Fee per byte for a payment, `None` when it names an absolute `fee`. Without
+/// `fee_rate`, the node's estimate for `conf_target` blocks is used, falling
+/// back to the lowest rate the mempool accepts.
+async fn fee_rate_param(
+    fee: Option<i64>,
+    fee_rate: Option<f64>,
+    conf_target: Option<u64>,
+) -> Result<Option<f64>, String> {
+    match (fee, fee_rate, conf_target) {
+        (Some(_), None, None) => Ok(None),
+        (Some(_), _, _) | (None, Some(_), Some(_)) => {
+            Err("Give only one of fee, fee_rate and conf_target".to_string())
+        }
+        (None, Some(fee_rate), None) => Ok(Some(fee_rate)),
+        (None, None, conf_target) => {
+            let target = conf_target.unwrap_or(DEFAULT_CONFIRM_TARGET);
+            if target == 0 || target > MAX_CONFIRM_TARGET {
+                return Err(format!(
+                    "conf_target must be between 1 and {}",
+                    MAX_CONFIRM_TARGET
+                ));
+            }
+            let node = get_node().await;
+            let now = get_current_timestamp().and_utc().timestamp();
+            Ok(Some(
+                node.estimate_fee(target)
+                    .unwrap_or_else(|| node.get_mempool().min_fee_rate(now)),
+            ))
+        }
+    }
+}
+
+/// Fee per byte the mempool currently requires, the default for spends
+/// without an explicit fee.
+async fn min_fee_rate() -> f64 {
+    let now = get_current_timestamp().and_utc().timestamp();
+    get_node().await.get_mempool().min_fee_rate(now)
+}
+
 pub async fn handle_import_wallet(id: Option<u64>, params: serde_json::Value) -> RpcResponse {
     let params: WalletImportParams = match serde_json::from_value(params) {
         Ok(p) => p,
@@ -151,23 +207,24 @@ pub async fn handle_wallet_balance(id: Option<u64>, params: serde_json::Value) -
         }
     };
 
-    let utxos = wallet.get_wallet_utxos();
+    let (utxos, immature_utxos) = wallet.get_wallet_utxos_by_maturity();
     let total: i64 = utxos.iter().map(|u| u.output.value).sum();
+    let immature_total: i64 = immature_utxos.iter().map(|u| u.output.value).sum();
 
-    let utxo_infos: Vec<UtxoInfo> = utxos
-        .iter()
-        .map(|u| UtxoInfo {
-            tx_id: bytes_to_hex_string(&u.tx_id),
-            index: u.index,
-            value: u.output.value,
-            address: u.output.address.clone(),
-        })
-        .collect();
+    let to_info = |u: &UTXO| UtxoInfo {
+        tx_id: bytes_to_hex_string(&u.tx_id),
+        index: u.index,
+        value: u.output.value,
+        address: u.output.address.clone(),
+    };
+    let utxo_infos: Vec<UtxoInfo> = utxos.iter().map(to_info).collect();
 
     let response = WalletBalanceResponse {
         balance: total,
         utxo_count: utxo_infos.len(),
         utxos: utxo_infos,
+        immature_balance: immature_total,
+        immature_utxos: immature_utxos.iter().map(to_info).collect(),
     };
 
     RpcResponse::success(id, serde_json::to_value(response).unwrap())
@@ -192,46 +249,210 @@ pub async fn handle_wallet_send(id: Option<u64>, params: serde_json::Value) -> R
             return RpcResponse::error(id, INVALID_PARAMS, "Wallet not found".to_string());
         }
     };
+    // Coins spent by pending transactions are skipped; pending change may be spent.
+    wallet.track_mempool(get_node().await.get_mempool());
 
-    let outputs = vec![TxOutput {
-        value: params.amount,
-        address: params.to,
-    }];
-
-    match wallet.send_tx(outputs, params.fee, params.message) {
-        Ok(mempool_tx) => {
-            let mut node = get_node_mut().await;
-            let tx_id = mempool_tx.tx.id();
-            match node.receive_transaction(mempool_tx) {
-                Ok(_) => {
-                    node.persist_mempool();
-                    crate::network::broadcast_new_tx_hash(tx_id, None);
-                    let response = WalletSendResponse {
-                        success: true,
-                        tx_id: Some(bytes_to_hex_string(&tx_id)),
-                        error: None,
-                    };
-                    RpcResponse::success(id, serde_json::to_value(response).unwrap())
-                }
-                Err(e) => {
-                    let response = WalletSendResponse {
-                        success: false,
-                        tx_id: None,
-                        error: Some(e.to_string()),
-                    };
-                    RpcResponse::success(id, serde_json::to_value(response).unwrap())
-                }
-            }
+    let sighash_type = match sighash_param(params.sighash.as_deref()) {
+        Ok(t) => t,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+    };
+    let fee_rate = match fee_rate_param(params.fee, params.fee_rate, params.conf_target).await {
+        Ok(r) => r,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+    };
+    let outputs = vec![TxOutput::new(params.amount, params.to)];
+
+    let build = |wallet: &mut Wallet, fee: i64| {
+        wallet
+            .send_tx(
+                outputs.clone(),
+                Some(fee),
+                params.message.clone(),
+                params.lock_time,
+                sighash_type,
+                params.replaceable.unwrap_or(true),
+            )
+            .map_err(|e| e.to_string())
+    };
+    let built = match fee_rate {
+        Some(fee_rate) => wallet.with_fee_rate(fee_rate, build),
+        None => build(&mut wallet, params.fee.unwrap_or(0)),
+    };
+    let result = match built {
+        Ok(mempool_tx) => submit_transaction(mempool_tx).await,
+        Err(e) => Err(e),
+    };
+    let response = match result {
+        Ok(tx_id) => WalletSendResponse {
+            success: true,
+            tx_id: Some(tx_id),
+            error: None,
+        },
+        Err(e) => WalletSendResponse {
+            success: false,
+            tx_id: None,
+            error: Some(e),
+        },
+    };
+    RpcResponse::success(id, serde_json::to_value(response).unwrap())
+}
+
+pub async fn handle_wallet_send_data(id: Option<u64>, params: serde_json::Value) -> RpcResponse {
+    let params: WalletSendDataParams = match serde_json::from_value(params) {
+        Ok(p) => p,
+        Err(e) => {
+            return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e));
         }
+    };
+    let data = match hex::decode(&params.data) {
+        Ok(data) => data,
         Err(e) => {
-            let response = WalletSendResponse {
-                success: false,
-                tx_id: None,
-                error: Some(e.to_string()),
-            };
-            RpcResponse::success(id, serde_json::to_value(response).unwrap())
+            return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid data hex: {}", e));
         }
-    }
+    };
+
+    let safe_path = match sandboxed_path(&params.from.key_path, id) {
+        Ok(p) => p,
+        Err(r) => return r,
+    };
+    let mut wallet = match Wallet::from_keystore_file(&safe_path, &params.from.password) {
+        Ok(w) => w,
+        Err(_) => {
+            return RpcResponse::error(id, INVALID_PARAMS, "Wallet not found".to_string());
+        }
+    };
+    // Coins spent by pending transactions are skipped; pending change may be spent.
+    wallet.track_mempool(get_node().await.get_mempool());
+
+    let fee_rate = match fee_rate_param(params.fee, params.fee_rate, params.conf_target).await {
+        Ok(r) => r,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+    };
+
+    let built = match fee_rate {
+        Some(fee_rate) => {
+            wallet.with_fee_rate(fee_rate, |wallet, fee| wallet.send_data(&data, Some(fee)))
+        }
+        None => wallet.send_data(&data, params.fee),
+    };
+    let result = match built {
+        Ok(mempool_tx) => submit_transaction(mempool_tx).await,
+        Err(e) => Err(e),
+    };
+    let response = match result {
+        Ok(tx_id) => WalletSendResponse {
+            success: true,
+            tx_id: Some(tx_id),
+            error: None,
+        },
+        Err(e) => WalletSendResponse {
+            success: false,
+            tx_id: None,
+            error: Some(e),
+        },
+    };
+    RpcResponse::success(id, serde_json::to_value(response).unwrap())
+}
+
+pub async fn handle_wallet_bump_fee(id: Option<u64>, params: serde_json::Value) -> RpcResponse {
+    let params: WalletBumpFeeParams = match serde_json::from_value(params) {
+        Ok(p) => p,
+        Err(e) => {
+            return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e));
+        }
+    };
+    let tx_id = match hash_from_hex(&params.tx_id) {
+        Ok(tx_id) => tx_id,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+    };
+
+    let safe_path = match sandboxed_path(&params.wallet.key_path, id) {
+        Ok(p) => p,
+        Err(r) => return r,
+    };
+    let wallet = match Wallet::from_keystore_file(&safe_path, &params.wallet.password) {
+        Ok(w) => w,
+        Err(_) => {
+            return RpcResponse::error(id, INVALID_PARAMS, "Wallet not found".to_string());
+        }
+    };
+
+    let original = get_node().await.get_mempool_tx_by_id(tx_id).cloned();
+    let result = match original {
+        Some(original) => match wallet.bump_fee(&original, params.fee) {
+            Ok(mempool_tx) => submit_transaction(mempool_tx).await,
+            Err(e) => Err(e),
+        },
+        None => Err("Transaction not found in mempool".to_string()),
+    };
+    let response = match result {
+        Ok(tx_id) => WalletSendResponse {
+            success: true,
+            tx_id: Some(tx_id),
+            error: None,
+        },
+        Err(e) => WalletSendResponse {
+            success: false,
+            tx_id: None,
+            error: Some(e),
+        },
+    };
+    RpcResponse::success(id, serde_json::to_value(response).unwrap())
+}
+
+pub async fn handle_wallet_sign_raw(id: Option<u64>, params: serde_json::Value) -> RpcResponse {
+    let params: WalletSignRawParams = match serde_json::from_value(params) {
+        Ok(p) => p,
+        Err(e) => {
+            return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e));
+        }
+    };
+    let mut tx = match Transaction::from_hex(&params.hex) {
+        Ok(tx) => tx,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+    };
+    let sighash_type = match sighash_param(params.sighash.as_deref()) {
+        Ok(t) => t,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+    };
+
+    let safe_path = match sandboxed_path(&params.wallet.key_path, id) {
+        Ok(p) => p,
+        Err(r) => return r,
+    };
+    let wallet = match Wallet::from_keystore_file(&safe_path, &params.wallet.password) {
+        Ok(w) => w,
+        Err(_) => {
+            return RpcResponse::error(id, INVALID_PARAMS, "Wallet not found".to_string());
+        }
+    };
+
+    let utxos = match get_node().await.resolve_spent_utxos(&tx) {
+        Ok(utxos) => utxos,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+    };
+    let signed_inputs = match wallet.sign_raw(&mut tx, &utxos, sighash_type) {
+        Ok(signed) => signed,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+    };
+    let unsigned_inputs = tx.unsigned_inputs();
+    let response = WalletSignRawResponse {
+        hex: tx.to_hex(),
+        tx_id: bytes_to_hex_string(&tx.id()),
+        signed_inputs,
+        complete: unsigned_inputs.is_empty(),
+        unsigned_inputs,
+    };
+    RpcResponse::success(id, serde_json::to_value(response).unwrap())
+}
+
+/// Adds a signed transaction to the mempool and announces it to peers.
+pub(crate) async fn submit_transaction(mempool_tx: MempoolTx) -> Result<String, String> {
+    let mut node = get_node_mut().await;
+    let tx_id = mempool_tx.tx.id();
+    node.receive_transaction(mempool_tx)?;
+    crate::network::broadcast_new_tx_hash(tx_id, None);
+    Ok(bytes_to_hex_string(&tx_id))
 }
 
 pub async fn handle_wallet_generate_keys(
@@ -284,3 +505,356 @@ pub async fn handle_wallet_generate_keys(
     let response = WalletGenerateKeysResponse { keys: generated };
     RpcResponse::success(id, serde_json::to_value(response).unwrap())
 }
+
+pub async fn handle_wallet_multisig_create(
+    id: Option<u64>,
+    params: serde_json::Value,
+) -> RpcResponse {
+    let params: WalletMultisigCreateParams = match serde_json::from_value(params) {
+        Ok(p) => p,
+        Err(e) => {
+            return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e));
+        }
+    };
+
+    let public_keys = match params
+        .public_keys
+        .iter()
+        .map(|k| load_public_key_from_hex(k))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(keys) => keys,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+    };
+    let multisig = match MultisigAddress::new(params.required, &public_keys) {
+        Ok(m) => m,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+    };
+
+    let response = WalletMultisigCreateResponse {
+        address: multisig.address,
+        redeem_script: multisig.redeem_script.to_hex(),
+        required: multisig.required,
+        public_keys: multisig
+            .public_keys
+            .iter()
+            .map(|k| bytes_to_hex_string(k))
+            .collect(),
+    };
+    RpcResponse::success(id, serde_json::to_value(response).unwrap())
+}
+
+pub async fn handle_wallet_multisig_spend(
+    id: Option<u64>,
+    params: serde_json::Value,
+) -> RpcResponse {
+    let params: WalletMultisigSpendParams = match serde_json::from_value(params) {
+        Ok(p) => p,
+        Err(e) => {
+            return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e));
+        }
+    };
+
+    let safe_path = match sandboxed_path(&params.wallet.key_path, id) {
+        Ok(p) => p,
+        Err(r) => return r,
+    };
+    let wallet = match Wallet::from_keystore_file(&safe_path, &params.wallet.password) {
+        Ok(w) => w,
+        Err(_) => {
+            return RpcResponse::error(id, INVALID_PARAMS, "Wallet not found".to_string());
+        }
+    };
+    let multisig = match Script::from_hex(&params.redeem_script)
+        .and_then(MultisigAddress::from_redeem_script)
+    {
+        Ok(m) => m,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+    };
+    let sighash_type = match sighash_param(params.sighash.as_deref()) {
+        Ok(t) => t,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+    };
+
+    let outputs = vec![TxOutput::new(params.amount, params.to)];
+    let pending = match params.fee {
+        Some(fee) => PartialTransaction::new_multisig_spend(
+            &multisig,
+            outputs,
+            fee,
+            params.message,
+            sighash_type,
+        ),
+        None => PartialTransaction::new_multisig_spend_with_fee_rate(
+            &multisig,
+            outputs,
+            min_fee_rate().await,
+            params.message,
+            sighash_type,
+        ),
+    };
+    let response = match pending {
+        Ok(pending) => sign_pending(&wallet, pending, false).await,
+        Err(e) => multisig_error_response(e),
+    };
+    RpcResponse::success(id, serde_json::to_value(response).unwrap())
+}
+
+pub async fn handle_wallet_multisig_sign(
+    id: Option<u64>,
+    params: serde_json::Value,
+) -> RpcResponse {
+    let params: WalletMultisigSignParams = match serde_json::from_value(params) {
+        Ok(p) => p,
+        Err(e) => {
+            return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e));
+        }
+    };
+
+    let safe_path = match sandboxed_path(&params.wallet.key_path, id) {
+        Ok(p) => p,
+        Err(r) => return r,
+    };
+    let wallet = match Wallet::from_keystore_file(&safe_path, &params.wallet.password) {
+        Ok(w) => w,
+        Err(_) => {
+            return RpcResponse::error(id, INVALID_PARAMS, "Wallet not found".to_string());
+        }
+    };
+
+    let response = sign_pending(&wallet, params.pending, params.broadcast.unwrap_or(true)).await;
+    RpcResponse::success(id, serde_json::to_value(response).unwrap())
+}
+
+/// Adds the wallet's signatures and, when `broadcast` is set and the spend is
+/// complete, finalizes it and submits it to the mempool.
+async fn sign_pending(
+    wallet: &Wallet,
+    mut pending: PartialTransaction,
+    broadcast: bool,
+) -> WalletMultisigSignResponse {
+    let signatures_added = match wallet.sign_partial(&mut pending) {
+        Ok(n) => n,
+        Err(e) => return multisig_error_response(e),
+    };
+    let complete = pending.is_complete();
+    let mut response = WalletMultisigSignResponse {
+        success: true,
+        signatures_added,
+        missing_signatures: pending.missing_signatures(),
+        complete,
+        tx_id: None,
+        error: None,
+        pending: None,
+    };
+    if complete && broadcast {
+        match pending.finalize() {
+            Ok(mempool_tx) => match submit_transaction(mempool_tx).await {
+                Ok(tx_id) => response.tx_id = Some(tx_id),
+                Err(e) => {
+                    response.success = false;
+                    response.error = Some(e);
+                }
+            },
+            Err(e) => {
+                response.success = false;
+                response.error = Some(e);
+            }
+        }
+    }
+    response.pending = Some(pending);
+    response
+}
+
+fn multisig_error_response(error: String) -> WalletMultisigSignResponse {
+    WalletMultisigSignResponse {
+        success: false,
+        pending: None,
+        signatures_added: 0,
+        missing_signatures: 0,
+        complete: false,
+        tx_id: None,
+        error: Some(error),
+    }
+}
+
+pub async fn handle_wallet_htlc_fund(id: Option<u64>, params: serde_json::Value) -> RpcResponse {
+    let params: WalletHtlcFundParams = match serde_json::from_value(params) {
+        Ok(p) => p,
+        Err(e) => {
+            return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e));
+        }
+    };
+
+    let safe_path = match sandboxed_path(&params.from.key_path, id) {
+        Ok(p) => p,
+        Err(r) => return r,
+    };
+    let mut wallet = match Wallet::from_keystore_file(&safe_path, &params.from.password) {
+        Ok(w) => w,
+        Err(_) => {
+            return RpcResponse::error(id, INVALID_PARAMS, "Wallet not found".to_string());
+        }
+    };
+    // Coins spent by pending transactions are skipped; pending change may be spent.
+    wallet.track_mempool(get_node().await.get_mempool());
+    let hash = match hash_from_hex(&params.hash) {
+        Ok(hash) => hash,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+    };
+    let Some(recipient_pubkey_hash) = HDKey::pubkey_hash_from_address(&params.recipient) else {
+        return RpcResponse::error(
+            id,
+            INVALID_PARAMS,
+            "Recipient must be a pay-to-pubkey-hash address".to_string(),
+        );
+    };
+    // Refunds go to a fresh key of the funding wallet.
+    let refund_address = wallet.get_receive_addr();
+    let Some(sender_pubkey_hash) = HDKey::pubkey_hash_from_address(&refund_address) else {
+        return RpcResponse::error(id, INVALID_PARAMS, "Invalid refund address".to_string());
+    };
+
+    let script = Script::new_htlc(&HtlcParams {
+        hash,
+        recipient_pubkey_hash,
+        sender_pubkey_hash,
+        timeout: params.timeout,
+    });
+    let outputs = vec![TxOutput::with_script(params.amount, script.clone())];
+
+    let built = match params.fee {
+        Some(fee) => wallet
+            .send_tx(outputs, Some(fee), None, None, SIGHASH_ALL, false)
+            .map_err(|e| e.to_string()),
+        None => wallet.with_fee_rate(min_fee_rate().await, |wallet, fee| {
+            wallet
+                .send_tx(outputs.clone(), Some(fee), None, None, SIGHASH_ALL, false)
+                .map_err(|e| e.to_string())
+        }),
+    };
+    let result = match built {
+        Ok(mempool_tx) => submit_transaction(mempool_tx).await,
+        Err(e) => Err(e),
+    };
+    let response = match result {
+        Ok(tx_id) => WalletHtlcFundResponse {
+            success: true,
+            tx_id: Some(tx_id),
+            // send_tx keeps the requested outputs first, ahead of any change.
+            index: Some(0),
+            script: Some(script.to_hex()),
+            error: None,
+        },
+        Err(e) => WalletHtlcFundResponse {
+            success: false,
+            tx_id: None,
+            index: None,
+            script: None,
+            error: Some(e),
+        },
+    };
+    RpcResponse::success(id, serde_json::to_value(response).unwrap())
+}
+
+pub async fn handle_wallet_htlc_claim(id: Option<u64>, params: serde_json::Value) -> RpcResponse {
+    let params: WalletHtlcClaimParams = match serde_json::from_value(params) {
+        Ok(p) => p,
+        Err(e) => {
+            return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e));
+        }
+    };
+    let preimage = match hex::decode(&params.preimage) {
+        Ok(preimage) => preimage,
+        Err(e) => {
+            return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid preimage hex: {}", e));
+        }
+    };
+    spend_htlc(
+        id,
+        &params.wallet,
+        &params.tx_id,
+        params.index,
+        Some(&preimage),
+        params.to,
+        params.fee,
+    )
+    .await
+}
+
+pub async fn handle_wallet_htlc_refund(id: Option<u64>, params: serde_json::Value) -> RpcResponse {
+    let params: WalletHtlcRefundParams = match serde_json::from_value(params) {
+        Ok(p) => p,
+        Err(e) => {
+            return RpcResponse::error(id, INVALID_PARAMS, format!("Invalid params: {}", e));
+        }
+    };
+    spend_htlc(
+        id,
+        &params.wallet,
+        &params.tx_id,
+        params.index,
+        None,
+        params.to,
+        params.fee,
+    )
+    .await
+}
+
+/// Claims (with a preimage) or refunds (without one) the HTLC output
+/// `tx_id:index` and submits the spend to the mempool.
+async fn spend_htlc(
+    id: Option<u64>,
+    access: &WalletAccessParams,
+    tx_id: &str,
+    index: usize,
+    preimage: Option<&[u8]>,
+    to: Option<String>,
+    fee: Option<i64>,
+) -> RpcResponse {
+    let safe_path = match sandboxed_path(&access.key_path, id) {
+        Ok(p) => p,
+        Err(r) => return r,
+    };
+    let mut wallet = match Wallet::from_keystore_file(&safe_path, &access.password) {
+        Ok(w) => w,
+        Err(_) => {
+            return RpcResponse::error(id, INVALID_PARAMS, "Wallet not found".to_string());
+        }
+    };
+    let tx_id = match hash_from_hex(tx_id) {
+        Ok(tx_id) => tx_id,
+        Err(e) => return RpcResponse::error(id, INVALID_PARAMS, e),
+    };
+    let utxo = match LedgerRepository::new().get_utxo(tx_id, index) {
+        Ok(utxo) => utxo,
+        Err(_) => {
+            return RpcResponse::error(id, INVALID_PARAMS, "HTLC output not found".to_string());
+        }
+    };
+    let to = to.unwrap_or_else(|| wallet.get_receive_addr());
+
+    let built = match fee {
+        Some(fee) => wallet.spend_htlc(utxo, preimage, to, fee),
+        None => wallet.with_fee_rate(min_fee_rate().await, |wallet, fee| {
+            wallet.spend_htlc(utxo.clone(), preimage, to.clone(), fee)
+        }),
+    };
+    let result = match built {
+        Ok(mempool_tx) => submit_transaction(mempool_tx).await,
+        Err(e) => Err(e),
+    };
+    let response = match result {
+        Ok(tx_id) => WalletSendResponse {
+            success: true,
+            tx_id: Some(tx_id),
+            error: None,
+        },
+        Err(e) => WalletSendResponse {
+            success: false,
+            tx_id: None,
+            error: Some(e),
+        },
+    };
+    RpcResponse::success(id, serde_json::to_value(response).unwrap())
+}
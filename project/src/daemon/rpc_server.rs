@@ -1,21 +1,28 @@
 // JSON-RPC server for the daemon
 use crate::daemon::handlers::chain::{
-    handle_chain_show, handle_chain_status, handle_chain_utxos, handle_chain_validate,
-    handle_node_save,
+    handle_chain_deployments, handle_chain_emission, handle_chain_find_data, handle_chain_show,
+    handle_chain_status, handle_chain_utxos, handle_chain_validate, handle_node_save,
 };
 use crate::daemon::handlers::logs::handle_get_logs;
 use crate::daemon::handlers::mine::{
-    handle_get_last_mined_block, handle_get_mining_info, handle_keep_mining, handle_mine_block,
+    handle_generate_to_address, handle_get_last_mined_block, handle_get_mining_info,
+    handle_keep_mining, handle_mine_block,
 };
 use crate::daemon::handlers::node::{
-    handle_node_clear_mempool, handle_node_connect, handle_node_init, handle_node_mempool,
-    handle_node_status,
+    handle_node_clear_mempool, handle_node_connect, handle_node_estimate_fee, handle_node_init,
+    handle_node_mempool, handle_node_status, handle_set_mock_time,
 };
 use crate::daemon::handlers::peer::{handle_peer_disconnect, handle_peers_list};
-use crate::daemon::handlers::tx::handle_transaction_view;
+use crate::daemon::handlers::tx::{
+    handle_htlc_extract_preimage, handle_transaction_combine_raw, handle_transaction_create_raw,
+    handle_transaction_send_raw, handle_transaction_view,
+};
 use crate::daemon::handlers::wallet::{
     handle_import_wallet, handle_new_wallet, handle_wallet_address, handle_wallet_balance,
-    handle_wallet_generate_keys, handle_wallet_send,
+    handle_wallet_bump_fee, handle_wallet_generate_keys, handle_wallet_htlc_claim,
+    handle_wallet_htlc_fund, handle_wallet_htlc_refund, handle_wallet_multisig_create,
+    handle_wallet_multisig_sign, handle_wallet_multisig_spend, handle_wallet_send,
+    handle_wallet_send_data, handle_wallet_sign_raw,
 };
 use crate::daemon::types::rpc::{INVALID_REQUEST, METHOD_NOT_FOUND, PARSE_ERROR};
 use crate::daemon::types::{RpcRequest, RpcResponse};
@@ -120,6 +127,8 @@ pub async fn process_request(request_str: &str) -> RpcResponse {
         "node_clear_mempool" => handle_node_clear_mempool(request.id).await,
         "node_save" => handle_node_save(request.id).await,
         "node_connect" => handle_node_connect(request.id, request.params).await,
+        "node_estimate_fee" => handle_node_estimate_fee(request.id, request.params).await,
+        "set_mock_time" => handle_set_mock_time(request.id, request.params).await,
 
         // Peer methods
         "peers_list" => handle_peers_list(request.id).await,
@@ -130,12 +139,16 @@ pub async fn process_request(request_str: &str) -> RpcResponse {
         "mine_info" => handle_get_mining_info(request.id).await,
         "mine_last_block" => handle_get_last_mined_block(request.id).await,
         "mine_keep_mining" => handle_keep_mining(request.id, request.params).await,
+        "generate_to_address" => handle_generate_to_address(request.id, request.params).await,
 
         // Chain methods
         "chain_status" => handle_chain_status(request.id).await,
         "chain_show" => handle_chain_show(request.id).await,
         "chain_validate" => handle_chain_validate(request.id).await,
         "chain_utxos" => handle_chain_utxos(request.id, request.params).await,
+        "chain_emission" => handle_chain_emission(request.id, request.params).await,
+        "chain_deployments" => handle_chain_deployments(request.id).await,
+        "chain_find_data" => handle_chain_find_data(request.id, request.params).await,
 
         // Wallet methods
         "wallet_import" => handle_import_wallet(request.id, request.params).await,
@@ -143,10 +156,25 @@ pub async fn process_request(request_str: &str) -> RpcResponse {
         "wallet_address" => handle_wallet_address(request.id, request.params).await,
         "wallet_balance" => handle_wallet_balance(request.id, request.params).await,
         "wallet_send" => handle_wallet_send(request.id, request.params).await,
+        "wallet_send_data" => handle_wallet_send_data(request.id, request.params).await,
+        "wallet_bump_fee" => handle_wallet_bump_fee(request.id, request.params).await,
         "wallet_generate_keys" => handle_wallet_generate_keys(request.id, request.params).await,
+        "wallet_multisig_create" => handle_wallet_multisig_create(request.id, request.params).await,
+        "wallet_multisig_spend" => handle_wallet_multisig_spend(request.id, request.params).await,
+        "wallet_multisig_sign" => handle_wallet_multisig_sign(request.id, request.params).await,
+        "wallet_htlc_fund" => handle_wallet_htlc_fund(request.id, request.params).await,
+        "wallet_htlc_claim" => handle_wallet_htlc_claim(request.id, request.params).await,
+        "wallet_htlc_refund" => handle_wallet_htlc_refund(request.id, request.params).await,
+        "wallet_sign_raw" => handle_wallet_sign_raw(request.id, request.params).await,
 
         // Transaction methods
         "transaction_view" => handle_transaction_view(request.id, request.params).await,
+        "htlc_extract_preimage" => handle_htlc_extract_preimage(request.id, request.params).await,
+        "transaction_create_raw" => handle_transaction_create_raw(request.id, request.params).await,
+        "transaction_combine_raw" => {
+            handle_transaction_combine_raw(request.id, request.params).await
+        }
+        "transaction_send_raw" => handle_transaction_send_raw(request.id, request.params).await,
 
         // Logs methods
         "get_logs" => handle_get_logs(request.id, request.params).await,
@@ -14,6 +14,7 @@ pub struct ChainStatusResponse {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct BlockInfo {
     pub height: usize,
+    pub version: u32,
     pub hash: String,
     pub prev_hash: String,
     pub merkle_root: String,
@@ -23,6 +24,8 @@ pub struct BlockInfo {
     pub size_bytes: usize,
     pub target: String,
     pub difficulty: String,
+    /// Cumulative work of the chain up to and including this block.
+    pub chainwork: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -53,3 +56,85 @@ pub struct UtxosResponse {
     pub utxos: Vec<UtxoInfo>,
     pub total_value: i64,
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DataSearchParams {
+    /// hex prefix the payload must start with; empty matches every data output
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DataOutputInfo {
+    pub height: usize,
+    pub block_hash: String,
+    pub tx_id: String,
+    pub index: usize,
+    /// hex payload
+    pub data: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DataSearchResponse {
+    pub outputs: Vec<DataOutputInfo>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct EmissionParams {
+    /// Height to report on. Defaults to the current chain tip.
+    pub height: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EmissionEra {
+    pub era: u64,
+    pub start_height: u64,
+    pub end_height: u64,
+    pub block_subsidy: i64,
+    /// Total coins issued once the last block of this era is mined.
+    pub supply_at_end: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EmissionResponse {
+    pub height: u64,
+    pub block_subsidy: i64,
+    /// Coins issued by blocks `0..=height`, excluding fees.
+    pub total_supply: i64,
+    pub max_supply: i64,
+    pub initial_block_reward: i64,
+    pub halving_interval: u64,
+    pub next_halving_height: Option<u64>,
+    pub eras: Vec<EmissionEra>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeploymentInfo {
+    pub name: String,
+    pub bit: u8,
+    pub start_time: i64,
+    pub timeout: i64,
+    /// defined | started | locked_in | active | failed
+    pub state: String,
+    /// First height of the period in which the current state began.
+    pub since_height: u64,
+    /// Blocks of the current period mined so far.
+    pub elapsed: u64,
+    /// Blocks of the current period signalling the deployment.
+    pub signalled: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DeploymentsResponse {
+    /// Height of the next block, which the states apply to.
+    pub height: u64,
+    /// Header version the node mines the next block with.
+    pub block_version: u32,
+    pub window: usize,
+    pub threshold: usize,
+    /// First height of the current signalling period.
+    pub period_start: u64,
+    pub deployments: Vec<DeploymentInfo>,
+}
@@ -42,3 +42,16 @@ pub struct MiningInfoResponse {
 pub struct KeepMiningParams {
     pub keep_mining: bool,
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GenerateToAddressParams {
+    /// number of blocks to mine
+    pub blocks: u32,
+    /// address receiving the coinbase of every block
+    pub address: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GenerateToAddressResponse {
+    pub block_hashes: Vec<String>,
+}
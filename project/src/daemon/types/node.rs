@@ -11,16 +11,26 @@ pub struct NodeStatusResponse {
     pub block_height: usize,
     pub top_block_hash: String,
     pub genesis_hash: String,
+    /// Name of the network profile: main, test or regtest.
+    pub network: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MempoolEntry {
     pub tx: TransactionViewResponse,
+    pub fee: i64,
+    /// Fee divided by the serialized transaction size.
+    pub fee_per_byte: f64,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MempoolResponse {
     pub count: usize,
+    /// Serialized size of all mempool transactions, in bytes.
+    pub size_bytes: usize,
+    pub max_size_bytes: usize,
+    /// Lowest fee per byte a new transaction currently needs.
+    pub min_fee_rate: f64,
     pub transactions: Vec<MempoolEntry>,
 }
 
@@ -45,3 +55,22 @@ pub struct NewPeerConnectionResponse {
     pub success: bool,
     pub fail_message: Option<String>,
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EstimateFeeParams {
+    /// blocks within which the transaction should confirm (1 to 25)
+    pub target_blocks: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EstimateFeeResponse {
+    pub target_blocks: u64,
+    /// Fee per byte, or null until the node has seen enough blocks.
+    pub fee_rate: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetMockTimeParams {
+    /// Unix time the node clock is frozen at; 0 restores the system clock
+    pub timestamp: i64,
+}
@@ -10,25 +10,34 @@ pub struct TransactionViewParams {
 pub struct TxInputInfo {
     pub prev_tx_id: String,
     pub output_index: usize,
+    pub relative_lock: u32,
+    pub sighash_type: String,
+    pub replaceable: bool,
     pub signature: String,
     pub public_key: String,
+    pub unlock_script: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct TxOutputInfo {
     pub value: i64,
     pub address: String,
+    pub script: String,
+    pub script_type: String,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct TransactionViewResponse {
     pub id: String,
+    /// Hash including signatures and unlocking scripts; `id` leaves them out.
+    pub wtxid: String,
     pub date: String,
     pub message: Option<String>,
     pub inputs: Vec<TxInputInfo>,
     pub outputs: Vec<TxOutputInfo>,
     pub is_coinbase: bool,
     pub size: usize,
+    pub lock_time: u64,
 }
 impl std::fmt::Debug for TransactionViewResponse {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -43,3 +52,64 @@ impl std::fmt::Debug for TransactionViewResponse {
             .finish()
     }
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HtlcPreimageParams {
+    /// id of a confirmed transaction claiming an HTLC output
+    pub tx_id: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct HtlcPreimageResponse {
+    /// hex preimage revealed by the claim
+    pub preimage: String,
+    /// hex SHA-256 hash of the preimage
+    pub hash: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RawTxInputParams {
+    pub tx_id: String,
+    pub index: usize,
+    pub relative_lock: Option<u32>,
+    pub replaceable: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RawTxOutputParams {
+    pub address: String,
+    pub amount: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TransactionCreateRawParams {
+    /// hex raw transaction to extend, keeping its date, message and lock time
+    pub base: Option<String>,
+    #[serde(default)]
+    pub inputs: Vec<RawTxInputParams>,
+    #[serde(default)]
+    pub outputs: Vec<RawTxOutputParams>,
+    pub message: Option<String>,
+    /// block height, or Unix time from 500000000 on, before which the transaction cannot be mined
+    pub lock_time: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TransactionCombineRawParams {
+    /// hex copies of one raw transaction, signed or extended by different parties
+    pub txs: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TransactionSendRawParams {
+    pub hex: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RawTransactionResponse {
+    pub hex: String,
+    pub tx_id: String,
+    /// inputs still waiting for a signature
+    pub unsigned_inputs: Vec<usize>,
+    pub complete: bool,
+}
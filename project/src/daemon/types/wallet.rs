@@ -2,6 +2,7 @@
 use serde::{Deserialize, Serialize};
 
 use crate::daemon::types::UtxoInfo;
+use crate::model::multisig::PartialTransaction;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct WalletImportParams {
@@ -52,9 +53,13 @@ pub struct WalletBalanceParams {
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct WalletBalanceResponse {
+    /// Spendable balance, excluding immature coinbase outputs.
     pub balance: i64,
     pub utxo_count: usize,
     pub utxos: Vec<UtxoInfo>,
+    /// Coinbase rewards that have not reached the maturity depth yet.
+    pub immature_balance: i64,
+    pub immature_utxos: Vec<UtxoInfo>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -69,8 +74,19 @@ pub struct WalletSendParams {
     /// address to send funds to
     pub to: String,
     pub amount: i64,
+    /// absolute fee; without it the fee follows `fee_rate` or `conf_target`
     pub fee: Option<i64>,
+    /// fee per byte of the signed transaction
+    pub fee_rate: Option<f64>,
+    /// blocks within which the payment should confirm, priced by the fee estimator (default: 6)
+    pub conf_target: Option<u64>,
     pub message: Option<String>,
+    /// block height, or Unix time from 500000000 on, before which the payment cannot be mined
+    pub lock_time: Option<u64>,
+    /// signature hash type: all, none or single, optionally with |anyonecanpay (default: all)
+    pub sighash: Option<String>,
+    /// allow replacing the payment with a higher-fee one while unconfirmed (default: true)
+    pub replaceable: Option<bool>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -80,6 +96,25 @@ pub struct WalletSendResponse {
     pub error: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WalletBumpFeeParams {
+    pub wallet: WalletAccessParams,
+    /// id of the wallet's unconfirmed, replaceable transaction
+    pub tx_id: String,
+    /// new total fee, taken from the transaction's change
+    pub fee: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WalletSendDataParams {
+    pub from: WalletAccessParams,
+    /// hex payload of the data-carrier output (at most 80 bytes)
+    pub data: String,
+    pub fee: Option<i64>,
+    pub fee_rate: Option<f64>,
+    pub conf_target: Option<u64>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct WalletGenerateKeysParams {
     pub wallet: WalletAccessParams,
@@ -98,3 +133,121 @@ pub struct GeneratedKey {
 pub struct WalletGenerateKeysResponse {
     pub keys: Vec<GeneratedKey>,
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WalletMultisigCreateParams {
+    /// signatures needed to spend (m)
+    pub required: usize,
+    /// hex-encoded Ed25519 public keys (n)
+    pub public_keys: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WalletMultisigCreateResponse {
+    pub address: String,
+    /// hex redeem script, needed to build spends from the address
+    pub redeem_script: String,
+    pub required: usize,
+    pub public_keys: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WalletMultisigSpendParams {
+    /// signer building the spend; its signature is added right away
+    pub wallet: WalletAccessParams,
+    pub redeem_script: String,
+    pub to: String,
+    pub amount: i64,
+    /// absolute fee (default: the mempool's minimum fee rate for the signed size)
+    pub fee: Option<i64>,
+    pub message: Option<String>,
+    /// signature hash type of every input (default: all)
+    pub sighash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WalletMultisigSignParams {
+    pub wallet: WalletAccessParams,
+    pub pending: PartialTransaction,
+    /// submit to the mempool once complete (default: true)
+    pub broadcast: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WalletSignRawParams {
+    pub wallet: WalletAccessParams,
+    /// hex raw transaction
+    pub hex: String,
+    /// signature hash type of the inputs the wallet signs (default: all)
+    pub sighash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WalletSignRawResponse {
+    pub hex: String,
+    pub tx_id: String,
+    pub signed_inputs: Vec<usize>,
+    /// inputs still waiting for a signature
+    pub unsigned_inputs: Vec<usize>,
+    pub complete: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WalletMultisigSignResponse {
+    pub success: bool,
+    pub pending: Option<PartialTransaction>,
+    pub signatures_added: usize,
+    pub missing_signatures: usize,
+    pub complete: bool,
+    pub tx_id: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WalletHtlcFundParams {
+    pub from: WalletAccessParams,
+    /// address of the recipient, who claims with the preimage
+    pub recipient: String,
+    pub amount: i64,
+    /// hex SHA-256 hash of the secret preimage
+    pub hash: String,
+    /// block height, or Unix time from 500000000 on, from which the sender can refund
+    pub timeout: u64,
+    /// absolute fee (default: the mempool's minimum fee rate for the signed size)
+    pub fee: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WalletHtlcFundResponse {
+    pub success: bool,
+    pub tx_id: Option<String>,
+    /// output index of the HTLC in the funding transaction
+    pub index: Option<usize>,
+    /// hex locking script of the HTLC
+    pub script: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WalletHtlcClaimParams {
+    pub wallet: WalletAccessParams,
+    pub tx_id: String,
+    pub index: usize,
+    /// hex secret whose SHA-256 hash locks the HTLC
+    pub preimage: String,
+    /// destination address (default: a new receive address of the wallet)
+    pub to: Option<String>,
+    /// absolute fee (default: the mempool's minimum fee rate for the signed size)
+    pub fee: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct WalletHtlcRefundParams {
+    pub wallet: WalletAccessParams,
+    pub tx_id: String,
+    pub index: usize,
+    /// destination address (default: a new receive address of the wallet)
+    pub to: Option<String>,
+    /// absolute fee (default: the mempool's minimum fee rate for the signed size)
+    pub fee: Option<i64>,
+}
@@ -1,13 +1,19 @@
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::Result;
+use rusqlite::{Connection, Result, params, types::Type};
 use std::path::Path;
 
+use crate::db::repository::decode_raw_transaction;
 use crate::globals::CONFIG;
+use crate::model::Transaction;
 
 pub type DbPool = Pool<SqliteConnectionManager>;
 pub type DbConnection = r2d2::PooledConnection<SqliteConnectionManager>;
 
+/// Version of the schema left by `init_schema`, kept in `PRAGMA user_version`.
+/// Databases of older versions are migrated when opened.
+pub const SCHEMA_VERSION: i64 = 2;
+
 #[derive(Clone)]
 pub struct Db {
     pool: DbPool,
@@ -63,6 +69,7 @@ impl Db {
         conn.execute(
             "CREATE TABLE IF NOT EXISTS transactions (
                 txid         BLOB PRIMARY KEY,
+                wtxid        BLOB NOT NULL,
                 raw          BLOB NOT NULL,
                 block_hash   BLOB,
                 block_height INTEGER,
@@ -92,6 +99,18 @@ impl Db {
             [],
         )?;
 
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS mempool_entries (
+                txid      BLOB PRIMARY KEY,
+                raw       BLOB NOT NULL,
+                time      INTEGER NOT NULL,
+                ancestors INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        migrate(&conn)?;
+
         // Indices for performance
         conn.execute(
             "CREATE INDEX IF NOT EXISTS idx_utxos_addr ON utxos(addr)",
@@ -105,11 +124,81 @@ impl Db {
             "CREATE INDEX IF NOT EXISTS idx_tx_block_hash ON transactions(block_hash)",
             [],
         )?;
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS idx_tx_wtxid ON transactions(wtxid)",
+            [],
+        )?;
 
         Ok(())
     }
 }
 
+/// Brings the tables of an older database up to `SCHEMA_VERSION`; the
+/// tables just created are already there.
+fn migrate(conn: &Connection) -> Result<()> {
+    let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if version < 1 {
+        encode_raw_transactions(conn)?;
+    }
+    if version < 2 && !has_column(conn, "transactions", "wtxid")? {
+        add_wtxid_column(conn)?;
+    }
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2",
+        params![table, column],
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|count| count > 0)
+}
+
+/// Txid and stored encoding of every transaction.
+fn raw_transactions(conn: &Connection) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    conn.prepare("SELECT txid, raw FROM transactions")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect()
+}
+
+/// Version 1: transactions are stored in their binary encoding. Rows still
+/// holding JSON, which opens with `{` where the encoding opens with its
+/// version byte, are re-encoded.
+fn encode_raw_transactions(conn: &Connection) -> Result<()> {
+    let db_tx = conn.unchecked_transaction()?;
+    let rows = raw_transactions(&db_tx)?;
+    {
+        let mut update = db_tx.prepare("UPDATE transactions SET raw = ?1 WHERE txid = ?2")?;
+        for (txid, raw) in rows
+            .into_iter()
+            .filter(|(_, raw)| raw.first() == Some(&b'{'))
+        {
+            let tx: Transaction = serde_json::from_slice(&raw).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(0, Type::Blob, Box::new(e))
+            })?;
+            update.execute(params![tx.as_bytes(), txid])?;
+        }
+    }
+    db_tx.commit()
+}
+
+/// Version 2: confirmed transactions are also found by witness id, filled
+/// in from the stored encodings.
+fn add_wtxid_column(conn: &Connection) -> Result<()> {
+    let db_tx = conn.unchecked_transaction()?;
+    db_tx.execute("ALTER TABLE transactions ADD COLUMN wtxid BLOB", [])?;
+    let rows = raw_transactions(&db_tx)?;
+    {
+        let mut update = db_tx.prepare("UPDATE transactions SET wtxid = ?1 WHERE txid = ?2")?;
+        for (txid, raw) in rows {
+            let wtxid = decode_raw_transaction(&raw)?.wtxid();
+            update.execute(params![wtxid.as_slice(), txid])?;
+        }
+    }
+    db_tx.commit()
+}
+
 static mut DB: Option<Db> = None;
 
 pub fn init_db() {
@@ -1,13 +1,26 @@
 use crate::{
     db::db,
-    model::{Block, Transaction, TxOutput, UTXO, block::BlockHeader, transaction::TxId},
+    globals::CONSENSUS_RULES,
+    model::{Block, Script, Transaction, TxOutput, UTXO, block::BlockHeader, transaction::TxId},
+    utils::median_time_past,
 };
+use chrono::{DateTime, NaiveDateTime};
 use rusqlite::{Result, params};
+use std::collections::HashMap;
 
 pub struct LedgerRepository {
     conn: db::DbConnection,
 }
 
+/// A mempool transaction as saved in the `mempool_entries` table.
+pub struct MempoolRecord {
+    pub tx: Transaction,
+    /// Unix time the transaction entered the mempool.
+    pub time: i64,
+    /// Unconfirmed ancestors the transaction had when saved.
+    pub ancestors: usize,
+}
+
 impl LedgerRepository {
     pub fn new() -> Self {
         let conn = db::get_db().get_conn();
@@ -17,7 +30,7 @@ impl LedgerRepository {
     pub fn get_utxos_for_address(&self, addr: &str) -> Result<Vec<UTXO>> {
         let mut stmt = self
             .conn
-            .prepare("SELECT txid, vout, value, addr FROM utxos WHERE addr = ?1")?;
+            .prepare("SELECT txid, vout, value, addr, script FROM utxos WHERE addr = ?1")?;
         stmt.query_map([addr], |row| build_utxo_from_row(row))?
             .collect()
     }
@@ -29,7 +42,7 @@ impl LedgerRepository {
 
         let placeholders: Vec<String> = (0..addrs.len()).map(|_| "?".to_string()).collect();
         let query = format!(
-            "SELECT txid, vout, value, addr FROM utxos WHERE addr IN ({})",
+            "SELECT txid, vout, value, addr, script FROM utxos WHERE addr IN ({})",
             placeholders.join(", ")
         );
 
@@ -51,7 +64,7 @@ impl LedgerRepository {
             .map(|i| format!("(txid = ?{} AND vout = ?{})", i * 2 + 1, i * 2 + 2))
             .collect();
         let query = format!(
-            "SELECT txid, vout, value, addr FROM utxos WHERE {}",
+            "SELECT txid, vout, value, addr, script FROM utxos WHERE {}",
             conditions.join(" OR ")
         );
 
@@ -70,7 +83,7 @@ impl LedgerRepository {
 
     pub fn get_all_utxos(&self, limit: Option<usize>) -> Result<Vec<UTXO>> {
         let query = format!(
-            "SELECT txid, vout, value, addr FROM utxos LIMIT {}",
+            "SELECT txid, vout, value, addr, script FROM utxos LIMIT {}",
             limit.unwrap_or(20)
         );
         let mut stmt = self.conn.prepare(&query)?;
@@ -87,14 +100,126 @@ impl LedgerRepository {
         match rows.next()? {
             Some(row) => {
                 let raw: Vec<u8> = row.get(0)?;
-                let tx: Transaction = serde_json::from_slice(&raw)
-                    .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+                let tx = decode_raw_transaction(&raw)?;
+                Ok(Some(tx))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Looks a transaction up by its witness id.
+    pub fn get_transaction_by_wtxid(&self, wtxid: &[u8; 32]) -> Result<Option<Transaction>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT raw FROM transactions WHERE wtxid = ?1")?;
+        let mut rows = stmt.query([wtxid.as_slice()])?;
+
+        match rows.next()? {
+            Some(row) => {
+                let raw: Vec<u8> = row.get(0)?;
+                let tx = decode_raw_transaction(&raw)?;
                 Ok(Some(tx))
             }
             None => Ok(None),
         }
     }
 
+    /// Like `get_transaction`, also returning the height of the block that confirmed it
+    /// (`None` while the transaction is only in the mempool).
+    pub fn get_transaction_with_height(
+        &self,
+        txid: &[u8; 32],
+    ) -> Result<Option<(Transaction, Option<u64>)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT raw, block_height FROM transactions WHERE txid = ?1")?;
+        let mut rows = stmt.query([txid.as_slice()])?;
+
+        match rows.next()? {
+            Some(row) => {
+                let raw: Vec<u8> = row.get(0)?;
+                let height: Option<i64> = row.get(1)?;
+                let tx = decode_raw_transaction(&raw)?;
+                Ok(Some((tx, height.map(|h| h as u64))))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Transactions among `txids` found in the ledger, with the height of the
+    /// block that confirmed them, fetched in a single query.
+    pub fn get_transactions_with_height(
+        &self,
+        txids: &[TxId],
+    ) -> Result<HashMap<TxId, (Transaction, Option<u64>)>> {
+        if txids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let placeholders: Vec<String> = (1..=txids.len()).map(|i| format!("?{}", i)).collect();
+        let query = format!(
+            "SELECT raw, block_height FROM transactions WHERE txid IN ({})",
+            placeholders.join(", ")
+        );
+        let mut stmt = self.conn.prepare(&query)?;
+        let rows = stmt.query_map(
+            rusqlite::params_from_iter(txids.iter().map(|txid| txid.as_slice())),
+            |row| {
+                let raw: Vec<u8> = row.get(0)?;
+                let height: Option<i64> = row.get(1)?;
+                Ok((decode_raw_transaction(&raw)?, height.map(|h| h as u64)))
+            },
+        )?;
+
+        let mut found = HashMap::with_capacity(txids.len());
+        for row in rows {
+            let (tx, height) = row?;
+            found.insert(tx.id(), (tx, height));
+        }
+        Ok(found)
+    }
+
+    /// Number of blocks applied to the ledger, i.e. the height of the next block.
+    pub fn get_block_count(&self) -> Result<u64> {
+        let max_height: Option<i64> =
+            self.conn
+                .query_row("SELECT MAX(height) FROM block_headers", [], |row| {
+                    row.get(0)
+                })?;
+        Ok(max_height.map(|h| h as u64 + 1).unwrap_or(0))
+    }
+
+    /// Timestamp of the main-chain block at `height`.
+    pub fn get_block_time(&self, height: u64) -> Result<Option<NaiveDateTime>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT timestamp FROM block_headers WHERE height = ?1")?;
+        let mut rows = stmt.query([height as i64])?;
+        match rows.next()? {
+            Some(row) => {
+                Ok(DateTime::from_timestamp(row.get::<_, i64>(0)?, 0).map(|dt| dt.naive_utc()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Median-time-past for a block at `height`, from the stored main-chain headers below it.
+    pub fn get_median_time_past_at(&self, height: u64) -> Result<Option<NaiveDateTime>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT timestamp FROM block_headers WHERE height < ?1 AND height >= ?2")?;
+        let start = height.saturating_sub(CONSENSUS_RULES.median_time_span as u64);
+        let timestamps = stmt
+            .query_map(params![height as i64, start as i64], |row| {
+                row.get::<_, i64>(0)
+            })?
+            .collect::<Result<Vec<i64>>>()?
+            .into_iter()
+            .filter_map(|secs| DateTime::from_timestamp(secs, 0).map(|dt| dt.naive_utc()))
+            .collect::<Vec<_>>();
+        Ok(median_time_past(&timestamps))
+    }
+
     pub fn apply_block(&mut self, block: Block) -> Result<()> {
         let db_tx = self.conn.transaction()?;
         let block_hash = block.header_hash();
@@ -111,9 +236,9 @@ impl LedgerRepository {
     }
 
     pub fn get_utxo(&self, txid: TxId, vout: usize) -> Result<UTXO> {
-        let mut stmt = self
-            .conn
-            .prepare("SELECT txid, vout, value, addr FROM utxos WHERE txid = ?1 AND vout = ?2")?;
+        let mut stmt = self.conn.prepare(
+            "SELECT txid, vout, value, addr, script FROM utxos WHERE txid = ?1 AND vout = ?2",
+        )?;
         let mut rows = stmt.query(params![txid.as_slice(), vout as i64])?;
 
         match rows.next()? {
@@ -122,28 +247,62 @@ impl LedgerRepository {
         }
     }
 
-    pub fn insert_mempool_tx(&self, tx: &Transaction) -> Result<()> {
-        let txid = tx.id();
-        let raw = serde_json::to_vec(tx)
-            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
-        let timestamp = tx.date.and_utc().timestamp();
-
+    /// Saves a mempool entry, replacing any earlier one for the same transaction.
+    pub fn save_mempool_entry(&self, record: &MempoolRecord) -> Result<()> {
         self.conn.execute(
-            "INSERT OR REPLACE INTO transactions (txid, raw, block_hash, block_height, timestamp)
-             VALUES (?1, ?2, NULL, NULL, ?3)",
-            params![txid.as_slice(), raw, timestamp],
+            "INSERT OR REPLACE INTO mempool_entries (txid, raw, time, ancestors)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                record.tx.id().as_slice(),
+                record.tx.as_bytes(),
+                record.time,
+                record.ancestors as i64
+            ],
         )?;
         Ok(())
     }
 
-    pub fn remove_mempool_tx(&self, txid: &[u8; 32]) -> Result<()> {
-        self.conn.execute(
-            "DELETE FROM transactions WHERE txid = ?1 AND block_hash IS NULL",
-            [txid.as_slice()],
-        )?;
+    pub fn delete_mempool_entries(&mut self, txids: &[TxId]) -> Result<()> {
+        let db_tx = self.conn.transaction()?;
+        for txid in txids {
+            db_tx.execute(
+                "DELETE FROM mempool_entries WHERE txid = ?1",
+                [txid.as_slice()],
+            )?;
+        }
+        db_tx.commit()
+    }
+
+    pub fn clear_mempool_entries(&self) -> Result<()> {
+        self.conn.execute("DELETE FROM mempool_entries", [])?;
         Ok(())
     }
 
+    /// Saved mempool entries, parents ahead of the children spending their
+    /// outputs. An entry whose transaction cannot be decoded comes back as its
+    /// txid and the decoding error, leaving the other entries readable.
+    pub fn get_mempool_entries(
+        &self,
+    ) -> Result<Vec<std::result::Result<MempoolRecord, (TxId, String)>>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT txid, raw, time, ancestors FROM mempool_entries ORDER BY ancestors, time",
+        )?;
+        stmt.query_map([], |row| {
+            let txid: TxId = row.get(0)?;
+            let raw: Vec<u8> = row.get(1)?;
+            let ancestors: i64 = row.get(3)?;
+            Ok(match Transaction::from_bytes(&raw) {
+                Ok(tx) => Ok(MempoolRecord {
+                    tx,
+                    time: row.get(2)?,
+                    ancestors: ancestors as usize,
+                }),
+                Err(e) => Err((txid, e)),
+            })
+        })?
+        .collect()
+    }
+
     pub fn get_transactions_for_address(&self, addr: &str) -> Result<Vec<[u8; 32]>> {
         let mut stmt = self
             .conn
@@ -281,15 +440,16 @@ fn apply_transaction(
     height: i64,
 ) -> Result<()> {
     let txid = transaction.id();
-    let raw = serde_json::to_vec(transaction)
-        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    let wtxid = transaction.wtxid();
+    let raw = transaction.as_bytes();
     let tx_timestamp = transaction.date.and_utc().timestamp();
 
     db_tx.execute(
-        "INSERT OR REPLACE INTO transactions (txid, raw, block_hash, block_height, timestamp)
-         VALUES (?1, ?2, ?3, ?4, ?5)",
+        "INSERT OR REPLACE INTO transactions (txid, wtxid, raw, block_hash, block_height, timestamp)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
         params![
             txid.as_slice(),
+            wtxid.as_slice(),
             raw,
             block_hash.as_slice(),
             height,
@@ -305,6 +465,10 @@ fn apply_transaction(
     }
 
     for (vout, output) in transaction.outputs.iter().enumerate() {
+        // Data-carrier and other unspendable outputs never become spendable coins.
+        if output.script.is_unspendable() {
+            continue;
+        }
         db_tx.execute(
             "INSERT INTO utxos (txid, vout, value, addr, script)
              VALUES (?1, ?2, ?3, ?4, ?5)",
@@ -313,7 +477,7 @@ fn apply_transaction(
                 vout as i64,
                 output.value,
                 &output.address,
-                Vec::<u8>::new()
+                output.script.as_bytes()
             ],
         )?;
         db_tx.execute(
@@ -349,13 +513,14 @@ fn rollback_transaction(
         let output = &prev_tx.outputs[input.output_index];
 
         db_tx.execute(
-            "INSERT INTO utxos (txid, vout, value, addr)
-             VALUES (?1, ?2, ?3, ?4)",
+            "INSERT INTO utxos (txid, vout, value, addr, script)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
             params![
                 input.prev_tx_id.as_slice(),
                 input.output_index as i64,
                 output.value,
-                &output.address
+                &output.address,
+                output.script.as_bytes()
             ],
         )?;
     }
@@ -381,6 +546,12 @@ fn rollback_transaction(
     Ok(())
 }
 
+pub(crate) fn decode_raw_transaction(raw: &[u8]) -> Result<Transaction> {
+    Transaction::from_bytes(raw).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Blob, e.into())
+    })
+}
+
 fn build_utxo_from_row(row: &rusqlite::Row) -> rusqlite::Result<UTXO> {
     let txid_vec: Vec<u8> = row.get(0)?;
     let mut txid = [0u8; 32];
@@ -389,10 +560,15 @@ fn build_utxo_from_row(row: &rusqlite::Row) -> rusqlite::Result<UTXO> {
     let vout: i64 = row.get(1)?;
     let value: i64 = row.get(2)?;
     let address: String = row.get(3)?;
+    let script: Option<Vec<u8>> = row.get(4)?;
 
     Ok(UTXO {
         tx_id: txid,
         index: vout as usize,
-        output: TxOutput { value, address },
+        output: TxOutput {
+            value,
+            address,
+            script: Script::from_bytes(script.unwrap_or_default()),
+        },
     })
 }
@@ -2,6 +2,22 @@ use once_cell::sync::Lazy;
 use primitive_types::U256;
 use std::env;
 
+use crate::chain_params::{Network, NetworkParams};
+use crate::model::checkpoints::Checkpoints;
+use crate::model::versionbits::Deployment;
+
+/// Profile of the network this process runs on, chosen with `NETWORK`
+/// (`main`, `test` or `regtest`; default `main`).
+pub static NETWORK: Lazy<NetworkParams> = Lazy::new(|| {
+    dotenv::dotenv().ok();
+    let network = match env::var("NETWORK") {
+        Ok(name) => Network::from_name(&name)
+            .unwrap_or_else(|| panic!("Unknown NETWORK '{}': use main, test or regtest", name)),
+        Err(_) => Network::Main,
+    };
+    NetworkParams::for_network(network)
+});
+
 #[derive(Debug, Clone)]
 pub struct Settings {
     pub persisted_chain_path: String,
@@ -19,6 +35,10 @@ pub struct Settings {
     pub log_file_path: String,
     pub log_mode: String,
     pub mining_threads: usize,
+    /// Worker threads verifying input scripts during block validation.
+    pub script_threads: usize,
+    /// Most passed script checks of mempool transactions remembered for blocks.
+    pub script_cache_size: usize,
     /// Address the RPC and HTTP servers bind to. Defaults to 127.0.0.1
     /// (loopback only). Set to 0.0.0.0 to expose the daemon inside a
     /// container so an external orchestrator can reach it (used by the
@@ -28,6 +48,12 @@ pub struct Settings {
     /// Root directory for wallet keystores accepted from RPC requests.
     /// All user-supplied wallet paths are resolved inside this directory.
     pub wallet_keys_dir: String,
+    /// Most kilobytes of transactions the mempool holds before evicting.
+    pub max_mempool_size_kb: usize,
+    /// Hours a transaction may wait in the mempool before it is dropped.
+    pub mempool_expiry_hours: u64,
+    /// Lowest fee per byte a transaction must pay to enter the mempool.
+    pub min_relay_fee_rate: f64,
 }
 
 pub static CONFIG: Lazy<Settings> = Lazy::new(|| {
@@ -35,12 +61,13 @@ pub static CONFIG: Lazy<Settings> = Lazy::new(|| {
     let p2p_port = env::var("P2P_PORT")
         .ok()
         .and_then(|v| v.parse().ok())
-        .unwrap_or(6000);
+        .unwrap_or(NETWORK.default_p2p_port);
+    let data_dir = NETWORK.data_dir;
 
     Settings {
         persisted_chain_path: env::var("PERSISTED_CHAIN_PATH")
-            .unwrap_or_else(|_| "saved_files".to_string()),
-        db_path: env::var("DB_PATH").unwrap_or_else(|_| "saved_files/bd".to_string()),
+            .unwrap_or_else(|_| data_dir.to_string()),
+        db_path: env::var("DB_PATH").unwrap_or_else(|_| format!("{}/bd", data_dir)),
         miner_wallet_seed_path: env::var("MINER_WALLET_SEED_PATH")
             .unwrap_or_else(|_| "keys/miner_wallet.json".to_string()),
         miner_wallet_password: env::var("MINER_WALLET_PASSWORD")
@@ -66,28 +93,52 @@ pub static CONFIG: Lazy<Settings> = Lazy::new(|| {
         rpc_port: env::var("RPC_PORT")
             .ok()
             .and_then(|v| v.parse().ok())
-            .unwrap_or(7000),
+            .unwrap_or(NETWORK.default_rpc_port),
         http_port: env::var("HTTP_PORT")
             .ok()
             .and_then(|v| v.parse().ok())
-            .unwrap_or(7001),
+            .unwrap_or(NETWORK.default_http_port),
         pbkdf2_iterations: env::var("PBKDF2_ITERATIONS")
             .ok()
             .and_then(|v| v.parse().ok())
             .unwrap_or(600_000),
         log_file_path: env::var("LOG_FILE_PATH")
-            .unwrap_or_else(|_| "saved_files/node.log".to_string()),
+            .unwrap_or_else(|_| format!("{}/node.log", data_dir)),
         log_mode: env::var("LOG_MODE").unwrap_or_else(|_| "full".to_string()),
         mining_threads: env::var("MINING_THREADS")
             .ok()
             .and_then(|v| v.parse().ok())
             .unwrap_or(1),
+        script_threads: env::var("SCRIPT_THREADS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &usize| *v > 0)
+            .unwrap_or_else(|| {
+                std::thread::available_parallelism().map_or(1, |threads| threads.get())
+            }),
+        script_cache_size: env::var("SCRIPT_CACHE_SIZE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(100_000),
         bind_addr: env::var("BIND_ADDR").unwrap_or_else(|_| "127.0.0.1".to_string()),
         max_fork_blocks: env::var("MAX_FORK_BLOCKS")
             .ok()
             .and_then(|v| v.parse().ok())
             .unwrap_or(1000),
         wallet_keys_dir: env::var("WALLET_KEYS_DIR").unwrap_or_else(|_| "keys".to_string()),
+        max_mempool_size_kb: env::var("MAX_MEMPOOL_SIZE_KB")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5000),
+        mempool_expiry_hours: env::var("MEMPOOL_EXPIRY_HOURS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(336),
+        min_relay_fee_rate: env::var("MIN_RELAY_FEE_RATE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &f64| *v >= 0.0)
+            .unwrap_or(1.0),
     }
 });
 
@@ -95,21 +146,127 @@ pub struct ConsensusRules {
     /// Initial mining target. Hashes must be lower than this value.
     pub initial_target: U256,
     pub max_block_size_kb: f32,
-    /// TODO: Implement block reward halving every N blocks
-    pub block_reward: i64,
+    /// Subsidy paid to the miner of the genesis block, before any halving.
+    pub initial_block_reward: i64,
+    /// Number of blocks between two subsidy halvings.
+    pub halving_interval: u64,
+    /// Confirmations a coinbase output needs before it can be spent.
+    pub coinbase_maturity: u64,
+    /// Number of previous blocks whose median timestamp (median-time-past)
+    /// a new block timestamp must exceed.
+    pub median_time_span: usize,
+    /// How far ahead of the node clock a block timestamp may be, in seconds.
+    pub max_future_block_time_secs: i64,
     /// LWMA window size: how many recent blocks to consider for difficulty adjustment
     pub lwma_n: usize,
     /// Target block time in seconds
     pub target_block_time_secs: u64,
+    /// Blocks per version-bits signalling period.
+    pub version_bits_window: usize,
+    /// Signalling blocks within a period needed to lock a deployment in.
+    pub version_bits_threshold: usize,
+    /// Soft forks signalled and tracked through version bits.
+    pub deployments: Vec<Deployment>,
+    /// Blocks the chain must contain, and the assume-valid block.
+    pub checkpoints: Checkpoints,
+}
+
+impl ConsensusRules {
+    /// Subsidy (excluding fees) a coinbase may claim at `height`.
+    /// Starts at `initial_block_reward`, halves every `halving_interval`
+    /// blocks and terminates at zero once the value is shifted out.
+    pub fn block_subsidy(&self, height: u64) -> i64 {
+        let halvings = height / self.halving_interval.max(1);
+        if halvings >= i64::BITS as u64 {
+            return 0;
+        }
+        self.initial_block_reward >> halvings
+    }
+
+    /// Total amount of coins issued by the subsidy of blocks `0..=height`.
+    pub fn total_supply_at(&self, height: u64) -> i64 {
+        let interval = self.halving_interval.max(1);
+        let mut total: i64 = 0;
+        let mut era_start: u64 = 0;
+
+        while era_start <= height {
+            let subsidy = self.block_subsidy(era_start);
+            if subsidy == 0 {
+                break;
+            }
+            let era_end = era_start.saturating_add(interval - 1).min(height);
+            let blocks = (era_end - era_start + 1) as i64;
+            total = total.saturating_add(subsidy.saturating_mul(blocks));
+            match era_start.checked_add(interval) {
+                Some(next) => era_start = next,
+                None => break,
+            }
+        }
+        total
+    }
+
+    /// Consensus limit on `Block::size`, the exact serialized block size.
+    pub fn max_block_size_bytes(&self) -> usize {
+        (self.max_block_size_kb * 1000.0) as usize
+    }
+
+    /// Whether a coinbase mined at `coinbase_height` may be spent by a block at `spend_height`.
+    pub fn is_coinbase_mature(&self, coinbase_height: u64, spend_height: u64) -> bool {
+        spend_height >= coinbase_height.saturating_add(self.coinbase_maturity)
+    }
+
+    /// Supply reached once the subsidy has dropped to zero.
+    pub fn max_supply(&self) -> i64 {
+        self.total_supply_at(u64::MAX)
+    }
 }
 
-pub static CONSENSUS_RULES: Lazy<ConsensusRules> = Lazy::new(|| ConsensusRules {
-    // 12 leading zero bits.
-    initial_target: U256::MAX >> 19u32,
-    max_block_size_kb: 10.0,
-    block_reward: 1 * COIN,
-    lwma_n: 10,
-    target_block_time_secs: 10,
+pub static CONSENSUS_RULES: Lazy<ConsensusRules> = Lazy::new(|| {
+    dotenv::dotenv().ok();
+    let version_bits_window = env::var("VERSION_BITS_WINDOW")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|v: &usize| *v > 0)
+        .unwrap_or(NETWORK.version_bits_window);
+    ConsensusRules {
+        initial_target: NETWORK.initial_target,
+        max_block_size_kb: 10.0,
+        initial_block_reward: env::var("INITIAL_BLOCK_REWARD")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &i64| *v >= 0)
+            .unwrap_or(NETWORK.initial_block_reward),
+        halving_interval: env::var("HALVING_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &u64| *v > 0)
+            .unwrap_or(NETWORK.halving_interval),
+        coinbase_maturity: 100,
+        median_time_span: 11,
+        max_future_block_time_secs: 2 * 60 * 60,
+        lwma_n: 10,
+        target_block_time_secs: NETWORK.target_block_time_secs,
+        version_bits_window,
+        version_bits_threshold: env::var("VERSION_BITS_THRESHOLD")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|v: &usize| *v > 0 && *v <= version_bits_window)
+            .unwrap_or(NETWORK.version_bits_threshold.min(version_bits_window)),
+        deployments: match env::var("VERSION_BITS_DEPLOYMENTS") {
+            Ok(specs) => specs
+                .split(',')
+                .filter(|spec| !spec.trim().is_empty())
+                .map(|spec| Deployment::parse(spec).unwrap_or_else(|e| panic!("{}", e)))
+                .collect(),
+            Err(_) => NETWORK.deployments.clone(),
+        },
+        checkpoints: match env::var("ASSUME_VALID") {
+            Ok(spec) => Checkpoints::for_network(&NETWORK).with_assume_valid(
+                Checkpoints::parse_assume_valid(&spec).unwrap_or_else(|e| panic!("{}", e)),
+            ),
+            Err(_) => Checkpoints::for_network(&NETWORK),
+        },
+    }
 });
 
 pub const COIN: i64 = 1_000_000;
@@ -1,3 +1,4 @@
+pub mod chain_params;
 pub mod cli;
 pub mod daemon;
 pub mod db;
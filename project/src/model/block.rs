@@ -1,12 +1,15 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use super::Transaction;
-use crate::globals::CONSENSUS_RULES;
+use super::script_check::{SCRIPT_CACHE, ScriptCheck, run_script_checks};
+use super::transaction::{InputContext, TxId};
+use super::utxo_view::{LedgerView, UtxoView};
+use crate::globals::{CONFIG, CONSENSUS_RULES};
 use crate::security_utils::hash_meets_target;
 use crate::utils::get_current_timestamp;
 use crate::{
     security_utils::{bytes_to_hex_string, sha256},
-    utils::{MerkleTree, format_date},
+    utils::{ByteReader, ByteWriter, MerkleTree, format_date, target_work},
 };
 use chrono::NaiveDateTime;
 use primitive_types::U256;
@@ -18,8 +21,16 @@ fn default_target() -> U256 {
     CONSENSUS_RULES.initial_target
 }
 
+fn default_version() -> u32 {
+    BlockHeader::INITIAL_VERSION
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BlockHeader {
+    /// Block version. Versions with the version-bits top bits set signal
+    /// readiness for soft-fork deployments (see `versionbits`).
+    #[serde(default = "default_version")]
+    pub version: u32,
     pub prev_block_hash: BlockID,
     pub merkle_root: BlockID,
     pub nonce: u32,
@@ -28,6 +39,52 @@ pub struct BlockHeader {
     pub target: U256,
 }
 
+/// Bytes used by the transaction count in the block encoding.
+const BLOCK_TX_COUNT_SIZE: usize = 4;
+
+impl BlockHeader {
+    /// Length of the header encoding: encoding version, block version, two hashes,
+    /// nonce, timestamp and target.
+    pub const ENCODED_SIZE: usize = 1 + 4 + 32 + 32 + 4 + 12 + 32;
+
+    /// Version of the genesis blocks, which predate version-bits signalling.
+    pub const INITIAL_VERSION: u32 = 1;
+
+    /// Canonical encoding hashed into the block id.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut w = ByteWriter::versioned();
+        self.encode_into(&mut w);
+        w.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<BlockHeader, String> {
+        let mut r = ByteReader::versioned(bytes)?;
+        let header = BlockHeader::decode_from(&mut r)?;
+        r.finish()?;
+        Ok(header)
+    }
+
+    fn encode_into(&self, w: &mut ByteWriter) {
+        w.put_u32(self.version);
+        w.put_hash(&self.prev_block_hash);
+        w.put_hash(&self.merkle_root);
+        w.put_u32(self.nonce);
+        w.put_datetime(&self.timestamp);
+        w.put_u256(self.target);
+    }
+
+    fn decode_from(r: &mut ByteReader) -> Result<BlockHeader, String> {
+        Ok(BlockHeader {
+            version: r.get_u32()?,
+            prev_block_hash: r.get_hash()?,
+            merkle_root: r.get_hash()?,
+            nonce: r.get_u32()?,
+            timestamp: r.get_datetime()?,
+            target: r.get_u256()?,
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Block {
     pub header: BlockHeader,
@@ -35,9 +92,10 @@ pub struct Block {
 }
 
 impl Block {
-    pub fn new(prev_block_hash: BlockID, target: U256) -> Self {
+    pub fn new(prev_block_hash: BlockID, target: U256, version: u32) -> Self {
         let timestamp = get_current_timestamp();
         let header = BlockHeader {
+            version,
             prev_block_hash,
             merkle_root: [0; 32],
             nonce: 0,
@@ -51,33 +109,51 @@ impl Block {
     }
 
     pub fn evaluate_merkle_root(&mut self) {
-        let leaf_hashes = self.transactions.iter().map(|tx| tx.id()).collect();
-        let merkle_tree = MerkleTree::from_leaves(leaf_hashes);
-        self.header.merkle_root = merkle_tree.root();
+        self.header.merkle_root = Block::eval_merkle_root_from_transactions(&self.transactions);
     }
 
     pub fn header_bytes(&self) -> Vec<u8> {
-        let mut out = Vec::new();
-        out.extend_from_slice(&self.header.prev_block_hash);
-        out.extend_from_slice(&self.header.merkle_root);
-        out.extend_from_slice(&self.header.nonce.to_be_bytes());
-        out.extend_from_slice(format_date(&self.header.timestamp).as_bytes());
-        let mut target_bytes = [0u8; 32];
-        self.header.target.to_big_endian(&mut target_bytes);
-        out.extend_from_slice(&target_bytes);
-        out
+        self.header.as_bytes()
     }
 
-    pub fn size(&self) -> usize {
-        let mut size = 0;
-        size += self.header.prev_block_hash.len();
-        size += self.header.merkle_root.len();
-        size += std::mem::size_of_val(&self.header.nonce);
-        size += std::mem::size_of_val(&self.header.timestamp);
+    /// Canonical encoding: the header encoding, the transaction count and then
+    /// the encoding of every transaction, exactly as produced by `as_bytes`.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut w = ByteWriter::versioned();
+        self.header.encode_into(&mut w);
+        w.put_len(self.transactions.len());
         for tx in &self.transactions {
-            size += tx.as_bytes().len();
+            w.put_version();
+            tx.encode_into(&mut w);
         }
-        size
+        w.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Block, String> {
+        let mut r = ByteReader::versioned(bytes)?;
+        let header = BlockHeader::decode_from(&mut r)?;
+        let tx_count = r.get_len(Transaction::MIN_ENCODED_SIZE)?;
+        let mut transactions = Vec::with_capacity(tx_count);
+        for _ in 0..tx_count {
+            r.expect_version()?;
+            transactions.push(Transaction::decode_from(&mut r)?);
+        }
+        r.finish()?;
+        Ok(Block {
+            header,
+            transactions,
+        })
+    }
+
+    /// Exact length of `as_bytes`: the encoded header, the transaction count
+    /// and the `Transaction::size` of every transaction.
+    pub fn size(&self) -> usize {
+        Self::size_with_transactions(self.transactions.iter().map(|tx| tx.size()).sum())
+    }
+
+    /// Block size for transactions whose `Transaction::size` add up to `transactions_size`.
+    pub fn size_with_transactions(transactions_size: usize) -> usize {
+        BlockHeader::ENCODED_SIZE + BLOCK_TX_COUNT_SIZE + transactions_size
     }
 
     pub fn header_hash(&self) -> BlockID {
@@ -88,32 +164,125 @@ impl Block {
         self.header_hash()
     }
 
+    /// Root committed to by the header: the hash of the txid merkle root
+    /// followed by the wtxid merkle root, so the header covers every
+    /// transaction and its witness data.
     pub fn eval_merkle_root_from_transactions(txs: &[Transaction]) -> BlockID {
-        let mut leaf_hashes: Vec<BlockID> = Vec::new();
-        for tx in txs {
-            leaf_hashes.push(tx.id());
+        let txid_root = MerkleTree::from_leaves(txs.iter().map(|tx| tx.id()).collect()).root();
+        let witness_root =
+            MerkleTree::from_leaves(txs.iter().map(|tx| tx.wtxid()).collect()).root();
+        sha256(&[txid_root, witness_root].concat())
+    }
+
+    /** Validate the block as the block at `height` of its chain, where the
+     * blocks below it have the given `median_time_past`.
+     * Checks:
+     * - Block structure (see `check_structure`)
+     * - All transactions are valid (scripts, inputs >= outputs, coinbase maturity, lock times),
+     *   spending unspent outputs of the ledger or those of earlier transactions in the block
+     * - No double spending within the block
+     * - Coinbase output does not exceed `block_subsidy(height) + total_fees`
+     *
+     * Input scripts run last, spread over `SCRIPT_THREADS` workers (see `script_check`).
+     */
+    pub fn validate(
+        &self,
+        height: u64,
+        median_time_past: Option<NaiveDateTime>,
+    ) -> Result<(), String> {
+        self.validate_with_options(height, median_time_past, true)
+    }
+
+    /// Like `validate`, skipping input scripts and signatures without
+    /// `verify_scripts` (see `Checkpoints::verifies_scripts`).
+    pub fn validate_with_options(
+        &self,
+        height: u64,
+        median_time_past: Option<NaiveDateTime>,
+        verify_scripts: bool,
+    ) -> Result<(), String> {
+        self.validate_in(&LedgerView, height, median_time_past, verify_scripts)
+    }
+
+    /// Like `validate_with_options`, spending the outputs of `view` instead
+    /// of the ledger's UTXO set, e.g. a fork branch or a block template.
+    pub fn validate_in(
+        &self,
+        view: &dyn UtxoView,
+        height: u64,
+        median_time_past: Option<NaiveDateTime>,
+        verify_scripts: bool,
+    ) -> Result<(), String> {
+        self.check_structure()?;
+
+        let context = InputContext::fetch(
+            view,
+            &self.transactions,
+            height,
+            median_time_past,
+            verify_scripts,
+        )?;
+        let mut checks = Vec::new();
+        let total_fees = self.check_transactions(&context, &mut checks);
+        // Scripts of transactions accepted to our mempool already passed.
+        SCRIPT_CACHE.retain_unverified(&mut checks);
+        // Every queued check belongs to an input before any failure above.
+        run_script_checks(&checks, CONFIG.script_threads)?;
+
+        self.validate_coinbase_reward(height, total_fees?)?;
+        Ok(())
+    }
+
+    /// Transaction checks of `validate`, queueing the input scripts on
+    /// `checks`. Returns the total fees.
+    fn check_transactions(
+        &self,
+        context: &InputContext,
+        checks: &mut Vec<ScriptCheck>,
+    ) -> Result<i64, String> {
+        let mut spent_utxos = HashSet::new();
+        let mut total_fees: i64 = 0;
+        // Transactions may spend outputs of those before them in the block.
+        let mut earlier: HashMap<TxId, &Transaction> =
+            HashMap::with_capacity(self.transactions.len());
+        for tx in &self.transactions {
+            let fee = tx.check_inputs(context, &earlier, checks)?;
+            earlier.insert(tx.id(), tx);
+            if !tx.is_coinbase() {
+                total_fees = total_fees
+                    .checked_add(fee)
+                    .ok_or_else(|| "Total fees overflow".to_string())?;
+            }
+            for input in &tx.inputs {
+                let utxo_key = (input.prev_tx_id, input.output_index);
+                if spent_utxos.contains(&utxo_key) {
+                    return Err(format!(
+                        "Double spending detected in block for UTXO: tx_id: {}, output_index: {}",
+                        bytes_to_hex_string(&input.prev_tx_id),
+                        input.output_index
+                    ));
+                }
+
+                spent_utxos.insert(utxo_key);
+            }
         }
-        let merkle_tree = MerkleTree::from_leaves(leaf_hashes);
-        merkle_tree.root()
+        Ok(total_fees)
     }
 
-    /** Validate the block.
+    /** Context-free checks that do not depend on the block height.
      * Checks:
      * - Block has at least one transaction
      * - Block size is within limits
      * - Proof of work is valid
      * - Merkle root is valid
      * - First transaction is coinbase, and it is the only coinbase
-     * - All transactions are valid (ownership, signatures, inputs >= outputs)
-     * - No double spending within the block
-     * - Coinbase output does not exceed `block_reward + total_fees`
      */
-    pub fn validate(&self) -> Result<(), String> {
+    pub fn check_structure(&self) -> Result<(), String> {
         if self.transactions.is_empty() {
             return Err("Block has no transactions".to_string());
         }
 
-        if self.size() > (CONSENSUS_RULES.max_block_size_kb * 1000.0) as usize {
+        if self.size() > CONSENSUS_RULES.max_block_size_bytes() {
             return Err(format!(
                 "Block size exceeds maximum limit: {} bytes",
                 self.size()
@@ -129,32 +298,47 @@ impl Block {
             return Err("Invalid Merkle root".to_string());
         }
 
-        self.validate_coinbase_uniqueness()?;
+        self.validate_coinbase_uniqueness()
+    }
 
-        let mut spent_utxos = HashSet::new();
-        let mut total_fees: i64 = 0;
-        for tx in &self.transactions {
-            let fee = tx.validate()?;
-            if !tx.is_coinbase() {
-                total_fees = total_fees
-                    .checked_add(fee)
-                    .ok_or_else(|| "Total fees overflow".to_string())?;
-            }
-            for input in &tx.inputs {
-                let utxo_key = (input.prev_tx_id, input.output_index);
-                if spent_utxos.contains(&utxo_key) {
-                    return Err(format!(
-                        "Double spending detected in block for UTXO: tx_id: {}, output_index: {}",
-                        bytes_to_hex_string(&input.prev_tx_id),
-                        input.output_index
-                    ));
-                }
+    /// Work contributed by this block, derived from its header target.
+    pub fn work(&self) -> U256 {
+        target_work(self.header.target)
+    }
 
-                spent_utxos.insert(utxo_key);
-            }
+    /** Timestamp consensus rules.
+     * Checks:
+     * - Timestamp is strictly after the median-time-past of the previous blocks (if any)
+     * - Timestamp is at most `max_future_block_time_secs` ahead of `now`
+     */
+    pub fn check_timestamp(
+        &self,
+        median_time_past: Option<NaiveDateTime>,
+        now: NaiveDateTime,
+    ) -> Result<(), String> {
+        if let Some(mtp) = median_time_past
+            && self.header.timestamp.and_utc().timestamp() <= mtp.and_utc().timestamp()
+        {
+            return Err(format!(
+                "Block timestamp {} is not after median time past {}",
+                format_date(&self.header.timestamp),
+                format_date(&mtp)
+            ));
         }
+        self.check_future_drift(now)
+    }
 
-        self.validate_coinbase_reward(total_fees)?;
+    /// Future drift half of `check_timestamp`, usable when the ancestors are unknown.
+    pub fn check_future_drift(&self, now: NaiveDateTime) -> Result<(), String> {
+        let max_allowed = now.and_utc().timestamp() + CONSENSUS_RULES.max_future_block_time_secs;
+        if self.header.timestamp.and_utc().timestamp() > max_allowed {
+            return Err(format!(
+                "Block timestamp {} is too far in the future (node time {}, max drift {}s)",
+                format_date(&self.header.timestamp),
+                format_date(&now),
+                CONSENSUS_RULES.max_future_block_time_secs
+            ));
+        }
         Ok(())
     }
 
@@ -170,7 +354,7 @@ impl Block {
         Ok(())
     }
 
-    fn validate_coinbase_reward(&self, total_fees: i64) -> Result<(), String> {
+    fn validate_coinbase_reward(&self, height: u64, total_fees: i64) -> Result<(), String> {
         let coinbase = &self.transactions[0];
         let mut coinbase_output_sum: i64 = 0;
         for output in &coinbase.outputs {
@@ -178,14 +362,14 @@ impl Block {
                 .checked_add(output.value)
                 .ok_or_else(|| "Coinbase output sum overflow".to_string())?;
         }
-        let max_reward = CONSENSUS_RULES
-            .block_reward
+        let subsidy = CONSENSUS_RULES.block_subsidy(height);
+        let max_reward = subsidy
             .checked_add(total_fees)
             .ok_or_else(|| "Max reward overflow".to_string())?;
         if coinbase_output_sum > max_reward {
             return Err(format!(
-                "Coinbase reward {} exceeds allowed {} (block_reward {} + fees {})",
-                coinbase_output_sum, max_reward, CONSENSUS_RULES.block_reward, total_fees
+                "Coinbase reward {} exceeds allowed {} (subsidy {} at height {} + fees {})",
+                coinbase_output_sum, max_reward, subsidy, height, total_fees
             ));
         }
         Ok(())
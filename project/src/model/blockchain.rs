@@ -1,10 +1,13 @@
 use super::Block;
+use super::checkpoints::Checkpoints;
+use super::utxo_view::{LedgerView, UtxoView};
+use super::versionbits::{self, Deployment, DeploymentStatus};
 use crate::{
     db::repository::LedgerRepository,
-    globals::{CONFIG, CONSENSUS_RULES},
-    security_utils::bytes_to_hex_string,
+    globals::{CONFIG, CONSENSUS_RULES, NETWORK},
     utils,
 };
+use chrono::NaiveDateTime;
 use primitive_types::U256;
 use serde::{Deserialize, Serialize};
 use std::{
@@ -14,46 +17,65 @@ use std::{
 
 const BLOCKCHAIN_FILE: &str = "bc.json";
 
+/// A data-carrier output found in the main chain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataOutput {
+    pub height: usize,
+    pub block_hash: [u8; 32],
+    pub tx_id: [u8; 32],
+    pub index: usize,
+    pub data: Vec<u8>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Blockchain {
     pub chain: Vec<Block>,
 }
 
-impl Blockchain {
-    pub fn new() -> Self {
-        Blockchain { chain: Vec::new() }
-    }
+/// The first blocks of the main chain followed by the blocks of a branch,
+/// read in place as one chain.
+#[derive(Clone, Copy)]
+struct Branch<'a> {
+    base: &'a [Block],
+    blocks: &'a [Block],
+}
 
-    pub fn get_last_block(&self) -> Option<&Block> {
-        self.chain.last()
+impl<'a> Branch<'a> {
+    fn len(&self) -> usize {
+        self.base.len() + self.blocks.len()
     }
 
-    pub fn get_last_block_hash(&self) -> [u8; 32] {
-        match self.get_last_block() {
-            Some(block) => block.header_hash(),
-            None => [0; 32],
+    fn block(&self, height: usize) -> &'a Block {
+        match height.checked_sub(self.base.len()) {
+            Some(offset) => &self.blocks[offset],
+            None => &self.base[height],
         }
     }
 
-    /// this is used as a network identity in the version handshake.
-    pub fn get_genesis_hash(&self) -> [u8; 32] {
-        self.chain
-            .first()
-            .map(|b| b.header_hash())
-            .unwrap_or([0; 32])
+    fn last_block_hash(&self) -> [u8; 32] {
+        match self.len() {
+            0 => [0; 32],
+            len => self.block(len - 1).header_hash(),
+        }
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.chain.is_empty()
+    /// Median timestamp of the last `median_time_span` blocks, the lower bound for the next block.
+    fn median_time_past(&self) -> Option<NaiveDateTime> {
+        let end = self.len();
+        let start = end.saturating_sub(CONSENSUS_RULES.median_time_span);
+        let timestamps: Vec<NaiveDateTime> = (start..end)
+            .map(|height| self.block(height).header.timestamp)
+            .collect();
+        utils::median_time_past(&timestamps)
     }
 
     /// Calculate the target for the next block using LWMA (zawy12).
-    pub fn calculate_next_target(&self) -> U256 {
-        let height = self.chain.len();
+    fn next_target(&self) -> U256 {
+        let height = self.len();
         let lwma_n = CONSENSUS_RULES.lwma_n;
         let target_secs = CONSENSUS_RULES.target_block_time_secs as i64;
 
-        if height == 0 {
+        if height == 0 || NETWORK.pow_no_retargeting {
             return CONSENSUS_RULES.initial_target;
         }
 
@@ -69,12 +91,12 @@ impl Blockchain {
         for i in window_start..height {
             let prev_ts = if i > 0 {
                 // normal case: use timestamp of previous block
-                self.chain[i - 1].header.timestamp
+                self.block(i - 1).header.timestamp
             } else {
                 // edge case: if we're at the first block, use its timestamp as "previous"
-                self.chain[0].header.timestamp
+                self.block(0).header.timestamp
             };
-            let curr = &self.chain[i];
+            let curr = self.block(i);
 
             let solvetime = curr
                 .header
@@ -117,7 +139,7 @@ impl Blockchain {
             }
         };
 
-        let prev_target = self.chain[height - 1].header.target;
+        let prev_target = self.block(height - 1).header.target;
         next_target
             .max(prev_target / 2)
             .min(match prev_target.checked_mul(U256::from(2u32)) {
@@ -126,16 +148,155 @@ impl Blockchain {
             })
             .max(U256::one())
     }
+}
+
+impl Blockchain {
+    pub fn new() -> Self {
+        Blockchain { chain: Vec::new() }
+    }
 
-    /** Validate the recently mined block and if valid, add it to the chain */
-    pub fn add_block(&mut self, block: Block) -> Result<(), String> {
-        let last_block_hash = self.get_last_block_hash();
+    pub fn get_last_block(&self) -> Option<&Block> {
+        self.chain.last()
+    }
 
-        if block.header.prev_block_hash != last_block_hash {
+    pub fn get_last_block_hash(&self) -> [u8; 32] {
+        match self.get_last_block() {
+            Some(block) => block.header_hash(),
+            None => [0; 32],
+        }
+    }
+
+    /// this is used as a network identity in the version handshake.
+    pub fn get_genesis_hash(&self) -> [u8; 32] {
+        self.chain
+            .first()
+            .map(|b| b.header_hash())
+            .unwrap_or([0; 32])
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chain.is_empty()
+    }
+
+    /// Calculate the target for the next block using LWMA (zawy12).
+    pub fn calculate_next_target(&self) -> U256 {
+        self.branch(self.chain.len(), &[]).next_target()
+    }
+
+    /// Cumulative work of the whole chain, used for fork choice.
+    pub fn chainwork(&self) -> U256 {
+        self.chainwork_at(self.chain.len())
+    }
+
+    /// Cumulative work of the first `height` blocks of the chain.
+    pub fn chainwork_at(&self, height: usize) -> U256 {
+        self.chain[..height.min(self.chain.len())]
+            .iter()
+            .fold(U256::zero(), |acc, block| acc.saturating_add(block.work()))
+    }
+
+    /// Median timestamp of the last `median_time_span` blocks, the lower bound for the next block.
+    pub fn median_time_past(&self) -> Option<NaiveDateTime> {
+        self.median_time_past_at(self.chain.len())
+    }
+
+    /// Median-time-past for a block at `height`, using the main-chain blocks below it.
+    pub fn median_time_past_at(&self, height: usize) -> Option<NaiveDateTime> {
+        self.branch(height.min(self.chain.len()), &[])
+            .median_time_past()
+    }
+
+    /// The first `fork_height` blocks of the chain followed by `blocks`.
+    fn branch<'a>(&'a self, fork_height: usize, blocks: &'a [Block]) -> Branch<'a> {
+        Branch {
+            base: &self.chain[..fork_height],
+            blocks,
+        }
+    }
+
+    /// Version-bits status of `deployment` for the next block.
+    pub fn deployment_status(&self, deployment: &Deployment) -> DeploymentStatus {
+        versionbits::deployment_status(
+            &self.chain,
+            self.chain.len(),
+            deployment,
+            CONSENSUS_RULES.version_bits_window,
+            CONSENSUS_RULES.version_bits_threshold,
+        )
+    }
+
+    /// Header version for the next block, signalling every started or locked-in deployment.
+    pub fn compute_block_version(&self) -> u32 {
+        versionbits::compute_block_version(
+            &self.chain,
+            self.chain.len(),
+            &CONSENSUS_RULES.deployments,
+            CONSENSUS_RULES.version_bits_window,
+            CONSENSUS_RULES.version_bits_threshold,
+        )
+    }
+
+    /** Validate the recently mined block and if valid, apply it to the ledger and add it to the chain.
+     * The block must match `checkpoints`; its scripts are not verified if `successors`, the
+     * blocks known to follow it, lead to the assume-valid block.
+     * The ledger goes first, so a block it refuses leaves both unchanged. */
+    pub fn add_block(
+        &mut self,
+        block: Block,
+        successors: &[Block],
+        checkpoints: &Checkpoints,
+    ) -> Result<(), String> {
+        self.check_next_block(&block, successors, checkpoints, &LedgerView)?;
+        LedgerRepository::new()
+            .apply_block(block.clone())
+            .map_err(|e| format!("Failed to apply block to the ledger: {}", e))?;
+        self.chain.push(block);
+        Ok(())
+    }
+
+    /// Checks `block` as `add_block` does without adding it, spending the
+    /// outputs of `view`, which must follow this chain.
+    pub fn check_next_block(
+        &self,
+        block: &Block,
+        successors: &[Block],
+        checkpoints: &Checkpoints,
+        view: &dyn UtxoView,
+    ) -> Result<(), String> {
+        let branch = self.branch(self.chain.len(), &[]);
+        Self::check_block_after(branch, block, successors, checkpoints, view)
+    }
+
+    /// Like `check_next_block`, for a side branch made of the first
+    /// `fork_height` blocks of this chain followed by `branch`, read in place.
+    pub fn check_branch_block(
+        &self,
+        fork_height: usize,
+        branch: &[Block],
+        block: &Block,
+        successors: &[Block],
+        checkpoints: &Checkpoints,
+        view: &dyn UtxoView,
+    ) -> Result<(), String> {
+        let branch = self.branch(fork_height, branch);
+        Self::check_block_after(branch, block, successors, checkpoints, view)
+    }
+
+    fn check_block_after(
+        branch: Branch,
+        block: &Block,
+        successors: &[Block],
+        checkpoints: &Checkpoints,
+        view: &dyn UtxoView,
+    ) -> Result<(), String> {
+        if block.header.prev_block_hash != branch.last_block_hash() {
             return Err("Previous block hash does not match".to_string());
         }
 
-        let expected_target = self.calculate_next_target();
+        let height = branch.len() as u64;
+        checkpoints.check_block(height, block.id())?;
+
+        let expected_target = branch.next_target();
         if block.header.target != expected_target {
             return Err(format!(
                 "Invalid target: expected {:x}, got {:x}",
@@ -143,23 +304,19 @@ impl Blockchain {
             ));
         }
 
-        if let Err(e) = block.validate() {
-            return Err(format!("Block validation failed: {}", e));
+        let median_time_past = branch.median_time_past();
+        if let Err(e) = block.check_timestamp(median_time_past, utils::get_current_timestamp()) {
+            return Err(format!("Block timestamp rejected: {}", e));
         }
-        let repo = LedgerRepository::new();
-        for tx in &block.transactions {
-            for input in &tx.inputs {
-                let input_utxo = repo.get_utxo(input.prev_tx_id, input.output_index);
-                if input_utxo.is_err() {
-                    return Err(format!(
-                        "Transaction input is not a valid UTXO: tx_id: {}, output_index: {}",
-                        bytes_to_hex_string(&input.prev_tx_id),
-                        input.output_index
-                    ));
-                }
-            }
+
+        if let Err(e) = block.validate_in(
+            view,
+            height,
+            median_time_past,
+            checkpoints.verifies_scripts(height, block, successors),
+        ) {
+            return Err(format!("Block validation failed: {}", e));
         }
-        self.chain.push(block);
         Ok(())
     }
 
@@ -173,6 +330,35 @@ impl Blockchain {
             .position(|block| block.header_hash() == hash)
     }
 
+    /// Data-carrier outputs whose payload starts with `prefix`, oldest first,
+    /// stopping after `limit` matches.
+    pub fn find_data_outputs(&self, prefix: &[u8], limit: usize) -> Vec<DataOutput> {
+        let mut found = Vec::new();
+        for (height, block) in self.chain.iter().enumerate() {
+            for tx in &block.transactions {
+                for (index, output) in tx.outputs.iter().enumerate() {
+                    let Some(data) = output.script.data_payload() else {
+                        continue;
+                    };
+                    if !data.starts_with(prefix) {
+                        continue;
+                    }
+                    if found.len() == limit {
+                        return found;
+                    }
+                    found.push(DataOutput {
+                        height,
+                        block_hash: block.header_hash(),
+                        tx_id: tx.id(),
+                        index,
+                        data,
+                    });
+                }
+            }
+        }
+        found
+    }
+
     pub fn build_block_sequence(&self) -> Vec<[u8; 32]> {
         self.chain.iter().map(|block| block.header_hash()).collect()
     }
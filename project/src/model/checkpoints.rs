@@ -0,0 +1,136 @@
+// Checkpoints and the assume-valid block.
+//
+// A checkpoint pins the hash of the block at a given height. Blocks that
+// conflict with one are invalid, and once the local chain has passed a
+// checkpoint no branch forking at or below it is followed any more, however
+// much work it carries.
+//
+// The assume-valid block is a block whose ancestors are known to carry valid
+// scripts. It and its ancestors skip script and signature verification;
+// amounts, maturity, lock times and double spends are still checked, so the
+// UTXO set stays exact. A block only counts as an ancestor once the
+// assume-valid block is at hand: it must be the block itself or one of the
+// blocks known to follow it, such as the rest of a loaded chain or of a branch
+// being connected. Any other block, on the main chain or not, has its scripts
+// verified. The assume-valid block is also enforced like a checkpoint.
+use std::collections::BTreeMap;
+
+use crate::chain_params::NetworkParams;
+use crate::model::block::{Block, BlockID};
+use crate::security_utils::bytes_to_hex_string;
+
+#[derive(Debug, Clone, Default)]
+pub struct Checkpoints {
+    points: BTreeMap<u64, BlockID>,
+    assume_valid: Option<(u64, BlockID)>,
+}
+
+impl Checkpoints {
+    pub fn new(points: Vec<(u64, BlockID)>, assume_valid: Option<(u64, BlockID)>) -> Self {
+        Checkpoints {
+            points: points.into_iter().collect(),
+            assume_valid,
+        }
+    }
+
+    /// Checkpoints and assume-valid block hard-coded in the network profile.
+    pub fn for_network(params: &NetworkParams) -> Self {
+        let decode = |(height, hash): &(u64, &str)| {
+            let mut id = [0; 32];
+            hex::decode_to_slice(hash, &mut id).expect("invalid checkpoint hash");
+            (*height, id)
+        };
+        Checkpoints::new(
+            params.checkpoints.iter().map(decode).collect(),
+            params.assume_valid.as_ref().map(decode),
+        )
+    }
+
+    /// Parses an assume-valid block given as `<height>:<hex hash>`.
+    /// `0` turns assume-valid off, so every script is verified.
+    pub fn parse_assume_valid(spec: &str) -> Result<Option<(u64, BlockID)>, String> {
+        let spec = spec.trim();
+        if spec == "0" {
+            return Ok(None);
+        }
+        let (height, hash) = spec
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid assume-valid block '{}': use <height>:<hash>", spec))?;
+        let height = height
+            .trim()
+            .parse()
+            .map_err(|_| format!("Invalid assume-valid height '{}'", height))?;
+        let mut id = [0; 32];
+        hex::decode_to_slice(hash.trim(), &mut id)
+            .map_err(|_| format!("Invalid assume-valid hash '{}'", hash))?;
+        Ok(Some((height, id)))
+    }
+
+    /// Replaces the assume-valid block; `None` verifies every script.
+    pub fn with_assume_valid(mut self, assume_valid: Option<(u64, BlockID)>) -> Self {
+        self.assume_valid = assume_valid;
+        self
+    }
+
+    pub fn assume_valid(&self) -> Option<(u64, BlockID)> {
+        self.assume_valid
+    }
+
+    /// Hash the block at `height` must have, if it is pinned.
+    pub fn expected_hash(&self, height: u64) -> Option<BlockID> {
+        match self.assume_valid {
+            Some((assumed_height, hash)) if assumed_height == height => Some(hash),
+            _ => self.points.get(&height).copied(),
+        }
+    }
+
+    /// Rejects a block at `height` whose hash differs from the pinned one.
+    pub fn check_block(&self, height: u64, hash: BlockID) -> Result<(), String> {
+        match self.expected_hash(height) {
+            Some(expected) if expected != hash => Err(format!(
+                "Block {} conflicts with checkpoint {} at height {}",
+                bytes_to_hex_string(&hash),
+                bytes_to_hex_string(&expected),
+                height
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Highest pinned height below `chain_height`, i.e. already passed by a
+    /// chain of that many blocks.
+    pub fn last_passed(&self, chain_height: usize) -> Option<u64> {
+        let below = |height: &u64| *height < chain_height as u64;
+        let checkpoint = self.points.keys().copied().rfind(below);
+        let assumed = self.assume_valid.map(|(height, _)| height).filter(below);
+        checkpoint.max(assumed)
+    }
+
+    /// Rejects a branch whose first block not shared with a local chain of
+    /// `chain_height` blocks sits at `fork_height`, at or below a passed checkpoint.
+    pub fn check_fork(&self, fork_height: u64, chain_height: usize) -> Result<(), String> {
+        match self.last_passed(chain_height) {
+            Some(checkpoint) if fork_height <= checkpoint => Err(format!(
+                "Fork at height {} reorganizes below the checkpoint at height {}",
+                fork_height, checkpoint
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether scripts and signatures of `block`, at `height`, are verified.
+    /// They are skipped only if `block` is the assume-valid block or one of its
+    /// ancestors, the assume-valid block being among `successors`: the blocks
+    /// following `block` on its branch, each the parent of the next.
+    pub fn verifies_scripts(&self, height: u64, block: &Block, successors: &[Block]) -> bool {
+        let Some((assumed_height, assumed)) = self.assume_valid else {
+            return true;
+        };
+        let assumed_block = match assumed_height.checked_sub(height) {
+            Some(0) => Some(block),
+            Some(offset) => successors.get(offset as usize - 1),
+            None => None,
+        };
+        assumed_block.is_none_or(|assumed_block| assumed_block.id() != assumed)
+    }
+}
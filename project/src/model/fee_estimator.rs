@@ -0,0 +1,138 @@
+// Fee estimation from the node's own view of recent blocks.
+//
+// Every transaction accepted into the mempool without unconfirmed parents is
+// tracked with its fee rate and the height of the block it could first enter.
+// When a block confirms it, the number of blocks it waited is recorded. One
+// still unconfirmed after `MAX_CONFIRM_TARGET` blocks is recorded as never
+// confirmed; one that leaves the mempool unmined (replaced, evicted, expired
+// or invalidated) is forgotten without a sample. Samples older than `HISTORY_BLOCKS` are forgotten.
+//
+// To estimate for a target, the samples are walked from the highest fee rate
+// down in groups of `ESTIMATE_GROUP_SIZE`. The estimate is the lowest fee rate
+// of the last group in which at least `SUCCESS_THRESHOLD` of the transactions
+// confirmed within the target.
+use std::collections::{HashMap, VecDeque};
+
+use crate::model::transaction::TxId;
+
+/// Confirmation target of wallet payments that name no fee.
+pub const DEFAULT_CONFIRM_TARGET: u64 = 6;
+/// Most blocks a fee estimate may target.
+pub const MAX_CONFIRM_TARGET: u64 = 25;
+/// Blocks of history the estimates are drawn from.
+pub const HISTORY_BLOCKS: u64 = 500;
+/// Share of transactions in a group that must confirm within the target.
+pub const SUCCESS_THRESHOLD: f64 = 0.85;
+/// Transactions judged together when walking down the fee rates.
+pub const ESTIMATE_GROUP_SIZE: usize = 10;
+
+#[derive(Debug, Clone, Copy)]
+struct FeeSample {
+    fee_rate: f64,
+    /// Blocks waited until confirmation, `None` if it never confirmed.
+    blocks: Option<u64>,
+    /// Height of the block at which the sample was recorded.
+    height: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FeeEstimator {
+    /// Unconfirmed transactions: fee rate and the first height they could be mined at.
+    tracked: HashMap<TxId, (f64, u64)>,
+    samples: VecDeque<FeeSample>,
+    best_height: u64,
+}
+
+impl FeeEstimator {
+    pub fn new() -> Self {
+        FeeEstimator::default()
+    }
+
+    /// Starts tracking `id`, paying `fee_rate` per byte, which can first be
+    /// mined in the block at `height`.
+    pub fn track(&mut self, id: TxId, fee_rate: f64, height: u64) {
+        self.tracked.entry(id).or_insert((fee_rate, height));
+    }
+
+    /// Stops tracking `ids`, which left the mempool without being mined.
+    pub fn untrack(&mut self, ids: &[TxId]) {
+        for id in ids {
+            self.tracked.remove(id);
+        }
+    }
+
+    /// Records how long the tracked transactions among `confirmed` waited
+    /// for the block at `height`.
+    pub fn process_block(&mut self, height: u64, confirmed: &[TxId]) {
+        self.best_height = height;
+        for id in confirmed {
+            if let Some((fee_rate, entry_height)) = self.tracked.remove(id) {
+                self.samples.push_back(FeeSample {
+                    fee_rate,
+                    blocks: Some(height.saturating_sub(entry_height) + 1),
+                    height,
+                });
+            }
+        }
+
+        let mut stale = Vec::new();
+        for (id, (fee_rate, entry_height)) in &self.tracked {
+            if height.saturating_sub(*entry_height) + 1 > MAX_CONFIRM_TARGET {
+                stale.push(*id);
+                self.samples.push_back(FeeSample {
+                    fee_rate: *fee_rate,
+                    blocks: None,
+                    height,
+                });
+            }
+        }
+        for id in stale {
+            self.tracked.remove(&id);
+        }
+
+        while self
+            .samples
+            .front()
+            .is_some_and(|sample| sample.height + HISTORY_BLOCKS <= height)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Fee per byte likely to get a transaction confirmed within
+    /// `target_blocks` blocks, or `None` without enough history.
+    pub fn estimate_fee(&self, target_blocks: u64) -> Option<f64> {
+        let target = target_blocks.clamp(1, MAX_CONFIRM_TARGET);
+        let mut points: Vec<(f64, bool)> = self
+            .samples
+            .iter()
+            .map(|sample| {
+                let success = sample.blocks.is_some_and(|blocks| blocks <= target);
+                (sample.fee_rate, success)
+            })
+            .collect();
+        // Transactions still waiting longer than the target already missed it.
+        points.extend(
+            self.tracked
+                .values()
+                .filter(|(_, entry_height)| {
+                    self.best_height.saturating_sub(*entry_height) + 1 > target
+                })
+                .map(|(fee_rate, _)| (*fee_rate, false)),
+        );
+        points.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut estimate = None;
+        for group in points.chunks(ESTIMATE_GROUP_SIZE) {
+            if group.len() < ESTIMATE_GROUP_SIZE {
+                break;
+            }
+            let successes = group.iter().filter(|(_, success)| *success).count();
+            if (successes as f64) < SUCCESS_THRESHOLD * group.len() as f64 {
+                break;
+            }
+            estimate = group.last().map(|(fee_rate, _)| *fee_rate);
+        }
+        estimate
+    }
+}
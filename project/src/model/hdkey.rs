@@ -1,13 +1,20 @@
+use crate::globals::NETWORK;
+use crate::model::script::hash160;
 use crate::security_utils::{
     bytes_to_hex_string, generate_sk_chain_code_from_data, sha256, sign_hash,
 };
 use bs58;
 use ed25519_dalek::{SecretKey, SigningKey};
 use ed25519_dalek::{Signature, VerifyingKey};
-use ripemd::Ripemd160;
-use sha2::Digest;
 use std::fmt;
 
+/// What the 20-byte hash inside an address commits to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressKind {
+    PubKeyHash,
+    ScriptHash,
+}
+
 #[derive(Debug, Clone)]
 pub struct HDKey {
     pub private_key: SecretKey,
@@ -24,18 +31,38 @@ impl HDKey {
     }
 
     pub fn validate_address(address: &str) -> bool {
-        let decoded = match bs58::decode(address).into_vec() {
-            Ok(vec) => vec,
-            Err(_) => return false,
-        };
+        HDKey::decode_address(address).is_some()
+    }
 
-        if decoded.len() != 26 || decoded[0] != 0x00 || decoded[1] != 0x00 {
-            return false;
+    /// Kind and hash of a valid Base58Check address.
+    pub fn decode_address(address: &str) -> Option<(AddressKind, [u8; 20])> {
+        let decoded = bs58::decode(address).into_vec().ok()?;
+        if decoded.len() != 26 {
+            return None;
         }
+        // Addresses of other networks carry other prefixes and are rejected.
+        let prefix = [decoded[0], decoded[1]];
+        let kind = if prefix == NETWORK.pubkey_hash_prefix {
+            AddressKind::PubKeyHash
+        } else if prefix == NETWORK.script_hash_prefix {
+            AddressKind::ScriptHash
+        } else {
+            return None;
+        };
 
         let (body, checksum) = decoded.split_at(22);
-        let calculated_checksum = &sha256(body)[..4];
-        checksum == calculated_checksum
+        if checksum != &sha256(body)[..4] {
+            return None;
+        }
+        Some((kind, body[2..].try_into().ok()?))
+    }
+
+    /// Pubkey hash carried by a valid pay-to-pubkey-hash address.
+    pub fn pubkey_hash_from_address(address: &str) -> Option<[u8; 20]> {
+        match HDKey::decode_address(address)? {
+            (AddressKind::PubKeyHash, hash) => Some(hash),
+            (AddressKind::ScriptHash, _) => None,
+        }
     }
 
     pub fn derive_child(&self, index: u32) -> HDKey {
@@ -54,11 +81,23 @@ impl HDKey {
     }
 
     fn get_address_impl(public_key: &VerifyingKey) -> String {
-        let digest_1 = sha256(public_key.as_bytes());
-        let ripemd = Ripemd160::digest(digest_1);
-        let mut raw_addr = Vec::with_capacity(2 + ripemd.len() + 4);
-        raw_addr.extend_from_slice(&[0x00, 0x00]);
-        raw_addr.extend_from_slice(&ripemd);
+        HDKey::address_from_pubkey_hash(&hash160(public_key.as_bytes()))
+    }
+
+    /// Encodes a 20-byte pubkey hash as a Base58Check address.
+    pub fn address_from_pubkey_hash(pubkey_hash: &[u8; 20]) -> String {
+        HDKey::encode_address(NETWORK.pubkey_hash_prefix, pubkey_hash)
+    }
+
+    /// Encodes the hash of a redeem script as a Base58Check address.
+    pub fn address_from_script_hash(script_hash: &[u8; 20]) -> String {
+        HDKey::encode_address(NETWORK.script_hash_prefix, script_hash)
+    }
+
+    fn encode_address(prefix: [u8; 2], hash: &[u8; 20]) -> String {
+        let mut raw_addr = Vec::with_capacity(2 + hash.len() + 4);
+        raw_addr.extend_from_slice(&prefix);
+        raw_addr.extend_from_slice(hash);
 
         let checksum = &sha256(&raw_addr)[..4];
         let mut address = raw_addr;
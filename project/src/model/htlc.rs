@@ -0,0 +1,66 @@
+// Hash time-locked contracts, the building block of cross-chain atomic swaps.
+//
+// An HTLC output pays the recipient once they reveal the preimage of a SHA-256
+// hash, or refunds the sender after a timeout. Claiming publishes the preimage
+// on chain, which lets the other side of a swap claim their half with it.
+use serde::{Deserialize, Serialize};
+
+use crate::db::repository::LedgerRepository;
+use crate::model::Script;
+use crate::model::script::{Instruction, OP_1};
+use crate::security_utils::sha256;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct HtlcParams {
+    pub hash: [u8; 32],
+    pub recipient_pubkey_hash: [u8; 20],
+    pub sender_pubkey_hash: [u8; 20],
+    /// Lock time (block height or timestamp) from which the sender can refund.
+    pub timeout: u64,
+}
+
+/// Finds the preimage revealed by a confirmed transaction that claimed an HTLC
+/// output. Refunds and other transactions yield an error.
+pub fn extract_preimage(claim_tx_id: &[u8; 32]) -> Result<Vec<u8>, String> {
+    let repo = LedgerRepository::new();
+    let (tx, height) = repo
+        .get_transaction_with_height(claim_tx_id)
+        .map_err(|e| format!("Failed to fetch transaction: {}", e))?
+        .ok_or_else(|| "Transaction not found".to_string())?;
+    if height.is_none() {
+        return Err("Transaction is not confirmed yet".to_string());
+    }
+
+    for input in &tx.inputs {
+        let Some(prev_tx) = repo
+            .get_transaction(&input.prev_tx_id)
+            .map_err(|e| format!("Failed to fetch transaction: {}", e))?
+        else {
+            continue;
+        };
+        let Some(output) = prev_tx.outputs.get(input.output_index) else {
+            continue;
+        };
+        let Some(params) = output.script.htlc_params() else {
+            continue;
+        };
+        if let Some(preimage) = preimage_from_claim(&input.unlock_script, &params) {
+            return Ok(preimage);
+        }
+    }
+    Err("Transaction does not claim an HTLC output".to_string())
+}
+
+/// Reads the preimage from a claim's unlocking script,
+/// `<sig> <pubkey> <preimage> OP_1`, checking it against the contract's hash.
+pub fn preimage_from_claim(unlock_script: &Script, params: &HtlcParams) -> Option<Vec<u8>> {
+    let instructions = unlock_script.instructions().ok()?;
+    match instructions.as_slice() {
+        [.., Instruction::Push(preimage), Instruction::Op(op)]
+            if *op == OP_1 && sha256(preimage) == params.hash =>
+        {
+            Some(preimage.to_vec())
+        }
+        _ => None,
+    }
+}
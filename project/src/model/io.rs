@@ -1,31 +1,211 @@
 use serde::{Deserialize, Serialize};
 
+use crate::model::Script;
 use crate::security_utils::bytes_to_hex_string;
+use crate::utils::{ByteReader, ByteWriter};
+
+/// Set in `TxInput::relative_lock` when the lock counts seconds instead of blocks.
+pub const RELATIVE_LOCK_TIME_FLAG: u32 = 1 << 31;
+
+/// Signature hash types: which parts of the transaction an input's signature
+/// covers. `SIGHASH_ALL` signs every input and output, `SIGHASH_NONE` no
+/// outputs, and `SIGHASH_SINGLE` only the output at the input's own index.
+pub const SIGHASH_ALL: u8 = 0x01;
+pub const SIGHASH_NONE: u8 = 0x02;
+pub const SIGHASH_SINGLE: u8 = 0x03;
+/// Combined with a base type, the signature covers only its own input, so
+/// others may add inputs afterwards.
+pub const SIGHASH_ANYONECANPAY: u8 = 0x80;
+
+fn default_sighash_type() -> u8 {
+    SIGHASH_ALL
+}
+
+/// Checks that `sighash_type` is a base type, optionally with `SIGHASH_ANYONECANPAY`.
+pub fn check_sighash_type(sighash_type: u8) -> Result<(), String> {
+    match sighash_type & !SIGHASH_ANYONECANPAY {
+        SIGHASH_ALL | SIGHASH_NONE | SIGHASH_SINGLE => Ok(()),
+        _ => Err(format!(
+            "Invalid signature hash type: 0x{:02x}",
+            sighash_type
+        )),
+    }
+}
+
+/// Parses `all`, `none` or `single`, optionally followed by `|anyonecanpay`.
+pub fn parse_sighash_type(name: &str) -> Result<u8, String> {
+    let name = name.trim().to_lowercase();
+    let (base, anyone_can_pay) = match name.split_once('|') {
+        Some((base, "anyonecanpay")) => (base, true),
+        Some(_) => return Err(format!("Invalid signature hash type: {}", name)),
+        None => (name.as_str(), false),
+    };
+    let base = match base {
+        "all" => SIGHASH_ALL,
+        "none" => SIGHASH_NONE,
+        "single" => SIGHASH_SINGLE,
+        _ => return Err(format!("Invalid signature hash type: {}", name)),
+    };
+    Ok(if anyone_can_pay {
+        base | SIGHASH_ANYONECANPAY
+    } else {
+        base
+    })
+}
+
+/// Name of `sighash_type` as accepted by `parse_sighash_type`.
+pub fn sighash_type_name(sighash_type: u8) -> String {
+    let base = match sighash_type & !SIGHASH_ANYONECANPAY {
+        SIGHASH_ALL => "all".to_string(),
+        SIGHASH_NONE => "none".to_string(),
+        SIGHASH_SINGLE => "single".to_string(),
+        _ => format!("0x{:02x}", sighash_type),
+    };
+    if sighash_type & SIGHASH_ANYONECANPAY != 0 {
+        format!("{}|anyonecanpay", base)
+    } else {
+        base
+    }
+}
+
+/// How long after its confirmation an output must wait before this input may spend it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RelativeLock {
+    /// Blocks on top of the one confirming the output.
+    Blocks(u32),
+    /// Seconds of median-time-past since the output was confirmed.
+    Seconds(u32),
+}
+
+impl RelativeLock {
+    /// Value for `TxInput::relative_lock`.
+    pub fn encode(self) -> u32 {
+        match self {
+            RelativeLock::Blocks(n) => n & !RELATIVE_LOCK_TIME_FLAG,
+            RelativeLock::Seconds(n) => (n & !RELATIVE_LOCK_TIME_FLAG) | RELATIVE_LOCK_TIME_FLAG,
+        }
+    }
+}
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct TxInput {
     pub prev_tx_id: [u8; 32], // previous transaction ID
     pub output_index: usize,  // spent output index
+    #[serde(default)]
+    pub relative_lock: u32, // minimum age of the spent output, 0 for none
+    #[serde(default = "default_sighash_type")]
+    pub sighash_type: u8, // parts of the transaction the signature covers
+    #[serde(default)]
+    pub replaceable: bool, // lets a higher-fee conflict replace the transaction in mempools
     pub signature: String,    // owner's signature
     pub public_key: String,   // owner's public key
+    #[serde(default)]
+    pub unlock_script: Script, // explicit unlocking script, empty for [signature, public_key]
 }
 impl TxInput {
-    pub fn get_partial(&self) -> TxInput {
+    /// Unsigned input spending output `output_index` of `prev_tx_id`, with no
+    /// relative lock, `SIGHASH_ALL` and no replacement signal.
+    pub fn new(prev_tx_id: [u8; 32], output_index: usize) -> TxInput {
         TxInput {
-            prev_tx_id: self.prev_tx_id,
-            output_index: self.output_index,
+            prev_tx_id,
+            output_index,
+            relative_lock: 0,
+            sighash_type: SIGHASH_ALL,
+            replaceable: false,
             signature: String::new(),
             public_key: String::new(),
+            unlock_script: Script::new(),
+        }
+    }
+
+    /// Whether the input carries a signature or an unlocking script.
+    pub fn is_signed(&self) -> bool {
+        !self.signature.is_empty() || !self.unlock_script.is_empty()
+    }
+
+    pub fn get_partial(&self) -> TxInput {
+        TxInput {
+            relative_lock: self.relative_lock,
+            sighash_type: self.sighash_type,
+            replaceable: self.replaceable,
+            ..TxInput::new(self.prev_tx_id, self.output_index)
+        }
+    }
+
+    /// Decoded `relative_lock`, `None` when the input has no relative lock.
+    pub fn relative_lock(&self) -> Option<RelativeLock> {
+        match self.relative_lock {
+            0 => None,
+            lock if lock & RELATIVE_LOCK_TIME_FLAG != 0 => {
+                Some(RelativeLock::Seconds(lock & !RELATIVE_LOCK_TIME_FLAG))
+            }
+            lock => Some(RelativeLock::Blocks(lock)),
         }
     }
 
+    /// Script run before the spent output's locking script. Inputs without an
+    /// explicit one push their signature and public key, which satisfies a
+    /// pay-to-pubkey-hash output.
+    pub fn unlocking_script(&self) -> Result<Script, String> {
+        if !self.unlock_script.is_empty() {
+            return Ok(self.unlock_script.clone());
+        }
+        let mut script = Script::new();
+        if !self.signature.is_empty() {
+            let sig = hex::decode(&self.signature)
+                .map_err(|e| format!("Invalid signature hex: {}", e))?;
+            script.push_data(&sig);
+        }
+        if !self.public_key.is_empty() {
+            let pubkey = hex::decode(&self.public_key)
+                .map_err(|e| format!("Invalid public key hex: {}", e))?;
+            script.push_data(&pubkey);
+        }
+        Ok(script)
+    }
+
+    /// Smallest possible encoded input, used to bound decoded sequence lengths.
+    pub(crate) const MIN_ENCODED_SIZE: usize = 32 + 4 + 4 + 1 + 1 + 4 + 4 + 4;
+
     pub fn as_bytes(&self) -> Vec<u8> {
-        let mut out = Vec::new();
-        out.extend_from_slice(&self.prev_tx_id);
-        out.extend_from_slice(&self.output_index.to_be_bytes());
-        out.extend_from_slice(self.signature.as_bytes());
-        out.extend_from_slice(self.public_key.as_bytes());
-        out
+        let mut w = ByteWriter::versioned();
+        self.encode_into(&mut w);
+        w.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<TxInput, String> {
+        let mut r = ByteReader::versioned(bytes)?;
+        let input = TxInput::decode_from(&mut r)?;
+        r.finish()?;
+        Ok(input)
+    }
+
+    pub(crate) fn encode_into(&self, w: &mut ByteWriter) {
+        w.put_hash(&self.prev_tx_id);
+        w.put_u32(self.output_index as u32);
+        w.put_u32(self.relative_lock);
+        w.put_u8(self.sighash_type);
+        w.put_u8(self.replaceable as u8);
+        w.put_str(&self.signature);
+        w.put_str(&self.public_key);
+        w.put_bytes(self.unlock_script.as_bytes());
+    }
+
+    pub(crate) fn decode_from(r: &mut ByteReader) -> Result<TxInput, String> {
+        Ok(TxInput {
+            prev_tx_id: r.get_hash()?,
+            output_index: r.get_u32()? as usize,
+            relative_lock: r.get_u32()?,
+            sighash_type: r.get_u8()?,
+            replaceable: match r.get_u8()? {
+                0 => false,
+                1 => true,
+                flag => return Err(format!("Invalid replaceable flag: {}", flag)),
+            },
+            signature: r.get_string()?,
+            public_key: r.get_string()?,
+            unlock_script: Script::from_bytes(r.get_bytes()?),
+        })
     }
 }
 
@@ -34,8 +214,12 @@ impl std::fmt::Debug for TxInput {
         f.debug_struct("TxInput")
             .field("prev_tx_id", &bytes_to_hex_string(&self.prev_tx_id))
             .field("output_index", &self.output_index)
+            .field("relative_lock", &self.relative_lock())
+            .field("sighash_type", &self.sighash_type)
+            .field("replaceable", &self.replaceable)
             .field("signature", &self.signature)
             .field("public_key", &self.public_key)
+            .field("unlock_script", &self.unlock_script)
             .finish()
     }
 }
@@ -44,14 +228,66 @@ impl std::fmt::Debug for TxInput {
 pub struct TxOutput {
     pub value: i64,
     pub address: String, // endereço destino (ex: Base58Check)
+    #[serde(default)]
+    pub script: Script, // locking script, empty for pay-to-pubkey-hash to `address`
 }
 
 impl TxOutput {
+    /// Pays `value` to a Base58 address.
+    pub fn new(value: i64, address: String) -> Self {
+        TxOutput {
+            value,
+            address,
+            script: Script::new(),
+        }
+    }
+
+    /// Locks `value` with an explicit script. The address is derived from the
+    /// script when it pays to a single key and left empty otherwise.
+    pub fn with_script(value: i64, script: Script) -> Self {
+        TxOutput {
+            value,
+            address: script.address().unwrap_or_default(),
+            script,
+        }
+    }
+
+    /// Script that must be satisfied to spend this output.
+    pub fn locking_script(&self) -> Result<Script, String> {
+        if self.script.is_empty() {
+            Script::for_address(&self.address)
+        } else {
+            Ok(self.script.clone())
+        }
+    }
+
+    pub(crate) const MIN_ENCODED_SIZE: usize = 8 + 4 + 4;
+
     pub fn as_bytes(o: &TxOutput) -> Vec<u8> {
-        let mut out = Vec::new();
-        out.extend_from_slice(&o.value.to_be_bytes());
-        out.extend_from_slice(o.address.as_bytes());
-        out
+        let mut w = ByteWriter::versioned();
+        o.encode_into(&mut w);
+        w.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<TxOutput, String> {
+        let mut r = ByteReader::versioned(bytes)?;
+        let output = TxOutput::decode_from(&mut r)?;
+        r.finish()?;
+        Ok(output)
+    }
+
+    pub(crate) fn encode_into(&self, w: &mut ByteWriter) {
+        w.put_i64(self.value);
+        w.put_str(&self.address);
+        w.put_bytes(self.script.as_bytes());
+    }
+
+    pub(crate) fn decode_from(r: &mut ByteReader) -> Result<TxOutput, String> {
+        Ok(TxOutput {
+            value: r.get_i64()?,
+            address: r.get_string()?,
+            script: Script::from_bytes(r.get_bytes()?),
+        })
     }
 }
 
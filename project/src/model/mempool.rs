@@ -0,0 +1,571 @@
+// Mempool policy: chains of unconfirmed transactions, conflicts, replace-by-fee
+// and the limits that keep the pool bounded.
+//
+// A mempool transaction may spend outputs of other mempool transactions, its
+// parents. Chains are bounded by `MAX_ANCESTORS` and `MAX_DESCENDANTS`, counting
+// the transaction itself.
+//
+// A transaction that spends an output some mempool transaction already spends
+// conflicts with it. It is refused unless every conflicting transaction opted
+// in to replacement (see `Transaction::signals_replacement`) and it pays more,
+// both in total and per byte, than everything it would evict.
+//
+// The pool holds at most `MempoolLimits::max_size` bytes of transactions. Once
+// full, the packages (a transaction and its descendants) with the lowest fee
+// rate are evicted and the minimum fee rate for entry rises above theirs, then
+// decays back towards the minimum relay fee rate. Transactions older than
+// `MempoolLimits::expiry_secs` are dropped along with their descendants.
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::globals::CONFIG;
+use crate::model::transaction::TxId;
+use crate::model::{MempoolTx, Transaction, UTXO};
+use crate::security_utils::bytes_to_hex_string;
+
+/// Most transactions, conflicts and their descendants together, one replacement may evict.
+pub const MAX_REPLACEMENT_EVICTIONS: usize = 100;
+/// Most mempool transactions in a chain ending at a transaction, itself included.
+pub const MAX_ANCESTORS: usize = 25;
+/// Most mempool transactions in a chain starting at a transaction, itself included.
+pub const MAX_DESCENDANTS: usize = 25;
+/// Fee per byte the minimum fee rate rises above an evicted package's rate.
+pub const INCREMENTAL_RELAY_FEE_RATE: f64 = 1.0;
+/// Seconds for the raised minimum fee rate to decay by half.
+pub const ROLLING_FEE_HALFLIFE_SECS: i64 = 12 * 60 * 60;
+
+/// Bounds on the mempool.
+#[derive(Debug, Clone, Copy)]
+pub struct MempoolLimits {
+    /// Most bytes of transactions held at once.
+    pub max_size: usize,
+    /// Seconds a transaction may wait before it is dropped.
+    pub expiry_secs: i64,
+    /// Lowest fee per byte accepted, whatever the mempool size.
+    pub min_relay_fee_rate: f64,
+}
+
+impl MempoolLimits {
+    /// Limits set by `MAX_MEMPOOL_SIZE_KB`, `MEMPOOL_EXPIRY_HOURS` and `MIN_RELAY_FEE_RATE`.
+    pub fn from_config() -> Self {
+        MempoolLimits {
+            max_size: CONFIG.max_mempool_size_kb * 1000,
+            expiry_secs: CONFIG.mempool_expiry_hours as i64 * 60 * 60,
+            min_relay_fee_rate: CONFIG.min_relay_fee_rate,
+        }
+    }
+}
+
+/// A mempool transaction with what the pool tracks about it.
+#[derive(Debug, Clone)]
+pub struct MempoolEntry {
+    pub mem_tx: MempoolTx,
+    /// Unix time the transaction entered the mempool.
+    pub time: i64,
+    pub fee: i64,
+    pub size: usize,
+    /// Fee and size of the transaction together with all its mempool descendants.
+    pub descendant_fee: i64,
+    pub descendant_size: usize,
+    sequence: u64,
+}
+
+impl MempoolEntry {
+    /// Fee rate deciding eviction: the better of the transaction's own rate
+    /// and that of its package with its descendants, so a parent is kept for
+    /// a child paying for it.
+    fn eviction_score(&self) -> i64 {
+        fee_rate_key(self.fee, self.size)
+            .max(fee_rate_key(self.descendant_fee, self.descendant_size))
+    }
+}
+
+/// Fee per thousand bytes, an exact order for fee rates.
+fn fee_rate_key(fee: i64, size: usize) -> i64 {
+    (fee as i128 * 1000 / size.max(1) as i128) as i64
+}
+
+/// Unconfirmed transactions, indexed by id, by the outputs they spend, by
+/// arrival and by eviction score.
+#[derive(Debug, Clone)]
+pub struct Mempool {
+    limits: MempoolLimits,
+    entries: HashMap<TxId, MempoolEntry>,
+    spent_by: HashMap<(TxId, usize), TxId>,
+    by_arrival: BTreeMap<u64, TxId>,
+    by_score: BTreeSet<(i64, TxId)>,
+    total_size: usize,
+    next_sequence: u64,
+    rolling_min_fee_rate: f64,
+    rolling_updated_at: i64,
+}
+
+impl Mempool {
+    pub fn new(limits: MempoolLimits) -> Self {
+        Mempool {
+            limits,
+            entries: HashMap::new(),
+            spent_by: HashMap::new(),
+            by_arrival: BTreeMap::new(),
+            by_score: BTreeSet::new(),
+            total_size: 0,
+            next_sequence: 0,
+            rolling_min_fee_rate: 0.0,
+            rolling_updated_at: 0,
+        }
+    }
+
+    /// A mempool without size or fee limits holding `txs`, all arrived at time 0.
+    pub fn from_txs(txs: Vec<MempoolTx>) -> Self {
+        let mut mempool = Mempool::new(MempoolLimits {
+            max_size: usize::MAX,
+            expiry_secs: i64::MAX,
+            min_relay_fee_rate: 0.0,
+        });
+        for mem_tx in txs {
+            mempool.insert(mem_tx, 0);
+        }
+        mempool
+    }
+
+    pub fn limits(&self) -> &MempoolLimits {
+        &self.limits
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Bytes of transactions held.
+    pub fn total_size(&self) -> usize {
+        self.total_size
+    }
+
+    pub fn contains(&self, id: &TxId) -> bool {
+        self.entries.contains_key(id)
+    }
+
+    pub fn get(&self, id: &TxId) -> Option<&MempoolTx> {
+        self.entries.get(id).map(|entry| &entry.mem_tx)
+    }
+
+    pub fn get_entry(&self, id: &TxId) -> Option<&MempoolEntry> {
+        self.entries.get(id)
+    }
+
+    /// Transactions in the order they arrived.
+    pub fn iter(&self) -> impl Iterator<Item = &MempoolTx> {
+        self.by_arrival.values().map(|id| &self.entries[id].mem_tx)
+    }
+
+    pub fn to_vec(&self) -> Vec<MempoolTx> {
+        self.iter().cloned().collect()
+    }
+
+    pub fn clear(&mut self) {
+        *self = Mempool::new(self.limits);
+    }
+
+    /// Lowest fee per byte a new transaction must pay: the minimum relay fee
+    /// rate, or more while the raised rate after an eviction decays.
+    pub fn min_fee_rate(&self, now: i64) -> f64 {
+        let elapsed = (now - self.rolling_updated_at).max(0) as f64;
+        let rolling =
+            self.rolling_min_fee_rate * 0.5_f64.powf(elapsed / ROLLING_FEE_HALFLIFE_SECS as f64);
+        if rolling < INCREMENTAL_RELAY_FEE_RATE / 2.0 {
+            return self.limits.min_relay_fee_rate;
+        }
+        rolling.max(self.limits.min_relay_fee_rate)
+    }
+
+    /// Adds `mem_tx`, arrived at `now`, without any policy check.
+    pub fn insert(&mut self, mem_tx: MempoolTx, now: i64) {
+        let id = mem_tx.id();
+        if self.entries.contains_key(&id) {
+            return;
+        }
+        let fee = mem_tx.calculate_fee();
+        let size = mem_tx.tx.size();
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.add_entry(MempoolEntry {
+            mem_tx,
+            time: now,
+            fee,
+            size,
+            descendant_fee: fee,
+            descendant_size: size,
+            sequence,
+        });
+    }
+
+    /// Files `entry` in every index. Its descendant totals, and those of its
+    /// ancestors, are recomputed, so entries can be added in any order.
+    fn add_entry(&mut self, entry: MempoolEntry) {
+        let id = entry.mem_tx.id();
+        for input in &entry.mem_tx.tx.inputs {
+            self.spent_by
+                .insert((input.prev_tx_id, input.output_index), id);
+        }
+        self.by_arrival.insert(entry.sequence, id);
+        self.total_size += entry.size;
+        let mut changed = self.find_ancestors(&entry.mem_tx.tx);
+        self.entries.insert(id, entry);
+        changed.push(id);
+        self.refresh_descendant_totals(&changed);
+    }
+
+    /// Adds `mem_tx`, arrived at `time`, in place of the transactions
+    /// `replaced`, then trims the pool to its size limit. Returns the ids of
+    /// the trimmed transactions, or fails and leaves the pool as it was if
+    /// `mem_tx` itself would be trimmed.
+    pub fn insert_replacing(
+        &mut self,
+        mem_tx: MempoolTx,
+        replaced: &[TxId],
+        time: i64,
+        now: i64,
+    ) -> Result<Vec<TxId>, String> {
+        let id = mem_tx.id();
+        let rolling = (self.rolling_min_fee_rate, self.rolling_updated_at);
+        let mut removed = self.take(replaced);
+        self.insert(mem_tx, time);
+        let trimmed = self.trim(now);
+        if !trimmed.iter().any(|entry| entry.mem_tx.id() == id) {
+            return Ok(trimmed.iter().map(|entry| entry.mem_tx.id()).collect());
+        }
+        let min_fee_rate = self.min_fee_rate(now);
+        removed.extend(trimmed.into_iter().filter(|entry| entry.mem_tx.id() != id));
+        for entry in removed {
+            self.add_entry(entry);
+        }
+        (self.rolling_min_fee_rate, self.rolling_updated_at) = rolling;
+        Err(format!(
+            "Mempool full: fee rate below the minimum of {:.3}",
+            min_fee_rate
+        ))
+    }
+
+    /// Removes the transactions `ids`, leaving their descendants in place.
+    pub fn remove(&mut self, ids: &[TxId]) {
+        self.take(ids);
+    }
+
+    /// Removes the transactions `ids` like `remove`, returning their entries.
+    fn take(&mut self, ids: &[TxId]) -> Vec<MempoolEntry> {
+        let removed: HashSet<TxId> = ids
+            .iter()
+            .copied()
+            .filter(|id| self.entries.contains_key(id))
+            .collect();
+        let mut ancestors: HashSet<TxId> = HashSet::new();
+        for id in &removed {
+            ancestors.extend(self.find_ancestors(&self.entries[id].mem_tx.tx));
+        }
+        let mut entries = Vec::with_capacity(removed.len());
+        for id in ids {
+            let Some(entry) = self.entries.remove(id) else {
+                continue;
+            };
+            self.by_arrival.remove(&entry.sequence);
+            self.by_score.remove(&(entry.eviction_score(), *id));
+            self.total_size -= entry.size;
+            for input in &entry.mem_tx.tx.inputs {
+                let outpoint = (input.prev_tx_id, input.output_index);
+                if self.spent_by.get(&outpoint) == Some(id) {
+                    self.spent_by.remove(&outpoint);
+                }
+            }
+            entries.push(entry);
+        }
+        let remaining: Vec<TxId> = ancestors
+            .into_iter()
+            .filter(|id| !removed.contains(id))
+            .collect();
+        self.refresh_descendant_totals(&remaining);
+        entries
+    }
+
+    /// Removes the transactions `ids` and every mempool transaction depending
+    /// on them, returning the ids of all removed.
+    pub fn remove_with_descendants(&mut self, ids: &[TxId]) -> Vec<TxId> {
+        self.take_with_descendants(ids)
+            .iter()
+            .map(|entry| entry.mem_tx.id())
+            .collect()
+    }
+
+    fn take_with_descendants(&mut self, ids: &[TxId]) -> Vec<MempoolEntry> {
+        let mut removed: Vec<TxId> = ids
+            .iter()
+            .copied()
+            .filter(|id| self.entries.contains_key(id))
+            .collect();
+        removed.extend(self.find_descendants(&removed));
+        self.take(&removed)
+    }
+
+    /// Drops transactions that waited longer than the expiry time, with
+    /// their descendants, returning the ids of all removed.
+    pub fn expire(&mut self, now: i64) -> Vec<TxId> {
+        let cutoff = now.saturating_sub(self.limits.expiry_secs);
+        let expired: Vec<TxId> = self
+            .entries
+            .values()
+            .filter(|entry| entry.time < cutoff)
+            .map(|entry| entry.mem_tx.id())
+            .collect();
+        self.remove_with_descendants(&expired)
+    }
+
+    /// Evicts the lowest-scoring packages until the pool fits its size limit,
+    /// raising the minimum fee rate above the best one evicted. Returns the
+    /// ids of all evicted transactions.
+    pub fn trim_to_size(&mut self, now: i64) -> Vec<TxId> {
+        self.trim(now)
+            .iter()
+            .map(|entry| entry.mem_tx.id())
+            .collect()
+    }
+
+    fn trim(&mut self, now: i64) -> Vec<MempoolEntry> {
+        let mut evicted = Vec::new();
+        while self.total_size > self.limits.max_size {
+            let Some(&(score, id)) = self.by_score.first() else {
+                break;
+            };
+            let rate = score as f64 / 1000.0 + INCREMENTAL_RELAY_FEE_RATE;
+            self.rolling_min_fee_rate = self.min_fee_rate(now).max(rate);
+            self.rolling_updated_at = now;
+            evicted.extend(self.take_with_descendants(&[id]));
+        }
+        evicted
+    }
+
+    /// Recomputes the descendant totals of `ids` and re-files them by score.
+    fn refresh_descendant_totals(&mut self, ids: &[TxId]) {
+        for id in ids {
+            let descendants = self.find_descendants(&[*id]);
+            let (mut fee, mut size) = (0, 0);
+            for descendant in &descendants {
+                let entry = &self.entries[descendant];
+                fee += entry.fee;
+                size += entry.size;
+            }
+            let Some(entry) = self.entries.get_mut(id) else {
+                continue;
+            };
+            self.by_score.remove(&(entry.eviction_score(), *id));
+            entry.descendant_fee = entry.fee + fee;
+            entry.descendant_size = entry.size + size;
+            self.by_score.insert((entry.eviction_score(), *id));
+        }
+    }
+
+    /// Mempool transactions whose outputs `tx` spends.
+    pub fn find_parents(&self, tx: &Transaction) -> Vec<&MempoolTx> {
+        let mut parents: Vec<&MempoolTx> = Vec::new();
+        for input in &tx.inputs {
+            if let Some(parent) = self.get(&input.prev_tx_id)
+                && !parents.iter().any(|p| p.id() == input.prev_tx_id)
+            {
+                parents.push(parent);
+            }
+        }
+        parents
+    }
+
+    /// Ids of the mempool transactions `tx` depends on: its parents, their
+    /// parents and so on.
+    pub fn find_ancestors(&self, tx: &Transaction) -> Vec<TxId> {
+        let mut ancestors: Vec<TxId> = Vec::new();
+        let mut pending: Vec<&MempoolTx> = self.find_parents(tx);
+        while let Some(parent) = pending.pop() {
+            let id = parent.id();
+            if ancestors.contains(&id) {
+                continue;
+            }
+            ancestors.push(id);
+            pending.extend(self.find_parents(&parent.tx));
+        }
+        ancestors
+    }
+
+    /// The mempool ancestors of `tx`, each one ahead of the transactions
+    /// spending it, so they can be connected in turn onto a UTXO view.
+    pub fn sorted_ancestors(&self, tx: &Transaction) -> Vec<&MempoolTx> {
+        let mut sorted = Vec::new();
+        self.push_ancestors(tx, &mut HashSet::new(), &mut sorted);
+        sorted
+    }
+
+    fn push_ancestors<'a>(
+        &'a self,
+        tx: &Transaction,
+        seen: &mut HashSet<TxId>,
+        sorted: &mut Vec<&'a MempoolTx>,
+    ) {
+        for parent in self.find_parents(tx) {
+            if seen.insert(parent.id()) {
+                self.push_ancestors(&parent.tx, seen, sorted);
+                sorted.push(parent);
+            }
+        }
+    }
+
+    /// Ids of the mempool transactions spending outputs of `roots`, directly or
+    /// through other mempool transactions. `roots` themselves are left out.
+    pub fn find_descendants(&self, roots: &[TxId]) -> Vec<TxId> {
+        let mut seen: HashSet<TxId> = roots.iter().copied().collect();
+        let mut pending: Vec<TxId> = roots.to_vec();
+        let mut descendants = Vec::new();
+        while let Some(parent) = pending.pop() {
+            let Some(entry) = self.entries.get(&parent) else {
+                continue;
+            };
+            for index in 0..entry.mem_tx.tx.outputs.len() {
+                if let Some(&child) = self.spent_by.get(&(parent, index))
+                    && seen.insert(child)
+                {
+                    pending.push(child);
+                    descendants.push(child);
+                }
+            }
+        }
+        descendants
+    }
+
+    /// Ids of the mempool transactions spending an output that `tx` also spends.
+    pub fn find_conflicts(&self, tx: &Transaction) -> Vec<TxId> {
+        let mut conflicts: Vec<TxId> = Vec::new();
+        for input in &tx.inputs {
+            if let Some(&id) = self.spent_by.get(&(input.prev_tx_id, input.output_index))
+                && !conflicts.contains(&id)
+            {
+                conflicts.push(id);
+            }
+        }
+        conflicts
+    }
+
+    /// Outputs, confirmed or not, spent by mempool transactions.
+    pub fn spent_outpoints(&self) -> HashSet<(TxId, usize)> {
+        self.spent_by.keys().copied().collect()
+    }
+
+    /// Output `index` of mempool transaction `tx_id`, if no other mempool
+    /// transaction spends it and it can be spent at all.
+    pub fn get_unspent_output(&self, tx_id: &TxId, index: usize) -> Option<UTXO> {
+        let output = self.get(tx_id)?.tx.outputs.get(index)?;
+        if output.script.is_unspendable() || self.spent_by.contains_key(&(*tx_id, index)) {
+            return None;
+        }
+        Some(UTXO {
+            tx_id: *tx_id,
+            index,
+            output: output.clone(),
+        })
+    }
+
+    /// Outputs of mempool transactions that no other mempool transaction spends
+    /// and that can be spent at all.
+    pub fn unspent_outputs(&self) -> Vec<UTXO> {
+        self.iter()
+            .flat_map(|mem_tx| {
+                let tx_id = mem_tx.id();
+                (0..mem_tx.tx.outputs.len())
+                    .filter_map(move |index| self.get_unspent_output(&tx_id, index))
+            })
+            .collect()
+    }
+
+    /// Checks that adding `tx` keeps every chain it joins within `MAX_ANCESTORS`
+    /// and `MAX_DESCENDANTS`.
+    pub fn check_chain_limits(&self, tx: &Transaction) -> Result<(), String> {
+        let ancestors = self.find_ancestors(tx);
+        if ancestors.len() + 1 > MAX_ANCESTORS {
+            return Err(format!(
+                "Transaction would have {} unconfirmed ancestors, more than {}",
+                ancestors.len(),
+                MAX_ANCESTORS - 1
+            ));
+        }
+        for ancestor in &ancestors {
+            let descendants = self.find_descendants(&[*ancestor]).len();
+            if descendants + 2 > MAX_DESCENDANTS {
+                return Err(format!(
+                    "Unconfirmed ancestor {} already has {} descendants",
+                    bytes_to_hex_string(ancestor),
+                    descendants
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that `replacement` may take the place of the mempool transactions
+    /// `conflicts` and returns every transaction it evicts: the conflicts and
+    /// their descendants.
+    pub fn check_replacement(
+        &self,
+        replacement: &MempoolTx,
+        conflicts: &[TxId],
+    ) -> Result<Vec<TxId>, String> {
+        let replacement_fee_rate = replacement.calculate_fee_per_byte();
+        for id in conflicts {
+            let Some(conflict) = self.get(id) else {
+                continue;
+            };
+            if !conflict.tx.signals_replacement() {
+                return Err(format!(
+                    "Transaction conflicts with non-replaceable mempool transaction {}",
+                    bytes_to_hex_string(id)
+                ));
+            }
+            if replacement_fee_rate <= conflict.calculate_fee_per_byte() {
+                return Err(format!(
+                    "Replacement fee rate {:.3} does not exceed the {:.3} of transaction {}",
+                    replacement_fee_rate,
+                    conflict.calculate_fee_per_byte(),
+                    bytes_to_hex_string(id)
+                ));
+            }
+        }
+
+        let mut evicted = conflicts.to_vec();
+        evicted.extend(self.find_descendants(conflicts));
+        if let Some(id) = self
+            .find_ancestors(&replacement.tx)
+            .iter()
+            .find(|id| evicted.contains(id))
+        {
+            return Err(format!(
+                "Replacement spends an output of transaction {}, which it would evict",
+                bytes_to_hex_string(id)
+            ));
+        }
+        if evicted.len() > MAX_REPLACEMENT_EVICTIONS {
+            return Err(format!(
+                "Replacement would evict {} transactions, more than {}",
+                evicted.len(),
+                MAX_REPLACEMENT_EVICTIONS
+            ));
+        }
+        let evicted_fees: i64 = evicted
+            .iter()
+            .filter_map(|id| self.entries.get(id))
+            .map(|entry| entry.fee)
+            .sum();
+        if replacement.calculate_fee() <= evicted_fees {
+            return Err(format!(
+                "Replacement fee {} does not exceed the {} paid by the transactions it replaces",
+                replacement.calculate_fee(),
+                evicted_fees
+            ));
+        }
+        Ok(evicted)
+    }
+}
@@ -1,9 +1,10 @@
-use std::collections::HashSet;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::sync::{
     Arc,
     atomic::{AtomicBool, Ordering},
 };
 
+use chrono::{Duration, NaiveDateTime};
 use tokio::task::JoinSet;
 
 use primitive_types::U256;
@@ -12,8 +13,12 @@ use crate::model::get_node_mut;
 use crate::utils::log_info;
 use crate::{
     daemon::types::MineBlockResponse,
-    globals::{CONFIG, CONSENSUS_RULES},
-    model::{Block, MempoolTx, Transaction, Wallet, transaction::TxId},
+    globals::{CONFIG, CONSENSUS_RULES, NETWORK},
+    model::{
+        Block, HDKey, MempoolTx, Transaction, Wallet,
+        transaction::TxId,
+        utxo_view::{LedgerView, UtxoOverlay},
+    },
     security_utils::{bytes_to_hex_string, hash_meets_target},
     utils::{self, format_difficulty, format_target_hex, transaction_model_to_view},
 };
@@ -22,6 +27,20 @@ pub struct Miner {
     pub wallet: Option<Wallet>,
 }
 
+/// Work captured from the node under the lock, mined without holding it.
+pub struct MiningSnapshot {
+    pub mempool: Vec<MempoolTx>,
+    pub previous_hash: [u8; 32],
+    /// Height of the block being mined, used for its coinbase subsidy.
+    pub height: u64,
+    /// The mined block timestamp must be strictly after this value.
+    pub median_time_past: Option<NaiveDateTime>,
+    pub target: U256,
+    /// Header version, signalling the deployments this node is ready for.
+    pub version: u32,
+    pub receive_addr: String,
+}
+
 impl Miner {
     pub fn new() -> Self {
         Miner {
@@ -69,11 +88,30 @@ impl Miner {
     }
 }
 
-fn get_legit_txs<'a>(mempool: &'a Vec<MempoolTx>) -> Vec<&'a MempoolTx> {
+fn get_legit_txs<'a>(
+    mempool: &'a Vec<MempoolTx>,
+    height: u64,
+    median_time_past: Option<NaiveDateTime>,
+) -> Vec<&'a MempoolTx> {
+    let mempool_ids: HashSet<TxId> = mempool.iter().map(|mem_tx| mem_tx.id()).collect();
     let mut seen_utxos: HashSet<(TxId, usize)> = HashSet::new();
     let mut selected_txs: Vec<&MempoolTx> = Vec::new();
     for mem_tx in mempool {
         let tx = &mem_tx.tx;
+        if let Err(e) = tx
+            .check_coinbase_maturity(height, |id| mempool_ids.contains(id))
+            .and_then(|_| tx.check_finality(height, median_time_past))
+        {
+            utils::log_warning(
+                utils::LogCategory::Core,
+                &format!(
+                    "Skipping transaction {}: {}",
+                    bytes_to_hex_string(&tx.id()),
+                    e
+                ),
+            );
+            continue;
+        }
         let mut double_input = false;
         for input in &tx.inputs {
             if seen_utxos.contains(&(input.prev_tx_id, input.output_index)) {
@@ -91,127 +129,280 @@ fn get_legit_txs<'a>(mempool: &'a Vec<MempoolTx>) -> Vec<&'a MempoolTx> {
         );
         selected_txs.push(mem_tx);
     }
-    selected_txs
-}
 
-fn sorted_legit_txs_by_fee_rate(mempool: &Vec<MempoolTx>) -> Vec<&MempoolTx> {
-    let mut txs = get_legit_txs(mempool);
-    txs.sort_by(|a, b| {
-        let fee_rate_a = a.calculate_fee_per_byte();
-        let fee_rate_b = b.calculate_fee_per_byte();
-        fee_rate_b
-            .partial_cmp(&fee_rate_a)
-            .unwrap_or(std::cmp::Ordering::Equal)
-    });
-    txs
+    // A child can only be mined along with its mempool parents.
+    loop {
+        let selected_ids: HashSet<TxId> = selected_txs.iter().map(|mem_tx| mem_tx.id()).collect();
+        let before = selected_txs.len();
+        selected_txs.retain(|mem_tx| {
+            mem_tx.tx.inputs.iter().all(|input| {
+                !mempool_ids.contains(&input.prev_tx_id) || selected_ids.contains(&input.prev_tx_id)
+            })
+        });
+        if selected_txs.len() == before {
+            break;
+        }
+    }
+    selected_txs
 }
 
 fn configured_max_block_size_bytes() -> usize {
-    (CONSENSUS_RULES.max_block_size_kb * 1000.0) as usize
+    CONSENSUS_RULES.max_block_size_bytes()
 }
 
 fn build_transactions_with_coinbase(
     selected_txs: &[Transaction],
     receive_addr: &str,
+    height: u64,
     total_fees: i64,
 ) -> Vec<Transaction> {
     let mut block_txs = selected_txs.to_vec();
     block_txs.insert(
         0,
-        Transaction::new_coinbase(receive_addr.to_string(), total_fees),
+        Transaction::new_coinbase(receive_addr.to_string(), height, total_fees),
     );
     block_txs
 }
 
 fn build_block_from_transactions(
-    previous_hash: [u8; 32],
-    target: U256,
+    snapshot: &MiningSnapshot,
     transactions: Vec<Transaction>,
 ) -> Block {
-    let mut block = Block::new(previous_hash, target);
+    let mut block = Block::new(snapshot.previous_hash, snapshot.target, snapshot.version);
+    // keep the timestamp above the median-time-past even if the clock lags behind the chain
+    if let Some(mtp) = snapshot.median_time_past {
+        let min_timestamp = mtp + Duration::seconds(1);
+        if block.header.timestamp < min_timestamp {
+            block.header.timestamp = min_timestamp;
+        }
+    }
     block.transactions = transactions;
     block.evaluate_merkle_root();
     block
 }
 
-fn build_candidate_block(
-    selected_txs: &[Transaction],
-    candidate_tx: &Transaction,
-    candidate_total_fees: i64,
-    previous_hash: [u8; 32],
-    target: U256,
-    receive_addr: &str,
-) -> Block {
-    let mut candidate_txs = selected_txs.to_vec();
-    candidate_txs.push(candidate_tx.clone());
-    let block_txs =
-        build_transactions_with_coinbase(&candidate_txs, receive_addr, candidate_total_fees);
-    build_block_from_transactions(previous_hash, target, block_txs)
+/// Indexes of the transactions in `txs` that `txs[index]` depends on,
+/// directly or through others, given each transaction's direct `parents`.
+fn collect_ancestors(index: usize, parents: &[Vec<usize>]) -> Vec<usize> {
+    let mut ancestors = Vec::new();
+    let mut pending = parents[index].clone();
+    while let Some(parent) = pending.pop() {
+        if !ancestors.contains(&parent) {
+            ancestors.push(parent);
+            pending.extend(&parents[parent]);
+        }
+    }
+    ancestors
+}
+
+/// Fee and size of `txs[index]` together with its ancestors not yet in the
+/// block, as they were when queued. Orders by fee rate, the earlier
+/// transaction first among equal rates.
+struct PackageScore {
+    fee: i64,
+    size: usize,
+    index: usize,
+}
+
+impl Ord for PackageScore {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.fee as i128 * other.size as i128)
+            .cmp(&(other.fee as i128 * self.size as i128))
+            .then_with(|| other.index.cmp(&self.index))
+    }
 }
 
-fn candidate_fits_block_size(candidate_block: &Block, max_block_size_bytes: usize) -> bool {
-    candidate_block.size() <= max_block_size_bytes
+impl PartialOrd for PackageScore {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for PackageScore {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
 }
 
-fn select_transactions_for_block(
+impl Eq for PackageScore {}
+
+/// Fills a block by ancestor-package fee rate: each round takes the
+/// transaction that, together with its ancestors not yet selected, pays the
+/// most per byte, so a high-fee child pulls in the low-fee parents it spends.
+/// Parents always come before their children in the result.
+pub fn select_transactions_for_block(
     txs: Vec<&MempoolTx>,
-    previous_hash: [u8; 32],
-    target: U256,
-    receive_addr: &str,
+    snapshot: &MiningSnapshot,
     max_block_size_bytes: usize,
 ) -> (Vec<Transaction>, i64) {
+    let fees: Vec<i64> = txs.iter().map(|mtx| mtx.calculate_fee()).collect();
+    let sizes: Vec<usize> = txs.iter().map(|mtx| mtx.tx.size()).collect();
+    let index_of: HashMap<TxId, usize> = txs
+        .iter()
+        .enumerate()
+        .map(|(i, mtx)| (mtx.id(), i))
+        .collect();
+    let mut parents: Vec<Vec<usize>> = vec![Vec::new(); txs.len()];
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); txs.len()];
+    for (i, mtx) in txs.iter().enumerate() {
+        for input in &mtx.tx.inputs {
+            if let Some(&j) = index_of.get(&input.prev_tx_id)
+                && !parents[i].contains(&j)
+            {
+                parents[i].push(j);
+                children[j].push(i);
+            }
+        }
+    }
+    let ancestors: Vec<Vec<usize>> = (0..txs.len())
+        .map(|i| collect_ancestors(i, &parents))
+        .collect();
+    let descendants: Vec<Vec<usize>> = (0..txs.len())
+        .map(|i| collect_ancestors(i, &children))
+        .collect();
+
+    // Package fee and size of each transaction, lowered as ancestors are selected.
+    let mut package_fees: Vec<i64> = (0..txs.len())
+        .map(|i| fees[i] + ancestors[i].iter().map(|&j| fees[j]).sum::<i64>())
+        .collect();
+    let mut package_sizes: Vec<usize> = (0..txs.len())
+        .map(|i| sizes[i] + ancestors[i].iter().map(|&j| sizes[j]).sum::<usize>())
+        .collect();
+    let mut queue: BinaryHeap<PackageScore> = (0..txs.len())
+        .map(|index| PackageScore {
+            fee: package_fees[index],
+            size: package_sizes[index],
+            index,
+        })
+        .collect();
+
     let mut selected_txs = Vec::new();
     let mut total_fees: i64 = 0;
+    // the coinbase encodes its reward as a fixed-width value, so its size does not
+    // depend on the fees collected and the block size can be tracked incrementally
+    let coinbase = Transaction::new_coinbase(snapshot.receive_addr.clone(), snapshot.height, 0);
+    let mut transactions_size = coinbase.size();
+    let mut included = vec![false; txs.len()];
+    // Packages that did not fit; the block only grows, so they never will.
+    let mut rejected = vec![false; txs.len()];
+
+    while let Some(PackageScore { fee, size, index }) = queue.pop() {
+        // Skip scores queued before an ancestor was selected.
+        if included[index]
+            || rejected[index]
+            || (fee, size) != (package_fees[index], package_sizes[index])
+        {
+            continue;
+        }
+        let mut package: Vec<usize> = ancestors[index]
+            .iter()
+            .copied()
+            .filter(|&j| !included[j])
+            .collect();
+        package.push(index);
+        if package.iter().any(|&j| rejected[j]) {
+            rejected[index] = true;
+            continue;
+        }
 
-    for mtx in txs {
-        let candidate_total_fees = match total_fees.checked_add(mtx.calculate_fee()) {
+        let candidate_total_fees = match total_fees.checked_add(fee) {
             Some(fees) => fees,
             None => {
                 utils::log_warning(
                     utils::LogCategory::Core,
                     "Skipping transaction because total fees would overflow.",
                 );
+                rejected[index] = true;
                 continue;
             }
         };
-
-        let candidate_block = build_candidate_block(
-            &selected_txs,
-            &mtx.tx,
-            candidate_total_fees,
-            previous_hash,
-            target,
-            receive_addr,
-        );
-
-        if !candidate_fits_block_size(&candidate_block, max_block_size_bytes) {
+        let candidate_transactions_size = transactions_size + size;
+        if Block::size_with_transactions(candidate_transactions_size) > max_block_size_bytes {
+            rejected[index] = true;
             continue;
         }
 
-        selected_txs.push(mtx.tx.clone());
+        // An ancestor has fewer ancestors than any of its descendants.
+        package.sort_by_key(|&j| ancestors[j].len());
+        let mut changed = HashSet::new();
+        for &j in &package {
+            included[j] = true;
+            selected_txs.push(txs[j].tx.clone());
+        }
+        for &j in &package {
+            for &d in descendants[j].iter().filter(|&&d| !included[d]) {
+                package_fees[d] -= fees[j];
+                package_sizes[d] -= sizes[j];
+                changed.insert(d);
+            }
+        }
+        queue.extend(changed.into_iter().map(|index| PackageScore {
+            fee: package_fees[index],
+            size: package_sizes[index],
+            index,
+        }));
         total_fees = candidate_total_fees;
+        transactions_size = candidate_transactions_size;
     }
 
     (selected_txs, total_fees)
 }
 
-fn build_block(
-    mempool: &Vec<MempoolTx>,
-    previous_hash: [u8; 32],
-    target: U256,
-    receive_addr: &str,
-) -> Block {
-    let txs = sorted_legit_txs_by_fee_rate(mempool);
+/// Checks `selected_txs` in order against the ledger with the ones before
+/// connected on a `UtxoOverlay`, so children see their mempool parents. A
+/// transaction failing is dropped, and with it the ones spending its outputs.
+/// Returns the rest and the fees they pay.
+pub fn validate_template(
+    selected_txs: Vec<Transaction>,
+    snapshot: &MiningSnapshot,
+) -> (Vec<Transaction>, i64) {
+    let mut template = UtxoOverlay::new(&LedgerView);
+    let mut valid_txs = Vec::with_capacity(selected_txs.len());
+    let mut total_fees: i64 = 0;
+    for tx in selected_txs {
+        let checked = tx
+            .validate_in(
+                &template,
+                snapshot.height,
+                snapshot.median_time_past,
+                &[],
+                true,
+            )
+            .and_then(|fee| template.connect_transaction(&tx, None).map(|_| fee));
+        match checked {
+            Ok(fee) => {
+                total_fees += fee;
+                valid_txs.push(tx);
+            }
+            Err(e) => utils::log_warning(
+                utils::LogCategory::Core,
+                &format!(
+                    "Leaving transaction {} out of the block: {}",
+                    bytes_to_hex_string(&tx.id()),
+                    e
+                ),
+            ),
+        }
+    }
+    (valid_txs, total_fees)
+}
+
+fn build_block(snapshot: &MiningSnapshot) -> Block {
+    let txs = get_legit_txs(
+        &snapshot.mempool,
+        snapshot.height,
+        snapshot.median_time_past,
+    );
     let max_block_size_bytes = configured_max_block_size_bytes();
-    let (selected_txs, total_fees) = select_transactions_for_block(
-        txs,
-        previous_hash,
-        target,
-        receive_addr,
-        max_block_size_bytes,
+    let (selected_txs, _) = select_transactions_for_block(txs, snapshot, max_block_size_bytes);
+    let (selected_txs, total_fees) = validate_template(selected_txs, snapshot);
+    let block_txs = build_transactions_with_coinbase(
+        &selected_txs,
+        &snapshot.receive_addr,
+        snapshot.height,
+        total_fees,
     );
-    let block_txs = build_transactions_with_coinbase(&selected_txs, receive_addr, total_fees);
-    let block = build_block_from_transactions(previous_hash, target, block_txs);
+    let block = build_block_from_transactions(snapshot, block_txs);
     utils::log_info(
         utils::LogCategory::Core,
         &format!(
@@ -231,10 +422,7 @@ fn build_block(
 }
 
 async fn mine_block_impl(
-    mempool: Vec<MempoolTx>,
-    previous_hash: [u8; 32],
-    target: U256,
-    receive_addr: String,
+    snapshot: MiningSnapshot,
     cancel: Arc<AtomicBool>,
 ) -> Result<Block, String> {
     let threads = CONFIG.mining_threads.max(1);
@@ -251,7 +439,7 @@ async fn mine_block_impl(
             ));
         }
 
-        let block_template = build_block(&mempool, previous_hash, target, &receive_addr);
+        let block_template = build_block(&snapshot);
         let found = Arc::new(AtomicBool::new(false));
         let range_size = u32::MAX / threads as u32;
 
@@ -343,7 +531,7 @@ async fn mine_block_impl(
 }
 
 pub async fn mine() -> Result<Block, String> {
-    let (mempool, previous_hash, target, receive_addr, cancel) = {
+    let (snapshot, cancel) = {
         let mut node = get_node_mut().await;
         if node.is_keep_mining_enabled() || node.is_mining_task_running() {
             return Err(
@@ -352,10 +540,41 @@ pub async fn mine() -> Result<Block, String> {
         }
         let cancel = node.mining_cancel_flag();
         node.reset_mining_cancel();
-        let (mempool, previous_hash, target, receive_addr) = node.prepare_mining_snapshot()?;
-        (mempool, previous_hash, target, receive_addr, cancel)
+        let snapshot = node.prepare_mining_snapshot()?;
+        (snapshot, cancel)
     };
-    mine_block_impl(mempool, previous_hash, target, receive_addr, cancel).await
+    mine_block_impl(snapshot, cancel).await
+}
+
+/// Regtest only: mines `count` blocks paying their coinbase to `address`
+/// and returns their hashes.
+pub async fn generate_to_address(count: u32, address: &str) -> Result<Vec<[u8; 32]>, String> {
+    if !NETWORK.is_regtest() {
+        return Err("Block generation is only available on regtest".to_string());
+    }
+    if !HDKey::validate_address(address) {
+        return Err(format!("Invalid address: {}", address));
+    }
+
+    let mut hashes = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let (snapshot, cancel) = {
+            let mut node = get_node_mut().await;
+            if node.is_keep_mining_enabled() || node.is_mining_task_running() {
+                return Err(
+                    "Auto-mining is active. Disable keep mining before generating blocks."
+                        .to_string(),
+                );
+            }
+            let cancel = node.mining_cancel_flag();
+            node.reset_mining_cancel();
+            (node.prepare_mining_snapshot_to(address.to_string()), cancel)
+        };
+        let block = mine_block_impl(snapshot, cancel).await?;
+        let (block, _) = submit_block(block).await?;
+        hashes.push(block.header_hash());
+    }
+    Ok(hashes)
 }
 
 pub async fn submit_block(mined_block: Block) -> Result<(Block, U256), String> {
@@ -395,7 +614,7 @@ pub async fn run_keep_mining_loop(keep_mining_enabled: Arc<AtomicBool>, cancel:
             break;
         }
 
-        let (mempool, previous_hash, target, receive_addr) = {
+        let snapshot = {
             let mut node = get_node_mut().await;
             if !node.is_keep_mining_enabled() {
                 break;
@@ -417,14 +636,7 @@ pub async fn run_keep_mining_loop(keep_mining_enabled: Arc<AtomicBool>, cancel:
             }
         };
 
-        let mined_block = mine_block_impl(
-            mempool,
-            previous_hash,
-            target,
-            receive_addr,
-            Arc::clone(&cancel),
-        )
-        .await;
+        let mined_block = mine_block_impl(snapshot, Arc::clone(&cancel)).await;
 
         let block = match mined_block {
             Ok(block) => block,
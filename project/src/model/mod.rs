@@ -1,10 +1,19 @@
 pub mod block;
 pub mod blockchain;
+pub mod checkpoints;
+pub mod fee_estimator;
 pub mod hdkey;
+pub mod htlc;
 pub mod io;
+pub mod mempool;
 pub mod miner;
+pub mod multisig;
 pub mod node;
+pub mod script;
+pub mod script_check;
 pub mod transaction;
+pub mod utxo_view;
+pub mod versionbits;
 pub mod wallet;
 
 pub use block::Block;
@@ -13,5 +22,6 @@ pub use hdkey::HDKey;
 pub use io::{TxInput, TxOutput, UTXO};
 pub use miner::Miner;
 pub use node::{get_node, get_node_mut};
+pub use script::Script;
 pub use transaction::{MempoolTx, Transaction};
 pub use wallet::Wallet;
@@ -0,0 +1,293 @@
+// M-of-n multisig addresses and partially signed spends from them.
+//
+// A multisig address is the pay-to-script-hash address of a redeem script
+// `<m> <pubkey 1> ... <pubkey n> <n> OP_CHECKMULTISIG`. Spends are passed
+// between signers as a `PartialTransaction` until enough signatures are
+// collected, then finalized into a regular mempool transaction.
+use ed25519_dalek::{SIGNATURE_LENGTH, VerifyingKey};
+use serde::{Deserialize, Serialize};
+
+use crate::db::repository::LedgerRepository;
+use crate::model::io::check_sighash_type;
+use crate::model::script::{SignatureChecker, TransactionSignatureChecker};
+use crate::model::wallet::MAX_FEE_ROUNDS;
+use crate::model::{HDKey, MempoolTx, Script, Transaction, TxInput, TxOutput, UTXO};
+use crate::security_utils::bytes_to_hex_string;
+
+#[derive(Clone, Debug)]
+pub struct MultisigAddress {
+    pub address: String,
+    pub redeem_script: Script,
+    pub required: usize,
+    pub public_keys: Vec<[u8; 32]>,
+}
+
+impl MultisigAddress {
+    pub fn new(required: usize, public_keys: &[VerifyingKey]) -> Result<Self, String> {
+        MultisigAddress::from_redeem_script(Script::new_multisig(required, public_keys)?)
+    }
+
+    pub fn from_redeem_script(redeem_script: Script) -> Result<Self, String> {
+        let (required, public_keys) = redeem_script
+            .multisig_params()
+            .ok_or_else(|| "Redeem script is not a multisig script".to_string())?;
+        Ok(MultisigAddress {
+            address: HDKey::address_from_script_hash(&redeem_script.script_hash()),
+            redeem_script,
+            required,
+            public_keys,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PartialInput {
+    pub redeem_script: Script,
+    /// Hex signatures in the order of the redeem script's keys, `None` until signed.
+    pub signatures: Vec<Option<String>>,
+}
+
+/// A spend from multisig outputs that is still collecting signatures.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PartialTransaction {
+    pub tx: Transaction,
+    pub utxos: Vec<UTXO>,
+    pub inputs: Vec<PartialInput>,
+}
+
+impl PartialTransaction {
+    /// Builds an unsigned spend of the multisig address's outputs paying
+    /// `outputs` plus `fee`, with the change going back to the same address.
+    /// Every input is signed with `sighash_type`.
+    pub fn new_multisig_spend(
+        multisig: &MultisigAddress,
+        mut outputs: Vec<TxOutput>,
+        fee: i64,
+        message: Option<String>,
+        sighash_type: u8,
+    ) -> Result<Self, String> {
+        check_sighash_type(sighash_type)?;
+        if !outputs.iter().all(|o| HDKey::validate_address(&o.address)) {
+            return Err("One or more output addresses are invalid".to_string());
+        }
+        let total_needed = outputs.iter().map(|o| o.value).sum::<i64>() + fee;
+
+        let repo = LedgerRepository::new();
+        let mut available = repo
+            .get_utxos_for_address(&multisig.address)
+            .map_err(|e| format!("Failed to fetch UTXOs: {}", e))?;
+        available.sort_by_key(|utxo| std::cmp::Reverse(utxo.output.value));
+
+        let mut utxos = Vec::new();
+        let mut total = 0;
+        for utxo in available {
+            if total >= total_needed {
+                break;
+            }
+            total += utxo.output.value;
+            utxos.push(utxo);
+        }
+        if total < total_needed {
+            return Err("Insufficient funds".to_string());
+        }
+        if total > total_needed {
+            outputs.push(TxOutput::new(
+                total - total_needed,
+                multisig.address.clone(),
+            ));
+        }
+
+        let inputs = utxos
+            .iter()
+            .map(|utxo| TxInput {
+                sighash_type,
+                ..TxInput::new(utxo.tx_id, utxo.index)
+            })
+            .collect();
+        let partial_inputs = utxos
+            .iter()
+            .map(|_| PartialInput {
+                redeem_script: multisig.redeem_script.clone(),
+                signatures: vec![None; multisig.public_keys.len()],
+            })
+            .collect();
+
+        Ok(PartialTransaction {
+            tx: Transaction::new(inputs, outputs, message),
+            utxos,
+            inputs: partial_inputs,
+        })
+    }
+
+    /// Like `new_multisig_spend`, paying `fee_rate` per byte of the spend once
+    /// it carries its signatures.
+    pub fn new_multisig_spend_with_fee_rate(
+        multisig: &MultisigAddress,
+        outputs: Vec<TxOutput>,
+        fee_rate: f64,
+        message: Option<String>,
+        sighash_type: u8,
+    ) -> Result<Self, String> {
+        if !fee_rate.is_finite() || fee_rate < 0.0 {
+            return Err(format!("Invalid fee rate {}", fee_rate));
+        }
+        let mut fee = 0;
+        for _ in 0..MAX_FEE_ROUNDS {
+            let spend = PartialTransaction::new_multisig_spend(
+                multisig,
+                outputs.clone(),
+                fee,
+                message.clone(),
+                sighash_type,
+            )?;
+            let needed = (fee_rate * spend.finalized_size() as f64).ceil() as i64;
+            if fee >= needed {
+                return Ok(spend);
+            }
+            fee = needed;
+        }
+        Err("Could not settle the fee for the spend".to_string())
+    }
+
+    /// Size of the finalized spend, with the signatures it still lacks.
+    pub fn finalized_size(&self) -> usize {
+        let mut tx = self.tx.clone();
+        for (input, partial) in tx.inputs.iter_mut().zip(&self.inputs) {
+            let (required, _) = partial.redeem_script.multisig_params().unwrap_or_default();
+            let mut unlock_script = Script::new();
+            for _ in 0..required {
+                unlock_script.push_data(&[0; SIGNATURE_LENGTH]);
+            }
+            unlock_script.push_data(partial.redeem_script.as_bytes());
+            input.unlock_script = unlock_script;
+        }
+        tx.size()
+    }
+
+    /// Checks that the partial data lines up with the transaction and that every
+    /// redeem script matches the output it spends.
+    fn check_consistency(&self) -> Result<(), String> {
+        if self.inputs.len() != self.tx.inputs.len() || self.utxos.len() != self.tx.inputs.len() {
+            return Err("Pending transaction inputs do not line up".to_string());
+        }
+        for (index, partial) in self.inputs.iter().enumerate() {
+            let (_, keys) = partial
+                .redeem_script
+                .multisig_params()
+                .ok_or_else(|| format!("Input {} redeem script is not multisig", index))?;
+            if partial.signatures.len() != keys.len() {
+                return Err(format!("Input {} has a malformed signature list", index));
+            }
+            let expected = HDKey::address_from_script_hash(&partial.redeem_script.script_hash());
+            if self.utxos[index].output.address != expected {
+                return Err(format!(
+                    "Input {} redeem script does not match the spent output",
+                    index
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the signatures `key` can make and returns how many were added.
+    pub fn sign_with(&mut self, key: &HDKey) -> Result<usize, String> {
+        self.check_consistency()?;
+        let public_key = key.get_public_key().to_bytes();
+
+        let mut added = 0;
+        for (index, partial) in self.inputs.iter_mut().enumerate() {
+            let (_, keys) = partial.redeem_script.multisig_params().unwrap_or_default();
+            if let Some(position) = keys.iter().position(|k| *k == public_key)
+                && partial.signatures[position].is_none()
+            {
+                let signing_bytes = self.tx.signing_bytes_for_input(index)?;
+                let signature = bytes_to_hex_string(&key.sign(&signing_bytes).to_bytes());
+                partial.signatures[position] = Some(signature);
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    /// Adds a signature produced elsewhere for `public_key` on input `index`,
+    /// rejecting it unless it verifies.
+    pub fn add_signature(
+        &mut self,
+        index: usize,
+        public_key: &[u8; 32],
+        signature: &[u8],
+    ) -> Result<(), String> {
+        self.check_consistency()?;
+        let signing_bytes = self.tx.signing_bytes_for_input(index)?;
+        let partial = self
+            .inputs
+            .get_mut(index)
+            .ok_or_else(|| format!("Input index {} out of bounds", index))?;
+        let (_, keys) = partial.redeem_script.multisig_params().unwrap_or_default();
+        let position = keys
+            .iter()
+            .position(|k| k == public_key)
+            .ok_or_else(|| "Public key is not part of the multisig".to_string())?;
+        if !TransactionSignatureChecker::new(&signing_bytes).check_signature(signature, public_key)
+        {
+            return Err("Signature does not verify".to_string());
+        }
+        partial.signatures[position] = Some(bytes_to_hex_string(signature));
+        Ok(())
+    }
+
+    /// Signatures still missing before the spend can be finalized.
+    pub fn missing_signatures(&self) -> usize {
+        self.inputs
+            .iter()
+            .map(|partial| {
+                let (required, _) = partial.redeem_script.multisig_params().unwrap_or_default();
+                let present = partial.signatures.iter().flatten().count();
+                required.saturating_sub(present)
+            })
+            .sum()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.missing_signatures() == 0
+    }
+
+    /// Builds the unlocking scripts, `<sig>... <redeem script>`, and returns the
+    /// transaction ready for the mempool.
+    pub fn finalize(&self) -> Result<MempoolTx, String> {
+        self.check_consistency()?;
+        if !self.is_complete() {
+            return Err(format!(
+                "Missing {} signature(s)",
+                self.missing_signatures()
+            ));
+        }
+        let mut tx = self.tx.clone();
+        for (input, partial) in tx.inputs.iter_mut().zip(&self.inputs) {
+            let (required, _) = partial.redeem_script.multisig_params().unwrap_or_default();
+            let mut unlock_script = Script::new();
+            for signature in partial.signatures.iter().flatten().take(required) {
+                let signature =
+                    hex::decode(signature).map_err(|e| format!("Invalid signature hex: {}", e))?;
+                unlock_script.push_data(&signature);
+            }
+            unlock_script.push_data(partial.redeem_script.as_bytes());
+            input.unlock_script = unlock_script;
+        }
+        Ok(MempoolTx::new(tx, self.utxos.clone()))
+    }
+
+    /// Public keys across all inputs that have not signed yet.
+    pub fn unsigned_public_keys(&self) -> Vec<[u8; 32]> {
+        let mut keys = Vec::new();
+        for partial in &self.inputs {
+            let (_, input_keys) = partial.redeem_script.multisig_params().unwrap_or_default();
+            for (key, signature) in input_keys.into_iter().zip(&partial.signatures) {
+                if signature.is_none() && !keys.contains(&key) {
+                    keys.push(key);
+                }
+            }
+        }
+        keys
+    }
+}
@@ -1,6 +1,4 @@
 use std::collections::HashSet;
-use std::fs::File;
-use std::io::BufWriter;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -12,10 +10,16 @@ use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
 
 use crate::daemon::types::MiningInfoResponse;
-use crate::db::repository::LedgerRepository;
-use crate::globals::{CONFIG, CONSENSUS_RULES};
+use crate::db::repository::{LedgerRepository, MempoolRecord};
+use crate::globals::{CONSENSUS_RULES, NETWORK};
+use crate::model::checkpoints::Checkpoints;
+use crate::model::fee_estimator::FeeEstimator;
+use crate::model::mempool::{Mempool, MempoolLimits};
+use crate::model::miner::MiningSnapshot;
+use crate::model::script_check::SCRIPT_CACHE;
 use crate::model::transaction::TxId;
-use crate::model::{Block, Blockchain, MempoolTx, Miner, Transaction};
+use crate::model::utxo_view::{LedgerView, UtxoOverlay};
+use crate::model::{Block, Blockchain, MempoolTx, Miner, Transaction, UTXO, block::BlockID};
 use crate::network::get_peer_count;
 use crate::network::network_message::InventoryType;
 use crate::security_utils::bytes_to_hex_string;
@@ -23,14 +27,13 @@ use crate::utils::get_current_timestamp;
 use crate::{network, utils};
 use primitive_types::U256;
 
-const MEMPOOL_FILE: &str = "mempool.json";
-
 const IBD_BATCH_SIZE: usize = 50;
 
 pub struct Node {
     pub miner: Miner,
     pub blockchain: Blockchain,
-    mempool: Vec<MempoolTx>,
+    mempool: Mempool,
+    fee_estimator: FeeEstimator,
     target: U256,
     fork_helper: utils::ForkHelper,
     mining_started_at: Option<NaiveDateTime>,
@@ -60,23 +63,31 @@ pub async fn restart_node() {
 impl Node {
     pub fn new() -> Self {
         utils::log_info(utils::LogCategory::Core, "Starting a new node...");
-        let bc = Blockchain::load_chain(None).unwrap_or_else(|_| Blockchain::new());
+        let mut bc = Blockchain::load_chain(None).unwrap_or_else(|_| Blockchain::new());
 
         if let Err(e) = Node::validate_blockchain(&bc) {
             panic!("Invalid blockchain data: {}", e);
         }
 
         if bc.is_empty() {
-            utils::log_info(utils::LogCategory::Core, "Blockchain is empty!");
+            utils::log_info(
+                utils::LogCategory::Core,
+                &format!(
+                    "Blockchain is empty, starting from the {} network genesis block.",
+                    NETWORK.network.name()
+                ),
+            );
+            Node::init_genesis(&mut bc);
         } else {
             utils::log_info(
                 utils::LogCategory::Core,
                 &format!("Loaded existing blockchain with {} blocks.", bc.chain.len()),
             );
         }
-        Node {
+        let mut node = Node {
             blockchain: bc,
-            mempool: Node::load_mempool(),
+            mempool: Mempool::new(MempoolLimits::from_config()),
+            fee_estimator: FeeEstimator::new(),
             miner: Miner::new(),
             target: CONSENSUS_RULES.initial_target,
             fork_helper: utils::ForkHelper::new(),
@@ -86,35 +97,117 @@ impl Node {
             mining_task_running: false,
             background_task: None,
             last_mined_block: None,
+        };
+        node.load_mempool();
+        node
+    }
+
+    /// Restores the mempool saved in the ledger database, revalidating every
+    /// entry against the current UTXO set. Entries no longer valid are dropped.
+    fn load_mempool(&mut self) {
+        let repo = LedgerRepository::new();
+        let records = match repo.get_mempool_entries() {
+            Ok(records) => records,
+            Err(e) => {
+                utils::log_warning(
+                    utils::LogCategory::Core,
+                    &format!("Discarding unreadable saved mempool: {}", e),
+                );
+                if let Err(e) = repo.clear_mempool_entries() {
+                    utils::log_warning(
+                        utils::LogCategory::Core,
+                        &format!("Failed to clear saved mempool: {}", e),
+                    );
+                }
+                return;
+            }
+        };
+        let mut invalid = Vec::new();
+        for entry in records {
+            let record = match entry {
+                Ok(record) => record,
+                Err((id, e)) => {
+                    utils::log_warning(
+                        utils::LogCategory::Core,
+                        &format!(
+                            "Skipping unreadable saved mempool transaction {}: {}",
+                            bytes_to_hex_string(&id),
+                            e
+                        ),
+                    );
+                    invalid.push(id);
+                    continue;
+                }
+            };
+            let id = record.tx.id();
+            let restored = self.resolve_spent_utxos(&record.tx).and_then(|utxos| {
+                self.accept_transaction(MempoolTx::new(record.tx, utxos), record.time)
+            });
+            if restored.is_err() {
+                invalid.push(id);
+            }
         }
+        self.drop_mempool_txs(&invalid, "no longer valid, dropped from the saved mempool");
+        utils::log_info(
+            utils::LogCategory::Core,
+            &format!("Restored {} mempool transaction(s).", self.mempool.len()),
+        );
     }
 
-    pub fn persist_mempool(&self) {
-        let path = CONFIG.persisted_chain_path.to_string();
-        utils::assert_parent_dir_exists(&path)
-            .expect("Failed to create parent directories for blockchain file");
+    /// Saves the mempool entry of `id` so it survives a restart.
+    fn save_mempool_tx(&self, id: &TxId) {
+        let Some(entry) = self.mempool.get_entry(id) else {
+            return;
+        };
+        let record = MempoolRecord {
+            tx: entry.mem_tx.tx.clone(),
+            time: entry.time,
+            ancestors: self.mempool.find_ancestors(&entry.mem_tx.tx).len(),
+        };
+        if let Err(e) = LedgerRepository::new().save_mempool_entry(&record) {
+            utils::log_warning(
+                utils::LogCategory::Core,
+                &format!("Failed to save mempool transaction: {}", e),
+            );
+        }
+    }
 
-        let file = File::create(format!("{}/{}", path, MEMPOOL_FILE))
-            .expect("Failed to create blockchain file");
-        let writer = BufWriter::new(file);
-        serde_json::to_writer(writer, &self.mempool).expect("Failed to write blockchain to file");
+    /// Deletes the saved entries of transactions that left the mempool.
+    fn forget_mempool_txs(ids: &[TxId]) {
+        if ids.is_empty() {
+            return;
+        }
+        if let Err(e) = LedgerRepository::new().delete_mempool_entries(ids) {
+            utils::log_warning(
+                utils::LogCategory::Core,
+                &format!("Failed to delete saved mempool transactions: {}", e),
+            );
+        }
     }
 
-    fn load_mempool() -> Vec<MempoolTx> {
-        let path = CONFIG.persisted_chain_path.to_string();
-        let file_path = format!("{}/{}", path, MEMPOOL_FILE);
+    /// Forgets transactions dropped from the mempool without being mined,
+    /// logging why.
+    fn drop_mempool_txs(&mut self, ids: &[TxId], reason: &str) {
+        if !ids.is_empty() {
+            utils::log_info(
+                utils::LogCategory::Core,
+                &format!("{} mempool transaction(s) {}", ids.len(), reason),
+            );
+        }
+        self.fee_estimator.untrack(ids);
+        Node::forget_mempool_txs(ids);
+    }
 
-        let file = match File::open(&file_path) {
-            Ok(f) => f,
-            Err(_) => {
-                utils::log_info(utils::LogCategory::Core, "No existing mempool file found.");
-                return Vec::new();
-            }
-        };
-        let reader = std::io::BufReader::new(file);
-        let mempool: Vec<MempoolTx> =
-            serde_json::from_reader(reader).expect("Failed to read mempool from file");
-        mempool
+    /// Starts `bc` from the network's hard-coded genesis block, adding it to the
+    /// ledger unless the database already holds blocks.
+    fn init_genesis(bc: &mut Blockchain) {
+        let genesis = NETWORK.genesis_block();
+        let mut repo = LedgerRepository::new();
+        if repo.get_block_count().unwrap_or(0) == 0 {
+            repo.apply_block(genesis.clone())
+                .expect("Failed to store the genesis block");
+        }
+        bc.chain.push(genesis);
     }
 
     fn validate_blockchain(bc: &Blockchain) -> Result<bool, String> {
@@ -123,8 +216,12 @@ impl Node {
 
         for (i, block) in chain_ref.iter().enumerate() {
             if i == 0 {
-                if block.header.prev_block_hash != [0; 32] {
-                    return Err("Genesis block has invalid previous hash".to_string());
+                if block.header_hash() != NETWORK.genesis_hash() {
+                    return Err(format!(
+                        "Genesis block {} does not belong to the {} network",
+                        bytes_to_hex_string(&block.header_hash()),
+                        NETWORK.network.name()
+                    ));
                 }
                 partial.chain.push(block.clone());
                 continue;
@@ -138,7 +235,13 @@ impl Node {
                 ));
             }
 
-            if let Err(e) = block.validate() {
+            let checkpoints = &CONSENSUS_RULES.checkpoints;
+            checkpoints.check_block(i as u64, block.header_hash())?;
+            if let Err(e) = block.validate_with_options(
+                i as u64,
+                partial.median_time_past(),
+                checkpoints.verifies_scripts(i as u64, block, &chain_ref[i + 1..]),
+            ) {
                 return Err(e);
             }
 
@@ -155,7 +258,12 @@ impl Node {
         Ok(true)
     }
 
-    pub fn get_mempool(&self) -> &Vec<MempoolTx> {
+    /// Replaces the checkpoints and assume-valid block the node enforces.
+    pub fn set_checkpoints(&mut self, checkpoints: Checkpoints) {
+        self.fork_helper.set_checkpoints(checkpoints);
+    }
+
+    pub fn get_mempool(&self) -> &Mempool {
         &self.mempool
     }
 
@@ -166,40 +274,64 @@ impl Node {
     /** Invalidate mempool transactions that are already included in the blockchain or are no longer valid */
     fn invalidate_mempool(&mut self) {
         let repo = LedgerRepository::new();
-        self.mempool
-            .retain(|tx| !matches!(repo.get_transaction(&tx.tx.id()), Ok(Some(_))));
+        let confirmed: Vec<TxId> = self
+            .mempool
+            .iter()
+            .map(|mem_tx| mem_tx.id())
+            .filter(|id| matches!(repo.get_transaction(id), Ok(Some(_))))
+            .collect();
+        self.mempool.remove(&confirmed);
+        // Confirmed without a block passing through `submit_block`, so no sample.
+        self.fee_estimator.untrack(&confirmed);
+        Node::forget_mempool_txs(&confirmed);
+        // after a rollback, coinbase outputs spent by the mempool may be immature again
+        // and time-locked transactions may no longer be final
+        let next_height = self.blockchain.height() as u64;
+        let median_time_past = self.blockchain.median_time_past();
         let txs_to_remove: Vec<TxId> = self
             .mempool
             .iter()
-            .enumerate()
-            .filter_map(|(_, mem_tx)| {
-                if let Err(_) = self.is_all_inputs_utxos(&mem_tx.tx) {
+            .filter_map(|mem_tx| {
+                if self.is_all_inputs_utxos(&mem_tx.tx).is_err()
+                    || mem_tx
+                        .tx
+                        .check_coinbase_maturity(next_height, |id| self.mempool.contains(id))
+                        .is_err()
+                    || mem_tx
+                        .tx
+                        .check_finality(next_height, median_time_past)
+                        .is_err()
+                {
                     Some(mem_tx.tx.id())
                 } else {
                     None
                 }
             })
             .collect();
-        let txs_to_remove_set: HashSet<TxId> = HashSet::from_iter(txs_to_remove);
-        self.mempool
-            .retain(|mem_tx| !txs_to_remove_set.contains(&mem_tx.tx.id()));
+        // Children cannot outlive the parents whose outputs they spend.
+        let invalid = self.mempool.remove_with_descendants(&txs_to_remove);
+        self.drop_mempool_txs(&invalid, "no longer valid");
+        let now = get_current_timestamp().and_utc().timestamp();
+        let expired = self.mempool.expire(now);
+        self.drop_mempool_txs(&expired, "expired");
     }
 
-    fn submit_block(&mut self, block: Block) -> Result<(), String> {
-        match self.blockchain.add_block(block) {
+    /// Adds `block` to the main chain, followed by `successors` if known.
+    fn submit_block(&mut self, block: Block, successors: &[Block]) -> Result<(), String> {
+        match self
+            .blockchain
+            .add_block(block, successors, self.fork_helper.checkpoints())
+        {
             Err(e) => return Err(e),
             Ok(()) => {
                 let added_block = self.blockchain.chain.last().unwrap();
 
-                self.mempool.retain(|mem_tx| {
-                    !added_block
-                        .transactions
-                        .iter()
-                        .any(|btx| btx.id() == mem_tx.tx.id())
-                });
-                let mut repo = LedgerRepository::new();
-                repo.apply_block(added_block.clone())
-                    .map_err(|e| e.to_string())?;
+                let confirmed: Vec<TxId> =
+                    added_block.transactions.iter().map(|tx| tx.id()).collect();
+                let height = self.blockchain.height() as u64 - 1;
+                self.fee_estimator.process_block(height, &confirmed);
+                self.mempool.remove(&confirmed);
+                Node::forget_mempool_txs(&confirmed);
                 self.invalidate_mempool();
                 self.notify_chain_tip_changed();
                 Ok(())
@@ -255,9 +387,9 @@ impl Node {
     }
 
     fn apply_connectable_blocks(&mut self, blocks: Vec<Block>, peer_addr: Option<SocketAddr>) {
-        for block in blocks {
+        for (i, block) in blocks.iter().enumerate() {
             let block_hash = block.id();
-            match self.submit_block(block) {
+            match self.submit_block(block.clone(), &blocks[i + 1..]) {
                 Ok(()) => {
                     utils::log_info(
                         utils::LogCategory::Core,
@@ -289,7 +421,7 @@ impl Node {
     /// Returns the rolled back block and its transactions for potential re-addition to mempool
     pub fn rollback_last_block(&mut self) -> Result<(Block, Vec<Transaction>), String> {
         // Validate that there is a block to rollback (not genesis)
-        if self.blockchain.height() == 0 {
+        if self.blockchain.height() <= 1 {
             return Err("Cannot rollback genesis block".to_string());
         }
 
@@ -317,16 +449,13 @@ impl Node {
         // Remove block from blockchain
         self.blockchain.chain.pop();
 
-        // Re-add non-coinbase transactions to mempool
-        let repo = LedgerRepository::new();
+        // Re-add non-coinbase transactions to mempool, parents ahead of the
+        // children spending their outputs
+        let now = get_current_timestamp().and_utc().timestamp();
         for tx in &transactions {
-            let utxo_ids: Vec<_> = tx
-                .inputs
-                .iter()
-                .map(|i| (i.prev_tx_id, i.output_index))
-                .collect();
-            let utxos = repo.get_utxos_from_ids(&utxo_ids).unwrap_or_default();
-            self.mempool.push(MempoolTx::new(tx.clone(), utxos));
+            let utxos = self.resolve_spent_utxos(tx).unwrap_or_default();
+            self.mempool.insert(MempoolTx::new(tx.clone(), utxos), now);
+            self.save_mempool_tx(&tx.id());
         }
 
         // Invalidate mempool to revalidate all transactions
@@ -392,13 +521,56 @@ impl Node {
         utils::log_info(
             utils::LogCategory::Core,
             &format!(
-                "Starting rebase to fork-tree candidate from ancestor {} with {} blocks and resulting height {}",
+                "Starting rebase to fork-tree candidate from ancestor {} with {} blocks, resulting height {} and chainwork {}",
                 bytes_to_hex_string(&candidate.ancestor_hash),
                 candidate.blocks.len(),
-                candidate.candidate_height
+                candidate.candidate_height,
+                candidate.candidate_chainwork
             ),
         );
 
+        // The branch is checked in memory first, so an invalid one is refused
+        // before any block of the main chain is rolled back.
+        let fork_height = if candidate.ancestor_hash == [0; 32] {
+            0
+        } else {
+            match self
+                .blockchain
+                .find_block_height_by_hash(candidate.ancestor_hash)
+            {
+                Some(height) => height + 1,
+                None => {
+                    utils::log_error(
+                        utils::LogCategory::Core,
+                        "Fork ancestor is no longer in the main chain",
+                    );
+                    return;
+                }
+            }
+        };
+        let mut view = UtxoOverlay::new(&LedgerView);
+        if let Err(e) = view.disconnect_blocks_from(&self.blockchain.chain, fork_height) {
+            utils::log_error(
+                utils::LogCategory::Core,
+                &format!("Failed to rewind the UTXO set to the fork ancestor: {}", e),
+            );
+            return;
+        }
+        if let Err((block_hash, e)) =
+            self.check_candidate_blocks(view, fork_height, &candidate.blocks)
+        {
+            utils::log_error(
+                utils::LogCategory::Core,
+                &format!(
+                    "Fork-tree block {} is invalid, keeping the current chain. Pruning subtree: {}",
+                    bytes_to_hex_string(&block_hash),
+                    e
+                ),
+            );
+            self.fork_helper.prune_subtree(block_hash);
+            return;
+        }
+
         if candidate.ancestor_hash == [0; 32] {
             if let Err(e) = self.reset_blockchain_for_full_sync() {
                 utils::log_error(
@@ -421,11 +593,11 @@ impl Node {
         }
 
         let mut applied_blocks = Vec::new();
-        for block in candidate.blocks {
+        for (i, block) in candidate.blocks.iter().enumerate() {
             let block_hash = block.id();
-            match self.submit_block(block.clone()) {
+            match self.submit_block(block.clone(), &candidate.blocks[i + 1..]) {
                 Ok(()) => {
-                    applied_blocks.push(block);
+                    applied_blocks.push(block.clone());
                     network::broadcast_new_block_hash(block_hash, peer_addr);
                 }
                 Err(e) => {
@@ -451,8 +623,34 @@ impl Node {
         network::ask_for_blocks(self.blockchain.get_last_block_hash(), peer_addr);
     }
 
+    /// Checks `blocks`, a branch forking at `fork_height`, as `submit_block`
+    /// would, connecting them to `view`, the UTXO set rewound to the fork.
+    /// Returns the first invalid block and why.
+    fn check_candidate_blocks(
+        &self,
+        mut view: UtxoOverlay,
+        fork_height: usize,
+        blocks: &[Block],
+    ) -> Result<(), (BlockID, String)> {
+        for (offset, block) in blocks.iter().enumerate() {
+            self.blockchain
+                .check_branch_block(
+                    fork_height,
+                    &blocks[..offset],
+                    block,
+                    &blocks[offset + 1..],
+                    self.fork_helper.checkpoints(),
+                    &view,
+                )
+                .and_then(|_| view.connect_block(block, (fork_height + offset) as u64))
+                .map_err(|e| (block.id(), e))?;
+        }
+        Ok(())
+    }
+
     fn reset_blockchain_for_full_sync(&mut self) -> Result<(), String> {
-        while !self.blockchain.is_empty() {
+        // The genesis block is shared by every node of the network and stays.
+        while self.blockchain.height() > 1 {
             self.rollback_last_block()?;
         }
 
@@ -462,13 +660,35 @@ impl Node {
         Ok(())
     }
 
+    /// Checks that every input spends either a confirmed UTXO or an output of
+    /// a mempool transaction.
     pub fn is_all_inputs_utxos(&self, tx: &Transaction) -> Result<(), String> {
+        let parents = self.mempool.find_parents(tx);
+        let mut inputs_ids = Vec::new();
+        for input in &tx.inputs {
+            match parents.iter().find(|p| p.id() == input.prev_tx_id) {
+                Some(parent) => {
+                    let spendable = parent
+                        .tx
+                        .outputs
+                        .get(input.output_index)
+                        .is_some_and(|output| !output.script.is_unspendable());
+                    if !spendable {
+                        return Err(format!(
+                            "Transaction input is not a valid unconfirmed output: tx_id: {}, output_index: {}",
+                            bytes_to_hex_string(&input.prev_tx_id),
+                            input.output_index
+                        ));
+                    }
+                }
+                None => inputs_ids.push((input.prev_tx_id, input.output_index)),
+            }
+        }
+        if inputs_ids.is_empty() {
+            return Ok(());
+        }
+
         let repo = LedgerRepository::new();
-        let inputs_ids = tx
-            .inputs
-            .iter()
-            .map(|i| (i.prev_tx_id, i.output_index))
-            .collect::<Vec<_>>();
         let utxos = repo
             .get_utxos_from_ids(&inputs_ids)
             .map_err(|e| return e.to_string())?;
@@ -491,16 +711,68 @@ impl Node {
         Ok(())
     }
 
+    /// Outputs spent by `tx`: confirmed UTXOs and outputs of its mempool parents.
+    pub fn resolve_spent_utxos(&self, tx: &Transaction) -> Result<Vec<UTXO>, String> {
+        let mut utxos = Vec::new();
+        let mut confirmed_ids = Vec::new();
+        for input in &tx.inputs {
+            match self
+                .mempool
+                .get_unspent_output(&input.prev_tx_id, input.output_index)
+            {
+                Some(utxo) => utxos.push(utxo),
+                None => confirmed_ids.push((input.prev_tx_id, input.output_index)),
+            }
+        }
+        let repo = LedgerRepository::new();
+        utxos.extend(
+            repo.get_utxos_from_ids(&confirmed_ids)
+                .map_err(|e| e.to_string())?,
+        );
+        Ok(utxos)
+    }
+
     pub fn receive_transaction(&mut self, mem_txs: MempoolTx) -> Result<(), String> {
+        let now = get_current_timestamp().and_utc().timestamp();
+        let id = mem_txs.id();
+        let fee_rate = mem_txs.calculate_fee_per_byte();
+        // A child's fee rate says little on its own when it pays for its parents.
+        let has_parents = !self.mempool.find_parents(&mem_txs.tx).is_empty();
+        self.accept_transaction(mem_txs, now)?;
+        if !has_parents {
+            self.fee_estimator
+                .track(id, fee_rate, self.blockchain.height() as u64);
+        }
+        Ok(())
+    }
+
+    /// Fee per byte likely to confirm a transaction within `target_blocks`
+    /// blocks, never below what the mempool currently accepts. `None` until
+    /// enough blocks have been seen.
+    pub fn estimate_fee(&self, target_blocks: u64) -> Option<f64> {
+        let now = get_current_timestamp().and_utc().timestamp();
+        self.fee_estimator
+            .estimate_fee(target_blocks)
+            .map(|fee_rate| fee_rate.max(self.mempool.min_fee_rate(now)))
+    }
+
+    /// Checks `mem_txs` against mempool policy and adds it as arrived at `time`.
+    fn accept_transaction(&mut self, mem_txs: MempoolTx, time: i64) -> Result<(), String> {
         let tx = &mem_txs.tx;
-        if let Err(e) = tx.validate() {
-            return Err(e.to_string());
+        // The package: the ledger with the transaction's mempool ancestors on top.
+        let mut package = UtxoOverlay::new(&LedgerView);
+        for ancestor in self.mempool.sorted_ancestors(tx) {
+            package.connect_transaction(&ancestor.tx, None)?;
         }
-        if self
-            .mempool
-            .iter()
-            .any(|mempool_tx| mempool_tx.tx.id() == tx.id())
-        {
+        let (_, checks) = tx.validate_with_checks(
+            &package,
+            self.blockchain.height() as u64,
+            self.blockchain.median_time_past(),
+            &[],
+            true,
+        )?;
+        tx.check_standard()?;
+        if self.mempool.contains(&tx.id()) {
             return Err("Transaction already in mempool".to_string());
         }
 
@@ -512,7 +784,47 @@ impl Node {
         if let Err(e) = self.is_all_inputs_utxos(&tx) {
             return Err(e);
         }
-        self.mempool.push(mem_txs);
+        self.mempool.check_chain_limits(tx)?;
+
+        let now = get_current_timestamp().and_utc().timestamp();
+        let min_fee_rate = self.mempool.min_fee_rate(now);
+        if mem_txs.calculate_fee_per_byte() < min_fee_rate {
+            return Err(format!(
+                "Transaction fee rate {:.3} is below the mempool minimum of {:.3}",
+                mem_txs.calculate_fee_per_byte(),
+                min_fee_rate
+            ));
+        }
+
+        let conflicts = self.mempool.find_conflicts(tx);
+        let replaced = if conflicts.is_empty() {
+            Vec::new()
+        } else {
+            self.mempool.check_replacement(&mem_txs, &conflicts)?
+        };
+        let id = mem_txs.id();
+        // Conflicts are only let go once the replacement is sure to stay.
+        let evicted = self
+            .mempool
+            .insert_replacing(mem_txs, &replaced, time, now)?;
+        if !replaced.is_empty() {
+            self.fee_estimator.untrack(&replaced);
+            Node::forget_mempool_txs(&replaced);
+            utils::log_info(
+                utils::LogCategory::Core,
+                &format!(
+                    "Transaction {} replaced {} mempool transaction(s)",
+                    bytes_to_hex_string(&id),
+                    replaced.len()
+                ),
+            );
+        }
+        self.save_mempool_tx(&id);
+        self.drop_mempool_txs(&evicted, "evicted from the full mempool");
+        let expired = self.mempool.expire(now);
+        self.drop_mempool_txs(&expired, "expired");
+        // Blocks carrying the transaction can skip these scripts.
+        SCRIPT_CACHE.insert_verified(&checks);
         Ok(())
     }
 
@@ -619,21 +931,35 @@ impl Node {
         }
     }
 
-    pub fn prepare_mining_snapshot(
-        &mut self,
-    ) -> Result<(Vec<MempoolTx>, [u8; 32], U256, String), String> {
+    pub fn prepare_mining_snapshot(&mut self) -> Result<MiningSnapshot, String> {
         let receive_addr = self.miner.ensure_wallet()?.get_receive_addr();
+        Ok(self.prepare_mining_snapshot_to(receive_addr))
+    }
+
+    /// Like `prepare_mining_snapshot`, paying the coinbase to `receive_addr`.
+    pub fn prepare_mining_snapshot_to(&mut self, receive_addr: String) -> MiningSnapshot {
         let previous_hash = self.blockchain.get_last_block_hash();
+        let height = self.blockchain.height() as u64;
+        let median_time_past = self.blockchain.median_time_past();
         let target = self.blockchain.calculate_next_target();
         self.target = target;
-        let mempool = self.mempool.clone();
+        let version = self.blockchain.compute_block_version();
+        let mempool = self.mempool.to_vec();
         self.flag_mining_start();
-        Ok((mempool, previous_hash, target, receive_addr))
+        MiningSnapshot {
+            mempool,
+            previous_hash,
+            height,
+            median_time_past,
+            target,
+            version,
+            receive_addr,
+        }
     }
 
     pub fn submit_mined_block(&mut self, block: Block) -> Result<Block, String> {
         self.flag_mining_end();
-        match self.submit_block(block) {
+        match self.submit_block(block, &[]) {
             Ok(()) => {
                 let new_block = self.blockchain.chain.last().unwrap();
                 network::broadcast_new_block_hash(new_block.id(), None);
@@ -645,17 +971,26 @@ impl Node {
 
     pub fn save_node(&self) {
         self.blockchain.persist_chain(None);
-        self.persist_mempool();
     }
 
     pub fn clear_mempool(&mut self) {
+        let ids: Vec<TxId> = self.mempool.iter().map(|mem_tx| mem_tx.id()).collect();
+        self.fee_estimator.untrack(&ids);
         self.mempool.clear();
+        if let Err(e) = LedgerRepository::new().clear_mempool_entries() {
+            utils::log_warning(
+                utils::LogCategory::Core,
+                &format!("Failed to clear saved mempool: {}", e),
+            );
+        }
     }
 
     pub fn get_node_version_info(&self) -> NodeVersion {
         NodeVersion {
             version: 1,
+            magic: NETWORK.magic,
             height: self.blockchain.height() as u64,
+            chainwork: self.blockchain.chainwork(),
             top_hash: self.blockchain.get_last_block_hash(),
             genesis_hash: self.blockchain.get_genesis_hash(),
             advertised_addr: crate::globals::CONFIG.p2p_advertised_addr.clone(),
@@ -672,7 +1007,7 @@ impl Node {
     }
 
     pub fn get_mempool_tx_by_id(&self, tx_id: [u8; 32]) -> Option<&MempoolTx> {
-        self.mempool.iter().find(|mtx| mtx.tx.id() == tx_id)
+        self.mempool.get(&tx_id)
     }
 
     pub async fn handle_inventory(
@@ -821,7 +1156,7 @@ impl Node {
             return;
         }
 
-        match self.submit_block(block) {
+        match self.submit_block(block, &[]) {
             Ok(()) => {
                 utils::log_info(
                     utils::LogCategory::Core,
@@ -866,13 +1201,7 @@ impl Node {
         tx: Transaction,
         exclude_peer: Option<SocketAddr>,
     ) {
-        let utxos_ids = tx
-            .inputs
-            .iter()
-            .map(|i| (i.prev_tx_id, i.output_index))
-            .collect::<Vec<_>>();
-        let repo = LedgerRepository::new();
-        let utxos = match repo.get_utxos_from_ids(&utxos_ids) {
+        let utxos = match self.resolve_spent_utxos(&tx) {
             Ok(u) => u,
             Err(e) => {
                 utils::log_error(
@@ -932,15 +1261,16 @@ impl Node {
     ) -> Result<(), String> {
         let node_v = self.get_node_version_info();
 
-        // Reject peers from a different network. We only enforce this when both
-        // sides already have a genesis block; if either side is still empty
-        // (`[0; 32]`), we let the handshake proceed so the empty node can
-        // bootstrap from the populated one.
-        let empty_hash = [0u8; 32];
-        if node_v.genesis_hash != empty_hash
-            && peer_v.genesis_hash != empty_hash
-            && node_v.genesis_hash != peer_v.genesis_hash
-        {
+        // Reject peers from a different network: both the magic and the
+        // hard-coded genesis block must match.
+        if node_v.magic != peer_v.magic {
+            return Err(format!(
+                "Network mismatch: local magic {} != peer magic {}",
+                bytes_to_hex_string(&node_v.magic),
+                bytes_to_hex_string(&peer_v.magic)
+            ));
+        }
+        if node_v.genesis_hash != peer_v.genesis_hash {
             return Err(format!(
                 "Network mismatch: local genesis {} != peer genesis {}",
                 bytes_to_hex_string(&node_v.genesis_hash),
@@ -958,11 +1288,11 @@ impl Node {
                 return Ok(());
             }
         };
-        if node_v.height == peer_v.height {
+        if node_v.chainwork == peer_v.chainwork {
             if node_v.top_hash != peer_v.top_hash {
                 utils::log_warning(
                     utils::LogCategory::P2P,
-                    "Peer has same height but different top hash.",
+                    "Peer has same chainwork but different top hash.",
                 );
                 utils::log_info(
                     utils::LogCategory::P2P,
@@ -970,10 +1300,13 @@ impl Node {
                 );
                 network::find_common_ancestor(self.blockchain.build_block_sequence(), peer);
             }
-        } else if peer_v.height > node_v.height {
+        } else if peer_v.chainwork > node_v.chainwork {
             utils::log_info(
                 utils::LogCategory::P2P,
-                "Peer has a longer chain. Requesting blocks...",
+                &format!(
+                    "Peer has a chain with more work ({} > {}). Requesting blocks...",
+                    peer_v.chainwork, node_v.chainwork
+                ),
             );
             network::find_common_ancestor(self.blockchain.build_block_sequence(), peer);
         }
@@ -1001,7 +1334,11 @@ impl Node {
             utils::LogCategory::P2P,
             &format!("No common ancestor found with peer {}", target_peer),
         );
-        network::notify_no_common_ancestor(target_peer, self.blockchain.height() as u64);
+        network::notify_no_common_ancestor(
+            target_peer,
+            self.blockchain.height() as u64,
+            self.blockchain.chainwork(),
+        );
     }
 
     pub async fn handle_received_common_block(
@@ -1011,7 +1348,7 @@ impl Node {
     ) {
         let block_hash = block.id();
 
-        if self.blockchain.find_block_by_hash(block_hash).is_none() {
+        let Some(common_height) = self.blockchain.find_block_height_by_hash(block_hash) else {
             utils::log_warning(
                 utils::LogCategory::P2P,
                 &format!(
@@ -1021,6 +1358,24 @@ impl Node {
                 ),
             );
             return;
+        };
+
+        // The peer's chain diverges right after the common block.
+        if let Err(e) = self
+            .fork_helper
+            .checkpoints()
+            .check_fork(common_height as u64 + 1, self.blockchain.height())
+        {
+            utils::log_warning(
+                utils::LogCategory::P2P,
+                &format!(
+                    "Ignoring the chain of peer {:?} diverging after common block {}: {}",
+                    peer_addr,
+                    bytes_to_hex_string(&block_hash),
+                    e
+                ),
+            );
+            return;
         }
 
         // If the common block is our chain tip, there is no fork
@@ -1052,15 +1407,33 @@ impl Node {
     pub async fn handle_no_common_ancestor(
         &mut self,
         peer_height: u64,
+        peer_chainwork: U256,
         peer_addr: Option<SocketAddr>,
     ) {
         let local_height = self.blockchain.height() as u64;
-        if peer_height <= local_height {
+        let local_chainwork = self.blockchain.chainwork();
+        if peer_chainwork <= local_chainwork {
             utils::log_warning(
                 utils::LogCategory::P2P,
                 &format!(
-                    "Peer {:?} reported no common ancestor, but its chain is not longer (peer={}, local={}).",
-                    peer_addr, peer_height, local_height
+                    "Peer {:?} reported no common ancestor, but its chain does not have more work (peer={} at height {}, local={} at height {}).",
+                    peer_addr, peer_chainwork, peer_height, local_chainwork, local_height
+                ),
+            );
+            return;
+        }
+
+        // Only the genesis block survives the reset below.
+        if let Err(e) = self
+            .fork_helper
+            .checkpoints()
+            .check_fork(1, self.blockchain.height())
+        {
+            utils::log_warning(
+                utils::LogCategory::P2P,
+                &format!(
+                    "Peer {:?} has no common ancestor with us, refusing to reset the local chain: {}",
+                    peer_addr, e
                 ),
             );
             return;
@@ -1069,8 +1442,8 @@ impl Node {
         utils::log_warning(
             utils::LogCategory::P2P,
             &format!(
-                "Peer {:?} has no common ancestor with us and a longer chain (peer={}, local={}). Resetting local chain and requesting a full sync.",
-                peer_addr, peer_height, local_height
+                "Peer {:?} has no common ancestor with us and a chain with more work (peer={} at height {}, local={} at height {}). Resetting local chain and requesting a full sync.",
+                peer_addr, peer_chainwork, peer_height, local_chainwork, local_height
             ),
         );
 
@@ -1089,7 +1462,13 @@ impl Node {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct NodeVersion {
     pub version: u32,
+    /// Magic of the sender's network, see `NetworkParams::magic`.
+    #[serde(default)]
+    pub magic: [u8; 4],
     pub height: u64,
+    /// Cumulative work of the advertised chain. Peers compare chains by work, not height.
+    #[serde(default)]
+    pub chainwork: U256,
     pub top_hash: [u8; 32],
     #[serde(default)]
     pub genesis_hash: [u8; 32],
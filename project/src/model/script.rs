@@ -0,0 +1,880 @@
+// Small stack-based script language used to lock outputs and unlock inputs.
+//
+// An input is valid when running its unlocking script followed by the locking
+// script of the output it spends leaves a true value on top of the stack.
+// Unlocking scripts may only push data, so spending conditions are decided
+// entirely by the locking script.
+use ed25519_dalek::{Signature, VerifyingKey};
+use ripemd::Ripemd160;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::Digest;
+
+use crate::model::HDKey;
+use crate::model::hdkey::AddressKind;
+use crate::model::htlc::HtlcParams;
+use crate::model::transaction::LOCKTIME_THRESHOLD;
+use crate::security_utils::{bytes_to_hex_string, sha256, verify_signature};
+
+pub const OP_0: u8 = 0x00;
+pub const OP_PUSHDATA1: u8 = 0x4c;
+pub const OP_PUSHDATA2: u8 = 0x4d;
+pub const OP_1: u8 = 0x51;
+pub const OP_16: u8 = 0x60;
+pub const OP_IF: u8 = 0x63;
+pub const OP_NOTIF: u8 = 0x64;
+pub const OP_ELSE: u8 = 0x67;
+pub const OP_ENDIF: u8 = 0x68;
+pub const OP_VERIFY: u8 = 0x69;
+pub const OP_RETURN: u8 = 0x6a;
+pub const OP_DROP: u8 = 0x75;
+pub const OP_DUP: u8 = 0x76;
+pub const OP_EQUAL: u8 = 0x87;
+pub const OP_EQUALVERIFY: u8 = 0x88;
+pub const OP_SHA256: u8 = 0xa8;
+pub const OP_HASH160: u8 = 0xa9;
+pub const OP_CHECKSIG: u8 = 0xac;
+pub const OP_CHECKSIGVERIFY: u8 = 0xad;
+pub const OP_CHECKMULTISIG: u8 = 0xae;
+pub const OP_CHECKMULTISIGVERIFY: u8 = 0xaf;
+pub const OP_CHECKLOCKTIMEVERIFY: u8 = 0xb1;
+
+/// Largest script accepted for execution.
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+/// Largest single stack element.
+pub const MAX_ELEMENT_SIZE: usize = 520;
+/// Largest number of elements on the stack at any point.
+pub const MAX_STACK_SIZE: usize = 1_000;
+/// Largest number of non-push operations in a single script.
+pub const MAX_OPS_PER_SCRIPT: usize = 201;
+/// Largest encoded number accepted by `OP_CHECKLOCKTIMEVERIFY`.
+const MAX_LOCK_TIME_NUM_SIZE: usize = 5;
+/// Largest number of public keys in a multisig script.
+pub const MAX_MULTISIG_KEYS: usize = 16;
+/// Largest payload of a data-carrier (`OP_RETURN`) output that is relayed.
+pub const MAX_DATA_CARRIER_SIZE: usize = 80;
+
+const PUBKEY_HASH_SIZE: usize = 20;
+const PUBKEY_SIZE: usize = 32;
+const SHA256_SIZE: usize = 32;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Instruction<'a> {
+    Push(&'a [u8]),
+    Op(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScriptType {
+    PubKeyHash,
+    PubKey,
+    HashLock,
+    Multisig,
+    ScriptHash,
+    Htlc,
+    NullData,
+    NonStandard,
+}
+
+impl ScriptType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScriptType::PubKeyHash => "pubkeyhash",
+            ScriptType::PubKey => "pubkey",
+            ScriptType::HashLock => "hashlock",
+            ScriptType::Multisig => "multisig",
+            ScriptType::ScriptHash => "scripthash",
+            ScriptType::Htlc => "htlc",
+            ScriptType::NullData => "nulldata",
+            ScriptType::NonStandard => "nonstandard",
+        }
+    }
+}
+
+/// Raw script bytes. Serialized as a hex string.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Script(Vec<u8>);
+
+impl Script {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Script(bytes)
+    }
+
+    pub fn from_hex(hex: &str) -> Result<Self, String> {
+        hex::decode(hex)
+            .map(Script)
+            .map_err(|e| format!("Invalid script hex: {}", e))
+    }
+
+    /// `OP_DUP OP_HASH160 <pubkey hash> OP_EQUALVERIFY OP_CHECKSIG`
+    pub fn new_p2pkh(pubkey_hash: &[u8; PUBKEY_HASH_SIZE]) -> Self {
+        let mut script = Script::new();
+        script.push_opcode(OP_DUP);
+        script.push_opcode(OP_HASH160);
+        script.push_data(pubkey_hash);
+        script.push_opcode(OP_EQUALVERIFY);
+        script.push_opcode(OP_CHECKSIG);
+        script
+    }
+
+    /// `<pubkey> OP_CHECKSIG`
+    pub fn new_p2pk(public_key: &VerifyingKey) -> Self {
+        let mut script = Script::new();
+        script.push_data(public_key.as_bytes());
+        script.push_opcode(OP_CHECKSIG);
+        script
+    }
+
+    /// `OP_SHA256 <hash> OP_EQUAL`, spendable by anyone who knows the preimage.
+    pub fn new_hashlock(hash: &[u8; SHA256_SIZE]) -> Self {
+        let mut script = Script::new();
+        script.push_opcode(OP_SHA256);
+        script.push_data(hash);
+        script.push_opcode(OP_EQUAL);
+        script
+    }
+
+    /// `<m> <pubkey 1> ... <pubkey n> <n> OP_CHECKMULTISIG`, spendable with
+    /// signatures from `required` of the keys, given in key order.
+    pub fn new_multisig(required: usize, public_keys: &[VerifyingKey]) -> Result<Self, String> {
+        let total = public_keys.len();
+        if total == 0 || total > MAX_MULTISIG_KEYS {
+            return Err(format!(
+                "Multisig needs between 1 and {} public keys, got {}",
+                MAX_MULTISIG_KEYS, total
+            ));
+        }
+        if required == 0 || required > total {
+            return Err(format!(
+                "Required signatures must be between 1 and {}, got {}",
+                total, required
+            ));
+        }
+        let mut script = Script::new();
+        script.push_opcode(OP_1 + required as u8 - 1);
+        for key in public_keys {
+            script.push_data(key.as_bytes());
+        }
+        script.push_opcode(OP_1 + total as u8 - 1);
+        script.push_opcode(OP_CHECKMULTISIG);
+        Ok(script)
+    }
+
+    /// `OP_HASH160 <script hash> OP_EQUAL`. The spender reveals the redeem
+    /// script as the last push and its conditions are then evaluated.
+    pub fn new_p2sh(script_hash: &[u8; PUBKEY_HASH_SIZE]) -> Self {
+        let mut script = Script::new();
+        script.push_opcode(OP_HASH160);
+        script.push_data(script_hash);
+        script.push_opcode(OP_EQUAL);
+        script
+    }
+
+    /// Hash committed to by a pay-to-script-hash output locked with this script.
+    pub fn script_hash(&self) -> [u8; PUBKEY_HASH_SIZE] {
+        hash160(&self.0)
+    }
+
+    /// `(required, public keys)` of a multisig script.
+    pub fn multisig_params(&self) -> Option<(usize, Vec<[u8; PUBKEY_SIZE]>)> {
+        if self.classify() != ScriptType::Multisig {
+            return None;
+        }
+        let instructions = self.instructions().ok()?;
+        let Instruction::Op(m) = instructions[0] else {
+            return None;
+        };
+        let keys = instructions[1..instructions.len() - 2]
+            .iter()
+            .filter_map(|i| match i {
+                Instruction::Push(key) => (*key).try_into().ok(),
+                Instruction::Op(_) => None,
+            })
+            .collect();
+        Some(((m - OP_1 + 1) as usize, keys))
+    }
+
+    /// Hash time-locked contract: the recipient claims with the SHA-256
+    /// preimage of `hash`, or the sender takes the funds back once the
+    /// spending transaction's lock time reaches `timeout`.
+    ///
+    /// `OP_IF OP_SHA256 <hash> OP_EQUALVERIFY OP_DUP OP_HASH160 <recipient>
+    /// OP_ELSE <timeout> OP_CHECKLOCKTIMEVERIFY OP_DROP OP_DUP OP_HASH160 <sender>
+    /// OP_ENDIF OP_EQUALVERIFY OP_CHECKSIG`
+    pub fn new_htlc(params: &HtlcParams) -> Self {
+        let mut script = Script::new();
+        script.push_opcode(OP_IF);
+        script.push_opcode(OP_SHA256);
+        script.push_data(&params.hash);
+        script.push_opcode(OP_EQUALVERIFY);
+        script.push_opcode(OP_DUP);
+        script.push_opcode(OP_HASH160);
+        script.push_data(&params.recipient_pubkey_hash);
+        script.push_opcode(OP_ELSE);
+        script.push_int(params.timeout);
+        script.push_opcode(OP_CHECKLOCKTIMEVERIFY);
+        script.push_opcode(OP_DROP);
+        script.push_opcode(OP_DUP);
+        script.push_opcode(OP_HASH160);
+        script.push_data(&params.sender_pubkey_hash);
+        script.push_opcode(OP_ENDIF);
+        script.push_opcode(OP_EQUALVERIFY);
+        script.push_opcode(OP_CHECKSIG);
+        script
+    }
+
+    /// Hash, recipient, sender and timeout of an HTLC script.
+    pub fn htlc_params(&self) -> Option<HtlcParams> {
+        if self.classify() != ScriptType::Htlc {
+            return None;
+        }
+        let instructions = self.instructions().ok()?;
+        let push = |i: usize| match instructions[i] {
+            Instruction::Push(data) => Some(data),
+            Instruction::Op(_) => None,
+        };
+        Some(HtlcParams {
+            hash: push(2)?.try_into().ok()?,
+            recipient_pubkey_hash: push(6)?.try_into().ok()?,
+            sender_pubkey_hash: push(13)?.try_into().ok()?,
+            timeout: instruction_num(&instructions[8])?,
+        })
+    }
+
+    /// `OP_RETURN <data>`, a provably unspendable output carrying `data`.
+    pub fn new_data(data: &[u8]) -> Self {
+        let mut script = Script::new();
+        script.push_opcode(OP_RETURN);
+        if !data.is_empty() {
+            script.push_data(data);
+        }
+        script
+    }
+
+    /// Locking script for a Base58 address: pay-to-pubkey-hash or pay-to-script-hash.
+    pub fn for_address(address: &str) -> Result<Self, String> {
+        match HDKey::decode_address(address) {
+            Some((AddressKind::PubKeyHash, hash)) => Ok(Script::new_p2pkh(&hash)),
+            Some((AddressKind::ScriptHash, hash)) => Ok(Script::new_p2sh(&hash)),
+            None => Err(format!("Invalid address: {}", address)),
+        }
+    }
+
+    pub fn push_opcode(&mut self, opcode: u8) {
+        self.0.push(opcode);
+    }
+
+    /// Appends `value` as a script number, using `OP_0` and `OP_1`..`OP_16`
+    /// for small values.
+    pub fn push_int(&mut self, value: u64) {
+        match value {
+            0 => self.0.push(OP_0),
+            1..=16 => self.0.push(OP_1 + value as u8 - 1),
+            _ => self.push_data(&encode_num(value as i64)),
+        }
+    }
+
+    /// Appends the shortest push of `data`.
+    pub fn push_data(&mut self, data: &[u8]) {
+        let len = data.len();
+        if len == 0 {
+            self.0.push(OP_0);
+        } else if len < OP_PUSHDATA1 as usize {
+            self.0.push(len as u8);
+        } else if len <= u8::MAX as usize {
+            self.0.push(OP_PUSHDATA1);
+            self.0.push(len as u8);
+        } else {
+            let len = u16::try_from(len).expect("script push longer than u16::MAX");
+            self.0.push(OP_PUSHDATA2);
+            self.0.extend_from_slice(&len.to_le_bytes());
+        }
+        self.0.extend_from_slice(data);
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn to_hex(&self) -> String {
+        bytes_to_hex_string(&self.0)
+    }
+
+    /// Splits the script into pushes and opcodes, failing on truncated pushes.
+    pub fn instructions(&self) -> Result<Vec<Instruction<'_>>, String> {
+        let bytes = &self.0;
+        let mut instructions = Vec::new();
+        let mut pos = 0;
+        while pos < bytes.len() {
+            let opcode = bytes[pos];
+            pos += 1;
+            let len = match opcode {
+                OP_0 => 0,
+                0x01..=0x4b => opcode as usize,
+                OP_PUSHDATA1 => {
+                    let len = *bytes.get(pos).ok_or("Truncated OP_PUSHDATA1")? as usize;
+                    pos += 1;
+                    len
+                }
+                OP_PUSHDATA2 => {
+                    let raw = bytes.get(pos..pos + 2).ok_or("Truncated OP_PUSHDATA2")?;
+                    pos += 2;
+                    u16::from_le_bytes([raw[0], raw[1]]) as usize
+                }
+                _ => {
+                    instructions.push(Instruction::Op(opcode));
+                    continue;
+                }
+            };
+            let data = bytes
+                .get(pos..pos + len)
+                .ok_or_else(|| format!("Push of {} bytes runs past end of script", len))?;
+            pos += len;
+            instructions.push(Instruction::Push(data));
+        }
+        Ok(instructions)
+    }
+
+    /// True when the script only pushes data (including `OP_1`..`OP_16`).
+    pub fn is_push_only(&self) -> bool {
+        match self.instructions() {
+            Ok(instructions) => instructions.iter().all(|i| match i {
+                Instruction::Push(_) => true,
+                Instruction::Op(op) => (OP_1..=OP_16).contains(op),
+            }),
+            Err(_) => false,
+        }
+    }
+
+    pub fn classify(&self) -> ScriptType {
+        let Ok(instructions) = self.instructions() else {
+            return ScriptType::NonStandard;
+        };
+        use Instruction::{Op, Push};
+        match instructions.as_slice() {
+            [
+                Op(OP_DUP),
+                Op(OP_HASH160),
+                Push(hash),
+                Op(OP_EQUALVERIFY),
+                Op(OP_CHECKSIG),
+            ] if hash.len() == PUBKEY_HASH_SIZE => ScriptType::PubKeyHash,
+            [Push(key), Op(OP_CHECKSIG)] if key.len() == PUBKEY_SIZE => ScriptType::PubKey,
+            [Op(OP_SHA256), Push(hash), Op(OP_EQUAL)] if hash.len() == SHA256_SIZE => {
+                ScriptType::HashLock
+            }
+            [Op(OP_HASH160), Push(hash), Op(OP_EQUAL)] if hash.len() == PUBKEY_HASH_SIZE => {
+                ScriptType::ScriptHash
+            }
+            [Op(m), keys @ .., Op(n), Op(OP_CHECKMULTISIG)]
+                if (OP_1..=OP_16).contains(m)
+                    && (OP_1..=OP_16).contains(n)
+                    && m <= n
+                    && keys.len() == (n - OP_1 + 1) as usize
+                    && keys
+                        .iter()
+                        .all(|k| matches!(k, Push(key) if key.len() == PUBKEY_SIZE)) =>
+            {
+                ScriptType::Multisig
+            }
+            [
+                Op(OP_IF),
+                Op(OP_SHA256),
+                Push(hash),
+                Op(OP_EQUALVERIFY),
+                Op(OP_DUP),
+                Op(OP_HASH160),
+                Push(recipient),
+                Op(OP_ELSE),
+                timeout,
+                Op(OP_CHECKLOCKTIMEVERIFY),
+                Op(OP_DROP),
+                Op(OP_DUP),
+                Op(OP_HASH160),
+                Push(sender),
+                Op(OP_ENDIF),
+                Op(OP_EQUALVERIFY),
+                Op(OP_CHECKSIG),
+            ] if hash.len() == SHA256_SIZE
+                && recipient.len() == PUBKEY_HASH_SIZE
+                && sender.len() == PUBKEY_HASH_SIZE
+                && instruction_num(timeout).is_some() =>
+            {
+                ScriptType::Htlc
+            }
+            [Op(OP_RETURN), rest @ ..] if rest.iter().all(|i| matches!(i, Push(_))) => {
+                ScriptType::NullData
+            }
+            _ => ScriptType::NonStandard,
+        }
+    }
+
+    /// Outputs whose script begins with `OP_RETURN` can never be spent.
+    pub fn is_unspendable(&self) -> bool {
+        self.0.first() == Some(&OP_RETURN) || self.0.len() > MAX_SCRIPT_SIZE
+    }
+
+    /// Payload of a data-carrier script: its pushes after `OP_RETURN`, concatenated.
+    pub fn data_payload(&self) -> Option<Vec<u8>> {
+        if self.classify() != ScriptType::NullData {
+            return None;
+        }
+        let payload = self
+            .instructions()
+            .ok()?
+            .into_iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Push(data) => Some(data),
+                Instruction::Op(_) => None,
+            })
+            .flatten()
+            .copied()
+            .collect();
+        Some(payload)
+    }
+
+    /// Address an output with this script pays to, if it pays to a single key
+    /// or to a script hash.
+    pub fn address(&self) -> Option<String> {
+        let instructions = self.instructions().ok()?;
+        match (self.classify(), instructions.as_slice()) {
+            (ScriptType::PubKeyHash, [_, _, Instruction::Push(hash), ..]) => {
+                Some(HDKey::address_from_pubkey_hash(&(*hash).try_into().ok()?))
+            }
+            (ScriptType::PubKey, [Instruction::Push(key), ..]) => {
+                let key: [u8; PUBKEY_SIZE] = (*key).try_into().ok()?;
+                let key = VerifyingKey::from_bytes(&key).ok()?;
+                Some(HDKey::get_address_from_public_key(&key))
+            }
+            (ScriptType::ScriptHash, [_, Instruction::Push(hash), ..]) => {
+                Some(HDKey::address_from_script_hash(&(*hash).try_into().ok()?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Human-readable form, e.g. `OP_DUP OP_HASH160 <hex> OP_EQUALVERIFY OP_CHECKSIG`.
+    pub fn to_asm(&self) -> String {
+        match self.instructions() {
+            Ok(instructions) => instructions
+                .iter()
+                .map(|i| match i {
+                    Instruction::Push([]) => "OP_0".to_string(),
+                    Instruction::Push(data) => bytes_to_hex_string(data),
+                    Instruction::Op(op) => opcode_name(*op),
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+            Err(_) => format!("[invalid] {}", self.to_hex()),
+        }
+    }
+}
+
+impl std::fmt::Debug for Script {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Script({})", self.to_asm())
+    }
+}
+
+impl Serialize for Script {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for Script {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        Script::from_hex(&hex).map_err(serde::de::Error::custom)
+    }
+}
+
+pub fn opcode_name(opcode: u8) -> String {
+    match opcode {
+        OP_0 => "OP_0".to_string(),
+        OP_PUSHDATA1 => "OP_PUSHDATA1".to_string(),
+        OP_PUSHDATA2 => "OP_PUSHDATA2".to_string(),
+        OP_1..=OP_16 => format!("OP_{}", opcode - OP_1 + 1),
+        OP_IF => "OP_IF".to_string(),
+        OP_NOTIF => "OP_NOTIF".to_string(),
+        OP_ELSE => "OP_ELSE".to_string(),
+        OP_ENDIF => "OP_ENDIF".to_string(),
+        OP_VERIFY => "OP_VERIFY".to_string(),
+        OP_RETURN => "OP_RETURN".to_string(),
+        OP_DROP => "OP_DROP".to_string(),
+        OP_DUP => "OP_DUP".to_string(),
+        OP_EQUAL => "OP_EQUAL".to_string(),
+        OP_EQUALVERIFY => "OP_EQUALVERIFY".to_string(),
+        OP_SHA256 => "OP_SHA256".to_string(),
+        OP_HASH160 => "OP_HASH160".to_string(),
+        OP_CHECKSIG => "OP_CHECKSIG".to_string(),
+        OP_CHECKSIGVERIFY => "OP_CHECKSIGVERIFY".to_string(),
+        OP_CHECKMULTISIG => "OP_CHECKMULTISIG".to_string(),
+        OP_CHECKMULTISIGVERIFY => "OP_CHECKMULTISIGVERIFY".to_string(),
+        OP_CHECKLOCKTIMEVERIFY => "OP_CHECKLOCKTIMEVERIFY".to_string(),
+        _ => format!("OP_UNKNOWN_{:02x}", opcode),
+    }
+}
+
+/// Verifies signatures found by `OP_CHECKSIG` against the spending transaction.
+/// Also answers `OP_CHECKLOCKTIMEVERIFY`, which depends on the same transaction.
+pub trait SignatureChecker {
+    fn check_signature(&self, signature: &[u8], public_key: &[u8]) -> bool;
+
+    /// Whether the spending transaction's lock time reaches `lock_time`.
+    fn check_lock_time(&self, _lock_time: u64) -> bool {
+        false
+    }
+}
+
+/// Checks signatures over the transaction's signing bytes.
+pub struct TransactionSignatureChecker<'a> {
+    signing_bytes: &'a [u8],
+    lock_time: u64,
+}
+
+impl<'a> TransactionSignatureChecker<'a> {
+    pub fn new(signing_bytes: &'a [u8]) -> Self {
+        TransactionSignatureChecker {
+            signing_bytes,
+            lock_time: 0,
+        }
+    }
+
+    /// Lock time of the spending transaction, compared by `OP_CHECKLOCKTIMEVERIFY`.
+    pub fn with_lock_time(mut self, lock_time: u64) -> Self {
+        self.lock_time = lock_time;
+        self
+    }
+}
+
+impl SignatureChecker for TransactionSignatureChecker<'_> {
+    fn check_signature(&self, signature: &[u8], public_key: &[u8]) -> bool {
+        let Ok(signature) = <[u8; 64]>::try_from(signature) else {
+            return false;
+        };
+        let Ok(public_key) = <[u8; PUBKEY_SIZE]>::try_from(public_key) else {
+            return false;
+        };
+        let Ok(public_key) = VerifyingKey::from_bytes(&public_key) else {
+            return false;
+        };
+        verify_signature(
+            &public_key,
+            self.signing_bytes,
+            Signature::from_bytes(&signature),
+        )
+        .is_ok()
+    }
+
+    /// The transaction's lock time must be of the same kind (height or time)
+    /// and at least `lock_time`; block validation then keeps it from being
+    /// mined any earlier.
+    fn check_lock_time(&self, lock_time: u64) -> bool {
+        let is_height = |t: u64| t < LOCKTIME_THRESHOLD;
+        is_height(lock_time) == is_height(self.lock_time) && self.lock_time >= lock_time
+    }
+}
+
+/// Runs `unlocking` then `locking` on a shared stack and succeeds when the
+/// final top of the stack is true. For pay-to-script-hash outputs the redeem
+/// script pushed last by `unlocking` is then run on the remaining stack.
+pub fn verify_script(
+    unlocking: &Script,
+    locking: &Script,
+    checker: &impl SignatureChecker,
+) -> Result<(), String> {
+    if !unlocking.is_push_only() {
+        return Err("Unlocking script must only push data".to_string());
+    }
+    let mut stack = Vec::new();
+    execute(unlocking, &mut stack, checker)?;
+    let mut redeem_stack = stack.clone();
+    execute(locking, &mut stack, checker)?;
+    check_final_stack(&stack)?;
+
+    if locking.classify() == ScriptType::ScriptHash {
+        let redeem_script = Script::from_bytes(pop(&mut redeem_stack)?);
+        execute(&redeem_script, &mut redeem_stack, checker)
+            .map_err(|e| format!("Redeem script failed: {}", e))?;
+        check_final_stack(&redeem_stack)?;
+    }
+    Ok(())
+}
+
+fn check_final_stack(stack: &[Vec<u8>]) -> Result<(), String> {
+    match stack.last() {
+        Some(top) if cast_to_bool(top) => Ok(()),
+        Some(_) => Err("Script evaluated to false".to_string()),
+        None => Err("Script left an empty stack".to_string()),
+    }
+}
+
+fn execute(
+    script: &Script,
+    stack: &mut Vec<Vec<u8>>,
+    checker: &impl SignatureChecker,
+) -> Result<(), String> {
+    if script.len() > MAX_SCRIPT_SIZE {
+        return Err(format!(
+            "Script of {} bytes exceeds the {} byte limit",
+            script.len(),
+            MAX_SCRIPT_SIZE
+        ));
+    }
+    let mut op_count = 0;
+    // One entry per open OP_IF/OP_NOTIF: whether its current branch runs.
+    let mut branches: Vec<bool> = Vec::new();
+    for instruction in script.instructions()? {
+        let executing = branches.iter().all(|b| *b);
+        let opcode = match instruction {
+            Instruction::Push(data) => {
+                if data.len() > MAX_ELEMENT_SIZE {
+                    return Err(format!(
+                        "Push of {} bytes exceeds the {} byte element limit",
+                        data.len(),
+                        MAX_ELEMENT_SIZE
+                    ));
+                }
+                if executing {
+                    stack.push(data.to_vec());
+                    check_stack_size(stack)?;
+                }
+                continue;
+            }
+            Instruction::Op(opcode) => opcode,
+        };
+        if opcode > OP_16 {
+            op_count += 1;
+            if op_count > MAX_OPS_PER_SCRIPT {
+                return Err(format!("Script exceeds {} operations", MAX_OPS_PER_SCRIPT));
+            }
+        }
+        match opcode {
+            OP_IF | OP_NOTIF => {
+                let mut taken = false;
+                if executing {
+                    taken = cast_to_bool(&pop(stack)?) == (opcode == OP_IF);
+                }
+                branches.push(taken);
+                continue;
+            }
+            OP_ELSE => {
+                let open = branches.last_mut().ok_or("OP_ELSE without OP_IF")?;
+                *open = !*open;
+                continue;
+            }
+            OP_ENDIF => {
+                branches.pop().ok_or("OP_ENDIF without OP_IF")?;
+                continue;
+            }
+            _ if !executing => continue,
+            _ => {}
+        }
+        match opcode {
+            OP_1..=OP_16 => stack.push(vec![opcode - OP_1 + 1]),
+            OP_VERIFY => {
+                if !cast_to_bool(&pop(stack)?) {
+                    return Err("OP_VERIFY failed".to_string());
+                }
+            }
+            OP_RETURN => return Err("OP_RETURN encountered".to_string()),
+            OP_DROP => {
+                pop(stack)?;
+            }
+            OP_DUP => {
+                let top = stack.last().ok_or("Stack underflow")?.clone();
+                stack.push(top);
+            }
+            OP_EQUAL | OP_EQUALVERIFY => {
+                let a = pop(stack)?;
+                let b = pop(stack)?;
+                if opcode == OP_EQUALVERIFY {
+                    if a != b {
+                        return Err("OP_EQUALVERIFY failed".to_string());
+                    }
+                } else {
+                    stack.push(bool_bytes(a == b));
+                }
+            }
+            OP_SHA256 => {
+                let data = pop(stack)?;
+                stack.push(sha256(&data).to_vec());
+            }
+            OP_HASH160 => {
+                let data = pop(stack)?;
+                stack.push(hash160(&data).to_vec());
+            }
+            OP_CHECKSIG | OP_CHECKSIGVERIFY => {
+                let public_key = pop(stack)?;
+                let signature = pop(stack)?;
+                let valid = checker.check_signature(&signature, &public_key);
+                if opcode == OP_CHECKSIGVERIFY {
+                    if !valid {
+                        return Err("OP_CHECKSIGVERIFY failed".to_string());
+                    }
+                } else {
+                    stack.push(bool_bytes(valid));
+                }
+            }
+            OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => {
+                let valid = check_multisig(stack, checker)?;
+                if opcode == OP_CHECKMULTISIGVERIFY {
+                    if !valid {
+                        return Err("OP_CHECKMULTISIGVERIFY failed".to_string());
+                    }
+                } else {
+                    stack.push(bool_bytes(valid));
+                }
+            }
+            OP_CHECKLOCKTIMEVERIFY => {
+                let top = stack.last().ok_or("Stack underflow")?;
+                let lock_time = decode_num(top, MAX_LOCK_TIME_NUM_SIZE)?;
+                if lock_time < 0 {
+                    return Err("Negative lock time".to_string());
+                }
+                if !checker.check_lock_time(lock_time as u64) {
+                    return Err("OP_CHECKLOCKTIMEVERIFY failed".to_string());
+                }
+            }
+            _ => return Err(format!("Unknown opcode {}", opcode_name(opcode))),
+        }
+        check_stack_size(stack)?;
+    }
+    if !branches.is_empty() {
+        return Err("Unbalanced conditional".to_string());
+    }
+    Ok(())
+}
+
+/// Non-negative number pushed by `instruction`, as read by `OP_CHECKLOCKTIMEVERIFY`.
+fn instruction_num(instruction: &Instruction) -> Option<u64> {
+    match instruction {
+        Instruction::Op(op @ OP_1..=OP_16) => Some((op - OP_1 + 1) as u64),
+        Instruction::Push(data) => decode_num(data, MAX_LOCK_TIME_NUM_SIZE)
+            .ok()
+            .and_then(|n| u64::try_from(n).ok()),
+        Instruction::Op(_) => None,
+    }
+}
+
+/// Minimal little-endian encoding with a sign bit, as pushed for script numbers.
+pub fn encode_num(value: i64) -> Vec<u8> {
+    if value == 0 {
+        return Vec::new();
+    }
+    let negative = value < 0;
+    let mut abs = value.unsigned_abs();
+    let mut bytes = Vec::new();
+    while abs > 0 {
+        bytes.push((abs & 0xff) as u8);
+        abs >>= 8;
+    }
+    if bytes.last().is_some_and(|b| b & 0x80 != 0) {
+        bytes.push(if negative { 0x80 } else { 0x00 });
+    } else if negative {
+        *bytes.last_mut().unwrap() |= 0x80;
+    }
+    bytes
+}
+
+/// Decodes a script number of at most `max_size` bytes.
+pub fn decode_num(data: &[u8], max_size: usize) -> Result<i64, String> {
+    if data.len() > max_size {
+        return Err(format!(
+            "Number of {} bytes exceeds the {} byte limit",
+            data.len(),
+            max_size
+        ));
+    }
+    let Some((last, _)) = data.split_last() else {
+        return Ok(0);
+    };
+    let mut value: i64 = 0;
+    for (i, byte) in data.iter().enumerate() {
+        let byte = if i == data.len() - 1 {
+            byte & 0x7f
+        } else {
+            *byte
+        };
+        value |= (byte as i64) << (8 * i);
+    }
+    Ok(if last & 0x80 != 0 { -value } else { value })
+}
+
+/// Pops `n`, `n` keys, `m` and `m` signatures, and checks that every
+/// signature matches a distinct key, with signatures in the same order as keys.
+fn check_multisig(
+    stack: &mut Vec<Vec<u8>>,
+    checker: &impl SignatureChecker,
+) -> Result<bool, String> {
+    let total = pop_count(stack, MAX_MULTISIG_KEYS)?;
+    let mut keys = Vec::with_capacity(total);
+    for _ in 0..total {
+        keys.push(pop(stack)?);
+    }
+    keys.reverse();
+    let required = pop_count(stack, total)?;
+    let mut signatures = Vec::with_capacity(required);
+    for _ in 0..required {
+        signatures.push(pop(stack)?);
+    }
+    signatures.reverse();
+
+    let mut keys = keys.iter();
+    for signature in &signatures {
+        if !keys.any(|key| checker.check_signature(signature, key)) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Pops a small count pushed with `OP_0`..`OP_16`, at most `max`.
+fn pop_count(stack: &mut Vec<Vec<u8>>, max: usize) -> Result<usize, String> {
+    let count = match pop(stack)?.as_slice() {
+        [] => 0,
+        [n] => *n as usize,
+        _ => return Err("Invalid count on stack".to_string()),
+    };
+    if count > max {
+        return Err(format!("Count {} exceeds the limit of {}", count, max));
+    }
+    Ok(count)
+}
+
+fn pop(stack: &mut Vec<Vec<u8>>) -> Result<Vec<u8>, String> {
+    stack.pop().ok_or_else(|| "Stack underflow".to_string())
+}
+
+fn check_stack_size(stack: &[Vec<u8>]) -> Result<(), String> {
+    if stack.len() > MAX_STACK_SIZE {
+        return Err(format!("Stack exceeds {} elements", MAX_STACK_SIZE));
+    }
+    Ok(())
+}
+
+fn bool_bytes(value: bool) -> Vec<u8> {
+    if value { vec![1] } else { Vec::new() }
+}
+
+/// Any non-zero byte makes an element true.
+fn cast_to_bool(data: &[u8]) -> bool {
+    data.iter().any(|b| *b != 0)
+}
+
+/// `ripemd160(sha256(data))`, the hash used for pubkey-hash addresses.
+pub fn hash160(data: &[u8]) -> [u8; PUBKEY_HASH_SIZE] {
+    Ripemd160::digest(sha256(data)).into()
+}
@@ -0,0 +1,200 @@
+// Parallel verification of input scripts.
+//
+// Transactions and blocks are validated in two passes. The first fetches
+// every transaction spent with a single ledger query and checks amounts,
+// maturity and lock times input by input, queueing a `ScriptCheck` for each
+// input instead of running its script. The second runs the queue across a pool
+// of worker threads.
+//
+// Every check queued before a failure of the first pass comes before it in
+// input order, so the failing check earliest in the queue, if any, is reported
+// ahead of the first pass's error. This gives the same error as checking the
+// inputs one by one, whatever the number of workers.
+//
+// Signatures are checked one at a time within the workers. Ed25519 batch
+// verification only says whether a whole batch is valid, while `OP_CHECKSIG`
+// and `OP_CHECKMULTISIG` branch on the result of each signature. It also
+// weighs each signature by a scalar, which cancels a small-order component of
+// R or of the key for some weights, so a batch can accept what a single check
+// rejects; ruling that out costs two subgroup checks per signature, more than
+// batching saves.
+//
+// The checks of transactions accepted to the mempool are remembered in
+// `SCRIPT_CACHE`, so a block carrying transactions already relayed to us skips
+// their scripts. A check is identified by the wtxid of its transaction and the
+// input index: the wtxid commits to the unlocking script, the signed data and
+// the spent outpoint, so a malleated signature never matches a cached check.
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use once_cell::sync::Lazy;
+
+use crate::globals::CONFIG;
+use crate::model::Script;
+use crate::model::script::{TransactionSignatureChecker, verify_script};
+use crate::model::transaction::TxId;
+
+/// Fewest checks handed to each worker; smaller queues use fewer workers.
+pub const MIN_CHECKS_PER_WORKER: usize = 8;
+
+/// Identifies a script check: wtxid of the spending transaction and input index.
+pub type ScriptCheckKey = (TxId, usize);
+
+/// Script checks already passed by mempool transactions.
+pub static SCRIPT_CACHE: Lazy<ScriptCache> =
+    Lazy::new(|| ScriptCache::new(CONFIG.script_cache_size));
+
+/// An input script waiting to be run against the output it spends.
+#[derive(Debug, Clone)]
+pub struct ScriptCheck {
+    key: ScriptCheckKey,
+    /// `txid:index` of the spent output, for error messages.
+    input_ref: String,
+    unlocking: Script,
+    locking: Script,
+    signing_bytes: Arc<[u8]>,
+    lock_time: u64,
+}
+
+impl ScriptCheck {
+    pub fn new(
+        key: ScriptCheckKey,
+        input_ref: String,
+        unlocking: Script,
+        locking: Script,
+        signing_bytes: impl Into<Arc<[u8]>>,
+        lock_time: u64,
+    ) -> Self {
+        ScriptCheck {
+            key,
+            input_ref,
+            unlocking,
+            locking,
+            signing_bytes: signing_bytes.into(),
+            lock_time,
+        }
+    }
+
+    pub fn key(&self) -> ScriptCheckKey {
+        self.key
+    }
+
+    pub fn verify(&self) -> Result<(), String> {
+        let checker =
+            TransactionSignatureChecker::new(&self.signing_bytes).with_lock_time(self.lock_time);
+        verify_script(&self.unlocking, &self.locking, &checker).map_err(|e| {
+            format!(
+                "Script verification failed for input {}: {}",
+                self.input_ref, e
+            )
+        })
+    }
+}
+
+/// Runs `checks` on up to `workers` threads and returns the error of the
+/// first failing one in queue order.
+pub fn run_script_checks(checks: &[ScriptCheck], workers: usize) -> Result<(), String> {
+    let workers = workers
+        .min(checks.len().div_ceil(MIN_CHECKS_PER_WORKER))
+        .max(1);
+    if workers == 1 {
+        return checks.iter().try_for_each(ScriptCheck::verify);
+    }
+
+    // Lowest failing position found so far; workers skip the checks after it.
+    let first_failure = AtomicUsize::new(usize::MAX);
+    let chunk_size = checks.len().div_ceil(workers);
+    let failures: Vec<(usize, String)> = thread::scope(|scope| {
+        let handles: Vec<_> = checks
+            .chunks(chunk_size)
+            .enumerate()
+            .map(|(chunk_index, chunk)| {
+                let first_failure = &first_failure;
+                scope.spawn(move || {
+                    for (offset, check) in chunk.iter().enumerate() {
+                        let position = chunk_index * chunk_size + offset;
+                        if position > first_failure.load(Ordering::Relaxed) {
+                            return None;
+                        }
+                        if let Err(e) = check.verify() {
+                            first_failure.fetch_min(position, Ordering::Relaxed);
+                            return Some((position, e));
+                        }
+                    }
+                    None
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .filter_map(|handle| handle.join().expect("script check worker panicked"))
+            .collect()
+    });
+
+    match failures.into_iter().min_by_key(|(position, _)| *position) {
+        Some((_, e)) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Bounded set of script checks known to pass; the oldest are forgotten first.
+pub struct ScriptCache {
+    capacity: usize,
+    entries: Mutex<CacheEntries>,
+}
+
+#[derive(Default)]
+struct CacheEntries {
+    verified: HashSet<ScriptCheckKey>,
+    by_age: VecDeque<ScriptCheckKey>,
+}
+
+impl ScriptCache {
+    pub fn new(capacity: usize) -> Self {
+        ScriptCache {
+            capacity,
+            entries: Mutex::new(CacheEntries::default()),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().verified.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, key: &ScriptCheckKey) -> bool {
+        self.entries.lock().unwrap().verified.contains(key)
+    }
+
+    /// Drops the checks known to pass from `checks`.
+    pub fn retain_unverified(&self, checks: &mut Vec<ScriptCheck>) {
+        if checks.is_empty() {
+            return;
+        }
+        let entries = self.entries.lock().unwrap();
+        checks.retain(|check| !entries.verified.contains(&check.key));
+    }
+
+    /// Remembers that `checks` passed, forgetting the oldest entries beyond capacity.
+    pub fn insert_verified(&self, checks: &[ScriptCheck]) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        for check in checks {
+            if entries.verified.insert(check.key) {
+                entries.by_age.push_back(check.key);
+            }
+        }
+        while entries.by_age.len() > self.capacity {
+            if let Some(oldest) = entries.by_age.pop_front() {
+                entries.verified.remove(&oldest);
+            }
+        }
+    }
+}
@@ -1,24 +1,118 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use crate::{
-    db::repository::LedgerRepository,
-    globals::CONSENSUS_RULES,
-    model::{HDKey, TxInput, TxOutput, UTXO},
-    security_utils::{
-        bytes_to_hex_string, load_public_key_from_hex, load_signature_from_hex, sha256,
-        verify_signature,
+    globals::{CONFIG, CONSENSUS_RULES},
+    model::script::{MAX_DATA_CARRIER_SIZE, MAX_SCRIPT_SIZE, ScriptType},
+    model::script_check::{SCRIPT_CACHE, ScriptCheck, ScriptCheckKey, run_script_checks},
+    model::utxo_view::{Coin, LedgerView, OutPoint, UtxoView},
+    model::{
+        Script, TxInput, TxOutput, UTXO,
+        io::{
+            RelativeLock, SIGHASH_ALL, SIGHASH_ANYONECANPAY, SIGHASH_NONE, SIGHASH_SINGLE,
+            check_sighash_type,
+        },
     },
-    utils::{format_date, get_current_timestamp},
+    security_utils::{bytes_to_hex_string, sha256},
+    utils::{ByteReader, ByteWriter, get_current_timestamp},
 };
 use chrono::NaiveDateTime;
-use ed25519_dalek::VerifyingKey;
 use serde::{Deserialize, Serialize};
 
 pub type TxId = [u8; 32];
+
+/// The block transactions are checked for, and the unspent outputs their
+/// inputs spend.
+pub struct InputContext<'a> {
+    pub spend_height: u64,
+    pub median_time_past: Option<NaiveDateTime>,
+    /// Queue a script check per input; off below the assume-valid block.
+    pub verify_scripts: bool,
+    /// Outputs spent by the inputs, as found in `view`.
+    coins: HashMap<OutPoint, Coin>,
+    view: &'a dyn UtxoView,
+}
+
+impl<'a> InputContext<'a> {
+    /// Fetches the outputs spent by `txs` from `view` at once (a single
+    /// ledger query for `LedgerView`).
+    pub fn fetch(
+        view: &'a dyn UtxoView,
+        txs: &[Transaction],
+        spend_height: u64,
+        median_time_past: Option<NaiveDateTime>,
+        verify_scripts: bool,
+    ) -> Result<Self, String> {
+        let mut outpoints: Vec<OutPoint> = txs
+            .iter()
+            .flat_map(|tx| tx.inputs.iter())
+            .map(|input| (input.prev_tx_id, input.output_index))
+            .collect();
+        outpoints.sort_unstable();
+        outpoints.dedup();
+
+        Ok(InputContext {
+            spend_height,
+            median_time_past,
+            verify_scripts,
+            coins: view.coins(&outpoints)?,
+            view,
+        })
+    }
+}
+
+/// Signing bytes of the inputs of one transaction, encoding what their
+/// messages share once: the `SIGHASH_ALL` message and the stripped inputs.
+pub struct SighashCache<'a> {
+    tx: &'a Transaction,
+    all: Option<Arc<[u8]>>,
+    stripped_inputs: Option<Vec<TxInput>>,
+}
+
+impl<'a> SighashCache<'a> {
+    pub fn new(tx: &'a Transaction) -> Self {
+        SighashCache {
+            tx,
+            all: None,
+            stripped_inputs: None,
+        }
+    }
+
+    /// See `Transaction::signing_bytes_for_input`.
+    pub fn signing_bytes_for_input(&mut self, index: usize) -> Result<Arc<[u8]>, String> {
+        let tx = self.tx;
+        let input = tx
+            .inputs
+            .get(index)
+            .ok_or_else(|| format!("Input index {} out of bounds", index))?;
+        check_sighash_type(input.sighash_type)?;
+        if input.sighash_type == SIGHASH_ALL {
+            return Ok(self
+                .all
+                .get_or_insert_with(|| tx.signing_bytes().into())
+                .clone());
+        }
+        let stripped_inputs = self
+            .stripped_inputs
+            .get_or_insert_with(|| tx.inputs.iter().map(|i| i.get_partial()).collect());
+        tx.signing_bytes_for_type(index, stripped_inputs)
+            .map(Arc::from)
+    }
+}
+
+/// Lock times below this value are block heights, at or above it Unix timestamps.
+pub const LOCKTIME_THRESHOLD: u64 = 500_000_000;
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Transaction {
     pub inputs: Vec<TxInput>,
     pub outputs: Vec<TxOutput>,
     pub date: NaiveDateTime,
     pub message: Option<String>,
+    /// Earliest block height or median-time-past (see `LOCKTIME_THRESHOLD`)
+    /// at which the transaction may be mined, 0 for none.
+    #[serde(default)]
+    pub lock_time: u64,
 }
 impl Transaction {
     pub fn new(inputs: Vec<TxInput>, outputs: Vec<TxOutput>, message: Option<String>) -> Self {
@@ -28,45 +122,169 @@ impl Transaction {
             outputs,
             date,
             message,
+            lock_time: 0,
         }
     }
 
+    /// Transaction id: the hash of `stripped_bytes`. Signatures, public keys
+    /// and unlocking scripts are left out, so re-encoding them cannot change it.
     pub fn id(&self) -> TxId {
+        sha256(&self.stripped_bytes())
+    }
+
+    /// Witness id: the hash of the full encoding, witness data included.
+    pub fn wtxid(&self) -> TxId {
         sha256(&self.as_bytes())
     }
 
-    pub fn new_coinbase(miner_address: String, fees: i64) -> Self {
+    /// Builds the coinbase for a block at `height`, paying the height's
+    /// subsidy plus the collected `fees` to `miner_address`. Its message
+    /// records the height, so coinbases of blocks built in the same second
+    /// for the same address still have different ids.
+    pub fn new_coinbase(miner_address: String, height: u64, fees: i64) -> Self {
         let date = get_current_timestamp();
         let inputs = Vec::new();
-        let reward_amount = CONSENSUS_RULES.block_reward + fees;
-        let outputs = vec![TxOutput {
-            value: reward_amount,
-            address: miner_address,
-        }];
-        let message = None;
+        let reward_amount = CONSENSUS_RULES.block_subsidy(height) + fees;
+        let outputs = vec![TxOutput::new(reward_amount, miner_address)];
+        let message = Some(format!("height {}", height));
 
         Transaction {
             date,
             inputs,
             outputs,
             message,
+            lock_time: 0,
         }
     }
 
-    /// Validates the transaction and returns its fee (`sum_inputs - sum_outputs`).
+    /// Validates the transaction for inclusion in a block at `spend_height`, whose
+    /// median-time-past is `median_time_past`, and returns its fee (`sum_inputs - sum_outputs`).
     /// Coinbase transactions return `0` - their reward is bounded by block-level rules.
-    pub fn validate(&self) -> Result<i64, String> {
+    pub fn validate(
+        &self,
+        spend_height: u64,
+        median_time_past: Option<NaiveDateTime>,
+    ) -> Result<i64, String> {
+        self.validate_with_unconfirmed(spend_height, median_time_past, &[])
+    }
+
+    /// Like `validate`, also letting inputs spend outputs of `unconfirmed`:
+    /// mempool parents, or transactions earlier in the same block.
+    pub fn validate_with_unconfirmed(
+        &self,
+        spend_height: u64,
+        median_time_past: Option<NaiveDateTime>,
+        unconfirmed: &[Transaction],
+    ) -> Result<i64, String> {
+        self.validate_with_options(spend_height, median_time_past, unconfirmed, true)
+    }
+
+    /// Like `validate_with_unconfirmed`. Without `verify_scripts` the input
+    /// scripts and signatures are not run, which is only sound for blocks
+    /// below the assume-valid block; every other rule is still enforced.
+    pub fn validate_with_options(
+        &self,
+        spend_height: u64,
+        median_time_past: Option<NaiveDateTime>,
+        unconfirmed: &[Transaction],
+        verify_scripts: bool,
+    ) -> Result<i64, String> {
+        self.validate_in(
+            &LedgerView,
+            spend_height,
+            median_time_past,
+            unconfirmed,
+            verify_scripts,
+        )
+    }
+
+    /// Like `validate_with_options`, spending the outputs of `view` instead
+    /// of the ledger's UTXO set.
+    pub fn validate_in(
+        &self,
+        view: &dyn UtxoView,
+        spend_height: u64,
+        median_time_past: Option<NaiveDateTime>,
+        unconfirmed: &[Transaction],
+        verify_scripts: bool,
+    ) -> Result<i64, String> {
+        self.validate_with_checks(
+            view,
+            spend_height,
+            median_time_past,
+            unconfirmed,
+            verify_scripts,
+        )
+        .map(|(fee, _)| fee)
+    }
+
+    /// Like `validate_in`, also returning the script checks it ran, for
+    /// `SCRIPT_CACHE` once the transaction is accepted to the mempool.
+    pub fn validate_with_checks(
+        &self,
+        view: &dyn UtxoView,
+        spend_height: u64,
+        median_time_past: Option<NaiveDateTime>,
+        unconfirmed: &[Transaction],
+        verify_scripts: bool,
+    ) -> Result<(i64, Vec<ScriptCheck>), String> {
+        let context = InputContext::fetch(
+            view,
+            std::slice::from_ref(self),
+            spend_height,
+            median_time_past,
+            verify_scripts,
+        )?;
+        let unconfirmed: HashMap<TxId, &Transaction> =
+            unconfirmed.iter().map(|tx| (tx.id(), tx)).collect();
+        let mut checks = Vec::new();
+        let fee = self.check_inputs(&context, &unconfirmed, &mut checks);
+        SCRIPT_CACHE.retain_unverified(&mut checks);
+        // Every queued check belongs to an input before any failure above.
+        run_script_checks(&checks, CONFIG.script_threads)?;
+        Ok((fee?, checks))
+    }
+
+    /// Every check of `validate_with_options` but running the input scripts,
+    /// which are queued on `checks` in input order instead. Inputs may spend
+    /// the `unconfirmed` transactions, indexed by id. Returns the fee.
+    pub fn check_inputs(
+        &self,
+        context: &InputContext,
+        unconfirmed: &HashMap<TxId, &Transaction>,
+        checks: &mut Vec<ScriptCheck>,
+    ) -> Result<i64, String> {
         let output_sum = self.checked_output_sum()?;
+        self.check_output_scripts()?;
 
         if self.is_coinbase() {
             return Ok(0);
         }
+        self.check_lock_time(context.spend_height, context.median_time_past)?;
 
-        let partial_tx_bytes = self.partial_tx_bytes();
-        let repo = LedgerRepository::new();
+        let wtxid = if context.verify_scripts {
+            self.wtxid()
+        } else {
+            [0; 32]
+        };
+        let mut sighashes = SighashCache::new(self);
         let mut input_sum: i64 = 0;
-        for input in &self.inputs {
-            let input_value = Self::validate_input(input, &partial_tx_bytes, &repo)?;
+        for (index, input) in self.inputs.iter().enumerate() {
+            let signing_bytes = if context.verify_scripts {
+                Some(sighashes.signing_bytes_for_input(index)?)
+            } else {
+                None
+            };
+            let referenced_output = Self::resolve_referenced_output(input, context, unconfirmed)?;
+            let input_value = referenced_output.value;
+            let check = Self::check_input(
+                (wtxid, index),
+                input,
+                referenced_output,
+                signing_bytes,
+                self.lock_time,
+            )?;
+            checks.extend(check);
             input_sum = input_sum
                 .checked_add(input_value)
                 .ok_or_else(|| "Input sum overflow".to_string())?;
@@ -94,108 +312,489 @@ impl Transaction {
         Ok(sum)
     }
 
-    fn partial_tx_bytes(&self) -> Vec<u8> {
+    /// Outputs with an explicit script must fit the script size limit and carry
+    /// the address derived from it (empty when it pays to no single key).
+    fn check_output_scripts(&self) -> Result<(), String> {
+        for (index, output) in self.outputs.iter().enumerate() {
+            if output.script.is_empty() {
+                continue;
+            }
+            if output.script.len() > MAX_SCRIPT_SIZE {
+                return Err(format!(
+                    "Output {} script of {} bytes exceeds the {} byte limit",
+                    index,
+                    output.script.len(),
+                    MAX_SCRIPT_SIZE
+                ));
+            }
+            if output.address != output.script.address().unwrap_or_default() {
+                return Err(format!(
+                    "Output {} address does not match its script",
+                    index
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Relay policy: every explicit output script must be of a known type, and
+    /// at most one output may carry data, burning no coins and holding at most
+    /// `MAX_DATA_CARRIER_SIZE` bytes. Consensus accepts any script, but the
+    /// mempool only relays these.
+    pub fn check_standard(&self) -> Result<(), String> {
+        let mut data_outputs = 0;
+        for (index, output) in self.outputs.iter().enumerate() {
+            if output.script.is_empty() {
+                continue;
+            }
+            match output.script.classify() {
+                ScriptType::NonStandard => {
+                    return Err(format!("Output {} has a non-standard script", index));
+                }
+                ScriptType::NullData => {
+                    data_outputs += 1;
+                    if output.value != 0 {
+                        return Err(format!(
+                            "Data-carrier output {} must have zero value",
+                            index
+                        ));
+                    }
+                    let size = output.script.data_payload().unwrap_or_default().len();
+                    if size > MAX_DATA_CARRIER_SIZE {
+                        return Err(format!(
+                            "Data-carrier output {} carries {} bytes, above the {} byte limit",
+                            index, size, MAX_DATA_CARRIER_SIZE
+                        ));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if data_outputs > 1 {
+            return Err("Transaction has more than one data-carrier output".to_string());
+        }
+        Ok(())
+    }
+
+    /// The canonical encoding with every input's witness data (signature,
+    /// public key and unlocking script) left empty.
+    pub fn stripped_bytes(&self) -> Vec<u8> {
         Transaction {
             inputs: self.inputs.iter().map(|i| i.get_partial()).collect(),
             outputs: self.outputs.clone(),
             date: self.date,
             message: self.message.clone(),
+            lock_time: self.lock_time,
         }
         .as_bytes()
     }
 
-    fn validate_input(
+    /// Bytes covered by `SIGHASH_ALL` signatures: the stripped encoding.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        self.stripped_bytes()
+    }
+
+    /// Bytes the signature on input `index` covers, selected by the input's
+    /// `sighash_type`. The input's own sighash type is always part of them.
+    pub fn signing_bytes_for_input(&self, index: usize) -> Result<Vec<u8>, String> {
+        SighashCache::new(self)
+            .signing_bytes_for_input(index)
+            .map(|signing_bytes| signing_bytes.to_vec())
+    }
+
+    /// `signing_bytes_for_input` for a type other than `SIGHASH_ALL`, given
+    /// the transaction's inputs without their witness data.
+    fn signing_bytes_for_type(
+        &self,
+        index: usize,
+        stripped_inputs: &[TxInput],
+    ) -> Result<Vec<u8>, String> {
+        let input = &self.inputs[index];
+        let mut inputs = stripped_inputs.to_vec();
+        let mut outputs = self.outputs.clone();
+        let base_type = input.sighash_type & !SIGHASH_ANYONECANPAY;
+        if base_type != SIGHASH_ALL {
+            // Other inputs' relative locks may change along with the outputs.
+            for (i, other) in inputs.iter_mut().enumerate() {
+                if i != index {
+                    other.relative_lock = 0;
+                }
+            }
+        }
+        match base_type {
+            SIGHASH_NONE => outputs.clear(),
+            SIGHASH_SINGLE => {
+                if index >= outputs.len() {
+                    return Err(format!(
+                        "Input {} signs a single output but has no matching output",
+                        index
+                    ));
+                }
+                outputs.truncate(index + 1);
+                for output in &mut outputs[..index] {
+                    *output = TxOutput::with_script(-1, Script::new());
+                }
+            }
+            _ => {}
+        }
+        let mut own_index = index;
+        if input.sighash_type & SIGHASH_ANYONECANPAY != 0 {
+            inputs = vec![inputs.swap_remove(index)];
+            own_index = 0;
+        }
+
+        let mut w = ByteWriter::versioned();
+        Transaction {
+            inputs,
+            outputs,
+            date: self.date,
+            message: self.message.clone(),
+            lock_time: self.lock_time,
+        }
+        .encode_into(&mut w);
+        w.put_u32(own_index as u32);
+        Ok(w.into_bytes())
+    }
+
+    /// Checks that no input spends a coinbase output that is still immature at `spend_height`.
+    /// Inputs spending a transaction for which `is_pending` holds are skipped:
+    /// those are mempool transactions, never coinbases. Any other input must
+    /// spend a transaction of the ledger.
+    pub fn check_coinbase_maturity(
+        &self,
+        spend_height: u64,
+        is_pending: impl Fn(&TxId) -> bool,
+    ) -> Result<(), String> {
+        for input in &self.inputs {
+            if is_pending(&input.prev_tx_id) {
+                continue;
+            }
+            let (prev_tx, prev_height) =
+                LedgerView.transaction(&input.prev_tx_id)?.ok_or_else(|| {
+                    format!(
+                        "Unable to find transaction for input: {}",
+                        bytes_to_hex_string(&input.prev_tx_id)
+                    )
+                })?;
+            Self::check_input_maturity(input, prev_tx.is_coinbase(), prev_height, spend_height)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the transaction opted in to replace-by-fee: any of its inputs
+    /// is marked `replaceable`.
+    pub fn signals_replacement(&self) -> bool {
+        self.inputs.iter().any(|input| input.replaceable)
+    }
+
+    /// Whether the absolute lock time allows inclusion in a block at `height`
+    /// whose median-time-past is `median_time_past`.
+    pub fn is_final(&self, height: u64, median_time_past: Option<NaiveDateTime>) -> bool {
+        if self.lock_time == 0 {
+            return true;
+        }
+        if self.lock_time < LOCKTIME_THRESHOLD {
+            return height >= self.lock_time;
+        }
+        median_time_past.is_some_and(|mtp| mtp.and_utc().timestamp() >= self.lock_time as i64)
+    }
+
+    fn check_lock_time(
+        &self,
+        height: u64,
+        median_time_past: Option<NaiveDateTime>,
+    ) -> Result<(), String> {
+        if self.is_final(height, median_time_past) {
+            return Ok(());
+        }
+        if self.lock_time < LOCKTIME_THRESHOLD {
+            Err(format!(
+                "Transaction is locked until height {} (block height {})",
+                self.lock_time, height
+            ))
+        } else {
+            Err(format!(
+                "Transaction is locked until time {} (median time past {})",
+                self.lock_time,
+                median_time_past.map_or(0, |mtp| mtp.and_utc().timestamp())
+            ))
+        }
+    }
+
+    /// Checks the absolute lock time and every input's relative lock for a block at
+    /// `spend_height` whose median-time-past is `median_time_past`.
+    pub fn check_finality(
+        &self,
+        spend_height: u64,
+        median_time_past: Option<NaiveDateTime>,
+    ) -> Result<(), String> {
+        self.check_lock_time(spend_height, median_time_past)?;
+        for input in self.inputs.iter().filter(|i| i.relative_lock().is_some()) {
+            let prev_height = LedgerView
+                .transaction(&input.prev_tx_id)?
+                .and_then(|(_, height)| height);
+            Self::check_relative_lock(
+                input,
+                prev_height,
+                spend_height,
+                median_time_past,
+                &LedgerView,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn check_relative_lock(
         input: &TxInput,
-        partial_tx_bytes: &[u8],
-        repo: &LedgerRepository,
-    ) -> Result<i64, String> {
-        let referenced_output = Self::resolve_referenced_output(input, repo)?;
-        let pubkey = load_public_key_from_hex(&input.public_key).map_err(|e| {
+        prev_height: Option<u64>,
+        spend_height: u64,
+        median_time_past: Option<NaiveDateTime>,
+        view: &dyn UtxoView,
+    ) -> Result<(), String> {
+        let Some(lock) = input.relative_lock() else {
+            return Ok(());
+        };
+        let prev_height = prev_height.ok_or_else(|| {
             format!(
-                "Invalid public key on input {}: {}",
-                bytes_to_hex_string(&input.prev_tx_id),
-                e
+                "Relative lock on input {} spends an unconfirmed output",
+                bytes_to_hex_string(&input.prev_tx_id)
             )
         })?;
-
-        Self::check_ownership(&pubkey, &referenced_output.address, &input.prev_tx_id)?;
-        Self::check_signature(input, &pubkey, partial_tx_bytes)?;
-        Ok(referenced_output.value)
+        match lock {
+            RelativeLock::Blocks(blocks) => {
+                let unlock_height = prev_height + blocks as u64;
+                if spend_height < unlock_height {
+                    return Err(format!(
+                        "Input {} is locked until height {} (block height {})",
+                        bytes_to_hex_string(&input.prev_tx_id),
+                        unlock_height,
+                        spend_height
+                    ));
+                }
+            }
+            RelativeLock::Seconds(seconds) => {
+                let confirmed_mtp = view
+                    .median_time_past_at(prev_height)?
+                    .map_or(0, |mtp| mtp.and_utc().timestamp());
+                let unlock_time = confirmed_mtp + seconds as i64;
+                let current = median_time_past.map_or(0, |mtp| mtp.and_utc().timestamp());
+                if current < unlock_time {
+                    return Err(format!(
+                        "Input {} is locked until time {} (median time past {})",
+                        bytes_to_hex_string(&input.prev_tx_id),
+                        unlock_time,
+                        current
+                    ));
+                }
+            }
+        }
+        Ok(())
     }
 
-    fn resolve_referenced_output(
+    fn check_input_maturity(
         input: &TxInput,
-        repo: &LedgerRepository,
-    ) -> Result<TxOutput, String> {
-        let tx = repo
-            .get_transaction(&input.prev_tx_id)
-            .map_err(|e| format!("Failed to fetch transaction: {}", e))?
-            .ok_or_else(|| {
-                format!(
-                    "Unable to find transaction for input: {}",
-                    bytes_to_hex_string(&input.prev_tx_id)
-                )
-            })?;
-
-        tx.outputs
-            .into_iter()
-            .nth(input.output_index)
-            .ok_or_else(|| {
-                format!(
-                    "Output index {} out of bounds for transaction {}",
-                    input.output_index,
-                    bytes_to_hex_string(&input.prev_tx_id)
-                )
-            })
-    }
-
-    fn check_ownership(
-        pubkey: &VerifyingKey,
-        output_address: &str,
-        prev_tx_id: &TxId,
+        spends_coinbase: bool,
+        prev_height: Option<u64>,
+        spend_height: u64,
     ) -> Result<(), String> {
-        if HDKey::get_address_from_public_key(pubkey) != output_address {
+        if !spends_coinbase {
+            return Ok(());
+        }
+        let coinbase_height = prev_height.ok_or_else(|| {
+            format!(
+                "Coinbase {} is not confirmed in the chain",
+                bytes_to_hex_string(&input.prev_tx_id)
+            )
+        })?;
+        if !CONSENSUS_RULES.is_coinbase_mature(coinbase_height, spend_height) {
             return Err(format!(
-                "Input public key does not match output address for input: {}",
-                bytes_to_hex_string(prev_tx_id)
+                "Immature coinbase spend: {} was mined at height {} and is spendable from height {} (spending at {})",
+                bytes_to_hex_string(&input.prev_tx_id),
+                coinbase_height,
+                coinbase_height + CONSENSUS_RULES.coinbase_maturity,
+                spend_height
             ));
         }
         Ok(())
     }
 
-    fn check_signature(
+    /// Parses the scripts of `input` and, given the signing bytes, returns the
+    /// check that runs them.
+    fn check_input(
+        key: ScriptCheckKey,
         input: &TxInput,
-        pubkey: &VerifyingKey,
-        partial_tx_bytes: &[u8],
-    ) -> Result<(), String> {
-        let sig = load_signature_from_hex(&input.signature).map_err(|e| {
-            format!(
-                "Invalid signature on input {}: {}",
+        referenced_output: TxOutput,
+        signing_bytes: Option<Arc<[u8]>>,
+        lock_time: u64,
+    ) -> Result<Option<ScriptCheck>, String> {
+        let input_ref = format!(
+            "{}:{}",
+            bytes_to_hex_string(&input.prev_tx_id),
+            input.output_index
+        );
+        let locking = referenced_output
+            .locking_script()
+            .map_err(|e| format!("Output {} cannot be spent: {}", input_ref, e))?;
+        let unlocking = input
+            .unlocking_script()
+            .map_err(|e| format!("Invalid unlocking script on input {}: {}", input_ref, e))?;
+
+        Ok(signing_bytes.map(|signing_bytes| {
+            ScriptCheck::new(key, input_ref, unlocking, locking, signing_bytes, lock_time)
+        }))
+    }
+
+    fn resolve_referenced_output(
+        input: &TxInput,
+        context: &InputContext,
+        unconfirmed: &HashMap<TxId, &Transaction>,
+    ) -> Result<TxOutput, String> {
+        let coin = match unconfirmed.get(&input.prev_tx_id) {
+            Some(tx) => Coin {
+                output: tx.outputs.get(input.output_index).cloned().ok_or_else(|| {
+                    format!(
+                        "Output index {} out of bounds for transaction {}",
+                        input.output_index,
+                        bytes_to_hex_string(&input.prev_tx_id)
+                    )
+                })?,
+                height: None,
+                is_coinbase: tx.is_coinbase(),
+            },
+            None => match context.coins.get(&(input.prev_tx_id, input.output_index)) {
+                Some(coin) => coin.clone(),
+                None => return Err(Self::missing_input_error(input, context.view)),
+            },
+        };
+        Self::check_input_maturity(input, coin.is_coinbase, coin.height, context.spend_height)?;
+        Self::check_relative_lock(
+            input,
+            coin.height,
+            context.spend_height,
+            context.median_time_past,
+            context.view,
+        )?;
+        Ok(coin.output)
+    }
+
+    /// Why `input` spends no output of `view`: its transaction is unknown, or
+    /// the output is spent, unspendable or out of bounds.
+    fn missing_input_error(input: &TxInput, view: &dyn UtxoView) -> String {
+        match view.transaction(&input.prev_tx_id) {
+            Ok(Some(_)) => format!(
+                "Transaction input is not a valid UTXO: tx_id: {}, output_index: {}",
                 bytes_to_hex_string(&input.prev_tx_id),
-                e
-            )
-        })?;
-        verify_signature(pubkey, partial_tx_bytes, sig)
-            .map_err(|e| format!("Invalid signature: {}", e))
+                input.output_index
+            ),
+            Ok(None) => format!(
+                "Unable to find transaction for input: {}",
+                bytes_to_hex_string(&input.prev_tx_id)
+            ),
+            Err(e) => e,
+        }
     }
 
+    /// Smallest possible encoded transaction, used to bound decoded sequence lengths.
+    pub(crate) const MIN_ENCODED_SIZE: usize = 4 + 4 + 12 + 1 + 8;
+
+    /// Canonical encoding, used for the txid, signatures, size and storage.
     pub fn as_bytes(&self) -> Vec<u8> {
-        let mut out = Vec::new();
+        let mut w = ByteWriter::versioned();
+        self.encode_into(&mut w);
+        w.into_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Transaction, String> {
+        let mut r = ByteReader::versioned(bytes)?;
+        let tx = Transaction::decode_from(&mut r)?;
+        r.finish()?;
+        Ok(tx)
+    }
+
+    /// Hex of the canonical encoding, the form raw transactions are passed around in.
+    pub fn to_hex(&self) -> String {
+        bytes_to_hex_string(&self.as_bytes())
+    }
+
+    pub fn from_hex(hex: &str) -> Result<Transaction, String> {
+        let bytes = hex::decode(hex).map_err(|e| format!("Invalid transaction hex: {}", e))?;
+        Transaction::from_bytes(&bytes)
+    }
+
+    /// Merges `other`, a copy of this transaction signed or extended
+    /// elsewhere: its signed inputs replace unsigned ones spending the same
+    /// output, and inputs this transaction lacks are appended. Both must pay
+    /// the same outputs with the same date, message and lock time.
+    pub fn combine(&mut self, other: &Transaction) -> Result<(), String> {
+        if self.outputs != other.outputs
+            || self.date != other.date
+            || self.message != other.message
+            || self.lock_time != other.lock_time
+        {
+            return Err("Transactions differ in more than their inputs".to_string());
+        }
+        for theirs in &other.inputs {
+            let ours = self.inputs.iter_mut().find(|ours| {
+                ours.prev_tx_id == theirs.prev_tx_id && ours.output_index == theirs.output_index
+            });
+            match ours {
+                Some(ours) => {
+                    if !ours.is_signed() && theirs.is_signed() {
+                        *ours = theirs.clone();
+                    }
+                }
+                None => self.inputs.push(theirs.clone()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Indexes of the inputs nobody has signed yet.
+    pub fn unsigned_inputs(&self) -> Vec<usize> {
+        (0..self.inputs.len())
+            .filter(|&index| !self.inputs[index].is_signed())
+            .collect()
+    }
+
+    pub(crate) fn encode_into(&self, w: &mut ByteWriter) {
+        w.put_len(self.inputs.len());
         for input in &self.inputs {
-            out.extend_from_slice(&input.as_bytes());
+            input.encode_into(w);
         }
+        w.put_len(self.outputs.len());
         for output in &self.outputs {
-            out.extend_from_slice(&TxOutput::as_bytes(output));
+            output.encode_into(w);
+        }
+        w.put_datetime(&self.date);
+        w.put_opt_str(self.message.as_deref());
+        w.put_u64(self.lock_time);
+    }
+
+    pub(crate) fn decode_from(r: &mut ByteReader) -> Result<Transaction, String> {
+        let input_count = r.get_len(TxInput::MIN_ENCODED_SIZE)?;
+        let mut inputs = Vec::with_capacity(input_count);
+        for _ in 0..input_count {
+            inputs.push(TxInput::decode_from(r)?);
+        }
+        let output_count = r.get_len(TxOutput::MIN_ENCODED_SIZE)?;
+        let mut outputs = Vec::with_capacity(output_count);
+        for _ in 0..output_count {
+            outputs.push(TxOutput::decode_from(r)?);
         }
-        out.extend_from_slice(format_date(&self.date).as_bytes());
-        out.extend_from_slice(self.message.as_ref().unwrap_or(&String::new()).as_bytes());
-        out
+        Ok(Transaction {
+            inputs,
+            outputs,
+            date: r.get_datetime()?,
+            message: r.get_opt_string()?,
+            lock_time: r.get_u64()?,
+        })
     }
 
     pub fn is_coinbase(&self) -> bool {
         self.inputs.is_empty()
     }
 
+    /// Serialized size in bytes, which is also the space the transaction takes in a block.
     pub fn size(&self) -> usize {
         self.as_bytes().len()
     }
@@ -223,6 +822,7 @@ impl std::fmt::Debug for Transaction {
             .field("outputs", &self.outputs)
             .field("date", &self.date)
             .field("message", &self.message)
+            .field("lock_time", &self.lock_time)
             .field("is coinbase", &self.is_coinbase())
             .field("size in bytes", &self.size())
             .finish()
@@ -239,6 +839,14 @@ impl MempoolTx {
         MempoolTx { tx, utxos }
     }
 
+    pub fn id(&self) -> TxId {
+        self.tx.id()
+    }
+
+    pub fn wtxid(&self) -> TxId {
+        self.tx.wtxid()
+    }
+
     pub fn calculate_fee(&self) -> i64 {
         let input_sum: i64 = self.utxos.iter().map(|u| u.output.value).sum();
         let output_sum: i64 = self.tx.outputs.iter().map(|o| o.value).sum();
@@ -247,7 +855,7 @@ impl MempoolTx {
 
     pub fn calculate_fee_per_byte(&self) -> f64 {
         let fee = self.calculate_fee();
-        let tx_size = self.tx.as_bytes().len() as i64;
+        let tx_size = self.tx.size() as i64;
         fee as f64 / tx_size as f64
     }
 }
@@ -0,0 +1,333 @@
+// Views of the UTXO set.
+//
+// Validation reads the outputs it spends through `UtxoView` rather than from
+// the ledger directly. `LedgerView` answers from the SQLite ledger, which
+// follows the tip of the main chain. `UtxoOverlay` keeps changes in memory on
+// top of another view: main-chain blocks can be disconnected from it and other
+// blocks or transactions connected. Fork blocks and reorg candidates are
+// checked this way against the UTXO set of their branch without writing to
+// disk, mempool transactions against the ledger with their mempool ancestors
+// connected, and block templates with the transactions before them. An
+// overlay is a view itself, so overlays stack, and dropping one discards its
+// changes.
+use std::collections::{HashMap, HashSet};
+
+use chrono::NaiveDateTime;
+
+use crate::db::repository::LedgerRepository;
+use crate::globals::CONSENSUS_RULES;
+use crate::model::transaction::TxId;
+use crate::model::{Block, Transaction, TxOutput};
+use crate::security_utils::bytes_to_hex_string;
+use crate::utils::median_time_past;
+
+/// A transaction output reference: transaction id and output index.
+pub type OutPoint = (TxId, usize);
+
+/// An unspent output with what validation needs to know about its origin.
+#[derive(Debug, Clone)]
+pub struct Coin {
+    pub output: TxOutput,
+    /// Height of the block that created it, `None` while unconfirmed.
+    pub height: Option<u64>,
+    pub is_coinbase: bool,
+}
+
+pub trait UtxoView {
+    /// The unspent output at `outpoint`, if any.
+    fn coin(&self, outpoint: &OutPoint) -> Result<Option<Coin>, String>;
+
+    /// Transaction `txid`, spent or not, with the height of the block that
+    /// confirmed it (`None` while unconfirmed).
+    fn transaction(&self, txid: &TxId) -> Result<Option<(Transaction, Option<u64>)>, String>;
+
+    /// Timestamp of the block at `height` on the chain the view follows.
+    fn block_time(&self, height: u64) -> Result<Option<NaiveDateTime>, String>;
+
+    /// The unspent outputs among `outpoints`.
+    fn coins(&self, outpoints: &[OutPoint]) -> Result<HashMap<OutPoint, Coin>, String> {
+        let mut found = HashMap::with_capacity(outpoints.len());
+        for outpoint in outpoints {
+            if let Some(coin) = self.coin(outpoint)? {
+                found.insert(*outpoint, coin);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Median-time-past for a block at `height`.
+    fn median_time_past_at(&self, height: u64) -> Result<Option<NaiveDateTime>, String> {
+        median_time_past_from_block_times(self, height)
+    }
+}
+
+fn median_time_past_from_block_times<V: UtxoView + ?Sized>(
+    view: &V,
+    height: u64,
+) -> Result<Option<NaiveDateTime>, String> {
+    let start = height.saturating_sub(CONSENSUS_RULES.median_time_span as u64);
+    let mut timestamps = Vec::new();
+    for block_height in start..height {
+        timestamps.extend(view.block_time(block_height)?);
+    }
+    Ok(median_time_past(&timestamps))
+}
+
+fn invalid_input_error(outpoint: &OutPoint) -> String {
+    format!(
+        "Transaction input is not a valid UTXO: tx_id: {}, output_index: {}",
+        bytes_to_hex_string(&outpoint.0),
+        outpoint.1
+    )
+}
+
+/// The UTXO set of the ledger, at the tip of the main chain. Each query
+/// takes its own connection, so a view that is never queried never opens
+/// the database.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LedgerView;
+
+impl UtxoView for LedgerView {
+    fn coin(&self, outpoint: &OutPoint) -> Result<Option<Coin>, String> {
+        Ok(self.coins(std::slice::from_ref(outpoint))?.remove(outpoint))
+    }
+
+    fn transaction(&self, txid: &TxId) -> Result<Option<(Transaction, Option<u64>)>, String> {
+        LedgerRepository::new()
+            .get_transaction_with_height(txid)
+            .map_err(|e| format!("Failed to fetch transaction: {}", e))
+    }
+
+    fn block_time(&self, height: u64) -> Result<Option<NaiveDateTime>, String> {
+        LedgerRepository::new()
+            .get_block_time(height)
+            .map_err(|e| format!("Failed to fetch block times: {}", e))
+    }
+
+    /// Fetches the outputs and then their transactions, one query each.
+    fn coins(&self, outpoints: &[OutPoint]) -> Result<HashMap<OutPoint, Coin>, String> {
+        if outpoints.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let repo = LedgerRepository::new();
+        let utxos = repo
+            .get_utxos_from_ids(outpoints)
+            .map_err(|e| format!("Failed to fetch UTXOs: {}", e))?;
+        let mut txids: Vec<TxId> = utxos.iter().map(|utxo| utxo.tx_id).collect();
+        txids.sort_unstable();
+        txids.dedup();
+        let transactions = repo
+            .get_transactions_with_height(&txids)
+            .map_err(|e| format!("Failed to fetch transaction: {}", e))?;
+
+        let mut found = HashMap::with_capacity(utxos.len());
+        for utxo in utxos {
+            let (tx, height) = transactions.get(&utxo.tx_id).ok_or_else(|| {
+                format!(
+                    "UTXO {}:{} has no transaction in the ledger",
+                    bytes_to_hex_string(&utxo.tx_id),
+                    utxo.index
+                )
+            })?;
+            let coin = Coin {
+                output: utxo.output,
+                height: *height,
+                is_coinbase: tx.is_coinbase(),
+            };
+            found.insert((utxo.tx_id, utxo.index), coin);
+        }
+        Ok(found)
+    }
+
+    fn median_time_past_at(&self, height: u64) -> Result<Option<NaiveDateTime>, String> {
+        LedgerRepository::new()
+            .get_median_time_past_at(height)
+            .map_err(|e| format!("Failed to fetch block times: {}", e))
+    }
+}
+
+/// In-memory changes to the UTXO set of `base`.
+pub struct UtxoOverlay<'a> {
+    base: &'a dyn UtxoView,
+    added: HashMap<OutPoint, Coin>,
+    spent: HashSet<OutPoint>,
+    transactions: HashMap<TxId, (Transaction, Option<u64>)>,
+    removed: HashSet<TxId>,
+    block_times: HashMap<u64, NaiveDateTime>,
+    /// Lowest height disconnected; the base's blocks from there up are hidden.
+    disconnected_from: Option<u64>,
+}
+
+impl<'a> UtxoOverlay<'a> {
+    pub fn new(base: &'a dyn UtxoView) -> Self {
+        UtxoOverlay {
+            base,
+            added: HashMap::new(),
+            spent: HashSet::new(),
+            transactions: HashMap::new(),
+            removed: HashSet::new(),
+            block_times: HashMap::new(),
+            disconnected_from: None,
+        }
+    }
+
+    /// Spends the inputs of `tx` and adds its spendable outputs, confirmed at
+    /// `height` or unconfirmed with `None`. Only checks that the inputs are
+    /// unspent; on error the overlay is left partly changed.
+    pub fn connect_transaction(
+        &mut self,
+        tx: &Transaction,
+        height: Option<u64>,
+    ) -> Result<(), String> {
+        for input in &tx.inputs {
+            let outpoint = (input.prev_tx_id, input.output_index);
+            if self.coin(&outpoint)?.is_none() {
+                return Err(invalid_input_error(&outpoint));
+            }
+            self.spend(outpoint);
+        }
+
+        let txid = tx.id();
+        for (index, output) in tx.outputs.iter().enumerate() {
+            // Data-carrier and other unspendable outputs never become spendable coins.
+            if output.script.is_unspendable() {
+                continue;
+            }
+            let coin = Coin {
+                output: output.clone(),
+                height,
+                is_coinbase: tx.is_coinbase(),
+            };
+            self.add((txid, index), coin);
+        }
+        self.removed.remove(&txid);
+        self.transactions.insert(txid, (tx.clone(), height));
+        Ok(())
+    }
+
+    /// Connects the transactions of `block` as the block at `height`.
+    pub fn connect_block(&mut self, block: &Block, height: u64) -> Result<(), String> {
+        for tx in &block.transactions {
+            self.connect_transaction(tx, Some(height))?;
+        }
+        self.block_times.insert(height, block.header.timestamp);
+        Ok(())
+    }
+
+    /// Undoes `block`, the last block connected at `height`: its outputs are
+    /// removed and the outputs it spent are unspent again.
+    pub fn disconnect_block(&mut self, block: &Block, height: u64) -> Result<(), String> {
+        for tx in block.transactions.iter().rev() {
+            let txid = tx.id();
+            for index in 0..tx.outputs.len() {
+                self.spend((txid, index));
+            }
+            self.transactions.remove(&txid);
+            self.removed.insert(txid);
+
+            for input in &tx.inputs {
+                let outpoint = (input.prev_tx_id, input.output_index);
+                let (prev_tx, prev_height) =
+                    self.transaction(&input.prev_tx_id)?.ok_or_else(|| {
+                        format!(
+                            "Unable to find transaction for input: {}",
+                            bytes_to_hex_string(&input.prev_tx_id)
+                        )
+                    })?;
+                let output = prev_tx
+                    .outputs
+                    .get(input.output_index)
+                    .cloned()
+                    .ok_or_else(|| invalid_input_error(&outpoint))?;
+                let coin = Coin {
+                    output,
+                    height: prev_height,
+                    is_coinbase: prev_tx.is_coinbase(),
+                };
+                self.add(outpoint, coin);
+            }
+        }
+        self.block_times.remove(&height);
+        self.disconnected_from = Some(self.disconnected_from.map_or(height, |h| h.min(height)));
+        Ok(())
+    }
+
+    /// Disconnects the blocks of `chain`, which the view follows, from `height` up.
+    pub fn disconnect_blocks_from(&mut self, chain: &[Block], height: usize) -> Result<(), String> {
+        for (block_height, block) in chain.iter().enumerate().skip(height).rev() {
+            self.disconnect_block(block, block_height as u64)?;
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, outpoint: OutPoint, coin: Coin) {
+        self.spent.remove(&outpoint);
+        self.added.insert(outpoint, coin);
+    }
+
+    fn spend(&mut self, outpoint: OutPoint) {
+        self.added.remove(&outpoint);
+        self.spent.insert(outpoint);
+    }
+
+    /// Lowest height whose block time differs from the base's.
+    fn changed_from(&self) -> Option<u64> {
+        let connected = self.block_times.keys().min().copied();
+        connected.into_iter().chain(self.disconnected_from).min()
+    }
+}
+
+impl UtxoView for UtxoOverlay<'_> {
+    fn coin(&self, outpoint: &OutPoint) -> Result<Option<Coin>, String> {
+        if let Some(coin) = self.added.get(outpoint) {
+            return Ok(Some(coin.clone()));
+        }
+        if self.spent.contains(outpoint) {
+            return Ok(None);
+        }
+        self.base.coin(outpoint)
+    }
+
+    fn transaction(&self, txid: &TxId) -> Result<Option<(Transaction, Option<u64>)>, String> {
+        if let Some(found) = self.transactions.get(txid) {
+            return Ok(Some(found.clone()));
+        }
+        if self.removed.contains(txid) {
+            return Ok(None);
+        }
+        self.base.transaction(txid)
+    }
+
+    fn block_time(&self, height: u64) -> Result<Option<NaiveDateTime>, String> {
+        if let Some(time) = self.block_times.get(&height) {
+            return Ok(Some(*time));
+        }
+        if self.disconnected_from.is_some_and(|from| height >= from) {
+            return Ok(None);
+        }
+        self.base.block_time(height)
+    }
+
+    /// Asks the base in one go for the outpoints the overlay does not know.
+    fn coins(&self, outpoints: &[OutPoint]) -> Result<HashMap<OutPoint, Coin>, String> {
+        let unknown: Vec<OutPoint> = outpoints
+            .iter()
+            .filter(|outpoint| !self.added.contains_key(outpoint) && !self.spent.contains(outpoint))
+            .copied()
+            .collect();
+        let mut found = self.base.coins(&unknown)?;
+        for outpoint in outpoints {
+            if let Some(coin) = self.added.get(outpoint) {
+                found.insert(*outpoint, coin.clone());
+            }
+        }
+        Ok(found)
+    }
+
+    fn median_time_past_at(&self, height: u64) -> Result<Option<NaiveDateTime>, String> {
+        if self.changed_from().is_none_or(|from| height <= from) {
+            return self.base.median_time_past_at(height);
+        }
+        median_time_past_from_block_times(self, height)
+    }
+}
@@ -0,0 +1,221 @@
+// Version-bits signalling for coordinated soft-fork rollouts.
+//
+// A block whose version carries the top bits `001` can signal readiness for up
+// to 29 deployments at once, one version bit each. Each deployment moves through
+// defined -> started -> locked-in -> active (or failed) only at the boundaries of
+// `window`-block periods, based on the median-time-past at the boundary and on
+// how many blocks of the period that just ended signalled its bit.
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::globals::CONSENSUS_RULES;
+use crate::model::Block;
+use crate::utils;
+
+/// Top three bits of a version that signals deployments.
+pub const VERSIONBITS_TOP_BITS: u32 = 0x2000_0000;
+pub const VERSIONBITS_TOP_MASK: u32 = 0xE000_0000;
+/// Number of bits available for deployments below the top bits.
+pub const VERSIONBITS_NUM_BITS: u8 = 29;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deployment {
+    pub name: String,
+    pub bit: u8,
+    /// Median-time-past from which blocks may start signalling.
+    pub start_time: i64,
+    /// Median-time-past after which a deployment that has not locked in fails.
+    pub timeout: i64,
+}
+
+impl Deployment {
+    pub fn new(name: &str, bit: u8, start_time: i64, timeout: i64) -> Result<Self, String> {
+        if name.is_empty() {
+            return Err("Deployment name must not be empty".to_string());
+        }
+        if bit >= VERSIONBITS_NUM_BITS {
+            return Err(format!(
+                "Deployment bit must be below {}, got {}",
+                VERSIONBITS_NUM_BITS, bit
+            ));
+        }
+        if timeout <= start_time {
+            return Err(format!("Deployment '{}' times out before it starts", name));
+        }
+        Ok(Deployment {
+            name: name.to_string(),
+            bit,
+            start_time,
+            timeout,
+        })
+    }
+
+    /// Parses `name:bit:start_time:timeout`.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = spec.trim().split(':').collect();
+        let [name, bit, start_time, timeout] = parts.as_slice() else {
+            return Err(format!(
+                "Invalid deployment '{}': expected name:bit:start_time:timeout",
+                spec
+            ));
+        };
+        let number_error = |field: &str| format!("Invalid {} in deployment '{}'", field, spec);
+        Deployment::new(
+            name,
+            bit.parse().map_err(|_| number_error("bit"))?,
+            start_time.parse().map_err(|_| number_error("start time"))?,
+            timeout.parse().map_err(|_| number_error("timeout"))?,
+        )
+    }
+
+    pub fn mask(&self) -> u32 {
+        1 << self.bit
+    }
+
+    pub fn is_signalled_by(&self, version: u32) -> bool {
+        version & VERSIONBITS_TOP_MASK == VERSIONBITS_TOP_BITS && version & self.mask() != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThresholdState {
+    /// Waiting for `start_time`.
+    Defined,
+    /// Blocks signal; locks in once a period reaches the threshold.
+    Started,
+    /// The threshold was reached; activates at the next period.
+    LockedIn,
+    /// The deployment's rules are enforced. Final.
+    Active,
+    /// `timeout` passed before lock-in. Final.
+    Failed,
+}
+
+impl ThresholdState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ThresholdState::Defined => "defined",
+            ThresholdState::Started => "started",
+            ThresholdState::LockedIn => "locked_in",
+            ThresholdState::Active => "active",
+            ThresholdState::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeploymentStatus {
+    pub state: ThresholdState,
+    /// First height of the period in which `state` began.
+    pub since_height: u64,
+    /// First height of the period containing the evaluated height.
+    pub period_start: u64,
+    /// Blocks of the current period mined so far.
+    pub elapsed: u64,
+    /// Blocks of the current period that signalled the deployment.
+    pub signalled: u64,
+}
+
+/// Status of `deployment` for a block at `height` on top of `chain[..height]`,
+/// with `threshold` signalling blocks out of `window` needed to lock in.
+pub fn deployment_status(
+    chain: &[Block],
+    height: usize,
+    deployment: &Deployment,
+    window: usize,
+    threshold: usize,
+) -> DeploymentStatus {
+    let window = window.max(1);
+    let height = height.min(chain.len());
+    let period_start = height - height % window;
+
+    let mut state = ThresholdState::Defined;
+    let mut since_height = 0;
+    let mut boundary = window;
+    while boundary <= period_start {
+        let period = &chain[boundary - window..boundary];
+        let next = next_state(
+            state,
+            deployment,
+            period,
+            median_time_past(chain, boundary),
+            threshold,
+        );
+        if next != state {
+            state = next;
+            since_height = boundary;
+        }
+        boundary += window;
+    }
+
+    DeploymentStatus {
+        state,
+        since_height: since_height as u64,
+        period_start: period_start as u64,
+        elapsed: (height - period_start) as u64,
+        signalled: count_signalling(&chain[period_start..height], deployment) as u64,
+    }
+}
+
+/// Version for a block at `height`: the top bits plus the bit of every
+/// deployment that is started or locked in.
+pub fn compute_block_version(
+    chain: &[Block],
+    height: usize,
+    deployments: &[Deployment],
+    window: usize,
+    threshold: usize,
+) -> u32 {
+    deployments
+        .iter()
+        .filter(|deployment| {
+            matches!(
+                deployment_status(chain, height, deployment, window, threshold).state,
+                ThresholdState::Started | ThresholdState::LockedIn
+            )
+        })
+        .fold(VERSIONBITS_TOP_BITS, |version, deployment| {
+            version | deployment.mask()
+        })
+}
+
+fn next_state(
+    state: ThresholdState,
+    deployment: &Deployment,
+    period: &[Block],
+    median_time_past: i64,
+    threshold: usize,
+) -> ThresholdState {
+    match state {
+        ThresholdState::Defined if median_time_past >= deployment.timeout => ThresholdState::Failed,
+        ThresholdState::Defined if median_time_past >= deployment.start_time => {
+            ThresholdState::Started
+        }
+        ThresholdState::Started if median_time_past >= deployment.timeout => ThresholdState::Failed,
+        ThresholdState::Started if count_signalling(period, deployment) >= threshold => {
+            ThresholdState::LockedIn
+        }
+        ThresholdState::LockedIn => ThresholdState::Active,
+        state => state,
+    }
+}
+
+fn count_signalling(blocks: &[Block], deployment: &Deployment) -> usize {
+    blocks
+        .iter()
+        .filter(|block| deployment.is_signalled_by(block.header.version))
+        .count()
+}
+
+/// Median-time-past, in Unix seconds, of the blocks below `height`.
+fn median_time_past(chain: &[Block], height: usize) -> i64 {
+    let start = height.saturating_sub(CONSENSUS_RULES.median_time_span);
+    let timestamps: Vec<NaiveDateTime> = chain[start..height]
+        .iter()
+        .map(|block| block.header.timestamp)
+        .collect();
+    utils::median_time_past(&timestamps)
+        .map(|mtp| mtp.and_utc().timestamp())
+        .unwrap_or(i64::MIN)
+}
@@ -1,10 +1,15 @@
 use std::collections::HashSet;
 
 use crate::db::repository::LedgerRepository;
+use crate::globals::CONSENSUS_RULES;
 use crate::model::MempoolTx;
-use crate::model::io::UTXO;
+use crate::model::io::{SIGHASH_ALL, UTXO, check_sighash_type};
+use crate::model::mempool::Mempool;
+use crate::model::multisig::PartialTransaction;
+use crate::model::script::{MAX_DATA_CARRIER_SIZE, Script, ScriptType, hash160};
+use crate::model::transaction::TxId;
 use crate::security_utils::keystore::Seed;
-use crate::security_utils::{Keystore, bytes_to_hex_string};
+use crate::security_utils::{Keystore, bytes_to_hex_string, sha256};
 use crate::{
     model::{HDKey, Transaction, TxInput, TxOutput},
     security_utils::public_key_to_hex,
@@ -15,9 +20,15 @@ pub struct Wallet {
     master_hdkey: HDKey,
     curr_rcv_idx: u32,
     curr_chg_idx: u32,
+    /// Outputs already spent by mempool transactions, never selected again.
+    pending_spent: HashSet<(TxId, usize)>,
+    /// Unconfirmed outputs paying the wallet, such as the change of a pending payment.
+    unconfirmed_utxos: Vec<UTXO>,
 }
 
 const GAP_LIMIT: u32 = 20;
+/// Most times a payment is rebuilt while settling its fee from a fee rate.
+pub(crate) const MAX_FEE_ROUNDS: usize = 10;
 
 #[derive(Clone, Copy)]
 pub enum DerivationType {
@@ -42,6 +53,8 @@ impl Wallet {
             master_hdkey: hdkey,
             curr_rcv_idx: 0,
             curr_chg_idx: 0,
+            pending_spent: HashSet::new(),
+            unconfirmed_utxos: Vec::new(),
         };
         let rcv_idx = w.get_last_used_index_for_type(DerivationType::Receive);
         let chg_idx = w.get_last_used_index_for_type(DerivationType::Change);
@@ -209,10 +222,25 @@ impl Wallet {
         utxos
     }
 
+    /// Splits the wallet UTXOs into `(spendable, immature)`, where immature ones are
+    /// coinbase outputs that cannot be spent yet by the next block.
+    pub fn get_wallet_utxos_by_maturity(&self) -> (Vec<UTXO>, Vec<UTXO>) {
+        let repo = LedgerRepository::new();
+        let spend_height = repo.get_block_count().unwrap_or(0);
+        self.get_wallet_utxos().into_iter().partition(|utxo| {
+            match repo.get_transaction_with_height(&utxo.tx_id) {
+                Ok(Some((tx, Some(height)))) if tx.is_coinbase() => {
+                    CONSENSUS_RULES.is_coinbase_mature(height, spend_height)
+                }
+                _ => true,
+            }
+        })
+    }
+
     /// Selects UTXOs from the wallet to cover the specified amount.
     ///
     /// This function implements a greedy coin selection algorithm:
-    /// 1. Retrieves all available UTXOs from the wallet
+    /// 1. Retrieves all spendable UTXOs from the wallet (immature coinbase outputs are skipped)
     /// 2. Sorts UTXOs in descending order by value (largest first)
     /// 3. Accumulates UTXOs until the total meets or exceeds the required amount
     ///
@@ -223,7 +251,8 @@ impl Wallet {
     /// * `Some(Vec<UTXO>)` - A vector of selected UTXOs if sufficient funds are available
     /// * `None` - If the wallet doesn't have enough funds to cover the amount
     pub fn select_utxos(&self, amount: i64) -> Option<Vec<UTXO>> {
-        let mut utxos = self.get_wallet_utxos();
+        let (mut utxos, _) = self.get_wallet_utxos_by_maturity();
+        utxos.retain(|u| !self.pending_spent.contains(&(u.tx_id, u.index)));
         // Sort UTXOs in descending order by value (largest first)
         utxos.sort_by(|a, b| {
             b.output
@@ -231,6 +260,10 @@ impl Wallet {
                 .partial_cmp(&a.output.value)
                 .unwrap_or(std::cmp::Ordering::Equal)
         });
+        // Unconfirmed outputs only once confirmed ones run out, keeping chains short
+        let mut unconfirmed = self.unconfirmed_utxos.clone();
+        unconfirmed.sort_by_key(|u| std::cmp::Reverse(u.output.value));
+        utxos.extend(unconfirmed);
 
         let mut selected = Vec::new();
         let mut total = 0;
@@ -245,17 +278,166 @@ impl Wallet {
         None
     }
 
+    /// Finds the wallet key for `public_key` among the receive and change keys
+    /// up to a gap limit past the current indexes. Multisig keys never show up
+    /// on chain, so used-address discovery alone would miss them.
+    pub fn find_key_for_public_key(&self, public_key: &[u8; 32]) -> Option<HDKey> {
+        self.find_key(|key| key.get_public_key().as_bytes() == public_key)
+    }
+
+    /// Like `find_key_for_public_key`, matching on the key's pubkey hash.
+    pub fn find_key_for_pubkey_hash(&self, pubkey_hash: &[u8; 20]) -> Option<HDKey> {
+        self.find_key(|key| hash160(key.get_public_key().as_bytes()) == *pubkey_hash)
+    }
+
+    /// Lets coin selection see the mempool: outputs its transactions spend are
+    /// skipped, and unconfirmed outputs paying the wallet become spendable.
+    pub fn track_mempool(&mut self, pending: &Mempool) {
+        self.pending_spent = pending.spent_outpoints();
+        self.unconfirmed_utxos = pending
+            .unspent_outputs()
+            .into_iter()
+            .filter(|utxo| self.find_address_key(&utxo.output.address).is_some())
+            .collect();
+    }
+
+    /// Derivation type and key of one of the wallet's addresses, including
+    /// addresses not yet seen on chain such as the change of a pending payment.
+    fn find_address_key(&self, address: &str) -> Option<(DerivationType, HDKey)> {
+        if let Some((d_type, index)) = self.owns_address(address) {
+            let mut path = self.get_base_path(&d_type);
+            path.push(index);
+            return Some((d_type, self.derive_path(&path)));
+        }
+        [
+            (DerivationType::Receive, self.curr_rcv_idx),
+            (DerivationType::Change, self.curr_chg_idx),
+        ]
+        .into_iter()
+        .find_map(|(d_type, curr_idx)| {
+            self.generate_n_keys(curr_idx + GAP_LIMIT, None, Some(d_type))
+                .into_iter()
+                .find(|key| key.get_address() == address)
+                .map(|key| (d_type, key))
+        })
+    }
+
+    fn find_key(&self, matches: impl Fn(&HDKey) -> bool) -> Option<HDKey> {
+        [
+            (DerivationType::Receive, self.curr_rcv_idx),
+            (DerivationType::Change, self.curr_chg_idx),
+        ]
+        .into_iter()
+        .flat_map(|(d_type, curr_idx)| {
+            self.generate_n_keys(curr_idx + GAP_LIMIT, None, Some(d_type))
+        })
+        .find(|key| matches(key))
+    }
+
+    /// Adds this wallet's signatures to a pending multisig spend and returns how
+    /// many were added.
+    pub fn sign_partial(&self, pending: &mut PartialTransaction) -> Result<usize, String> {
+        let mut added = 0;
+        for public_key in pending.unsigned_public_keys() {
+            if let Some(key) = self.find_key_for_public_key(&public_key) {
+                added += pending.sign_with(&key)?;
+            }
+        }
+        Ok(added)
+    }
+
+    /// Spends an HTLC output to `to`. With a `preimage` the output is claimed
+    /// by its recipient; without one it is refunded to its sender, which only
+    /// becomes valid once the chain reaches the contract's timeout.
+    pub fn spend_htlc(
+        &self,
+        utxo: UTXO,
+        preimage: Option<&[u8]>,
+        to: String,
+        fee: i64,
+    ) -> Result<MempoolTx, String> {
+        let params = utxo
+            .output
+            .script
+            .htlc_params()
+            .ok_or_else(|| "Output is not an HTLC".to_string())?;
+        if !HDKey::validate_address(&to) {
+            return Err("Destination address is invalid".to_string());
+        }
+        let value = utxo.output.value - fee;
+        if value <= 0 {
+            return Err("Fee exceeds the HTLC value".to_string());
+        }
+
+        let (key, lock_time) = match preimage {
+            Some(preimage) => {
+                if sha256(preimage) != params.hash {
+                    return Err("Preimage does not match the HTLC hash".to_string());
+                }
+                let key = self
+                    .find_key_for_pubkey_hash(&params.recipient_pubkey_hash)
+                    .ok_or_else(|| "Wallet is not the HTLC recipient".to_string())?;
+                (key, 0)
+            }
+            None => {
+                let key = self
+                    .find_key_for_pubkey_hash(&params.sender_pubkey_hash)
+                    .ok_or_else(|| "Wallet is not the HTLC sender".to_string())?;
+                (key, params.timeout)
+            }
+        };
+
+        let input = TxInput::new(utxo.tx_id, utxo.index);
+        let mut tx = Transaction::new(vec![input], vec![TxOutput::new(value, to)], None);
+        tx.lock_time = lock_time;
+
+        let signature = key.sign(&tx.signing_bytes()).to_bytes();
+        let mut unlock_script = Script::new();
+        unlock_script.push_data(&signature);
+        unlock_script.push_data(key.get_public_key().as_bytes());
+        match preimage {
+            Some(preimage) => {
+                unlock_script.push_data(preimage);
+                unlock_script.push_int(1);
+            }
+            None => unlock_script.push_int(0),
+        }
+        tx.inputs[0].unlock_script = unlock_script;
+
+        Ok(MempoolTx::new(tx, vec![utxo]))
+    }
+
+    /// Anchors `data` on chain in a zero-value data-carrier output, paying
+    /// `fee` from the wallet's coins.
+    pub fn send_data(&mut self, data: &[u8], fee: Option<i64>) -> Result<MempoolTx, String> {
+        if data.len() > MAX_DATA_CARRIER_SIZE {
+            return Err(format!(
+                "Data of {} bytes exceeds the {} byte limit",
+                data.len(),
+                MAX_DATA_CARRIER_SIZE
+            ));
+        }
+        let output = TxOutput::with_script(0, Script::new_data(data));
+        self.send_tx(vec![output], fee, None, None, SIGHASH_ALL, false)
+            .map_err(|e| e.to_string())
+    }
+
     pub fn send_tx(
         &mut self,
         mut outputs: Vec<TxOutput>,
         fee: Option<i64>,
         message: Option<String>,
+        lock_time: Option<u64>,
+        sighash_type: u8,
+        replaceable: bool,
     ) -> Result<MempoolTx, &'static str> {
-        // Validate output addresses
+        if check_sighash_type(sighash_type).is_err() {
+            return Err("Invalid signature hash type");
+        }
+        // Validate output addresses; outputs with an explicit script carry their own lock
         let is_outputs_valid = outputs
             .iter()
-            .map(|o| &o.address)
-            .all(|addr| HDKey::validate_address(&addr));
+            .all(|o| !o.script.is_empty() || HDKey::validate_address(&o.address));
         if !is_outputs_valid {
             return Err("One or more output addresses are invalid");
         }
@@ -271,49 +453,163 @@ impl Wallet {
         let change = utxos_to_spend.iter().map(|u| u.output.value).sum::<i64>() - total_needed;
         if change > 0 {
             let change_address = self.get_change_addr();
-            let change_output = TxOutput {
-                address: change_address,
-                value: change,
-            };
+            let change_output = TxOutput::new(change, change_address);
             outputs.push(change_output);
         }
 
         // Create inputs from selected UTXOs
-        let mut inputs = Vec::new();
-        for utxo in &utxos_to_spend {
-            // Use a reference to avoid moving
-            let input = TxInput {
-                prev_tx_id: utxo.tx_id,
-                output_index: utxo.index,
-                signature: "".to_string(), // will be signed later
-                public_key: String::new(), // will be filled later
+        let inputs = utxos_to_spend
+            .iter()
+            .map(|utxo| TxInput {
+                sighash_type,
+                replaceable,
+                ..TxInput::new(utxo.tx_id, utxo.index)
+            })
+            .collect();
+
+        // Create the transaction and sign inputs
+        let mut mem_tx = MempoolTx::new(Transaction::new(inputs, outputs, message), utxos_to_spend);
+        // The lock time is covered by the signatures, so it is set before signing.
+        mem_tx.tx.lock_time = lock_time.unwrap_or(0);
+        self.sign_inputs(&mut mem_tx)?;
+        Ok(mem_tx)
+    }
+
+    /// Builds a payment paying `fee_rate` per byte of the signed transaction.
+    /// `build` makes the payment for a given fee; it is called again with the
+    /// fee its last result needs until that result pays enough, as a higher
+    /// fee may take more inputs and a larger transaction.
+    pub fn with_fee_rate<F>(&mut self, fee_rate: f64, mut build: F) -> Result<MempoolTx, String>
+    where
+        F: FnMut(&mut Wallet, i64) -> Result<MempoolTx, String>,
+    {
+        if !fee_rate.is_finite() || fee_rate < 0.0 {
+            return Err(format!("Invalid fee rate {}", fee_rate));
+        }
+        // Every attempt pays its change to the same address.
+        let change_index = self.curr_chg_idx;
+        let mut fee = 0;
+        for _ in 0..MAX_FEE_ROUNDS {
+            self.curr_chg_idx = change_index;
+            let mem_tx = build(self, fee)?;
+            let needed = (fee_rate * mem_tx.tx.size() as f64).ceil() as i64;
+            if mem_tx.calculate_fee() >= needed {
+                return Ok(mem_tx);
+            }
+            fee = needed;
+        }
+        Err("Could not settle the fee for the payment".to_string())
+    }
+
+    /// Rebuilds `mem_tx`, one of the wallet's own replaceable transactions, so
+    /// that it pays `fee`. The difference comes out of its change output and
+    /// the result is signed again, ready to replace the original.
+    pub fn bump_fee(&self, mem_tx: &MempoolTx, fee: i64) -> Result<MempoolTx, String> {
+        if !mem_tx.tx.signals_replacement() {
+            return Err("Transaction does not signal replaceability".to_string());
+        }
+        let current_fee = mem_tx.calculate_fee();
+        if fee <= current_fee {
+            return Err(format!(
+                "New fee must exceed the current fee of {}",
+                current_fee
+            ));
+        }
+        let change_index = mem_tx
+            .tx
+            .outputs
+            .iter()
+            .rposition(|o| {
+                matches!(
+                    self.find_address_key(&o.address),
+                    Some((DerivationType::Change, _))
+                )
+            })
+            .ok_or_else(|| "Transaction has no change output to take the fee from".to_string())?;
+
+        let mut bumped = mem_tx.clone();
+        bumped.tx.inputs = bumped.tx.inputs.iter().map(|i| i.get_partial()).collect();
+        let change = &mut bumped.tx.outputs[change_index];
+        change.value -= fee - current_fee;
+        if change.value <= 0 {
+            return Err("Change output cannot cover the higher fee".to_string());
+        }
+        self.sign_inputs(&mut bumped)?;
+        Ok(bumped)
+    }
+
+    /// Signs the unsigned inputs of a raw transaction that spend the wallet's
+    /// outputs among `utxos`, each with `sighash_type`, and returns their
+    /// indexes. Inputs of other owners are left for them to sign.
+    pub fn sign_raw(
+        &self,
+        tx: &mut Transaction,
+        utxos: &[UTXO],
+        sighash_type: u8,
+    ) -> Result<Vec<usize>, String> {
+        check_sighash_type(sighash_type)?;
+        let mut signed = Vec::new();
+        for i in 0..tx.inputs.len() {
+            let input = &tx.inputs[i];
+            if input.is_signed() {
+                continue;
+            }
+            let Some(utxo) = utxos
+                .iter()
+                .find(|u| u.tx_id == input.prev_tx_id && u.index == input.output_index)
+            else {
+                continue;
             };
-            inputs.push((input, utxo.output.address.clone(), utxo.index));
+            let Some((_, key)) = self.find_address_key(&utxo.output.address) else {
+                continue;
+            };
+            tx.inputs[i].sighash_type = sighash_type;
+            Self::sign_input(tx, i, utxo, &key)?;
+            signed.push(i);
         }
+        Ok(signed)
+    }
 
-        // Create the transaction and sign inputs
-        let mut mem_tx = MempoolTx::new(
-            Transaction::new(
-                inputs.iter().map(|(inp, _, _)| inp.clone()).collect(),
-                outputs,
-                message,
-            ),
-            utxos_to_spend,
-        );
-        let tx_bytes = &mem_tx.tx.as_bytes();
-        for (i, (_, addr, _)) in inputs.into_iter().enumerate() {
-            if let Some((derivation_type, derivation_index)) = self.owns_address(&addr) {
-                let mut path = self.get_base_path(&derivation_type);
-                path.push(derivation_index);
-                let child_hdkey = self.derive_path(&path);
-                let sig = child_hdkey.sign(tx_bytes);
-                mem_tx.tx.inputs[i].signature = bytes_to_hex_string(&sig.to_bytes());
-                mem_tx.tx.inputs[i].public_key = public_key_to_hex(&child_hdkey.get_public_key());
-            } else {
+    /// Signs every input of `mem_tx` with the key of the output it spends,
+    /// using the input's own signature hash type.
+    fn sign_inputs(&self, mem_tx: &mut MempoolTx) -> Result<(), &'static str> {
+        let MempoolTx { tx, utxos } = mem_tx;
+        for i in 0..tx.inputs.len() {
+            let input = &tx.inputs[i];
+            let utxo = utxos
+                .iter()
+                .find(|u| u.tx_id == input.prev_tx_id && u.index == input.output_index)
+                .ok_or("Spent output missing from transaction")?;
+            let Some((_, child_hdkey)) = self.find_address_key(&utxo.output.address) else {
                 return Err("Address not owned by wallet");
-            }
+            };
+            Self::sign_input(tx, i, utxo, &child_hdkey)?;
         }
+        Ok(())
+    }
 
-        Ok(mem_tx)
+    /// Signs input `index` of `tx`, which spends `utxo`, with `key`.
+    fn sign_input(
+        tx: &mut Transaction,
+        index: usize,
+        utxo: &UTXO,
+        key: &HDKey,
+    ) -> Result<(), &'static str> {
+        let is_pubkey_output = utxo.output.script.classify() == ScriptType::PubKey;
+        // Single-output signing fails when the input has no output at its index.
+        let tx_bytes = tx
+            .signing_bytes_for_input(index)
+            .map_err(|_| "Signature hash type does not fit the transaction")?;
+        let sig = key.sign(&tx_bytes);
+        let input = &mut tx.inputs[index];
+        input.signature = bytes_to_hex_string(&sig.to_bytes());
+        input.public_key = public_key_to_hex(&key.get_public_key());
+        if is_pubkey_output {
+            // Pay-to-pubkey outputs already carry the key; only the signature is pushed.
+            let mut unlock_script = Script::new();
+            unlock_script.push_data(&sig.to_bytes());
+            input.unlock_script = unlock_script;
+        }
+        Ok(())
     }
 }
@@ -1,4 +1,5 @@
 use crate::model::{Block, Transaction, node::NodeVersion};
+use primitive_types::U256;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -40,6 +41,8 @@ pub enum NetworkMessage {
     SendCommonBlock(Block),
     NoCommonAncestor {
         peer_height: u64,
+        #[serde(default)]
+        peer_chainwork: U256,
     },
 
     GetConnectedPeers,
@@ -3,6 +3,7 @@ use crate::network::NetworkMessage;
 use crate::network::network_message::InventoryType;
 use crate::network::peer_manager::PeerSnapshot;
 use crate::network::server::{BROADCAST_CHANNEL, Delivery};
+use primitive_types::U256;
 use std::net::SocketAddr;
 
 pub fn broadcast_new_block_hash(block_hash: [u8; 32], exclude_peer: Option<SocketAddr>) {
@@ -91,8 +92,11 @@ pub fn send_common_block(block: &Block, target_peer: SocketAddr) {
         .send((block_msg, Delivery::Direct { target_peer }));
 }
 
-pub fn notify_no_common_ancestor(target_peer: SocketAddr, peer_height: u64) {
-    let msg = NetworkMessage::NoCommonAncestor { peer_height };
+pub fn notify_no_common_ancestor(target_peer: SocketAddr, peer_height: u64, peer_chainwork: U256) {
+    let msg = NetworkMessage::NoCommonAncestor {
+        peer_height,
+        peer_chainwork,
+    };
     let _ = BROADCAST_CHANNEL
         .sender
         .send((msg, Delivery::Direct { target_peer }));
@@ -415,19 +415,23 @@ async fn handle_connection(
                                 node.handle_received_common_block(block, peer_addr).await;
                             },
 
-                            NetworkMessage::NoCommonAncestor { peer_height } => {
+                            NetworkMessage::NoCommonAncestor {
+                                peer_height,
+                                peer_chainwork,
+                            } => {
                                 PEER_MANAGER
                                     .update_last_event(
                                         peer_addr.unwrap(),
                                         connection_id,
                                         format!(
-                                            "Received NO_COMMON_ANCESTOR (peer height {})",
-                                            peer_height
+                                            "Received NO_COMMON_ANCESTOR (peer height {}, chainwork {})",
+                                            peer_height, peer_chainwork
                                         ),
                                     )
                                     .await;
                                 let mut node = get_node_mut().await;
-                                node.handle_no_common_ancestor(peer_height, peer_addr).await;
+                                node.handle_no_common_ancestor(peer_height, peer_chainwork, peer_addr)
+                                    .await;
                             },
 
                             NetworkMessage::GetConnectedPeers => {
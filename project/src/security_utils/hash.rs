@@ -17,6 +17,14 @@ pub fn bytes_to_hex_string(bytes: &[u8]) -> String {
     hex::encode(bytes)
 }
 
+/// Parses a hex-encoded 32-byte hash, such as a transaction id.
+pub fn hash_from_hex(hex: &str) -> Result<[u8; 32], String> {
+    hex::decode(hex)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| format!("Invalid 32-byte hex hash: {}", hex))
+}
+
 /// Returns true if the hash is less than or equal to the given target (proof of work check).
 pub fn hash_meets_target(hash: &[u8; 32], target: &U256) -> bool {
     U256::from_big_endian(hash) <= *target
@@ -0,0 +1,212 @@
+// Canonical binary encoding shared by transactions and blocks.
+//
+// All integers are big-endian with a fixed width, variable-size fields are
+// prefixed with their length as a `u32`, and every standalone encoding starts
+// with `ENCODING_VERSION` so the format can evolve without ambiguity.
+use chrono::{DateTime, NaiveDateTime};
+use primitive_types::U256;
+
+pub const ENCODING_VERSION: u8 = 1;
+
+#[derive(Default)]
+pub struct ByteWriter {
+    buf: Vec<u8>,
+}
+
+impl ByteWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writer that already holds the encoding version byte.
+    pub fn versioned() -> Self {
+        let mut writer = Self::new();
+        writer.put_version();
+        writer
+    }
+
+    /// Starts a nested versioned encoding, e.g. each transaction inside a block.
+    pub fn put_version(&mut self) {
+        self.put_u8(ENCODING_VERSION);
+    }
+
+    pub fn put_u8(&mut self, value: u8) {
+        self.buf.push(value);
+    }
+
+    pub fn put_u32(&mut self, value: u32) {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn put_i64(&mut self, value: i64) {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn put_u64(&mut self, value: u64) {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn put_hash(&mut self, hash: &[u8; 32]) {
+        self.buf.extend_from_slice(hash);
+    }
+
+    pub fn put_u256(&mut self, value: U256) {
+        let mut bytes = [0u8; 32];
+        value.to_big_endian(&mut bytes);
+        self.buf.extend_from_slice(&bytes);
+    }
+
+    /// Length-prefixed byte string.
+    pub fn put_bytes(&mut self, bytes: &[u8]) {
+        self.put_len(bytes.len());
+        self.buf.extend_from_slice(bytes);
+    }
+
+    pub fn put_str(&mut self, value: &str) {
+        self.put_bytes(value.as_bytes());
+    }
+
+    pub fn put_opt_str(&mut self, value: Option<&str>) {
+        match value {
+            Some(v) => {
+                self.put_u8(1);
+                self.put_str(v);
+            }
+            None => self.put_u8(0),
+        }
+    }
+
+    /// Seconds since the epoch followed by the sub-second nanoseconds.
+    pub fn put_datetime(&mut self, value: &NaiveDateTime) {
+        let utc = value.and_utc();
+        self.put_i64(utc.timestamp());
+        self.put_u32(utc.timestamp_subsec_nanos());
+    }
+
+    /// Element count of a following sequence.
+    pub fn put_len(&mut self, len: usize) {
+        let len = u32::try_from(len).expect("encoded field longer than u32::MAX");
+        self.put_u32(len);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+pub struct ByteReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        ByteReader { buf, pos: 0 }
+    }
+
+    /// Reader positioned after the version byte, rejecting unknown versions.
+    pub fn versioned(buf: &'a [u8]) -> Result<Self, String> {
+        let mut reader = Self::new(buf);
+        reader.expect_version()?;
+        Ok(reader)
+    }
+
+    pub fn expect_version(&mut self) -> Result<(), String> {
+        let version = self.get_u8()?;
+        if version != ENCODING_VERSION {
+            return Err(format!("Unsupported encoding version: {}", version));
+        }
+        Ok(())
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.buf.len() - self.pos < len {
+            return Err(format!(
+                "Unexpected end of data: needed {} bytes at offset {}, {} left",
+                len,
+                self.pos,
+                self.buf.len() - self.pos
+            ));
+        }
+        let slice = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    pub fn get_u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn get_u32(&mut self) -> Result<u32, String> {
+        let bytes: [u8; 4] = self.take(4)?.try_into().unwrap();
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    pub fn get_i64(&mut self) -> Result<i64, String> {
+        let bytes: [u8; 8] = self.take(8)?.try_into().unwrap();
+        Ok(i64::from_be_bytes(bytes))
+    }
+
+    pub fn get_u64(&mut self) -> Result<u64, String> {
+        let bytes: [u8; 8] = self.take(8)?.try_into().unwrap();
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    pub fn get_hash(&mut self) -> Result<[u8; 32], String> {
+        Ok(self.take(32)?.try_into().unwrap())
+    }
+
+    pub fn get_u256(&mut self) -> Result<U256, String> {
+        Ok(U256::from_big_endian(self.take(32)?))
+    }
+
+    pub fn get_bytes(&mut self) -> Result<Vec<u8>, String> {
+        let len = self.get_u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    pub fn get_string(&mut self) -> Result<String, String> {
+        String::from_utf8(self.get_bytes()?).map_err(|e| format!("Invalid UTF-8 string: {}", e))
+    }
+
+    pub fn get_opt_string(&mut self) -> Result<Option<String>, String> {
+        match self.get_u8()? {
+            0 => Ok(None),
+            1 => Ok(Some(self.get_string()?)),
+            flag => Err(format!("Invalid option flag: {}", flag)),
+        }
+    }
+
+    pub fn get_datetime(&mut self) -> Result<NaiveDateTime, String> {
+        let secs = self.get_i64()?;
+        let nanos = self.get_u32()?;
+        DateTime::from_timestamp(secs, nanos)
+            .map(|dt| dt.naive_utc())
+            .ok_or_else(|| format!("Invalid timestamp: {}s {}ns", secs, nanos))
+    }
+
+    /// Element count of a following sequence. Every element takes at least
+    /// `min_item_size` bytes, which bounds the count by the remaining data.
+    pub fn get_len(&mut self, min_item_size: usize) -> Result<usize, String> {
+        let len = self.get_u32()? as usize;
+        let remaining = self.buf.len() - self.pos;
+        if len.saturating_mul(min_item_size.max(1)) > remaining {
+            return Err(format!(
+                "Sequence length {} exceeds remaining data ({} bytes)",
+                len, remaining
+            ));
+        }
+        Ok(len)
+    }
+
+    /// Fails if there are bytes left after a complete decode.
+    pub fn finish(self) -> Result<(), String> {
+        if self.pos != self.buf.len() {
+            return Err(format!(
+                "Trailing data: {} unread bytes",
+                self.buf.len() - self.pos
+            ));
+        }
+        Ok(())
+    }
+}
@@ -2,13 +2,19 @@ use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 
 use chrono::NaiveDateTime;
+use primitive_types::U256;
 
 use super::logger::{LogCategory, log_info, log_warning};
-use crate::globals::CONFIG;
+use crate::globals::{CONFIG, CONSENSUS_RULES};
 use crate::{
-    model::{Block, Blockchain, block::BlockID},
+    model::{
+        Block, Blockchain,
+        block::BlockID,
+        checkpoints::Checkpoints,
+        utxo_view::{LedgerView, UtxoOverlay, UtxoView},
+    },
     security_utils::bytes_to_hex_string,
-    utils::get_current_timestamp,
+    utils::{get_current_timestamp, median_time_past},
 };
 
 #[derive(Debug, Clone)]
@@ -24,6 +30,8 @@ pub struct ReorgCandidate {
     pub ancestor_hash: BlockID,
     pub blocks: Vec<Block>,
     pub candidate_height: usize,
+    /// Cumulative work of the chain that would result from the reorg.
+    pub candidate_chainwork: U256,
 }
 
 #[derive(Debug, Clone)]
@@ -70,6 +78,7 @@ pub struct ForkHelper {
     nodes: HashMap<BlockID, ForkNode>,
     children_by_parent: HashMap<BlockID, HashSet<BlockID>>,
     max_blocks: usize,
+    checkpoints: Checkpoints,
 }
 
 impl ForkHelper {
@@ -82,9 +91,18 @@ impl ForkHelper {
             nodes: HashMap::with_capacity(max_blocks),
             children_by_parent: HashMap::with_capacity(max_blocks),
             max_blocks,
+            checkpoints: CONSENSUS_RULES.checkpoints.clone(),
         }
     }
 
+    pub fn checkpoints(&self) -> &Checkpoints {
+        &self.checkpoints
+    }
+
+    pub fn set_checkpoints(&mut self, checkpoints: Checkpoints) {
+        self.checkpoints = checkpoints;
+    }
+
     pub fn observe_block(
         &mut self,
         blockchain: &Blockchain,
@@ -101,7 +119,31 @@ impl ForkHelper {
             return ForkUpdate::empty(ForkUpdateStatus::DuplicateForkTree);
         }
 
-        if let Err(e) = block.validate() {
+        // Orphans whose height is still unknown only get the height-independent checks;
+        // the full validation runs again when the branch is applied to the main chain.
+        let now = get_current_timestamp();
+        let validation = match self.resolve_block_height(blockchain, &block) {
+            Some(height) => {
+                let mtp = self.branch_median_time_past(blockchain, block.header.prev_block_hash);
+                self.checkpoints
+                    .check_block(height, block_hash)
+                    .and_then(|_| self.checkpoints.check_fork(height, blockchain.height()))
+                    .and_then(|_| block.check_timestamp(mtp, now))
+                    .and_then(|_| {
+                        let view = self.branch_view(blockchain, &LedgerView, &block, height)?;
+                        block.validate_in(
+                            &view,
+                            height,
+                            mtp,
+                            self.checkpoints.verifies_scripts(height, &block, &[]),
+                        )
+                    })
+            }
+            None => block
+                .check_future_drift(now)
+                .and_then(|_| block.check_structure()),
+        };
+        if let Err(e) = validation {
             return ForkUpdate::empty(ForkUpdateStatus::Invalid(e));
         }
 
@@ -132,6 +174,7 @@ impl ForkHelper {
 
     pub fn find_best_reorg_candidate(&self, blockchain: &Blockchain) -> Option<ReorgCandidate> {
         let mut best: Option<ReorgCandidate> = None;
+        let local_chainwork = blockchain.chainwork();
 
         for leaf_hash in self.leaf_hashes() {
             let Some(candidate) = self.build_candidate_from_leaf(blockchain, leaf_hash) else {
@@ -141,22 +184,39 @@ impl ForkHelper {
             log_info(
                 LogCategory::Core,
                 &format!(
-                    "Evaluating fork-tree candidate ending at {} with height {} - BC height: {}",
+                    "Evaluating fork-tree candidate ending at {} with height {} and chainwork {} - BC height: {}, chainwork: {}",
                     bytes_to_hex_string(&leaf_hash),
                     candidate.candidate_height,
-                    blockchain.height()
+                    candidate.candidate_chainwork,
+                    blockchain.height(),
+                    local_chainwork
                 ),
             );
 
-            if candidate.candidate_height <= blockchain.height() {
+            if candidate.candidate_chainwork <= local_chainwork {
+                continue;
+            }
+
+            if let Err(e) = self
+                .check_candidate_checkpoints(blockchain, &candidate)
+                .and_then(|_| Self::check_candidate_timestamps(blockchain, &candidate))
+            {
+                log_warning(
+                    LogCategory::Core,
+                    &format!(
+                        "Discarding fork-tree candidate ending at {}: {}",
+                        bytes_to_hex_string(&leaf_hash),
+                        e
+                    ),
+                );
                 continue;
             }
 
             match &best {
                 Some(current_best)
-                    if current_best.candidate_height > candidate.candidate_height => {}
+                    if current_best.candidate_chainwork > candidate.candidate_chainwork => {}
                 Some(current_best)
-                    if current_best.candidate_height == candidate.candidate_height =>
+                    if current_best.candidate_chainwork == candidate.candidate_chainwork =>
                 {
                     // the block with lowest hash is considered the "best" candidate
                     let current_leaf = current_best.blocks.last().map(|b| b.id());
@@ -284,6 +344,137 @@ impl ForkHelper {
         }
     }
 
+    /// Height the block would have once connected, if its ancestry reaches the main chain.
+    fn resolve_block_height(&self, blockchain: &Blockchain, block: &Block) -> Option<u64> {
+        let mut parent_hash = block.header.prev_block_hash;
+        let mut depth: u64 = 0;
+        let mut visited = HashSet::new();
+
+        loop {
+            if parent_hash == [0; 32] {
+                return Some(depth);
+            }
+
+            if let Some(parent_height) = blockchain.find_block_height_by_hash(parent_hash) {
+                return Some(parent_height as u64 + 1 + depth);
+            }
+
+            if !visited.insert(parent_hash) {
+                return None;
+            }
+
+            let node = self.nodes.get(&parent_hash)?;
+            parent_hash = node.parent;
+            depth += 1;
+        }
+    }
+
+    /// Median-time-past for a child of `parent_hash`, walking the fork tree and then the main chain.
+    fn branch_median_time_past(
+        &self,
+        blockchain: &Blockchain,
+        mut parent_hash: BlockID,
+    ) -> Option<NaiveDateTime> {
+        let span = CONSENSUS_RULES.median_time_span;
+        let mut timestamps = Vec::with_capacity(span);
+
+        while timestamps.len() < span {
+            let Some(node) = self.nodes.get(&parent_hash) else {
+                break;
+            };
+            timestamps.push(node.block.header.timestamp);
+            parent_hash = node.parent;
+        }
+
+        if let Some(parent_height) = blockchain.find_block_height_by_hash(parent_hash) {
+            let remaining = span - timestamps.len();
+            let start = (parent_height + 1).saturating_sub(remaining);
+            timestamps.extend(
+                blockchain.chain[start..=parent_height]
+                    .iter()
+                    .map(|block| block.header.timestamp),
+            );
+        }
+
+        median_time_past(&timestamps)
+    }
+
+    /// UTXO set `block`, at `height`, spends from: `base`, the ledger at the
+    /// main-chain tip, with the main chain disconnected down to the fork point
+    /// and the fork-tree ancestors of `block` connected, all in memory.
+    fn branch_view<'a>(
+        &self,
+        blockchain: &Blockchain,
+        base: &'a dyn UtxoView,
+        block: &Block,
+        height: u64,
+    ) -> Result<UtxoOverlay<'a>, String> {
+        let mut ancestors = Vec::new();
+        let mut parent_hash = block.header.prev_block_hash;
+        while let Some(node) = self.nodes.get(&parent_hash) {
+            ancestors.push(&node.block);
+            parent_hash = node.parent;
+        }
+        let fork_height = height as usize - ancestors.len();
+
+        let mut view = UtxoOverlay::new(base);
+        view.disconnect_blocks_from(&blockchain.chain, fork_height)?;
+        for (offset, ancestor) in ancestors.iter().rev().enumerate() {
+            view.connect_block(ancestor, (fork_height + offset) as u64)
+                .map_err(|e| {
+                    format!(
+                        "fork ancestor {} cannot be connected: {}",
+                        bytes_to_hex_string(&ancestor.id()),
+                        e
+                    )
+                })?;
+        }
+        Ok(view)
+    }
+
+    /// Rejects a candidate branch that forks below a passed checkpoint or
+    /// conflicts with one. Orphans joined the branch before their height was known.
+    fn check_candidate_checkpoints(
+        &self,
+        blockchain: &Blockchain,
+        candidate: &ReorgCandidate,
+    ) -> Result<(), String> {
+        let first_height = (candidate.candidate_height - candidate.blocks.len()) as u64;
+        self.checkpoints
+            .check_fork(first_height, blockchain.height())?;
+        for (offset, block) in candidate.blocks.iter().enumerate() {
+            self.checkpoints
+                .check_block(first_height + offset as u64, block.id())?;
+        }
+        Ok(())
+    }
+
+    /// Re-checks the timestamp rules along a candidate branch, block by block.
+    fn check_candidate_timestamps(
+        blockchain: &Blockchain,
+        candidate: &ReorgCandidate,
+    ) -> Result<(), String> {
+        let span = CONSENSUS_RULES.median_time_span;
+        let first_height = candidate.candidate_height - candidate.blocks.len();
+        let start = first_height.saturating_sub(span);
+        let mut window: Vec<NaiveDateTime> = blockchain.chain[start..first_height]
+            .iter()
+            .map(|block| block.header.timestamp)
+            .collect();
+
+        let now = get_current_timestamp();
+        for block in &candidate.blocks {
+            block
+                .check_timestamp(median_time_past(&window), now)
+                .map_err(|e| format!("block {}: {}", bytes_to_hex_string(&block.id()), e))?;
+            window.push(block.header.timestamp);
+            if window.len() > span {
+                window.remove(0);
+            }
+        }
+        Ok(())
+    }
+
     fn leaf_hashes(&self) -> Vec<BlockID> {
         self.nodes
             .iter()
@@ -329,16 +520,19 @@ impl ForkHelper {
                 return Some(ReorgCandidate {
                     ancestor_hash: [0; 32],
                     candidate_height: blocks_reversed.len(),
+                    candidate_chainwork: Self::branch_work(U256::zero(), &blocks_reversed),
                     blocks: blocks_reversed,
                 });
             }
 
             if let Some(ancestor_height) = blockchain.find_block_height_by_hash(parent_hash) {
                 blocks_reversed.reverse();
+                let ancestor_chainwork = blockchain.chainwork_at(ancestor_height + 1);
 
                 return Some(ReorgCandidate {
                     ancestor_hash: parent_hash,
                     candidate_height: ancestor_height + 1 + blocks_reversed.len(),
+                    candidate_chainwork: Self::branch_work(ancestor_chainwork, &blocks_reversed),
                     blocks: blocks_reversed,
                 });
             }
@@ -351,6 +545,12 @@ impl ForkHelper {
         }
     }
 
+    fn branch_work(ancestor_chainwork: U256, blocks: &[Block]) -> U256 {
+        blocks.iter().fold(ancestor_chainwork, |acc, block| {
+            acc.saturating_add(block.work())
+        })
+    }
+
     fn remove_node_only(&mut self, hash: BlockID) -> Option<Block> {
         let node = self.nodes.remove(&hash)?;
 
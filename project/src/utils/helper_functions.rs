@@ -1,10 +1,12 @@
-use chrono::{NaiveDateTime, Utc};
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
 use primitive_types::U256;
 
 use crate::{
     daemon::types::{TransactionViewResponse, TxInputInfo, TxOutputInfo},
     globals::CONSENSUS_RULES,
-    model::Transaction,
+    model::{Transaction, io::sighash_type_name},
     security_utils::bytes_to_hex_string,
 };
 
@@ -12,8 +14,28 @@ pub fn format_date(date: &NaiveDateTime) -> String {
     date.format("%Y-%m-%d %H:%M:%S").to_string()
 }
 
+/// Unix time returned by `get_current_timestamp` instead of the clock, 0 when unset.
+static MOCK_TIME: AtomicI64 = AtomicI64::new(0);
+
 pub fn get_current_timestamp() -> NaiveDateTime {
-    Utc::now().naive_utc()
+    match MOCK_TIME.load(Ordering::Relaxed) {
+        0 => Utc::now().naive_utc(),
+        secs => DateTime::from_timestamp(secs, 0)
+            .map(|dt| dt.naive_utc())
+            .unwrap_or_else(|| Utc::now().naive_utc()),
+    }
+}
+
+/// Freezes the node clock at `secs` (Unix time); 0 goes back to the system clock.
+pub fn set_mock_time(secs: i64) {
+    MOCK_TIME.store(secs, Ordering::Relaxed);
+}
+
+/// Median of a set of block timestamps, at the second granularity committed by the header.
+pub fn median_time_past(timestamps: &[NaiveDateTime]) -> Option<NaiveDateTime> {
+    let mut sorted: Vec<NaiveDateTime> = timestamps.to_vec();
+    sorted.sort_by_key(|t| t.and_utc().timestamp());
+    sorted.get(sorted.len() / 2).copied()
 }
 
 pub fn assert_parent_dir_exists(file_path: &str) -> Result<(), String> {
@@ -52,6 +74,14 @@ pub fn format_difficulty(target: U256) -> String {
     )
 }
 
+/// Expected number of hashes needed to meet `target`, i.e. `2^256 / (target + 1)`.
+pub fn target_work(target: U256) -> U256 {
+    if target == U256::MAX {
+        return U256::one();
+    }
+    (!target / (target + U256::one())) + U256::one()
+}
+
 fn u256_to_f64(value: U256) -> f64 {
     let mut bytes = [0u8; 32];
     value.to_big_endian(&mut bytes);
@@ -64,6 +94,7 @@ fn u256_to_f64(value: U256) -> f64 {
 pub fn transaction_model_to_view(tx: &Transaction) -> TransactionViewResponse {
     TransactionViewResponse {
         id: bytes_to_hex_string(&tx.id()),
+        wtxid: bytes_to_hex_string(&tx.wtxid()),
         date: tx.date.to_string(),
         message: tx.message.clone(),
         inputs: tx
@@ -72,19 +103,29 @@ pub fn transaction_model_to_view(tx: &Transaction) -> TransactionViewResponse {
             .map(|input| TxInputInfo {
                 prev_tx_id: bytes_to_hex_string(&input.prev_tx_id),
                 output_index: input.output_index,
+                relative_lock: input.relative_lock,
+                sighash_type: sighash_type_name(input.sighash_type),
+                replaceable: input.replaceable,
                 signature: input.signature.clone(),
                 public_key: input.public_key.clone(),
+                unlock_script: input.unlock_script.to_asm(),
             })
             .collect(),
         outputs: tx
             .outputs
             .iter()
-            .map(|output| TxOutputInfo {
-                value: output.value,
-                address: output.address.clone(),
+            .map(|output| {
+                let script = output.locking_script().unwrap_or_default();
+                TxOutputInfo {
+                    value: output.value,
+                    address: output.address.clone(),
+                    script: script.to_asm(),
+                    script_type: script.classify().name().to_string(),
+                }
             })
             .collect(),
         is_coinbase: tx.is_coinbase(),
         size: tx.size(),
+        lock_time: tx.lock_time,
     }
 }
@@ -1,9 +1,11 @@
+pub mod codec;
 pub mod fork_helper;
 pub mod helper_functions;
 pub mod logger;
 pub mod merkle_tree;
 pub mod pid_file;
 
+pub use codec::{ByteReader, ByteWriter, ENCODING_VERSION};
 pub use fork_helper::{ForkHelper, ForkUpdate, ForkUpdateStatus, ReorgCandidate};
 pub use helper_functions::*;
 pub use logger::{
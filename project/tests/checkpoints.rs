@@ -0,0 +1,198 @@
+use chrono::NaiveDate;
+use primitive_types::U256;
+
+use project::chain_params::{Network, NetworkParams};
+use project::db::db::init_db;
+use project::db::repository::LedgerRepository;
+use project::model::checkpoints::Checkpoints;
+use project::model::{
+    Block, Blockchain, Script, Transaction, TxInput, TxOutput, block::BlockHeader,
+};
+use project::utils::{ForkHelper, ForkUpdateStatus};
+
+fn test_block(prev_block_hash: [u8; 32], nonce: u32) -> Block {
+    let timestamp = NaiveDate::from_ymd_opt(2026, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, nonce)
+        .unwrap();
+
+    let mut block = Block {
+        header: BlockHeader {
+            version: BlockHeader::INITIAL_VERSION,
+            prev_block_hash,
+            merkle_root: [0; 32],
+            nonce,
+            timestamp,
+            target: U256::MAX,
+        },
+        transactions: vec![Transaction::new_coinbase(format!("miner-{}", nonce), 0, 0)],
+    };
+    block.evaluate_merkle_root();
+    block
+}
+
+/// A chain of `len` blocks, the first one at `[0; 32]`.
+fn chain_of(len: u32) -> Vec<Block> {
+    let mut chain: Vec<Block> = Vec::new();
+    for n in 1..=len {
+        let prev = chain.last().map_or([0; 32], |block| block.id());
+        chain.push(test_block(prev, n));
+    }
+    chain
+}
+
+fn spend(prev_tx_id: [u8; 32], value: i64) -> Transaction {
+    let input = TxInput::new(prev_tx_id, 0);
+    Transaction::new(
+        vec![input],
+        vec![TxOutput::with_script(value, Script::new_hashlock(&[7; 32]))],
+        None,
+    )
+}
+
+fn expect_invalid(status: ForkUpdateStatus, expected: &str) {
+    match status {
+        ForkUpdateStatus::Invalid(reason) => assert!(reason.contains(expected), "{}", reason),
+        other => panic!("expected invalid block, got {:?}", other),
+    }
+}
+
+#[test]
+fn networks_pin_their_genesis_block() {
+    for network in [Network::Main, Network::Test] {
+        let params = NetworkParams::for_network(network);
+        let checkpoints = Checkpoints::for_network(&params);
+        assert_eq!(checkpoints.expected_hash(0), Some(params.genesis_hash()));
+        assert!(checkpoints.check_block(0, [1; 32]).is_err());
+    }
+}
+
+#[test]
+fn forks_below_a_passed_checkpoint_are_rejected() {
+    let checkpoints = Checkpoints::new(vec![(0, [1; 32]), (10, [2; 32])], None);
+
+    assert_eq!(checkpoints.last_passed(10), Some(0));
+    assert_eq!(checkpoints.last_passed(11), Some(10));
+    assert!(checkpoints.check_fork(5, 10).is_ok());
+    assert!(checkpoints.check_fork(10, 11).is_err());
+    assert!(checkpoints.check_fork(11, 11).is_ok());
+    assert!(checkpoints.check_block(10, [2; 32]).is_ok());
+    assert!(checkpoints.check_block(10, [3; 32]).is_err());
+    assert!(checkpoints.check_block(11, [3; 32]).is_ok());
+}
+
+#[test]
+fn assume_valid_block_is_parsed_and_enforced_like_a_checkpoint() {
+    let hash = [0xab; 32];
+    let spec = format!("12:{}", hex::encode(hash));
+    let assume_valid = Checkpoints::parse_assume_valid(&spec).unwrap();
+    assert_eq!(assume_valid, Some((12, hash)));
+    assert_eq!(Checkpoints::parse_assume_valid("0").unwrap(), None);
+    assert!(Checkpoints::parse_assume_valid("12").is_err());
+    assert!(Checkpoints::parse_assume_valid("x:00").is_err());
+
+    let checkpoints = Checkpoints::default().with_assume_valid(assume_valid);
+    assert!(checkpoints.check_block(12, [1; 32]).is_err());
+    assert!(checkpoints.check_fork(12, 13).is_err());
+}
+
+#[test]
+fn scripts_are_skipped_only_on_the_way_to_a_known_assume_valid_block() {
+    let chain = chain_of(4);
+    let checkpoints = Checkpoints::default().with_assume_valid(Some((2, chain[2].id())));
+    assert!(!checkpoints.verifies_scripts(2, &chain[2], &[]));
+    assert!(!checkpoints.verifies_scripts(0, &chain[0], &chain[1..]));
+    assert!(checkpoints.verifies_scripts(3, &chain[3], &[]));
+
+    // Until the assume-valid block is at hand, nothing leads to it.
+    assert!(checkpoints.verifies_scripts(1, &chain[1], &[]));
+    assert!(checkpoints.verifies_scripts(0, &chain[0], &chain[1..2]));
+
+    // Nor does a branch with another block at its height.
+    let rival = test_block(chain[1].id(), 20);
+    assert!(checkpoints.verifies_scripts(2, &rival, &[]));
+    assert!(checkpoints.verifies_scripts(1, &chain[1], &[rival]));
+    assert!(Checkpoints::default().verifies_scripts(0, &chain[0], &chain[1..]));
+}
+
+#[test]
+fn observe_block_rejects_blocks_conflicting_with_a_checkpoint() {
+    let chain = chain_of(2);
+    let fork = test_block(chain[0].id(), 10);
+    let blockchain = Blockchain {
+        chain: chain[..1].to_vec(),
+    };
+    let mut helper = ForkHelper::with_capacity_limit(1000);
+    helper.set_checkpoints(Checkpoints::new(vec![(1, chain[1].id())], None));
+
+    let update = helper.observe_block(&blockchain, fork.clone(), None);
+    expect_invalid(update.status, "conflicts with checkpoint");
+    assert!(!helper.contains_block(&fork.id()));
+}
+
+#[test]
+fn observe_block_rejects_forks_below_a_passed_checkpoint() {
+    let chain = chain_of(3);
+    let fork = test_block(chain[0].id(), 10);
+    let blockchain = Blockchain {
+        chain: chain.clone(),
+    };
+    let mut helper = ForkHelper::with_capacity_limit(1000);
+
+    // Without checkpoints the fork is kept for a possible reorg.
+    let update = helper.observe_block(&blockchain, fork.clone(), None);
+    assert!(matches!(update.status, ForkUpdateStatus::Stored));
+
+    helper.clear_forks();
+    helper.set_checkpoints(Checkpoints::new(vec![(2, chain[2].id())], None));
+    let update = helper.observe_block(&blockchain, fork, None);
+    expect_invalid(update.status, "below the checkpoint");
+}
+
+#[test]
+fn scripts_are_skipped_below_the_assume_valid_block_but_amounts_are_not() {
+    init_db();
+    let parent = spend([9; 32], 1_000);
+    // The hash-lock of the parent output is not satisfied by an empty script.
+    let child = spend(parent.id(), 900);
+    let greedy = spend(parent.id(), 1_100);
+    let unconfirmed = [parent];
+
+    let err = child
+        .validate_with_options(1, None, &unconfirmed, true)
+        .unwrap_err();
+    assert!(err.contains("Script verification failed"), "{}", err);
+    assert_eq!(
+        child.validate_with_options(1, None, &unconfirmed, false),
+        Ok(100)
+    );
+
+    let err = greedy
+        .validate_with_options(1, None, &unconfirmed, false)
+        .unwrap_err();
+    assert!(err.contains("exceed inputs"), "{}", err);
+}
+
+#[test]
+fn side_branches_below_the_assume_valid_block_have_their_scripts_verified() {
+    init_db();
+    // Confirmed without validation; its input is never looked up.
+    let parent = spend([9; 32], 1_000);
+    let mut funding_block = test_block([0; 32], 20);
+    funding_block.transactions.push(parent.clone());
+    funding_block.evaluate_merkle_root();
+    LedgerRepository::new().apply_block(funding_block).unwrap();
+
+    let chain = chain_of(3);
+    let mut fork = test_block(chain[0].id(), 10);
+    // The hash-lock of the parent output is not satisfied by an empty script.
+    fork.transactions.push(spend(parent.id(), 900));
+    fork.evaluate_merkle_root();
+    let blockchain = Blockchain { chain };
+    let mut helper = ForkHelper::with_capacity_limit(1000);
+    helper.set_checkpoints(Checkpoints::new(Vec::new(), Some((10, [0xab; 32]))));
+
+    let update = helper.observe_block(&blockchain, fork.clone(), None);
+    expect_invalid(update.status, "Script verification failed");
+    assert!(!helper.contains_block(&fork.id()));
+}
@@ -0,0 +1,62 @@
+use primitive_types::U256;
+
+use project::db::db::init_db;
+use project::db::repository::LedgerRepository;
+use project::globals::CONSENSUS_RULES;
+use project::model::{Block, Transaction, TxInput, TxOutput, block::BlockHeader};
+
+#[test]
+fn coinbase_is_immature_before_maturity_depth() {
+    let maturity = CONSENSUS_RULES.coinbase_maturity;
+
+    assert!(!CONSENSUS_RULES.is_coinbase_mature(0, 0));
+    assert!(!CONSENSUS_RULES.is_coinbase_mature(0, 1));
+    assert!(!CONSENSUS_RULES.is_coinbase_mature(10, 10 + maturity - 1));
+}
+
+#[test]
+fn coinbase_is_mature_at_maturity_depth() {
+    let maturity = CONSENSUS_RULES.coinbase_maturity;
+
+    assert!(CONSENSUS_RULES.is_coinbase_mature(0, maturity));
+    assert!(CONSENSUS_RULES.is_coinbase_mature(10, 10 + maturity));
+    assert!(!CONSENSUS_RULES.is_coinbase_mature(u64::MAX - 1, u64::MAX - 1));
+}
+
+fn spend(prev_tx_id: [u8; 32]) -> Transaction {
+    Transaction::new(
+        vec![TxInput::new(prev_tx_id, 0)],
+        vec![TxOutput::new(1, "addr".to_string())],
+        None,
+    )
+}
+
+#[test]
+fn maturity_check_rejects_inputs_of_unknown_transactions() {
+    init_db();
+    let coinbase = Transaction::new_coinbase("maturity-miner".to_string(), 0, 0);
+    let mut block = Block::new([0; 32], U256::MAX, BlockHeader::INITIAL_VERSION);
+    block.transactions = vec![coinbase.clone()];
+    block.evaluate_merkle_root();
+    LedgerRepository::new().apply_block(block).unwrap();
+    let maturity = CONSENSUS_RULES.coinbase_maturity;
+    let none_pending = |_: &[u8; 32]| false;
+
+    let spender = spend(coinbase.id());
+    assert!(spender.check_coinbase_maturity(1, none_pending).is_err());
+    assert_eq!(
+        spender.check_coinbase_maturity(maturity, none_pending),
+        Ok(())
+    );
+
+    let orphan = spend([77; 32]);
+    let err = orphan
+        .check_coinbase_maturity(maturity, none_pending)
+        .unwrap_err();
+    assert!(err.contains("Unable to find transaction"), "{}", err);
+    // A mempool parent is never a coinbase.
+    assert_eq!(
+        orphan.check_coinbase_maturity(maturity, |id| *id == [77; 32]),
+        Ok(())
+    );
+}
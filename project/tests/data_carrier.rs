@@ -0,0 +1,100 @@
+use chrono::NaiveDate;
+use primitive_types::U256;
+
+use project::model::script::{MAX_DATA_CARRIER_SIZE, ScriptType};
+use project::model::{Block, Blockchain, Script, Transaction, TxOutput, block::BlockHeader};
+
+fn data_output(data: &[u8]) -> TxOutput {
+    TxOutput::with_script(0, Script::new_data(data))
+}
+
+fn tx_with(outputs: Vec<TxOutput>) -> Transaction {
+    Transaction::new(Vec::new(), outputs, None)
+}
+
+fn block_with(transactions: Vec<Transaction>) -> Block {
+    Block {
+        header: BlockHeader {
+            version: BlockHeader::INITIAL_VERSION,
+            prev_block_hash: [0; 32],
+            merkle_root: [0; 32],
+            nonce: 0,
+            timestamp: NaiveDate::from_ymd_opt(2026, 1, 1)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap(),
+            target: U256::MAX,
+        },
+        transactions,
+    }
+}
+
+#[test]
+fn data_scripts_expose_their_payload() {
+    let script = Script::new_data(b"hello");
+
+    assert_eq!(script.classify(), ScriptType::NullData);
+    assert!(script.is_unspendable());
+    assert_eq!(script.data_payload(), Some(b"hello".to_vec()));
+    assert_eq!(Script::new_data(b"").data_payload(), Some(Vec::new()));
+    assert_eq!(Script::new_hashlock(&[0; 32]).data_payload(), None);
+    assert_eq!(data_output(b"hello").address, "");
+}
+
+#[test]
+fn data_outputs_follow_relay_policy() {
+    let payment = TxOutput::new(10, "addr".to_string());
+    let largest = vec![7; MAX_DATA_CARRIER_SIZE];
+
+    assert!(
+        tx_with(vec![data_output(&largest), payment.clone()])
+            .check_standard()
+            .is_ok()
+    );
+    assert!(
+        tx_with(vec![data_output(&[7; MAX_DATA_CARRIER_SIZE + 1])])
+            .check_standard()
+            .is_err()
+    );
+    assert!(
+        tx_with(vec![TxOutput::with_script(5, Script::new_data(b"burn"))])
+            .check_standard()
+            .is_err()
+    );
+    assert!(
+        tx_with(vec![data_output(b"one"), data_output(b"two")])
+            .check_standard()
+            .is_err()
+    );
+}
+
+#[test]
+fn data_outputs_are_found_by_prefix() {
+    let first = tx_with(vec![
+        data_output(b"app:first"),
+        TxOutput::new(1, "a".into()),
+    ]);
+    let other = tx_with(vec![data_output(b"other")]);
+    let second = tx_with(vec![
+        TxOutput::new(1, "b".into()),
+        data_output(b"app:second"),
+    ]);
+    let blockchain = Blockchain {
+        chain: vec![
+            block_with(vec![first.clone()]),
+            block_with(vec![other, second.clone()]),
+        ],
+    };
+
+    let found = blockchain.find_data_outputs(b"app:", 10);
+    assert_eq!(found.len(), 2);
+    assert_eq!((found[0].height, found[0].index), (0, 0));
+    assert_eq!(found[0].tx_id, first.id());
+    assert_eq!(found[0].data, b"app:first");
+    assert_eq!((found[1].height, found[1].index), (1, 1));
+    assert_eq!(found[1].tx_id, second.id());
+
+    assert_eq!(blockchain.find_data_outputs(b"app:", 1).len(), 1);
+    assert_eq!(blockchain.find_data_outputs(b"", 10).len(), 3);
+    assert!(blockchain.find_data_outputs(b"none", 10).is_empty());
+}
@@ -1,26 +1,30 @@
 use primitive_types::U256;
 use std::path::Path;
 
-use project::db::db::init_db;
-use project::db::repository::LedgerRepository;
+use project::db::db::{Db, SCHEMA_VERSION, get_db, init_db};
+use project::db::repository::{LedgerRepository, MempoolRecord};
 use project::globals::CONFIG;
 use project::model::block::BlockHeader;
-use project::model::{Block, Transaction, TxOutput};
+use project::model::{Block, Script, Transaction, TxOutput};
 use project::utils::get_current_timestamp;
 
 fn reset_test_db() {
-    let db_path = Path::new(&CONFIG.db_path);
+    remove_db_files(&CONFIG.db_path);
+}
+
+fn remove_db_files(path: &str) {
+    let db_path = Path::new(path);
     if db_path.exists() {
         let _ = std::fs::remove_file(db_path);
     }
 
-    let wal_path = format!("{}-wal", CONFIG.db_path);
+    let wal_path = format!("{}-wal", path);
     let wal_path = Path::new(&wal_path);
     if wal_path.exists() {
         let _ = std::fs::remove_file(wal_path);
     }
 
-    let shm_path = format!("{}-shm", CONFIG.db_path);
+    let shm_path = format!("{}-shm", path);
     let shm_path = Path::new(&shm_path);
     if shm_path.exists() {
         let _ = std::fs::remove_file(shm_path);
@@ -37,32 +41,6 @@ fn test_db_creation_and_schema() {
     assert!(utxos.is_empty());
 }
 
-#[test]
-fn test_mempool_operations() {
-    reset_test_db();
-    init_db();
-    let repo = LedgerRepository::new();
-
-    let tx = Transaction::new(
-        vec![],
-        vec![TxOutput {
-            value: 100,
-            address: "test_address".to_string(),
-        }],
-        Some("test".to_string()),
-    );
-
-    repo.insert_mempool_tx(&tx).unwrap();
-
-    let txid = tx.id();
-    let retrieved = repo.get_transaction(&txid).unwrap();
-    assert!(retrieved.is_some());
-
-    repo.remove_mempool_tx(&txid).unwrap();
-    let retrieved = repo.get_transaction(&txid).unwrap();
-    assert!(retrieved.is_none());
-}
-
 #[test]
 fn test_get_utxos_for_address() {
     reset_test_db();
@@ -72,13 +50,11 @@ fn test_get_utxos_for_address() {
     let addr = "test_address";
     let tx = Transaction::new(
         vec![],
-        vec![TxOutput {
-            value: 50,
-            address: addr.to_string(),
-        }],
+        vec![TxOutput::new(50, addr.to_string())],
         Some("utxo-test".to_string()),
     );
     let header = BlockHeader {
+        version: BlockHeader::INITIAL_VERSION,
         prev_block_hash: [0u8; 32],
         merkle_root: [2u8; 32],
         nonce: 1,
@@ -117,21 +93,16 @@ fn test_get_utxos_for_addresses_multiple() {
     let addr2 = "addr_two".to_string();
     let tx1 = Transaction::new(
         vec![],
-        vec![TxOutput {
-            value: 25,
-            address: addr1.clone(),
-        }],
+        vec![TxOutput::new(25, addr1.clone())],
         Some("addr1".to_string()),
     );
     let tx2 = Transaction::new(
         vec![],
-        vec![TxOutput {
-            value: 75,
-            address: addr2.clone(),
-        }],
+        vec![TxOutput::new(75, addr2.clone())],
         Some("addr2".to_string()),
     );
     let header = BlockHeader {
+        version: BlockHeader::INITIAL_VERSION,
         prev_block_hash: [0u8; 32],
         merkle_root: [3u8; 32],
         nonce: 2,
@@ -169,6 +140,7 @@ fn test_apply_block_genesis() {
     let mut repo = LedgerRepository::new();
 
     let header = BlockHeader {
+        version: BlockHeader::INITIAL_VERSION,
         prev_block_hash: [0u8; 32],
         merkle_root: [1u8; 32],
         nonce: 12345,
@@ -178,10 +150,7 @@ fn test_apply_block_genesis() {
 
     let tx = Transaction::new(
         vec![],
-        vec![TxOutput {
-            value: 50,
-            address: "miner_address".to_string(),
-        }],
+        vec![TxOutput::new(50, "miner_address".to_string())],
         Some("Genesis block".to_string()),
     );
 
@@ -201,3 +170,221 @@ fn test_apply_block_genesis() {
     assert_eq!(utxos.len(), 1);
     assert_eq!(utxos[0].output.value, 50);
 }
+
+#[test]
+fn test_data_outputs_never_become_utxos() {
+    reset_test_db();
+    init_db();
+    let mut repo = LedgerRepository::new();
+
+    let tx = Transaction::new(
+        vec![],
+        vec![
+            TxOutput::with_script(0, Script::new_data(b"anchor")),
+            TxOutput::new(40, "data_test_address".to_string()),
+        ],
+        Some("data-test".to_string()),
+    );
+    let header = BlockHeader {
+        version: BlockHeader::INITIAL_VERSION,
+        prev_block_hash: [0u8; 32],
+        merkle_root: [4u8; 32],
+        nonce: 1,
+        timestamp: get_current_timestamp(),
+        target: U256::MAX,
+    };
+    let block = Block {
+        header,
+        transactions: vec![tx.clone()],
+    };
+    repo.apply_block(block).unwrap();
+
+    let txid = tx.id();
+    let utxos = repo.get_utxos_from_ids(&[(txid, 0), (txid, 1)]).unwrap();
+    assert_eq!(utxos.len(), 1);
+    assert_eq!(utxos[0].index, 1);
+    assert!(repo.get_transaction(&txid).unwrap().is_some());
+}
+
+#[test]
+fn test_transactions_are_found_by_wtxid() {
+    reset_test_db();
+    init_db();
+    let mut repo = LedgerRepository::new();
+
+    let tx = Transaction::new(
+        vec![],
+        vec![TxOutput::new(100, "wtxid_address".to_string())],
+        Some("wtxid-test".to_string()),
+    );
+    let header = BlockHeader {
+        version: BlockHeader::INITIAL_VERSION,
+        prev_block_hash: [0u8; 32],
+        merkle_root: [5u8; 32],
+        nonce: 1,
+        timestamp: get_current_timestamp(),
+        target: U256::MAX,
+    };
+    let block = Block {
+        header,
+        transactions: vec![tx.clone()],
+    };
+    repo.apply_block(block).unwrap();
+
+    let found = repo.get_transaction_by_wtxid(&tx.wtxid()).unwrap();
+    assert_eq!(found.map(|found| found.id()), Some(tx.id()));
+    assert!(repo.get_transaction_by_wtxid(&[0; 32]).unwrap().is_none());
+}
+
+#[test]
+fn test_mempool_entries_round_trip() {
+    reset_test_db();
+    init_db();
+    let mut repo = LedgerRepository::new();
+
+    let record = |label: &str, time: i64, ancestors: usize| MempoolRecord {
+        tx: Transaction::new(
+            vec![],
+            vec![TxOutput::new(100, "mempool_address".to_string())],
+            Some(label.to_string()),
+        ),
+        time,
+        ancestors,
+    };
+    let child = record("child", 1, 1);
+    let parent = record("parent", 2, 0);
+    let later = record("later", 3, 0);
+    for entry in [&child, &parent, &later] {
+        repo.save_mempool_entry(entry).unwrap();
+    }
+    // Mempool entries stay out of the confirmed transaction index.
+    assert!(repo.get_transaction(&parent.tx.id()).unwrap().is_none());
+
+    let ids: Vec<_> = repo
+        .get_mempool_entries()
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.unwrap().tx.id())
+        .collect();
+    assert_eq!(ids, vec![parent.tx.id(), later.tx.id(), child.tx.id()]);
+
+    repo.delete_mempool_entries(&[parent.tx.id()]).unwrap();
+    assert_eq!(repo.get_mempool_entries().unwrap().len(), 2);
+    repo.clear_mempool_entries().unwrap();
+    assert!(repo.get_mempool_entries().unwrap().is_empty());
+}
+
+#[test]
+fn test_unreadable_mempool_entries_are_returned_apart() {
+    reset_test_db();
+    init_db();
+    let repo = LedgerRepository::new();
+
+    let record = |label: &str| MempoolRecord {
+        tx: Transaction::new(
+            vec![],
+            vec![TxOutput::new(100, "mempool_address".to_string())],
+            Some(label.to_string()),
+        ),
+        time: 1,
+        ancestors: 0,
+    };
+    let readable = record("readable");
+    let corrupt = record("corrupt");
+    repo.save_mempool_entry(&readable).unwrap();
+    repo.save_mempool_entry(&corrupt).unwrap();
+    get_db()
+        .get_conn()
+        .execute(
+            "UPDATE mempool_entries SET raw = x'00' WHERE txid = ?1",
+            [corrupt.tx.id().as_slice()],
+        )
+        .unwrap();
+
+    let entries = repo.get_mempool_entries().unwrap();
+    assert_eq!(entries.len(), 2);
+    let ids: Vec<_> = entries
+        .iter()
+        .map(|entry| match entry {
+            Ok(record) => record.tx.id(),
+            Err((txid, _)) => *txid,
+        })
+        .collect();
+    assert!(ids.contains(&readable.tx.id()) && ids.contains(&corrupt.tx.id()));
+    assert!(entries.iter().any(|entry| entry.is_err()));
+    assert!(
+        entries
+            .iter()
+            .filter_map(|entry| entry.as_ref().ok())
+            .all(|record| record.tx.id() == readable.tx.id())
+    );
+}
+
+/// Creates a database at `path` as written before witness ids, storing
+/// `raw` as the encoding of `tx`.
+fn create_legacy_db(path: &str, tx: &Transaction, raw: Vec<u8>) {
+    remove_db_files(path);
+    let conn = rusqlite::Connection::open(path).unwrap();
+    conn.execute(
+        "CREATE TABLE transactions (
+            txid         BLOB PRIMARY KEY,
+            raw          BLOB NOT NULL,
+            block_hash   BLOB,
+            block_height INTEGER,
+            timestamp    INTEGER
+        )",
+        [],
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO transactions (txid, raw) VALUES (?1, ?2)",
+        rusqlite::params![tx.id().as_slice(), raw],
+    )
+    .unwrap();
+}
+
+/// Opens the database at `path`, twice to check migrations run only once,
+/// and expects `tx` stored in its binary encoding with its witness id.
+fn expect_migrated(path: &str, tx: &Transaction) {
+    for _ in 0..2 {
+        let conn = Db::open(Some(path)).unwrap().get_conn();
+        let (raw, wtxid): (Vec<u8>, Vec<u8>) = conn
+            .query_row(
+                "SELECT raw, wtxid FROM transactions WHERE txid = ?1",
+                [tx.id().as_slice()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(raw, tx.as_bytes());
+        assert_eq!(wtxid, tx.wtxid().to_vec());
+        let version: i64 = conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+    }
+    remove_db_files(path);
+}
+
+fn migrated_tx() -> Transaction {
+    Transaction::new(
+        vec![],
+        vec![TxOutput::new(100, "migrated_address".to_string())],
+        Some("migration-test".to_string()),
+    )
+}
+
+#[test]
+fn test_databases_without_wtxids_are_migrated() {
+    let path = format!("{}-without-wtxids", CONFIG.db_path);
+    let tx = migrated_tx();
+    create_legacy_db(&path, &tx, tx.as_bytes());
+    expect_migrated(&path, &tx);
+}
+
+#[test]
+fn test_transactions_stored_as_json_are_re_encoded() {
+    let path = format!("{}-json-transactions", CONFIG.db_path);
+    let tx = migrated_tx();
+    create_legacy_db(&path, &tx, serde_json::to_vec(&tx).unwrap());
+    expect_migrated(&path, &tx);
+}
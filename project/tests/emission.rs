@@ -0,0 +1,55 @@
+use primitive_types::U256;
+
+use project::globals::ConsensusRules;
+use project::model::checkpoints::Checkpoints;
+
+fn rules(initial_block_reward: i64, halving_interval: u64) -> ConsensusRules {
+    ConsensusRules {
+        initial_target: U256::MAX,
+        max_block_size_kb: 10.0,
+        initial_block_reward,
+        halving_interval,
+        coinbase_maturity: 100,
+        median_time_span: 11,
+        max_future_block_time_secs: 7200,
+        lwma_n: 10,
+        target_block_time_secs: 10,
+        version_bits_window: 2016,
+        version_bits_threshold: 1916,
+        deployments: Vec::new(),
+        checkpoints: Checkpoints::default(),
+    }
+}
+
+#[test]
+fn subsidy_halves_every_interval() {
+    let rules = rules(1_000, 10);
+
+    assert_eq!(rules.block_subsidy(0), 1_000);
+    assert_eq!(rules.block_subsidy(9), 1_000);
+    assert_eq!(rules.block_subsidy(10), 500);
+    assert_eq!(rules.block_subsidy(25), 250);
+}
+
+#[test]
+fn subsidy_terminates_at_zero() {
+    let rules = rules(1_000, 10);
+
+    // 1000 >> 10 == 0, so the tenth era pays nothing.
+    assert_eq!(rules.block_subsidy(99), 1);
+    assert_eq!(rules.block_subsidy(100), 0);
+    assert_eq!(rules.block_subsidy(u64::MAX), 0);
+}
+
+#[test]
+fn total_supply_sums_subsidies_up_to_height() {
+    let rules = rules(1_000, 10);
+
+    assert_eq!(rules.total_supply_at(0), 1_000);
+    assert_eq!(rules.total_supply_at(9), 10_000);
+    assert_eq!(rules.total_supply_at(14), 12_500);
+
+    let brute_force: i64 = (0..200).map(|h| rules.block_subsidy(h)).sum();
+    assert_eq!(rules.max_supply(), brute_force);
+    assert_eq!(rules.total_supply_at(199), rules.max_supply());
+}
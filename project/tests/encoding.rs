@@ -0,0 +1,133 @@
+use chrono::NaiveDate;
+use primitive_types::U256;
+
+use project::model::{Block, Transaction, TxInput, TxOutput, block::BlockHeader};
+use project::utils::ENCODING_VERSION;
+
+fn sample_tx(message: Option<&str>) -> Transaction {
+    Transaction {
+        inputs: vec![TxInput {
+            relative_lock: 5,
+            signature: "aa".repeat(64),
+            public_key: "bb".repeat(32),
+            ..TxInput::new([7; 32], 3)
+        }],
+        outputs: vec![
+            TxOutput::new(1_500, "addr-one".to_string()),
+            TxOutput::new(42, "addr-two".to_string()),
+        ],
+        date: NaiveDate::from_ymd_opt(2026, 1, 1)
+            .unwrap()
+            .and_hms_nano_opt(12, 30, 15, 123_456_789)
+            .unwrap(),
+        message: message.map(str::to_string),
+        lock_time: 900,
+    }
+}
+
+fn sample_block() -> Block {
+    let mut block = Block {
+        header: BlockHeader {
+            version: BlockHeader::INITIAL_VERSION,
+            prev_block_hash: [1; 32],
+            merkle_root: [0; 32],
+            nonce: 99,
+            timestamp: NaiveDate::from_ymd_opt(2026, 1, 2)
+                .unwrap()
+                .and_hms_opt(8, 0, 0)
+                .unwrap(),
+            target: U256::MAX >> 19u32,
+        },
+        transactions: vec![
+            Transaction::new_coinbase("miner".to_string(), 0, 0),
+            sample_tx(Some("hello")),
+        ],
+    };
+    block.evaluate_merkle_root();
+    block
+}
+
+#[test]
+fn transaction_round_trips() {
+    let tx = sample_tx(Some("hello"));
+    let bytes = tx.as_bytes();
+
+    assert_eq!(bytes[0], ENCODING_VERSION);
+    let decoded = Transaction::from_bytes(&bytes).unwrap();
+    assert_eq!(decoded.as_bytes(), bytes);
+    assert_eq!(decoded.id(), tx.id());
+    assert_eq!(decoded.date, tx.date);
+    assert_eq!(decoded.message, tx.message);
+    assert_eq!(decoded.lock_time, 900);
+}
+
+#[test]
+fn inputs_and_outputs_round_trip() {
+    let tx = sample_tx(None);
+
+    let input = TxInput::from_bytes(&tx.inputs[0].as_bytes()).unwrap();
+    assert_eq!(input.prev_tx_id, tx.inputs[0].prev_tx_id);
+    assert_eq!(input.output_index, 3);
+    assert_eq!(input.relative_lock, 5);
+    assert_eq!(input.signature, tx.inputs[0].signature);
+
+    let output = TxOutput::from_bytes(&TxOutput::as_bytes(&tx.outputs[0])).unwrap();
+    assert_eq!(output, tx.outputs[0]);
+}
+
+#[test]
+fn block_and_header_round_trip() {
+    let block = sample_block();
+
+    let header = BlockHeader::from_bytes(&block.header.as_bytes()).unwrap();
+    assert_eq!(header.as_bytes(), block.header_bytes());
+
+    let decoded = Block::from_bytes(&block.as_bytes()).unwrap();
+    assert_eq!(decoded.id(), block.id());
+    assert_eq!(decoded.transactions.len(), 2);
+    assert_eq!(
+        Block::eval_merkle_root_from_transactions(&decoded.transactions),
+        block.header.merkle_root
+    );
+}
+
+#[test]
+fn fields_are_length_prefixed() {
+    // Without length prefixes both transactions would concatenate to the same bytes.
+    let mut left = sample_tx(Some("bc"));
+    left.outputs[1].address = "a".to_string();
+    let mut right = sample_tx(Some("c"));
+    right.outputs[1].address = "ab".to_string();
+
+    assert_ne!(left.as_bytes(), right.as_bytes());
+    assert_ne!(sample_tx(None).id(), sample_tx(Some("")).id());
+}
+
+#[test]
+fn rejects_malformed_encodings() {
+    let bytes = sample_tx(Some("hello")).as_bytes();
+
+    let mut wrong_version = bytes.clone();
+    wrong_version[0] = ENCODING_VERSION + 1;
+    assert!(Transaction::from_bytes(&wrong_version).is_err());
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(Transaction::from_bytes(&trailing).is_err());
+
+    assert!(Transaction::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+    let mut huge_count = vec![ENCODING_VERSION];
+    huge_count.extend_from_slice(&u32::MAX.to_be_bytes());
+    assert!(Transaction::from_bytes(&huge_count).is_err());
+}
+
+#[test]
+fn block_size_is_serialized_length() {
+    let block = sample_block();
+    let tx_sizes: usize = block.transactions.iter().map(|tx| tx.size()).sum();
+
+    assert_eq!(block.size(), block.as_bytes().len());
+    assert_eq!(block.size(), Block::size_with_transactions(tx_sizes));
+    assert_eq!(block.header.as_bytes().len(), BlockHeader::ENCODED_SIZE);
+}
@@ -0,0 +1,95 @@
+use project::model::fee_estimator::{
+    ESTIMATE_GROUP_SIZE, FeeEstimator, HISTORY_BLOCKS, MAX_CONFIRM_TARGET,
+};
+
+fn id(n: usize) -> [u8; 32] {
+    let mut id = [0; 32];
+    id[..8].copy_from_slice(&(n as u64).to_le_bytes());
+    id
+}
+
+/// Tracks `count` transactions paying `fee_rate` from `height`, numbered from `first`.
+fn track_many(
+    estimator: &mut FeeEstimator,
+    first: usize,
+    count: usize,
+    fee_rate: f64,
+    height: u64,
+) {
+    for n in first..first + count {
+        estimator.track(id(n), fee_rate, height);
+    }
+}
+
+fn ids(first: usize, count: usize) -> Vec<[u8; 32]> {
+    (first..first + count).map(id).collect()
+}
+
+#[test]
+fn no_estimate_without_history() {
+    let mut estimator = FeeEstimator::new();
+    assert_eq!(estimator.estimate_fee(1), None);
+
+    track_many(&mut estimator, 0, ESTIMATE_GROUP_SIZE - 1, 5.0, 10);
+    estimator.process_block(10, &ids(0, ESTIMATE_GROUP_SIZE - 1));
+    assert_eq!(estimator.estimate_fee(1), None);
+}
+
+#[test]
+fn higher_fee_rates_are_needed_for_faster_confirmation() {
+    let mut estimator = FeeEstimator::new();
+    let group = ESTIMATE_GROUP_SIZE;
+    // Rich transactions confirm in the next block, cheap ones wait five.
+    track_many(&mut estimator, 0, group, 10.0, 10);
+    track_many(&mut estimator, group, group, 2.0, 10);
+    estimator.process_block(10, &ids(0, group));
+    for height in 11..14 {
+        estimator.process_block(height, &[]);
+    }
+    estimator.process_block(14, &ids(group, group));
+
+    assert_eq!(estimator.estimate_fee(1), Some(10.0));
+    assert_eq!(estimator.estimate_fee(5), Some(2.0));
+    assert_eq!(estimator.estimate_fee(0), estimator.estimate_fee(1));
+}
+
+#[test]
+fn transactions_never_confirmed_count_against_their_fee_rate() {
+    let mut estimator = FeeEstimator::new();
+    let group = ESTIMATE_GROUP_SIZE;
+    track_many(&mut estimator, 0, group, 10.0, 1);
+    track_many(&mut estimator, group, group, 1.0, 1);
+    estimator.process_block(1, &ids(0, group));
+    for height in 2..=MAX_CONFIRM_TARGET + 1 {
+        estimator.process_block(height, &[]);
+    }
+
+    assert_eq!(estimator.estimate_fee(MAX_CONFIRM_TARGET), Some(10.0));
+}
+
+#[test]
+fn old_samples_are_forgotten() {
+    let mut estimator = FeeEstimator::new();
+    track_many(&mut estimator, 0, ESTIMATE_GROUP_SIZE, 3.0, 1);
+    estimator.process_block(1, &ids(0, ESTIMATE_GROUP_SIZE));
+    assert_eq!(estimator.estimate_fee(1), Some(3.0));
+
+    estimator.process_block(1 + HISTORY_BLOCKS, &[]);
+    assert_eq!(estimator.estimate_fee(1), None);
+}
+
+#[test]
+fn transactions_leaving_the_mempool_unmined_are_not_failures() {
+    let mut estimator = FeeEstimator::new();
+    let group = ESTIMATE_GROUP_SIZE;
+    track_many(&mut estimator, 0, group, 5.0, 1);
+    // Replaced or evicted before they could be mined.
+    track_many(&mut estimator, group, group, 20.0, 1);
+    estimator.untrack(&ids(group, group));
+    estimator.process_block(1, &ids(0, group));
+    for height in 2..=MAX_CONFIRM_TARGET + 1 {
+        estimator.process_block(height, &[]);
+    }
+
+    assert_eq!(estimator.estimate_fee(1), Some(5.0));
+}
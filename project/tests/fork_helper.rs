@@ -1,8 +1,11 @@
 use chrono::NaiveDate;
 use primitive_types::U256;
 
+use project::model::checkpoints::Checkpoints;
+use project::model::utxo_view::LedgerView;
 use project::model::{Block, Blockchain, Transaction, block::BlockHeader};
-use project::utils::{ForkHelper, ForkUpdateStatus};
+use project::security_utils::hash_meets_target;
+use project::utils::{ForkHelper, ForkUpdateStatus, target_work};
 
 fn test_block(prev_block_hash: [u8; 32], nonce: u32) -> Block {
     let timestamp = NaiveDate::from_ymd_opt(2026, 1, 1)
@@ -12,18 +15,28 @@ fn test_block(prev_block_hash: [u8; 32], nonce: u32) -> Block {
 
     let mut block = Block {
         header: BlockHeader {
+            version: BlockHeader::INITIAL_VERSION,
             prev_block_hash,
             merkle_root: [0; 32],
             nonce,
             timestamp,
             target: U256::MAX,
         },
-        transactions: vec![Transaction::new_coinbase(format!("miner-{}", nonce), 0)],
+        transactions: vec![Transaction::new_coinbase(format!("miner-{}", nonce), 0, 0)],
     };
     block.evaluate_merkle_root();
     block
 }
 
+fn mined_block(prev_block_hash: [u8; 32], seconds: u32, target: U256) -> Block {
+    let mut block = test_block(prev_block_hash, seconds);
+    block.header.target = target;
+    while !hash_meets_target(&block.header_hash(), &block.header.target) {
+        block.header.nonce += 1;
+    }
+    block
+}
+
 fn blockchain_with(chain: Vec<Block>) -> Blockchain {
     Blockchain { chain }
 }
@@ -98,6 +111,33 @@ fn selects_longer_branch_for_reorg_without_local_tie() {
     );
 }
 
+#[test]
+fn selects_shorter_branch_with_more_work() {
+    let genesis = test_block([0; 32], 1);
+    let local_second = test_block(genesis.id(), 2);
+    let local_third = test_block(local_second.id(), 3);
+    let heavy = mined_block(genesis.id(), 4, U256::MAX >> 2);
+    let blockchain = blockchain_with(vec![genesis.clone(), local_second, local_third]);
+    let mut helper = ForkHelper::with_capacity_limit(1000);
+
+    assert_eq!(heavy.work(), target_work(U256::MAX >> 2));
+    assert!(heavy.work() > U256::from(2u32));
+
+    let update = helper.observe_block(&blockchain, heavy.clone(), None);
+
+    let candidate = update.best_reorg.expect("heavier fork should win");
+    assert_eq!(candidate.candidate_height, 2);
+    assert!(candidate.candidate_chainwork > blockchain.chainwork());
+    assert_eq!(
+        candidate
+            .blocks
+            .iter()
+            .map(|block| block.id())
+            .collect::<Vec<_>>(),
+        vec![heavy.id()]
+    );
+}
+
 #[test]
 fn does_not_reorg_when_candidate_ties_local_height() {
     let genesis = test_block([0; 32], 1);
@@ -126,3 +166,63 @@ fn prunes_oldest_blocks_when_capacity_is_exceeded() {
 
     assert!(!helper.contains_block(&first.id()));
 }
+
+#[test]
+fn rejects_block_not_after_median_time_past() {
+    let genesis = test_block([0; 32], 5);
+    let stale = test_block(genesis.id(), 5);
+    let blockchain = blockchain_with(vec![genesis]);
+    let mut helper = ForkHelper::with_capacity_limit(1000);
+
+    let update = helper.observe_block(&blockchain, stale, None);
+
+    match update.status {
+        ForkUpdateStatus::Invalid(reason) => assert!(reason.contains("median time past")),
+        other => panic!("expected invalid block, got {:?}", other),
+    }
+}
+
+#[test]
+fn rejects_block_too_far_in_the_future() {
+    let genesis = test_block([0; 32], 1);
+    let mut future = test_block(genesis.id(), 2);
+    future.header.timestamp = NaiveDate::from_ymd_opt(2100, 1, 1)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    let blockchain = blockchain_with(vec![genesis]);
+    let mut helper = ForkHelper::with_capacity_limit(1000);
+
+    let update = helper.observe_block(&blockchain, future, None);
+
+    match update.status {
+        ForkUpdateStatus::Invalid(reason) => assert!(reason.contains("too far in the future")),
+        other => panic!("expected invalid block, got {:?}", other),
+    }
+}
+
+#[test]
+fn branch_blocks_are_checked_against_the_chain_below_the_fork() {
+    let genesis = test_block([0; 32], 1);
+    let main_1 = test_block(genesis.id(), 2);
+    let main_2 = test_block(main_1.id(), 3);
+    let branch_1 = test_block(genesis.id(), 30);
+    let branch_2 = test_block(branch_1.id(), 50);
+    let blockchain = blockchain_with(vec![genesis, main_1, main_2.clone()]);
+    let branch = [branch_1, branch_2];
+    let checkpoints = Checkpoints::default();
+    let check = |block: &Block| {
+        blockchain.check_branch_block(1, &branch, block, &[], &checkpoints, &LedgerView)
+    };
+
+    assert_eq!(check(&test_block(branch[1].id(), 59)), Ok(()));
+    // Timestamps are bounded by the branch's median time past, not the main chain's.
+    let err = check(&test_block(branch[1].id(), 20)).unwrap_err();
+    assert!(err.contains("median time past"), "{}", err);
+    let err = check(&test_block(main_2.id(), 59)).unwrap_err();
+    assert!(
+        err.contains("Previous block hash does not match"),
+        "{}",
+        err
+    );
+}
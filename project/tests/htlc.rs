@@ -0,0 +1,140 @@
+use project::model::htlc::{HtlcParams, preimage_from_claim};
+use project::model::script::{
+    ScriptType, TransactionSignatureChecker, decode_num, encode_num, hash160, verify_script,
+};
+use project::model::transaction::LOCKTIME_THRESHOLD;
+use project::model::{HDKey, Script};
+use project::security_utils::sha256;
+
+const MESSAGE: &[u8] = b"htlc spend";
+const SECRET: &[u8] = b"swap secret";
+const TIMEOUT: u64 = 1_000;
+
+fn recipient() -> HDKey {
+    HDKey::new(b"htlc recipient")
+}
+
+fn sender() -> HDKey {
+    HDKey::new(b"htlc sender")
+}
+
+fn params() -> HtlcParams {
+    HtlcParams {
+        hash: sha256(SECRET),
+        recipient_pubkey_hash: hash160(recipient().get_public_key().as_bytes()),
+        sender_pubkey_hash: hash160(sender().get_public_key().as_bytes()),
+        timeout: TIMEOUT,
+    }
+}
+
+fn claim(key: &HDKey, preimage: &[u8]) -> Script {
+    let mut script = Script::new();
+    script.push_data(&key.sign(MESSAGE).to_bytes());
+    script.push_data(key.get_public_key().as_bytes());
+    script.push_data(preimage);
+    script.push_int(1);
+    script
+}
+
+fn refund(key: &HDKey) -> Script {
+    let mut script = Script::new();
+    script.push_data(&key.sign(MESSAGE).to_bytes());
+    script.push_data(key.get_public_key().as_bytes());
+    script.push_int(0);
+    script
+}
+
+#[test]
+fn htlc_script_round_trips_its_params() {
+    let script = Script::new_htlc(&params());
+
+    assert_eq!(script.classify(), ScriptType::Htlc);
+    assert_eq!(script.htlc_params(), Some(params()));
+    assert_eq!(script.address(), None);
+
+    let small_timeout = HtlcParams {
+        timeout: 7,
+        ..params()
+    };
+    assert_eq!(
+        Script::new_htlc(&small_timeout).htlc_params(),
+        Some(small_timeout)
+    );
+}
+
+#[test]
+fn recipient_claims_with_preimage() {
+    let locking = Script::new_htlc(&params());
+    let checker = TransactionSignatureChecker::new(MESSAGE);
+
+    assert!(verify_script(&claim(&recipient(), SECRET), &locking, &checker).is_ok());
+    assert!(verify_script(&claim(&recipient(), b"wrong"), &locking, &checker).is_err());
+    assert!(verify_script(&claim(&sender(), SECRET), &locking, &checker).is_err());
+}
+
+#[test]
+fn sender_refunds_after_timeout() {
+    let locking = Script::new_htlc(&params());
+    let early = TransactionSignatureChecker::new(MESSAGE).with_lock_time(TIMEOUT - 1);
+    let due = TransactionSignatureChecker::new(MESSAGE).with_lock_time(TIMEOUT);
+    let time_locked =
+        TransactionSignatureChecker::new(MESSAGE).with_lock_time(LOCKTIME_THRESHOLD + TIMEOUT);
+
+    assert!(verify_script(&refund(&sender()), &locking, &early).is_err());
+    assert!(verify_script(&refund(&sender()), &locking, &due).is_ok());
+    // A timestamp lock time never satisfies a height timeout.
+    assert!(verify_script(&refund(&sender()), &locking, &time_locked).is_err());
+    assert!(verify_script(&refund(&recipient()), &locking, &due).is_err());
+}
+
+#[test]
+fn preimage_is_read_from_claims_only() {
+    let params = params();
+
+    assert_eq!(
+        preimage_from_claim(&claim(&recipient(), SECRET), &params),
+        Some(SECRET.to_vec())
+    );
+    assert_eq!(preimage_from_claim(&refund(&sender()), &params), None);
+    assert_eq!(
+        preimage_from_claim(&claim(&recipient(), b"wrong"), &params),
+        None
+    );
+}
+
+#[test]
+fn script_numbers_round_trip() {
+    for value in [0, 1, 127, 128, 255, 256, 500_000_000, -1, -128] {
+        assert_eq!(decode_num(&encode_num(value), 8), Ok(value));
+    }
+    assert_eq!(encode_num(128), vec![0x80, 0x00]);
+    assert!(decode_num(&[1, 2, 3, 4, 5, 6], 5).is_err());
+}
+
+#[test]
+fn unbalanced_conditionals_are_rejected() {
+    let checker = TransactionSignatureChecker::new(MESSAGE);
+    let mut unlocking = Script::new();
+    unlocking.push_int(1);
+
+    // OP_IF OP_1 (missing OP_ENDIF)
+    assert!(verify_script(&unlocking, &Script::from_bytes(vec![0x63, 0x51]), &checker).is_err());
+    // OP_IF OP_1 OP_ENDIF
+    assert!(
+        verify_script(
+            &unlocking,
+            &Script::from_bytes(vec![0x63, 0x51, 0x68]),
+            &checker
+        )
+        .is_ok()
+    );
+    // OP_1 OP_ENDIF
+    assert!(
+        verify_script(
+            &Script::new(),
+            &Script::from_bytes(vec![0x51, 0x68]),
+            &checker
+        )
+        .is_err()
+    );
+}
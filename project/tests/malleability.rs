@@ -0,0 +1,61 @@
+use primitive_types::U256;
+
+use project::model::{Block, HDKey, Transaction, TxInput, TxOutput, block::BlockHeader};
+
+fn signed_tx() -> Transaction {
+    let key = HDKey::new(b"malleability key");
+    let input = TxInput::new([5; 32], 0);
+    let mut tx = Transaction::new(
+        vec![input],
+        vec![TxOutput::new(10, key.get_address())],
+        None,
+    );
+    let signature = key.sign(&tx.signing_bytes());
+    tx.inputs[0].signature = hex::encode(signature.to_bytes());
+    tx.inputs[0].public_key = hex::encode(key.get_public_key().as_bytes());
+    tx
+}
+
+#[test]
+fn txid_ignores_witness_data() {
+    let tx = signed_tx();
+    let mut relayed = tx.clone();
+    relayed.inputs[0].signature = relayed.inputs[0].signature.to_uppercase();
+
+    assert_eq!(tx.id(), relayed.id());
+    assert_ne!(tx.wtxid(), relayed.wtxid());
+
+    let mut unlocked = tx.clone();
+    unlocked.inputs[0].unlock_script.push_data(b"extra");
+    assert_eq!(tx.id(), unlocked.id());
+    assert_ne!(tx.wtxid(), unlocked.wtxid());
+
+    let mut changed = tx.clone();
+    changed.outputs[0].value = 11;
+    assert_ne!(tx.id(), changed.id());
+}
+
+#[test]
+fn merkle_root_commits_to_witness_data() {
+    let tx = signed_tx();
+    let mut block = Block {
+        header: BlockHeader {
+            version: BlockHeader::INITIAL_VERSION,
+            prev_block_hash: [0; 32],
+            merkle_root: [0; 32],
+            nonce: 0,
+            timestamp: tx.date,
+            target: U256::MAX,
+        },
+        transactions: vec![Transaction::new_coinbase("miner".to_string(), 0, 0), tx],
+    };
+    block.evaluate_merkle_root();
+    let root = block.header.merkle_root;
+
+    block.transactions[1].inputs[0].signature =
+        block.transactions[1].inputs[0].signature.to_uppercase();
+    assert_ne!(
+        Block::eval_merkle_root_from_transactions(&block.transactions),
+        root
+    );
+}
@@ -0,0 +1,122 @@
+use project::model::mempool::{
+    INCREMENTAL_RELAY_FEE_RATE, Mempool, MempoolLimits, ROLLING_FEE_HALFLIFE_SECS,
+};
+use project::model::{MempoolTx, Transaction, TxInput, TxOutput, UTXO};
+
+const SPENT_VALUE: i64 = 1_000;
+
+/// Spends output 0 of `prev_tx_id`, worth `SPENT_VALUE`, paying `fee`.
+fn spend(prev_tx_id: [u8; 32], fee: i64) -> MempoolTx {
+    let input = TxInput::new(prev_tx_id, 0);
+    let tx = Transaction::new(
+        vec![input],
+        vec![TxOutput::new(SPENT_VALUE - fee, "addr".to_string())],
+        None,
+    );
+    let utxo = UTXO {
+        tx_id: prev_tx_id,
+        index: 0,
+        output: TxOutput::new(SPENT_VALUE, "addr".to_string()),
+    };
+    MempoolTx::new(tx, vec![utxo])
+}
+
+/// A mempool with room for `count` transactions like those built by `spend`.
+fn mempool_for(count: usize) -> Mempool {
+    Mempool::new(MempoolLimits {
+        max_size: count * spend([0; 32], 0).tx.size(),
+        expiry_secs: 1_000,
+        min_relay_fee_rate: 0.0,
+    })
+}
+
+#[test]
+fn lowest_fee_rate_transactions_are_evicted() {
+    let mut mempool = mempool_for(2);
+    let cheap = spend([1; 32], 10);
+    let rich = spend([2; 32], 50);
+    let middle = spend([3; 32], 30);
+    for mem_tx in [&cheap, &rich, &middle] {
+        mempool.insert(mem_tx.clone(), 0);
+    }
+
+    assert_eq!(mempool.trim_to_size(0), vec![cheap.id()]);
+    assert_eq!(mempool.len(), 2);
+    assert!(mempool.total_size() <= mempool.limits().max_size);
+    assert!(
+        mempool.min_fee_rate(0)
+            > cheap.calculate_fee_per_byte() + INCREMENTAL_RELAY_FEE_RATE - 0.001
+    );
+    assert!(mempool.find_conflicts(&spend([1; 32], 20).tx).is_empty());
+}
+
+#[test]
+fn children_keep_their_parents_in_the_mempool() {
+    let mut mempool = mempool_for(2);
+    let parent = spend([1; 32], 5);
+    let child = spend(parent.id(), 100);
+    let unrelated = spend([2; 32], 30);
+    for mem_tx in [&parent, &child, &unrelated] {
+        mempool.insert(mem_tx.clone(), 0);
+    }
+
+    assert_eq!(mempool.trim_to_size(0), vec![unrelated.id()]);
+    let entry = mempool.get_entry(&parent.id()).unwrap();
+    assert_eq!(entry.descendant_fee, 105);
+
+    // Evicting a parent takes its descendants with it.
+    let mut full = mempool_for(1);
+    full.insert(parent.clone(), 0);
+    full.insert(child.clone(), 0);
+    assert_eq!(full.trim_to_size(0).len(), 2);
+    assert!(full.is_empty());
+}
+
+#[test]
+fn minimum_fee_rate_decays_after_eviction() {
+    let mut mempool = mempool_for(1);
+    mempool.insert(spend([1; 32], 400), 0);
+    mempool.insert(spend([2; 32], 500), 0);
+    mempool.trim_to_size(0);
+
+    let raised = mempool.min_fee_rate(0);
+    let halved = mempool.min_fee_rate(ROLLING_FEE_HALFLIFE_SECS);
+    assert!((halved - raised / 2.0).abs() < 0.001);
+    assert_eq!(mempool.min_fee_rate(100 * ROLLING_FEE_HALFLIFE_SECS), 0.0);
+}
+
+#[test]
+fn expired_transactions_leave_with_their_descendants() {
+    let mut mempool = mempool_for(10);
+    let old = spend([1; 32], 10);
+    let child = spend(old.id(), 10);
+    let recent = spend([2; 32], 10);
+    mempool.insert(old.clone(), 0);
+    mempool.insert(child.clone(), 900);
+    mempool.insert(recent.clone(), 900);
+
+    let mut expired = mempool.expire(1_500);
+    expired.sort();
+    let mut expected = vec![old.id(), child.id()];
+    expected.sort();
+    assert_eq!(expired, expected);
+    assert_eq!(mempool.to_vec().len(), 1);
+    assert!(mempool.contains(&recent.id()));
+    assert_eq!(mempool.total_size(), recent.tx.size());
+}
+
+#[test]
+fn removing_a_parent_updates_the_indexes() {
+    let mut mempool = mempool_for(10);
+    let parent = spend([1; 32], 10);
+    let child = spend(parent.id(), 20);
+    mempool.insert(parent.clone(), 0);
+    mempool.insert(child.clone(), 0);
+    assert_eq!(mempool.get_entry(&parent.id()).unwrap().descendant_fee, 30);
+    assert!(mempool.get_unspent_output(&parent.id(), 0).is_none());
+
+    mempool.remove(&[child.id()]);
+    assert_eq!(mempool.get_entry(&parent.id()).unwrap().descendant_fee, 10);
+    assert!(mempool.get_unspent_output(&parent.id(), 0).is_some());
+    assert_eq!(mempool.spent_outpoints().len(), 1);
+}
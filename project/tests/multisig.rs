@@ -0,0 +1,184 @@
+use primitive_types::U256;
+
+use project::db::db::init_db;
+use project::db::repository::LedgerRepository;
+use project::model::block::BlockHeader;
+use project::model::hdkey::AddressKind;
+use project::model::io::SIGHASH_ALL;
+use project::model::multisig::{MultisigAddress, PartialInput, PartialTransaction};
+use project::model::script::{ScriptType, TransactionSignatureChecker, verify_script};
+use project::model::{Block, HDKey, Script, Transaction, TxInput, TxOutput, UTXO};
+
+fn keys() -> Vec<HDKey> {
+    (0..3u8).map(|i| HDKey::new(&[i; 32])).collect()
+}
+
+fn two_of_three() -> MultisigAddress {
+    let public_keys: Vec<_> = keys().iter().map(|k| k.get_public_key()).collect();
+    MultisigAddress::new(2, &public_keys).unwrap()
+}
+
+fn pending_spend(multisig: &MultisigAddress) -> PartialTransaction {
+    let utxo = UTXO {
+        tx_id: [9; 32],
+        index: 0,
+        output: TxOutput::new(100, multisig.address.clone()),
+    };
+    let input = TxInput::new(utxo.tx_id, utxo.index);
+    PartialTransaction {
+        tx: Transaction::new(
+            vec![input],
+            vec![TxOutput::new(90, keys()[0].get_address())],
+            None,
+        ),
+        utxos: vec![utxo],
+        inputs: vec![PartialInput {
+            redeem_script: multisig.redeem_script.clone(),
+            signatures: vec![None; 3],
+        }],
+    }
+}
+
+fn verify_finalized(pending: &PartialTransaction) -> Result<(), String> {
+    let mempool_tx = pending.finalize()?;
+    let signing_bytes = mempool_tx.tx.signing_bytes();
+    let checker = TransactionSignatureChecker::new(&signing_bytes);
+    let locking = mempool_tx.utxos[0].output.locking_script()?;
+    verify_script(&mempool_tx.tx.inputs[0].unlock_script, &locking, &checker)
+}
+
+#[test]
+fn multisig_address_is_a_script_hash_address() {
+    let multisig = two_of_three();
+
+    assert_eq!(multisig.redeem_script.classify(), ScriptType::Multisig);
+    assert_eq!(multisig.required, 2);
+    assert_eq!(multisig.public_keys.len(), 3);
+    assert!(HDKey::validate_address(&multisig.address));
+    assert_eq!(
+        HDKey::decode_address(&multisig.address).map(|(kind, _)| kind),
+        Some(AddressKind::ScriptHash)
+    );
+    let locking = Script::for_address(&multisig.address).unwrap();
+    assert_eq!(locking.classify(), ScriptType::ScriptHash);
+    assert_eq!(locking.address(), Some(multisig.address.clone()));
+    assert!(HDKey::pubkey_hash_from_address(&multisig.address).is_none());
+}
+
+#[test]
+fn rejects_invalid_thresholds() {
+    let public_keys: Vec<_> = keys().iter().map(|k| k.get_public_key()).collect();
+
+    assert!(MultisigAddress::new(0, &public_keys).is_err());
+    assert!(MultisigAddress::new(4, &public_keys).is_err());
+    assert!(MultisigAddress::new(1, &[]).is_err());
+    assert!(MultisigAddress::from_redeem_script(Script::new_data(b"x")).is_err());
+}
+
+#[test]
+fn spend_completes_with_threshold_signatures() {
+    let keys = keys();
+    let mut pending = pending_spend(&two_of_three());
+
+    assert_eq!(pending.missing_signatures(), 2);
+    assert_eq!(pending.sign_with(&keys[2]).unwrap(), 1);
+    assert_eq!(pending.sign_with(&keys[2]).unwrap(), 0);
+    assert!(!pending.is_complete());
+    assert!(pending.finalize().is_err());
+
+    assert_eq!(pending.sign_with(&keys[0]).unwrap(), 1);
+    assert!(pending.is_complete());
+    assert!(verify_finalized(&pending).is_ok());
+}
+
+#[test]
+fn foreign_keys_and_bad_signatures_are_rejected() {
+    let keys = keys();
+    let outsider = HDKey::new(b"not a signer");
+    let mut pending = pending_spend(&two_of_three());
+
+    assert_eq!(pending.sign_with(&outsider).unwrap(), 0);
+
+    let signature = keys[1].sign(b"some other transaction").to_bytes();
+    let public_key = keys[1].get_public_key().to_bytes();
+    assert!(pending.add_signature(0, &public_key, &signature).is_err());
+
+    let signature = keys[1].sign(&pending.tx.signing_bytes()).to_bytes();
+    assert!(
+        pending
+            .add_signature(0, &outsider.get_public_key().to_bytes(), &signature)
+            .is_err()
+    );
+    assert!(pending.add_signature(0, &public_key, &signature).is_ok());
+    assert_eq!(pending.missing_signatures(), 1);
+}
+
+#[test]
+fn script_requires_signatures_in_key_order() {
+    let keys = keys();
+    let multisig = two_of_three();
+    let pending = pending_spend(&multisig);
+    let signing_bytes = pending.tx.signing_bytes();
+    let checker = TransactionSignatureChecker::new(&signing_bytes);
+    let locking = Script::for_address(&multisig.address).unwrap();
+
+    let unlock = |signers: &[usize]| {
+        let mut script = Script::new();
+        for &i in signers {
+            script.push_data(&keys[i].sign(&signing_bytes).to_bytes());
+        }
+        script.push_data(multisig.redeem_script.as_bytes());
+        script
+    };
+
+    assert!(verify_script(&unlock(&[0, 1]), &locking, &checker).is_ok());
+    assert!(verify_script(&unlock(&[1, 2]), &locking, &checker).is_ok());
+    assert!(verify_script(&unlock(&[1, 0]), &locking, &checker).is_err());
+    assert!(verify_script(&unlock(&[1]), &locking, &checker).is_err());
+    assert!(verify_script(&unlock(&[1, 1]), &locking, &checker).is_err());
+}
+
+#[test]
+fn redeem_script_must_match_the_address() {
+    let multisig = two_of_three();
+    let other: Vec<_> = keys()[..2].iter().map(|k| k.get_public_key()).collect();
+    let mut pending = pending_spend(&multisig);
+    pending.inputs[0].redeem_script = Script::new_multisig(1, &other).unwrap();
+    pending.inputs[0].signatures = vec![None; 2];
+
+    assert!(pending.sign_with(&keys()[0]).is_err());
+}
+
+#[test]
+fn spend_at_a_fee_rate_pays_for_its_signed_size() {
+    init_db();
+    let keys: Vec<HDKey> = (0..3u8).map(|i| HDKey::new(&[50 + i; 32])).collect();
+    let public_keys: Vec<_> = keys.iter().map(|k| k.get_public_key()).collect();
+    let multisig = MultisigAddress::new(2, &public_keys).unwrap();
+    let funding = Transaction::new(
+        Vec::new(),
+        vec![TxOutput::new(10_000, multisig.address.clone())],
+        Some("multisig-fee-rate".to_string()),
+    );
+    let mut block = Block::new([0; 32], U256::MAX, BlockHeader::INITIAL_VERSION);
+    block.transactions = vec![funding];
+    block.evaluate_merkle_root();
+    LedgerRepository::new().apply_block(block).unwrap();
+
+    let outputs = vec![TxOutput::new(1_000, keys[0].get_address())];
+    let mut pending = PartialTransaction::new_multisig_spend_with_fee_rate(
+        &multisig,
+        outputs,
+        2.0,
+        None,
+        SIGHASH_ALL,
+    )
+    .unwrap();
+    let expected_size = pending.finalized_size();
+    pending.sign_with(&keys[0]).unwrap();
+    pending.sign_with(&keys[1]).unwrap();
+
+    let mempool_tx = pending.finalize().unwrap();
+    assert_eq!(mempool_tx.tx.size(), expected_size);
+    assert!(mempool_tx.calculate_fee() as f64 >= 2.0 * expected_size as f64);
+}
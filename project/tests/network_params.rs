@@ -0,0 +1,49 @@
+use project::chain_params::{Network, NetworkParams};
+use project::security_utils::hash_meets_target;
+
+#[test]
+fn genesis_blocks_match_their_hard_coded_hashes() {
+    for network in Network::ALL {
+        let params = NetworkParams::for_network(network);
+        let genesis = params.genesis_block();
+
+        assert_eq!(
+            genesis.header_hash(),
+            params.genesis_hash(),
+            "{:?}",
+            network
+        );
+        assert_eq!(genesis.header.prev_block_hash, [0; 32]);
+        assert!(hash_meets_target(
+            &genesis.header_hash(),
+            &params.initial_target
+        ));
+        assert!(genesis.transactions[0].is_coinbase());
+    }
+}
+
+#[test]
+fn networks_have_distinct_identities() {
+    let params: Vec<_> = Network::ALL
+        .into_iter()
+        .map(NetworkParams::for_network)
+        .collect();
+
+    for (i, a) in params.iter().enumerate() {
+        for b in &params[i + 1..] {
+            assert_ne!(a.magic, b.magic);
+            assert_ne!(a.genesis_hash, b.genesis_hash);
+            assert_ne!(a.default_p2p_port, b.default_p2p_port);
+            assert_ne!(a.data_dir, b.data_dir);
+        }
+    }
+}
+
+#[test]
+fn network_names_round_trip() {
+    for network in Network::ALL {
+        assert_eq!(Network::from_name(network.name()), Some(network));
+    }
+    assert_eq!(Network::from_name(" Regtest "), Some(Network::Regtest));
+    assert_eq!(Network::from_name("signet"), None);
+}
@@ -5,12 +5,20 @@ use primitive_types::U256;
 use tokio::time::timeout;
 
 use project::{
-    db::db::init_db,
-    model::{Block, block::BlockHeader, node::Node},
+    db::{
+        db::{get_db, init_db},
+        repository::{LedgerRepository, MempoolRecord},
+    },
+    model::{
+        Block, Script, Transaction, TxInput, TxOutput, block::BlockHeader,
+        checkpoints::Checkpoints, node::Node,
+    },
     network::{
         NetworkMessage,
         server::{BROADCAST_CHANNEL, Delivery},
     },
+    security_utils::sha256,
+    utils::get_current_timestamp,
 };
 
 fn test_block(prev_block_hash: [u8; 32], nonce: u32) -> Block {
@@ -21,6 +29,7 @@ fn test_block(prev_block_hash: [u8; 32], nonce: u32) -> Block {
 
     Block {
         header: BlockHeader {
+            version: BlockHeader::INITIAL_VERSION,
             prev_block_hash,
             merkle_root: [nonce as u8; 32],
             nonce,
@@ -93,3 +102,130 @@ async fn keeps_fork_flow_when_common_block_is_not_local_tip() {
 
     expect_direct_get_blocks(&mut receiver, second.id(), peer).await;
 }
+
+#[tokio::test]
+async fn ignores_peers_diverging_below_a_passed_checkpoint() {
+    let mut receiver = BROADCAST_CHANNEL.sender.subscribe();
+    let peer: SocketAddr = "127.0.0.1:6102".parse().unwrap();
+
+    let genesis = test_block([0; 32], 6);
+    let second = test_block(genesis.id(), 7);
+    let third = test_block(second.id(), 8);
+
+    let mut node = build_test_node(vec![genesis.clone(), second.clone(), third]);
+    node.set_checkpoints(Checkpoints::new(vec![(1, second.id())], None));
+
+    node.handle_received_common_block(genesis.clone(), Some(peer))
+        .await;
+
+    let requested = timeout(Duration::from_millis(300), async {
+        loop {
+            let (message, delivery) = receiver.recv().await.unwrap();
+            if matches!(
+                (&message, &delivery),
+                (NetworkMessage::GetBlocks { .. }, Delivery::Direct { target_peer })
+                    if *target_peer == peer
+            ) {
+                break;
+            }
+        }
+    })
+    .await;
+    assert!(requested.is_err(), "no blocks should be requested");
+}
+
+#[tokio::test]
+async fn saved_mempool_entries_are_revalidated_on_startup() {
+    init_db();
+    let repo = LedgerRepository::new();
+    let input = TxInput::new([7; 32], 0);
+    let orphan = Transaction::new(
+        vec![input],
+        vec![TxOutput::new(100, "addr".to_string())],
+        None,
+    );
+    repo.save_mempool_entry(&MempoolRecord {
+        tx: orphan.clone(),
+        time: 0,
+        ancestors: 0,
+    })
+    .unwrap();
+
+    // The entry spends no known output, so the restarted node drops it.
+    let node = Node::new();
+    assert!(!node.get_mempool().contains(&orphan.id()));
+    assert!(repo.get_mempool_entries().unwrap().iter().all(|entry| {
+        entry
+            .as_ref()
+            .is_ok_and(|entry| entry.tx.id() != orphan.id())
+    }));
+}
+
+#[tokio::test]
+async fn saved_mempool_entries_and_their_children_are_restored_on_startup() {
+    const SECRET: &[u8] = b"restart secret";
+    init_db();
+    let mut repo = LedgerRepository::new();
+    let hashlock = || Script::new_hashlock(&sha256(SECRET));
+    let unlock = || {
+        let mut unlock_script = Script::new();
+        unlock_script.push_data(SECRET);
+        unlock_script
+    };
+
+    // A confirmed, non-coinbase output to spend: blocks applied to the ledger
+    // directly are not validated.
+    let funding = Transaction::new(
+        vec![TxInput::new([8; 32], 0)],
+        vec![TxOutput::with_script(100_000, hashlock())],
+        Some("restart-funding".to_string()),
+    );
+    let mut block = test_block([0; 32], 30);
+    block.transactions = vec![funding.clone()];
+    block.evaluate_merkle_root();
+    repo.apply_block(block).unwrap();
+
+    let spend = |prev_tx_id: [u8; 32], value: i64| {
+        let input = TxInput {
+            unlock_script: unlock(),
+            ..TxInput::new(prev_tx_id, 0)
+        };
+        Transaction::new(
+            vec![input],
+            vec![TxOutput::with_script(value, hashlock())],
+            None,
+        )
+    };
+    let parent = spend(funding.id(), 90_000);
+    let child = spend(parent.id(), 80_000);
+    let unreadable = spend([9; 32], 1_000);
+    for (tx, ancestors) in [(&child, 1), (&parent, 0), (&unreadable, 0)] {
+        repo.save_mempool_entry(&MempoolRecord {
+            tx: tx.clone(),
+            time: get_current_timestamp().and_utc().timestamp(),
+            ancestors,
+        })
+        .unwrap();
+    }
+    get_db()
+        .get_conn()
+        .execute(
+            "UPDATE mempool_entries SET raw = x'00' WHERE txid = ?1",
+            [unreadable.id().as_slice()],
+        )
+        .unwrap();
+
+    let node = Node::new();
+    assert!(node.get_mempool().contains(&parent.id()));
+    assert!(node.get_mempool().contains(&child.id()));
+    assert!(!node.get_mempool().contains(&unreadable.id()));
+    // The unreadable entry is deleted; the restored ones stay saved.
+    let saved: Vec<_> = repo
+        .get_mempool_entries()
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.map_or_else(|(id, _)| id, |record| record.tx.id()))
+        .collect();
+    assert!(saved.contains(&parent.id()) && saved.contains(&child.id()));
+    assert!(!saved.contains(&unreadable.id()));
+}
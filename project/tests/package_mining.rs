@@ -0,0 +1,126 @@
+use primitive_types::U256;
+
+use project::model::block::BlockHeader;
+use project::model::mempool::{MAX_ANCESTORS, Mempool};
+use project::model::miner::{MiningSnapshot, select_transactions_for_block};
+use project::model::{Block, MempoolTx, Transaction, TxInput, TxOutput, UTXO};
+
+const SPENT_VALUE: i64 = 1_000;
+
+/// Spends output 0 of `prev_tx_id`, worth `SPENT_VALUE`, paying `fee`.
+fn spend(prev_tx_id: [u8; 32], fee: i64) -> MempoolTx {
+    spend_output(prev_tx_id, 0, fee, 1)
+}
+
+/// Spends output `index` of `prev_tx_id` into `outputs` outputs worth
+/// `SPENT_VALUE` each, paying `fee`.
+fn spend_output(prev_tx_id: [u8; 32], index: usize, fee: i64, outputs: usize) -> MempoolTx {
+    let input = TxInput::new(prev_tx_id, index);
+    let value = SPENT_VALUE * outputs as i64;
+    let mut created = vec![TxOutput::new(SPENT_VALUE, "addr".to_string()); outputs];
+    created[0].value -= fee;
+    let tx = Transaction::new(vec![input], created, None);
+    let utxo = UTXO {
+        tx_id: prev_tx_id,
+        index,
+        output: TxOutput::new(value, "addr".to_string()),
+    };
+    MempoolTx::new(tx, vec![utxo])
+}
+
+fn snapshot() -> MiningSnapshot {
+    MiningSnapshot {
+        mempool: Vec::new(),
+        previous_hash: [0; 32],
+        height: 1,
+        median_time_past: None,
+        target: U256::MAX,
+        version: BlockHeader::INITIAL_VERSION,
+        receive_addr: "miner".to_string(),
+    }
+}
+
+/// Block size limit leaving room for the coinbase and `count` transactions like `tx`.
+fn room_for(count: usize, tx: &MempoolTx) -> usize {
+    let snapshot = snapshot();
+    let coinbase = Transaction::new_coinbase(snapshot.receive_addr, snapshot.height, 0);
+    Block::size_with_transactions(coinbase.size() + count * tx.tx.size())
+}
+
+#[test]
+fn child_pays_for_its_parent() {
+    let parent = spend([1; 32], 1);
+    let child = spend(parent.id(), 100);
+    let unrelated = spend([2; 32], 30);
+    let txs = vec![&unrelated, &child, &parent];
+
+    let (selected, fees) = select_transactions_for_block(txs, &snapshot(), room_for(2, &parent));
+    let ids: Vec<_> = selected.iter().map(|tx| tx.id()).collect();
+    assert_eq!(ids, vec![parent.id(), child.id()]);
+    assert_eq!(fees, 101);
+}
+
+#[test]
+fn packages_that_do_not_fit_leave_room_for_others() {
+    let parent = spend([1; 32], 5);
+    let child = spend(parent.id(), 100);
+    let unrelated = spend([2; 32], 30);
+    let txs = vec![&parent, &child, &unrelated];
+
+    // The best package needs two slots; the next best transaction takes the only one.
+    let (selected, fees) = select_transactions_for_block(txs, &snapshot(), room_for(1, &parent));
+    let ids: Vec<_> = selected.iter().map(|tx| tx.id()).collect();
+    assert_eq!(ids, vec![unrelated.id()]);
+    assert_eq!(fees, 30);
+}
+
+#[test]
+fn selected_parents_no_longer_weigh_on_their_other_children() {
+    let parent = spend_output([1; 32], 0, 1, 2);
+    let rich_child = spend(parent.id(), 100);
+    let child = spend_output(parent.id(), 1, 20, 1);
+    let unrelated = spend([2; 32], 15);
+    let txs = vec![&unrelated, &child, &rich_child, &parent];
+
+    // With its parent the child pays less per byte than the unrelated
+    // transaction, but once the parent is in, it pays more.
+    let (selected, fees) = select_transactions_for_block(txs, &snapshot(), room_for(3, &parent));
+    let ids: Vec<_> = selected.iter().map(|tx| tx.id()).collect();
+    assert_eq!(ids, vec![parent.id(), rich_child.id(), child.id()]);
+    assert_eq!(fees, 121);
+}
+
+#[test]
+fn mempool_tracks_parents_and_ancestors() {
+    let parent = spend([1; 32], 1);
+    let child = spend(parent.id(), 1);
+    let grandchild = spend(child.id(), 1);
+    let mempool = Mempool::from_txs(vec![parent.clone(), child.clone()]);
+
+    assert_eq!(mempool.find_parents(&grandchild.tx).len(), 1);
+    assert_eq!(
+        mempool.find_ancestors(&grandchild.tx),
+        vec![child.id(), parent.id()]
+    );
+
+    // Only the child's output is left for others to spend.
+    let unspent = mempool.unspent_outputs();
+    assert_eq!(unspent.len(), 1);
+    assert_eq!(unspent[0].tx_id, child.id());
+}
+
+#[test]
+fn unconfirmed_chains_are_bounded() {
+    let mut mempool = Mempool::from_txs(Vec::new());
+    let mut tip = spend([1; 32], 1);
+    while mempool.len() < MAX_ANCESTORS - 1 {
+        let next = spend(tip.id(), 1);
+        mempool.insert(tip, 0);
+        tip = next;
+    }
+
+    assert!(mempool.check_chain_limits(&tip.tx).is_ok());
+    let too_long = spend(tip.id(), 1);
+    mempool.insert(tip, 0);
+    assert!(mempool.check_chain_limits(&too_long.tx).is_err());
+}
@@ -0,0 +1,105 @@
+use project::db::db::init_db;
+use project::model::io::{SIGHASH_ALL, SIGHASH_ANYONECANPAY, UTXO};
+use project::model::utxo_view::{LedgerView, UtxoOverlay};
+use project::model::{Transaction, TxInput, TxOutput, Wallet};
+
+/// Height of the spends, past the maturity of the funding transaction.
+const HEIGHT: u64 = 200;
+const CONTRIBUTION: i64 = 1_000;
+
+fn address(wallet: &Wallet) -> String {
+    wallet.generate_n_keys(1, None, None)[0].get_address()
+}
+
+/// A transaction without inputs paying each wallet one contribution.
+fn fund(label: &str, wallets: &[&Wallet]) -> (Transaction, Vec<UTXO>) {
+    let outputs = wallets
+        .iter()
+        .map(|wallet| TxOutput::new(CONTRIBUTION, address(wallet)))
+        .collect();
+    let funding = Transaction::new(Vec::new(), outputs, Some(label.to_string()));
+    let utxos = funding
+        .outputs
+        .iter()
+        .enumerate()
+        .map(|(index, output)| UTXO {
+            tx_id: funding.id(),
+            index,
+            output: output.clone(),
+        })
+        .collect();
+    (funding, utxos)
+}
+
+#[test]
+fn crowdfunding_contributions_signed_apart_combine_into_a_valid_spend() {
+    init_db();
+    let alice = Wallet::from_seed([31; 32]);
+    let bob = Wallet::from_seed([32; 32]);
+    let goal = Wallet::from_seed([33; 32]);
+    let (funding, utxos) = fund("raw-crowdfund", &[&alice, &bob]);
+
+    let template = Transaction::new(
+        Vec::new(),
+        vec![TxOutput::new(2 * CONTRIBUTION - 100, address(&goal))],
+        None,
+    );
+    let contribute = |wallet: &Wallet, index: usize| {
+        let mut tx = Transaction::from_hex(&template.to_hex()).unwrap();
+        tx.inputs.push(TxInput::new(funding.id(), index));
+        let signed = wallet
+            .sign_raw(&mut tx, &utxos, SIGHASH_ALL | SIGHASH_ANYONECANPAY)
+            .unwrap();
+        assert_eq!(signed, vec![0]);
+        tx
+    };
+    let mut combined = contribute(&alice, 0);
+    combined.combine(&contribute(&bob, 1)).unwrap();
+    assert_eq!(combined.inputs.len(), 2);
+    assert!(combined.unsigned_inputs().is_empty());
+
+    let mut view = UtxoOverlay::new(&LedgerView);
+    view.connect_transaction(&funding, Some(0)).unwrap();
+    assert_eq!(
+        combined.validate_in(&view, HEIGHT, None, &[], true),
+        Ok(100)
+    );
+
+    // A contribution signed with SIGHASH_ALL no longer verifies once others join.
+    let mut tx = Transaction::from_hex(&template.to_hex()).unwrap();
+    tx.inputs.push(TxInput::new(funding.id(), 0));
+    alice.sign_raw(&mut tx, &utxos, SIGHASH_ALL).unwrap();
+    tx.combine(&contribute(&bob, 1)).unwrap();
+    assert!(tx.validate_in(&view, HEIGHT, None, &[], true).is_err());
+}
+
+#[test]
+fn signing_leaves_inputs_of_other_owners_and_signed_inputs_alone() {
+    init_db();
+    let alice = Wallet::from_seed([34; 32]);
+    let bob = Wallet::from_seed([35; 32]);
+    let (funding, utxos) = fund("raw-owners", &[&alice, &bob]);
+
+    let mut tx = Transaction::new(
+        vec![TxInput::new(funding.id(), 0), TxInput::new(funding.id(), 1)],
+        vec![TxOutput::new(CONTRIBUTION, address(&alice))],
+        None,
+    );
+    assert_eq!(alice.sign_raw(&mut tx, &utxos, SIGHASH_ALL), Ok(vec![0]));
+    assert_eq!(tx.unsigned_inputs(), vec![1]);
+    assert_eq!(alice.sign_raw(&mut tx, &utxos, SIGHASH_ALL), Ok(vec![]));
+
+    let mut from_bob = Transaction::from_hex(&tx.to_hex()).unwrap();
+    from_bob.inputs[0] = TxInput::new(funding.id(), 0);
+    assert_eq!(
+        bob.sign_raw(&mut from_bob, &utxos, SIGHASH_ALL),
+        Ok(vec![1])
+    );
+    tx.combine(&from_bob).unwrap();
+    assert!(tx.unsigned_inputs().is_empty());
+
+    let mut other = tx.clone();
+    other.outputs[0].value -= 1;
+    let err = tx.combine(&other).unwrap_err();
+    assert!(err.contains("differ"), "{}", err);
+}
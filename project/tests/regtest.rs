@@ -0,0 +1,66 @@
+use std::collections::HashSet;
+
+use chrono::Duration;
+
+use project::chain_params::Network;
+use project::db::db::init_db;
+use project::globals::{CONSENSUS_RULES, NETWORK};
+use project::model::miner::generate_to_address;
+use project::model::utxo_view::{LedgerView, UtxoView};
+use project::model::{HDKey, get_node};
+use project::utils::{get_current_timestamp, set_mock_time};
+
+#[tokio::test]
+async fn generates_blocks_on_demand_with_mock_time() {
+    // Must run before anything reads the network profile.
+    unsafe { std::env::set_var("NETWORK", "regtest") };
+    assert_eq!(NETWORK.network, Network::Regtest);
+    init_db();
+
+    // The regtest chain persists between runs; a fresh key keeps coinbases unique
+    // even when two runs mock the same second.
+    let address = HDKey::new(&rand::random::<[u8; 32]>()).get_address();
+    let start_height = get_node().await.blockchain.height();
+
+    let hashes = generate_to_address(3, &address).await.unwrap();
+    assert_eq!(hashes.len(), 3);
+    {
+        let node = get_node().await;
+        assert_eq!(node.blockchain.height(), start_height + 3);
+        assert_eq!(node.blockchain.get_last_block_hash(), hashes[2]);
+        // Retargeting is off: every block keeps the trivial initial target.
+        assert!(
+            node.blockchain
+                .chain
+                .iter()
+                .all(|b| b.header.target == CONSENSUS_RULES.initial_target)
+        );
+    }
+
+    let mock_time = (get_current_timestamp() + Duration::minutes(10))
+        .and_utc()
+        .timestamp();
+    set_mock_time(mock_time);
+    assert_eq!(get_current_timestamp().and_utc().timestamp(), mock_time);
+    let hashes = generate_to_address(3, &address).await;
+    set_mock_time(0);
+
+    // Blocks built in the same mocked second still get distinct coinbases.
+    let hashes = hashes.unwrap();
+    let node = get_node().await;
+    let height = node.blockchain.height();
+    assert_eq!(height, start_height + 6);
+    let tip = node.blockchain.get_last_block().unwrap();
+    assert_eq!(tip.header_hash(), hashes[2]);
+    assert_eq!(tip.header.timestamp.and_utc().timestamp(), mock_time);
+    let mocked = &node.blockchain.chain[height - 3..];
+    let coinbase_ids: HashSet<_> = mocked
+        .iter()
+        .map(|block| block.transactions[0].id())
+        .collect();
+    assert_eq!(coinbase_ids.len(), 3);
+    // The ledger follows the chain to its tip.
+    assert!(LedgerView.block_time(height as u64 - 1).unwrap().is_some());
+    assert!(LedgerView.block_time(height as u64).unwrap().is_none());
+    assert!(generate_to_address(1, "not-an-address").await.is_err());
+}
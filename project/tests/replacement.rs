@@ -0,0 +1,207 @@
+use project::db::db::init_db;
+use project::model::io::SIGHASH_ALL;
+use project::model::mempool::{MAX_REPLACEMENT_EVICTIONS, Mempool, MempoolLimits};
+use project::model::utxo_view::{LedgerView, UtxoOverlay};
+use project::model::{HDKey, MempoolTx, Transaction, TxInput, TxOutput, UTXO, Wallet};
+
+const SPENT_VALUE: i64 = 1_000;
+
+fn input(prev_tx_id: [u8; 32], replaceable: bool) -> TxInput {
+    TxInput {
+        replaceable,
+        ..TxInput::new(prev_tx_id, 0)
+    }
+}
+
+/// Spends output 0 of `prev_tx_id`, worth `SPENT_VALUE`, paying `fee`.
+fn spend(prev_tx_id: [u8; 32], fee: i64, replaceable: bool) -> MempoolTx {
+    let tx = Transaction::new(
+        vec![input(prev_tx_id, replaceable)],
+        vec![TxOutput::new(SPENT_VALUE - fee, "addr".to_string())],
+        None,
+    );
+    let utxo = UTXO {
+        tx_id: prev_tx_id,
+        index: 0,
+        output: TxOutput::new(SPENT_VALUE, "addr".to_string()),
+    };
+    MempoolTx::new(tx, vec![utxo])
+}
+
+#[test]
+fn conflicts_share_a_spent_output() {
+    let original = spend([1; 32], 10, true);
+    let unrelated = spend([2; 32], 10, true);
+    let mempool = Mempool::from_txs(vec![original.clone(), unrelated]);
+
+    let replacement = spend([1; 32], 20, true);
+    assert_eq!(mempool.find_conflicts(&replacement.tx), vec![original.id()]);
+    assert!(
+        mempool
+            .find_conflicts(&spend([3; 32], 20, true).tx)
+            .is_empty()
+    );
+}
+
+#[test]
+fn only_signalling_transactions_are_replaced() {
+    let original = spend([1; 32], 10, false);
+    let mempool = Mempool::from_txs(vec![original.clone()]);
+
+    let replacement = spend([1; 32], 50, true);
+    let err = mempool
+        .check_replacement(&replacement, &[original.id()])
+        .unwrap_err();
+    assert!(err.contains("non-replaceable"));
+}
+
+#[test]
+fn replacement_must_pay_more() {
+    let original = spend([1; 32], 10, true);
+    let mempool = Mempool::from_txs(vec![original.clone()]);
+    let conflicts = [original.id()];
+
+    assert!(
+        mempool
+            .check_replacement(&spend([1; 32], 10, true), &conflicts)
+            .is_err()
+    );
+    assert!(
+        mempool
+            .check_replacement(&spend([1; 32], 9, true), &conflicts)
+            .is_err()
+    );
+    assert_eq!(
+        mempool
+            .check_replacement(&spend([1; 32], 11, true), &conflicts)
+            .unwrap(),
+        vec![original.id()]
+    );
+}
+
+#[test]
+fn descendants_are_evicted_and_paid_for() {
+    let parent = spend([1; 32], 10, true);
+    let child = spend(parent.id(), 30, false);
+    let grandchild = spend(child.id(), 30, false);
+    let mempool = Mempool::from_txs(vec![parent.clone(), child.clone(), grandchild.clone()]);
+
+    assert_eq!(
+        mempool.find_descendants(&[parent.id()]),
+        vec![child.id(), grandchild.id()]
+    );
+    let conflicts = [parent.id()];
+    // Beats the parent's fee rate but not the 70 paid by the whole family.
+    assert!(
+        mempool
+            .check_replacement(&spend([1; 32], 50, true), &conflicts)
+            .is_err()
+    );
+    let evicted = mempool
+        .check_replacement(&spend([1; 32], 71, true), &conflicts)
+        .unwrap();
+    assert_eq!(evicted, vec![parent.id(), child.id(), grandchild.id()]);
+}
+
+#[test]
+fn evictions_are_bounded() {
+    let parent = spend([1; 32], 1, true);
+    let mut txs = vec![parent.clone()];
+    let mut tip = parent.id();
+    for _ in 0..MAX_REPLACEMENT_EVICTIONS {
+        let child = spend(tip, 1, false);
+        tip = child.id();
+        txs.push(child);
+    }
+    let mempool = Mempool::from_txs(txs);
+
+    let replacement = spend([1; 32], 500, true);
+    let err = mempool
+        .check_replacement(&replacement, &[parent.id()])
+        .unwrap_err();
+    assert!(err.contains("evict"));
+}
+
+#[test]
+fn wallet_bumps_the_fee_of_its_unconfirmed_payment() {
+    init_db();
+    let mut wallet = Wallet::from_seed([41; 32]);
+    let funding = Transaction::new(
+        Vec::new(),
+        vec![TxOutput::new(SPENT_VALUE, wallet.get_receive_addr())],
+        Some("bump-fee-funding".to_string()),
+    );
+    let funding = MempoolTx::new(funding, Vec::new());
+    wallet.track_mempool(&Mempool::from_txs(vec![funding.clone()]));
+
+    let recipient = HDKey::new(b"bump fee recipient").get_address();
+    let payment = wallet
+        .send_tx(
+            vec![TxOutput::new(400, recipient)],
+            Some(10),
+            None,
+            None,
+            SIGHASH_ALL,
+            true,
+        )
+        .unwrap();
+    // The change goes to an address the chain has never seen.
+    let bumped = wallet.bump_fee(&payment, 50).unwrap();
+    assert_eq!(bumped.calculate_fee(), 50);
+    assert_eq!(bumped.tx.outputs[0], payment.tx.outputs[0]);
+    assert_eq!(bumped.tx.outputs[1].value, payment.tx.outputs[1].value - 40);
+
+    let mempool = Mempool::from_txs(vec![funding.clone(), payment.clone()]);
+    assert_eq!(
+        mempool.check_replacement(&bumped, &[payment.id()]),
+        Ok(vec![payment.id()])
+    );
+    let mut view = UtxoOverlay::new(&LedgerView);
+    view.connect_transaction(&funding.tx, Some(0)).unwrap();
+    assert_eq!(bumped.tx.validate_in(&view, 200, None, &[], true), Ok(50));
+}
+
+#[test]
+fn replacements_trimmed_from_a_full_mempool_leave_the_originals_in_place() {
+    let original = spend([1; 32], 10, true);
+    let child = spend(original.id(), 10, true);
+    let rich = spend([2; 32], 500, true);
+    let mut mempool = Mempool::new(MempoolLimits {
+        max_size: 3 * original.tx.size(),
+        expiry_secs: 1_000,
+        min_relay_fee_rate: 0.0,
+    });
+    for mem_tx in [&original, &child, &rich] {
+        mempool.insert(mem_tx.clone(), 0);
+    }
+    let replaced = [original.id(), child.id()];
+
+    // Pays more than both conflicts, but too little for its size to fit.
+    let bulky = Transaction::new(
+        vec![input([1; 32], true)],
+        vec![TxOutput::new(SPENT_VALUE - 30, "addr".to_string())],
+        Some("x".repeat(4 * original.tx.size())),
+    );
+    let bulky = MempoolTx::new(bulky, original.utxos.clone());
+    let err = mempool
+        .insert_replacing(bulky.clone(), &replaced, 0, 0)
+        .unwrap_err();
+    assert!(err.contains("Mempool full"), "{}", err);
+    assert!(!mempool.contains(&bulky.id()));
+    assert_eq!(mempool.len(), 3);
+    assert_eq!(mempool.find_conflicts(&bulky.tx), vec![original.id()]);
+    assert_eq!(
+        mempool.get_entry(&original.id()).unwrap().descendant_fee,
+        20
+    );
+    assert_eq!(mempool.min_fee_rate(0), 0.0);
+
+    let replacement = spend([1; 32], 30, true);
+    assert_eq!(
+        mempool.insert_replacing(replacement.clone(), &replaced, 0, 0),
+        Ok(Vec::new())
+    );
+    assert!(mempool.contains(&replacement.id()));
+    assert!(!mempool.contains(&original.id()) && !mempool.contains(&child.id()));
+    assert_eq!(mempool.len(), 2);
+}
@@ -0,0 +1,135 @@
+use project::model::script::{
+    ScriptType, SignatureChecker, TransactionSignatureChecker, hash160, verify_script,
+};
+use project::model::{HDKey, Script, TxInput, TxOutput};
+use project::security_utils::sha256;
+
+const MESSAGE: &[u8] = b"spending transaction";
+
+fn key() -> HDKey {
+    HDKey::new(b"script test key")
+}
+
+fn signature_push(key: &HDKey, message: &[u8]) -> Script {
+    let mut script = Script::new();
+    script.push_data(&key.sign(message).to_bytes());
+    script
+}
+
+#[test]
+fn standard_scripts_are_classified() {
+    let key = key();
+    let pubkey = key.get_public_key();
+    let p2pkh = Script::for_address(&key.get_address()).unwrap();
+
+    assert_eq!(p2pkh.classify(), ScriptType::PubKeyHash);
+    assert_eq!(p2pkh, Script::new_p2pkh(&hash160(pubkey.as_bytes())));
+    assert_eq!(p2pkh.address(), Some(key.get_address()));
+    assert_eq!(Script::new_p2pk(&pubkey).classify(), ScriptType::PubKey);
+    assert_eq!(Script::new_p2pk(&pubkey).address(), Some(key.get_address()));
+    assert_eq!(
+        Script::new_hashlock(&sha256(b"secret")).classify(),
+        ScriptType::HashLock
+    );
+    assert_eq!(Script::new_data(b"hello").classify(), ScriptType::NullData);
+    assert_eq!(
+        Script::from_bytes(vec![0xff]).classify(),
+        ScriptType::NonStandard
+    );
+    assert!(Script::for_address("not-an-address").is_err());
+}
+
+#[test]
+fn legacy_input_satisfies_pubkey_hash_output() {
+    let key = key();
+    let checker = TransactionSignatureChecker::new(MESSAGE);
+    let output = TxOutput::new(10, key.get_address());
+    let mut input = TxInput {
+        signature: hex::encode(key.sign(MESSAGE).to_bytes()),
+        public_key: hex::encode(key.get_public_key().as_bytes()),
+        ..TxInput::new([1; 32], 0)
+    };
+
+    let locking = output.locking_script().unwrap();
+    assert!(verify_script(&input.unlocking_script().unwrap(), &locking, &checker).is_ok());
+
+    let other = HDKey::new(b"someone else");
+    input.public_key = hex::encode(other.get_public_key().as_bytes());
+    assert!(verify_script(&input.unlocking_script().unwrap(), &locking, &checker).is_err());
+
+    input.public_key = hex::encode(key.get_public_key().as_bytes());
+    let wrong_message = TransactionSignatureChecker::new(b"another transaction");
+    assert!(verify_script(&input.unlocking_script().unwrap(), &locking, &wrong_message).is_err());
+}
+
+#[test]
+fn pubkey_output_requires_matching_signature() {
+    let key = key();
+    let locking = Script::new_p2pk(&key.get_public_key());
+    let checker = TransactionSignatureChecker::new(MESSAGE);
+
+    assert!(verify_script(&signature_push(&key, MESSAGE), &locking, &checker).is_ok());
+    let other = HDKey::new(b"someone else");
+    assert!(verify_script(&signature_push(&other, MESSAGE), &locking, &checker).is_err());
+    assert!(verify_script(&Script::new(), &locking, &checker).is_err());
+}
+
+#[test]
+fn hashlock_requires_preimage() {
+    let locking = Script::new_hashlock(&sha256(b"secret"));
+    let checker = TransactionSignatureChecker::new(MESSAGE);
+    let mut right = Script::new();
+    right.push_data(b"secret");
+    let mut wrong = Script::new();
+    wrong.push_data(b"guess");
+
+    assert!(verify_script(&right, &locking, &checker).is_ok());
+    assert!(verify_script(&wrong, &locking, &checker).is_err());
+}
+
+#[test]
+fn data_outputs_are_unspendable() {
+    let locking = Script::new_data(b"payload");
+    let checker = TransactionSignatureChecker::new(MESSAGE);
+    let mut unlocking = Script::new();
+    unlocking.push_opcode(0x51);
+
+    assert!(locking.is_unspendable());
+    assert!(verify_script(&unlocking, &locking, &checker).is_err());
+}
+
+#[test]
+fn unlocking_scripts_must_be_push_only() {
+    struct AcceptAll;
+    impl SignatureChecker for AcceptAll {
+        fn check_signature(&self, _: &[u8], _: &[u8]) -> bool {
+            true
+        }
+    }
+    // OP_1 is a valid lock; an unlocking script that runs opcodes is still rejected.
+    let locking = Script::from_bytes(vec![0x51]);
+    let unlocking = Script::from_bytes(vec![0x51, 0x76]);
+
+    assert!(verify_script(&Script::new(), &locking, &AcceptAll).is_ok());
+    assert!(verify_script(&unlocking, &locking, &AcceptAll).is_err());
+}
+
+#[test]
+fn truncated_pushes_are_rejected() {
+    let script = Script::from_bytes(vec![0x05, 0x01, 0x02]);
+    assert!(script.instructions().is_err());
+    assert!(!script.is_push_only());
+}
+
+#[test]
+fn output_scripts_round_trip() {
+    let key = key();
+    let output = TxOutput::with_script(7, Script::new_p2pk(&key.get_public_key()));
+    assert_eq!(output.address, key.get_address());
+
+    let decoded = TxOutput::from_bytes(&TxOutput::as_bytes(&output)).unwrap();
+    assert_eq!(decoded, output);
+
+    let json = serde_json::to_string(&output).unwrap();
+    assert_eq!(serde_json::from_str::<TxOutput>(&json).unwrap(), output);
+}
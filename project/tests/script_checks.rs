@@ -0,0 +1,336 @@
+use curve25519_dalek::constants::EIGHT_TORSION;
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use ed25519_dalek::Signature;
+use primitive_types::U256;
+
+use project::db::db::init_db;
+use project::db::repository::LedgerRepository;
+use project::model::node::Node;
+use project::model::script_check::{SCRIPT_CACHE, ScriptCache, ScriptCheck, run_script_checks};
+use project::model::{
+    Block, HDKey, MempoolTx, Script, Transaction, TxInput, TxOutput, block::BlockHeader,
+};
+use project::security_utils::{bytes_to_hex_string, sha256, verify_signature};
+use project::utils::get_current_timestamp;
+
+const SECRET: &[u8] = b"secret";
+const VALUE: i64 = 1_000;
+/// Height of the validated blocks, past the maturity of the funding coinbase.
+const HEIGHT: u64 = 200;
+
+fn hashlock() -> Script {
+    Script::new_hashlock(&sha256(SECRET))
+}
+
+fn unlock(preimage: &[u8]) -> Script {
+    let mut script = Script::new();
+    script.push_data(preimage);
+    script
+}
+
+fn check(position: usize, preimage: &[u8]) -> ScriptCheck {
+    ScriptCheck::new(
+        ([position as u8; 32], 0),
+        format!("position-{}", position),
+        unlock(preimage),
+        hashlock(),
+        Vec::new(),
+        0,
+    )
+}
+
+/// A check spending a P2PKH output, or a P2PK one at odd positions, whose
+/// signature covers `signed` rather than the check's own signing bytes.
+fn signed_check(position: usize, signed: &[u8]) -> ScriptCheck {
+    let key = HDKey::new(format!("signer-{}", position).as_bytes());
+    let signing_bytes = format!("spend-{}", position).into_bytes();
+    let mut unlocking = Script::new();
+    unlocking.push_data(&key.sign(signed).to_bytes());
+    let locking = if position.is_multiple_of(2) {
+        unlocking.push_data(key.get_public_key().as_bytes());
+        Script::for_address(&key.get_address()).unwrap()
+    } else {
+        Script::new_p2pk(&key.get_public_key())
+    };
+    ScriptCheck::new(
+        ([position as u8; 32], 0),
+        format!("position-{}", position),
+        unlocking,
+        locking,
+        signing_bytes,
+        0,
+    )
+}
+
+/// `signature` with the small-order point `EIGHT_TORSION[torsion]` added to its R.
+fn with_torsioned_r(signature: &Signature, torsion: usize) -> Signature {
+    let r = CompressedEdwardsY(*signature.r_bytes())
+        .decompress()
+        .unwrap()
+        + EIGHT_TORSION[torsion];
+    let mut bytes = signature.to_bytes();
+    bytes[..32].copy_from_slice(r.compress().as_bytes());
+    Signature::from_bytes(&bytes)
+}
+
+/// Confirms a transaction with `outputs` hash-locked outputs at height 0.
+fn fund(label: &str, outputs: usize) -> Transaction {
+    init_db();
+    let funding = Transaction::new(
+        Vec::new(),
+        vec![TxOutput::with_script(VALUE, hashlock()); outputs],
+        Some(label.to_string()),
+    );
+    confirm(funding.clone());
+    funding
+}
+
+/// Applies a block holding only `tx` to the ledger, without validating it.
+fn confirm(tx: Transaction) {
+    let mut block = Block {
+        header: BlockHeader {
+            version: BlockHeader::INITIAL_VERSION,
+            prev_block_hash: [0; 32],
+            merkle_root: [0; 32],
+            nonce: 0,
+            timestamp: get_current_timestamp(),
+            target: U256::MAX,
+        },
+        transactions: vec![tx],
+    };
+    block.evaluate_merkle_root();
+    LedgerRepository::new().apply_block(block).unwrap();
+}
+
+/// Spends the given outputs of `prev_tx_id`, unlocking each with its preimage.
+fn spend(prev_tx_id: [u8; 32], inputs: &[(usize, &[u8])]) -> Transaction {
+    let inputs: Vec<TxInput> = inputs
+        .iter()
+        .map(|(output_index, preimage)| TxInput {
+            unlock_script: unlock(preimage),
+            ..TxInput::new(prev_tx_id, *output_index)
+        })
+        .collect();
+    let value = VALUE * inputs.len() as i64;
+    Transaction::new(inputs, vec![TxOutput::with_script(value, hashlock())], None)
+}
+
+fn block_with(transactions: Vec<Transaction>) -> Block {
+    let mut all = vec![Transaction::new_coinbase("miner".to_string(), HEIGHT, 0)];
+    all.extend(transactions);
+    let mut block = Block::new([1; 32], U256::MAX, BlockHeader::INITIAL_VERSION);
+    block.transactions = all;
+    block.evaluate_merkle_root();
+    block
+}
+
+fn input_ref(tx: &Transaction, index: usize) -> String {
+    format!("{}:{}", bytes_to_hex_string(&tx.id()), index)
+}
+
+#[test]
+fn first_failing_check_is_reported_for_any_number_of_workers() {
+    let checks: Vec<ScriptCheck> = (0..60)
+        .map(|position| match position {
+            13 | 41 => check(position, b"guess"),
+            _ => check(position, SECRET),
+        })
+        .collect();
+
+    for workers in 1..=8 {
+        let err = run_script_checks(&checks, workers).unwrap_err();
+        assert!(err.contains("position-13:"), "{} workers: {}", workers, err);
+    }
+    assert!(run_script_checks(&checks[..13], 4).is_ok());
+    assert!(run_script_checks(&[], 4).is_ok());
+}
+
+#[test]
+fn first_failing_signature_is_reported_for_any_number_of_workers() {
+    let checks: Vec<ScriptCheck> = (0..60)
+        .map(|position| match position {
+            17 => signed_check(position, b"something else"),
+            29 => check(position, b"guess"),
+            _ if position.is_multiple_of(3) => check(position, SECRET),
+            _ => signed_check(position, format!("spend-{}", position).as_bytes()),
+        })
+        .collect();
+
+    for workers in 1..=8 {
+        let err = run_script_checks(&checks, workers).unwrap_err();
+        assert!(err.contains("position-17:"), "{} workers: {}", workers, err);
+        let err = run_script_checks(&checks[18..], workers).unwrap_err();
+        assert!(err.contains("position-29:"), "{} workers: {}", workers, err);
+        assert!(run_script_checks(&checks[..17], workers).is_ok());
+    }
+}
+
+#[test]
+fn block_validation_reports_the_first_failing_input() {
+    let funding = fund("first-failing-input", 24);
+    let id = funding.id();
+    let good: Vec<(usize, &[u8])> = (0..20).map(|index| (index, SECRET)).collect();
+    let valid = spend(id, &good);
+
+    assert_eq!(
+        block_with(vec![valid.clone()]).validate(HEIGHT, None),
+        Ok(())
+    );
+
+    let mixed = spend(id, &[(20, SECRET), (21, b"guess"), (22, b"guess")]);
+    let late = spend(id, &[(23, b"guess")]);
+    let err = block_with(vec![valid, mixed, late])
+        .validate(HEIGHT, None)
+        .unwrap_err();
+    assert!(err.contains(&input_ref(&funding, 21)), "{}", err);
+}
+
+#[test]
+fn earlier_script_failure_wins_over_later_missing_input() {
+    let funding = fund("script-before-missing-input", 2);
+    let bad_script = spend(funding.id(), &[(0, b"guess")]);
+    let missing = spend([0x55; 32], &[(0, SECRET)]);
+
+    let err = block_with(vec![bad_script.clone(), missing.clone()])
+        .validate(HEIGHT, None)
+        .unwrap_err();
+    assert!(err.contains("Script verification failed"), "{}", err);
+    assert!(err.contains(&input_ref(&funding, 0)), "{}", err);
+
+    // Without the bad script, the missing input is what fails.
+    let err = block_with(vec![missing])
+        .validate(HEIGHT, None)
+        .unwrap_err();
+    assert!(err.contains("Unable to find transaction"), "{}", err);
+}
+
+#[test]
+fn script_cache_forgets_the_oldest_checks() {
+    let cache = ScriptCache::new(2);
+    cache.insert_verified(&[check(1, SECRET), check(2, SECRET)]);
+    cache.insert_verified(&[check(2, SECRET), check(3, SECRET)]);
+    assert_eq!(cache.len(), 2);
+    assert!(!cache.contains(&check(1, SECRET).key()));
+    assert!(cache.contains(&check(3, SECRET).key()));
+
+    let mut checks = vec![check(1, SECRET), check(2, SECRET), check(3, SECRET)];
+    cache.retain_unverified(&mut checks);
+    assert_eq!(checks.len(), 1);
+    assert_eq!(checks[0].key(), check(1, SECRET).key());
+
+    let disabled = ScriptCache::new(0);
+    disabled.insert_verified(&[check(1, SECRET)]);
+    assert!(disabled.is_empty());
+}
+
+#[test]
+fn mempool_checks_are_cached_and_reused_by_blocks() {
+    let funding = fund("cached-mempool-checks", 2);
+    let accepted = spend(funding.id(), &[(0, SECRET)]);
+    assert_eq!(accepted.validate_with_unconfirmed(HEIGHT, None, &[]), Ok(0));
+    // Only transactions accepted to the mempool fill the cache.
+    assert!(!SCRIPT_CACHE.contains(&(accepted.wtxid(), 0)));
+
+    // A failing transaction leaves nothing behind.
+    let rejected = spend(funding.id(), &[(1, b"guess")]);
+    assert!(
+        rejected
+            .validate_with_unconfirmed(HEIGHT, None, &[])
+            .is_err()
+    );
+    assert!(!SCRIPT_CACHE.contains(&(rejected.wtxid(), 0)));
+
+    // Once its check is cached, a block skips the input script.
+    SCRIPT_CACHE.insert_verified(&[ScriptCheck::new(
+        (rejected.wtxid(), 0),
+        input_ref(&funding, 1),
+        unlock(b"guess"),
+        hashlock(),
+        Vec::new(),
+        0,
+    )]);
+    assert_eq!(
+        block_with(vec![accepted, rejected.clone()]).validate(HEIGHT, None),
+        Ok(())
+    );
+
+    // Another unlocking script changes the wtxid and misses the cache.
+    let mut malleated = rejected;
+    malleated.inputs[0].unlock_script = unlock(b"other guess");
+    let err = block_with(vec![malleated])
+        .validate(HEIGHT, None)
+        .unwrap_err();
+    assert!(err.contains("Script verification failed"), "{}", err);
+}
+
+#[tokio::test]
+async fn transactions_rejected_by_the_mempool_leave_the_cache_unchanged() {
+    init_db();
+    // A confirmed, non-coinbase output, spendable at any height.
+    let funding = Transaction::new(
+        vec![TxInput::new([0x66; 32], 0)],
+        vec![TxOutput::with_script(VALUE * 100, hashlock()); 2],
+        Some("mempool-cache-funding".to_string()),
+    );
+    confirm(funding.clone());
+    let paying = |output_index: usize, fee: i64| {
+        let input = TxInput {
+            unlock_script: unlock(SECRET),
+            ..TxInput::new(funding.id(), output_index)
+        };
+        Transaction::new(
+            vec![input],
+            vec![TxOutput::with_script(VALUE * 100 - fee, hashlock())],
+            None,
+        )
+    };
+
+    let mut node = Node::new();
+    let free = paying(0, 0);
+    let utxos = node.resolve_spent_utxos(&free).unwrap();
+    let err = node
+        .receive_transaction(MempoolTx::new(free.clone(), utxos))
+        .unwrap_err();
+    assert!(err.contains("below the mempool minimum"), "{}", err);
+    assert!(!SCRIPT_CACHE.contains(&(free.wtxid(), 0)));
+
+    let accepted = paying(1, VALUE);
+    let utxos = node.resolve_spent_utxos(&accepted).unwrap();
+    node.receive_transaction(MempoolTx::new(accepted.clone(), utxos))
+        .unwrap();
+    assert!(SCRIPT_CACHE.contains(&(accepted.wtxid(), 0)));
+}
+
+#[test]
+fn signatures_with_a_torsioned_r_fail_for_any_number_of_workers() {
+    let key = HDKey::new(b"torsion signer");
+    let public_key = key.get_public_key();
+    let signing_bytes = b"spend-torsion".to_vec();
+    let valid = key.sign(&signing_bytes);
+    assert!(verify_signature(&public_key, &signing_bytes, valid).is_ok());
+
+    for torsion in 1..EIGHT_TORSION.len() {
+        let torsioned = with_torsioned_r(&valid, torsion);
+        assert!(verify_signature(&public_key, &signing_bytes, torsioned).is_err());
+
+        // A P2PK spend carrying it fails however the checks are split.
+        let mut unlocking = Script::new();
+        unlocking.push_data(&torsioned.to_bytes());
+        let torsioned_check = ScriptCheck::new(
+            ([0x70; 32], torsion),
+            "torsioned".to_string(),
+            unlocking,
+            Script::new_p2pk(&public_key),
+            signing_bytes.clone(),
+            0,
+        );
+        let checks: Vec<ScriptCheck> = (0..20)
+            .map(|position| signed_check(position, format!("spend-{}", position).as_bytes()))
+            .chain([torsioned_check])
+            .collect();
+        for workers in 1..=4 {
+            let err = run_script_checks(&checks, workers).unwrap_err();
+            assert!(err.contains("torsioned:"), "{} workers: {}", workers, err);
+        }
+    }
+}
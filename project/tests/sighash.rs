@@ -0,0 +1,162 @@
+use project::model::io::{
+    RelativeLock, SIGHASH_ALL, SIGHASH_ANYONECANPAY, SIGHASH_NONE, SIGHASH_SINGLE,
+    parse_sighash_type,
+};
+use project::model::script::{TransactionSignatureChecker, verify_script};
+use project::model::transaction::SighashCache;
+use project::model::{HDKey, Transaction, TxInput, TxOutput};
+
+fn input(prev: u8, sighash_type: u8) -> TxInput {
+    TxInput {
+        sighash_type,
+        ..TxInput::new([prev; 32], 0)
+    }
+}
+
+fn tx(inputs: Vec<TxInput>, values: &[i64]) -> Transaction {
+    let outputs = values
+        .iter()
+        .map(|&v| TxOutput::new(v, "addr".to_string()))
+        .collect();
+    Transaction::new(inputs, outputs, None)
+}
+
+fn message(tx: &Transaction, index: usize) -> Vec<u8> {
+    tx.signing_bytes_for_input(index).unwrap()
+}
+
+#[test]
+fn sighash_all_covers_every_output() {
+    let tx = tx(
+        vec![input(1, SIGHASH_ALL), input(2, SIGHASH_ALL)],
+        &[10, 20],
+    );
+    assert_eq!(message(&tx, 0), tx.signing_bytes());
+
+    let mut changed = tx.clone();
+    changed.outputs[1].value = 21;
+    assert_ne!(message(&tx, 0), message(&changed, 0));
+}
+
+#[test]
+fn sighash_none_leaves_outputs_open() {
+    let tx = tx(vec![input(1, SIGHASH_NONE), input(2, SIGHASH_ALL)], &[10]);
+
+    let mut changed = tx.clone();
+    changed.outputs.push(TxOutput::new(5, "other".to_string()));
+    changed.inputs[1].relative_lock = RelativeLock::Blocks(3).encode();
+    assert_eq!(message(&tx, 0), message(&changed, 0));
+
+    changed.inputs.push(input(3, SIGHASH_ALL));
+    assert_ne!(message(&tx, 0), message(&changed, 0));
+}
+
+#[test]
+fn sighash_single_covers_its_own_output() {
+    let tx = tx(
+        vec![input(1, SIGHASH_ALL), input(2, SIGHASH_SINGLE)],
+        &[10, 20],
+    );
+
+    let mut later = tx.clone();
+    later.outputs[0].value = 11;
+    later.outputs.push(TxOutput::new(5, "other".to_string()));
+    assert_eq!(message(&tx, 1), message(&later, 1));
+
+    let mut own = tx.clone();
+    own.outputs[1].value = 21;
+    assert_ne!(message(&tx, 1), message(&own, 1));
+
+    own.outputs.truncate(1);
+    assert!(own.signing_bytes_for_input(1).is_err());
+}
+
+#[test]
+fn anyonecanpay_lets_others_add_inputs() {
+    let key = HDKey::new(b"sighash test key");
+    let mut tx = tx(vec![input(1, SIGHASH_ALL | SIGHASH_ANYONECANPAY)], &[10]);
+    let signature = key.sign(&message(&tx, 0)).to_bytes();
+    tx.inputs[0].signature = hex::encode(signature);
+    tx.inputs[0].public_key = hex::encode(key.get_public_key().as_bytes());
+
+    // A second contributor adds an input in front; the first signature still holds.
+    tx.inputs.insert(0, input(2, SIGHASH_ALL));
+    let locking = TxOutput::new(10, key.get_address())
+        .locking_script()
+        .unwrap();
+    let signing_bytes = message(&tx, 1);
+    let checker = TransactionSignatureChecker::new(&signing_bytes);
+    assert!(
+        verify_script(
+            &tx.inputs[1].unlocking_script().unwrap(),
+            &locking,
+            &checker
+        )
+        .is_ok()
+    );
+
+    // Outputs are still covered.
+    tx.outputs[0].value = 9;
+    let signing_bytes = message(&tx, 1);
+    let checker = TransactionSignatureChecker::new(&signing_bytes);
+    assert!(
+        verify_script(
+            &tx.inputs[1].unlocking_script().unwrap(),
+            &locking,
+            &checker
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn sighash_type_is_committed() {
+    let tx = tx(vec![input(1, SIGHASH_ALL)], &[10]);
+    let mut changed = tx.clone();
+    changed.inputs[0].sighash_type = SIGHASH_ALL | SIGHASH_ANYONECANPAY;
+    assert_ne!(tx.id(), changed.id());
+    assert_ne!(message(&tx, 0), message(&changed, 0));
+
+    changed.inputs[0].sighash_type = 0x04;
+    assert!(changed.signing_bytes_for_input(0).is_err());
+    assert!(tx.signing_bytes_for_input(1).is_err());
+}
+
+#[test]
+fn sighash_types_parse_by_name() {
+    assert_eq!(parse_sighash_type("all").unwrap(), SIGHASH_ALL);
+    assert_eq!(parse_sighash_type("None").unwrap(), SIGHASH_NONE);
+    assert_eq!(
+        parse_sighash_type("single|anyonecanpay").unwrap(),
+        SIGHASH_SINGLE | SIGHASH_ANYONECANPAY
+    );
+    assert!(parse_sighash_type("anyonecanpay").is_err());
+    assert!(parse_sighash_type("all|everyone").is_err());
+}
+
+#[test]
+fn shared_sighash_cache_matches_each_input_alone() {
+    let tx = tx(
+        vec![
+            input(1, SIGHASH_ALL),
+            input(2, SIGHASH_SINGLE | SIGHASH_ANYONECANPAY),
+            input(3, SIGHASH_NONE),
+            input(4, SIGHASH_ALL),
+            input(5, SIGHASH_SINGLE),
+        ],
+        &[10, 20, 30],
+    );
+    let mut cache = SighashCache::new(&tx);
+    for index in [4, 0, 1, 3, 2, 1] {
+        let shared = cache.signing_bytes_for_input(index);
+        assert_eq!(
+            shared.map(|bytes| bytes.to_vec()),
+            tx.signing_bytes_for_input(index)
+        );
+    }
+    assert_eq!(
+        &*cache.signing_bytes_for_input(0).unwrap(),
+        tx.signing_bytes().as_slice()
+    );
+    assert!(cache.signing_bytes_for_input(5).is_err());
+}
@@ -0,0 +1,74 @@
+use chrono::DateTime;
+
+use project::model::io::{RELATIVE_LOCK_TIME_FLAG, RelativeLock};
+use project::model::transaction::LOCKTIME_THRESHOLD;
+use project::model::{Transaction, TxInput, TxOutput};
+
+fn input(relative_lock: u32) -> TxInput {
+    TxInput {
+        relative_lock,
+        ..TxInput::new([3; 32], 0)
+    }
+}
+
+fn tx_with_lock_time(lock_time: u64) -> Transaction {
+    let mut tx = Transaction::new(
+        vec![input(0)],
+        vec![TxOutput::new(10, "addr".to_string())],
+        None,
+    );
+    tx.lock_time = lock_time;
+    tx
+}
+
+#[test]
+fn unlocked_transactions_are_always_final() {
+    let tx = tx_with_lock_time(0);
+    assert!(tx.is_final(0, None));
+}
+
+#[test]
+fn height_lock_is_final_from_its_height() {
+    let tx = tx_with_lock_time(100);
+
+    assert!(!tx.is_final(99, None));
+    assert!(tx.is_final(100, None));
+    assert!(tx.is_final(101, None));
+}
+
+#[test]
+fn time_lock_is_checked_against_median_time_past() {
+    let unlock = LOCKTIME_THRESHOLD + 1_000;
+    let tx = tx_with_lock_time(unlock);
+    let at = |secs: u64| DateTime::from_timestamp(secs as i64, 0).map(|dt| dt.naive_utc());
+
+    // Heights never satisfy a time lock.
+    assert!(!tx.is_final(u64::MAX, None));
+    assert!(!tx.is_final(u64::MAX, at(unlock - 1)));
+    assert!(tx.is_final(0, at(unlock)));
+}
+
+#[test]
+fn relative_locks_round_trip() {
+    assert_eq!(input(0).relative_lock(), None);
+    for lock in [RelativeLock::Blocks(10), RelativeLock::Seconds(3_600)] {
+        assert_eq!(input(lock.encode()).relative_lock(), Some(lock));
+    }
+    assert_eq!(
+        RelativeLock::Seconds(60).encode(),
+        60 | RELATIVE_LOCK_TIME_FLAG
+    );
+}
+
+#[test]
+fn locks_are_covered_by_signatures() {
+    let tx = tx_with_lock_time(0);
+    let mut locked = tx.clone();
+    locked.lock_time = 50;
+    assert_ne!(tx.signing_bytes(), locked.signing_bytes());
+
+    let mut relative = tx.clone();
+    relative.inputs[0].relative_lock = RelativeLock::Blocks(5).encode();
+    assert_ne!(tx.signing_bytes(), relative.signing_bytes());
+    assert_eq!(relative.inputs[0].get_partial().relative_lock, 5);
+}
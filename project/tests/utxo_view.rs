@@ -0,0 +1,187 @@
+use primitive_types::U256;
+
+use project::db::db::init_db;
+use project::db::repository::LedgerRepository;
+use project::model::miner::{MiningSnapshot, validate_template};
+use project::model::utxo_view::{LedgerView, UtxoOverlay, UtxoView};
+use project::model::{Block, Script, Transaction, TxInput, TxOutput, block::BlockHeader};
+use project::security_utils::sha256;
+use project::utils::get_current_timestamp;
+
+const SECRET: &[u8] = b"secret";
+const VALUE: i64 = 1_000;
+/// Height of the validated blocks, past the maturity of the funding transactions.
+const HEIGHT: u64 = 200;
+
+fn hashlock() -> Script {
+    Script::new_hashlock(&sha256(SECRET))
+}
+
+fn block_after(prev_block_hash: [u8; 32], transactions: Vec<Transaction>) -> Block {
+    let mut block = Block::new(prev_block_hash, U256::MAX, BlockHeader::INITIAL_VERSION);
+    block.transactions = transactions;
+    block.evaluate_merkle_root();
+    block
+}
+
+/// A transaction without inputs paying `outputs` hash-locked outputs.
+fn mint(label: &str, outputs: usize) -> Transaction {
+    Transaction::new(
+        Vec::new(),
+        vec![TxOutput::with_script(VALUE, hashlock()); outputs],
+        Some(label.to_string()),
+    )
+}
+
+/// Applies a block confirming `mint(label, outputs)` at height 0 to the ledger.
+fn fund(label: &str, outputs: usize) -> (Block, Transaction) {
+    init_db();
+    let funding = mint(label, outputs);
+    let block = block_after([0; 32], vec![funding.clone()]);
+    LedgerRepository::new().apply_block(block.clone()).unwrap();
+    (block, funding)
+}
+
+fn spend(prev_tx_id: [u8; 32], output_index: usize) -> Transaction {
+    let mut unlock_script = Script::new();
+    unlock_script.push_data(SECRET);
+    let input = TxInput {
+        unlock_script,
+        ..TxInput::new(prev_tx_id, output_index)
+    };
+    Transaction::new(
+        vec![input],
+        vec![TxOutput::with_script(VALUE, hashlock())],
+        None,
+    )
+}
+
+fn block_with(transactions: Vec<Transaction>) -> Block {
+    let mut all = vec![Transaction::new_coinbase("miner".to_string(), HEIGHT, 0)];
+    all.extend(transactions);
+    block_after([1; 32], all)
+}
+
+#[test]
+fn overlay_disconnects_and_reconnects_blocks_in_memory() {
+    let (funding_block, funding) = fund("overlay-disconnect", 2);
+    let spent = (funding.id(), 0);
+    let spender = spend(funding.id(), 0);
+    let main = block_after(
+        funding_block.id(),
+        vec![mint("overlay-disconnect-reward", 1), spender.clone()],
+    );
+    LedgerRepository::new().apply_block(main.clone()).unwrap();
+    assert!(LedgerView.coin(&spent).unwrap().is_none());
+
+    let mut view = UtxoOverlay::new(&LedgerView);
+    view.disconnect_block(&main, 1).unwrap();
+    let coin = view.coin(&spent).unwrap().expect("spent output restored");
+    assert_eq!(coin.height, Some(0));
+    assert!(coin.is_coinbase);
+    assert!(view.coin(&(spender.id(), 0)).unwrap().is_none());
+    assert!(view.transaction(&spender.id()).unwrap().is_none());
+    assert!(view.block_time(1).unwrap().is_none());
+
+    // The ledger is left as it was.
+    assert!(LedgerView.coin(&spent).unwrap().is_none());
+    assert!(LedgerView.coin(&(spender.id(), 0)).unwrap().is_some());
+    assert!(LedgerView.block_time(1).unwrap().is_some());
+
+    view.connect_block(&main, 1).unwrap();
+    assert!(view.coin(&spent).unwrap().is_none());
+    assert_eq!(
+        view.coin(&(spender.id(), 0)).unwrap().unwrap().height,
+        Some(1)
+    );
+    assert_eq!(view.block_time(1).unwrap(), Some(main.header.timestamp));
+}
+
+#[test]
+fn fork_blocks_validate_against_the_chain_rewound_to_the_fork_point() {
+    let (funding_block, funding) = fund("overlay-fork", 1);
+    let main = block_after(
+        funding_block.id(),
+        vec![mint("overlay-fork-reward", 1), spend(funding.id(), 0)],
+    );
+    LedgerRepository::new().apply_block(main.clone()).unwrap();
+
+    // A competing block spending the same output on another branch.
+    let mut rival = spend(funding.id(), 0);
+    rival.date = get_current_timestamp() + chrono::Duration::seconds(1);
+    let fork = block_with(vec![rival]);
+    let err = fork.validate(HEIGHT, None).unwrap_err();
+    assert!(err.contains("not a valid UTXO"), "{}", err);
+
+    let mut view = UtxoOverlay::new(&LedgerView);
+    view.disconnect_block(&main, 1).unwrap();
+    assert_eq!(fork.validate_in(&view, HEIGHT, None, true), Ok(()));
+}
+
+#[test]
+fn mempool_packages_validate_against_stacked_overlays() {
+    let (_, funding) = fund("overlay-package", 1);
+    let parent = spend(funding.id(), 0);
+    let child = spend(parent.id(), 0);
+    let err = child.validate(HEIGHT, None).unwrap_err();
+    assert!(err.contains("Unable to find transaction"), "{}", err);
+
+    let mut package = UtxoOverlay::new(&LedgerView);
+    package.connect_transaction(&parent, None).unwrap();
+    assert_eq!(child.validate_in(&package, HEIGHT, None, &[], true), Ok(0));
+
+    // Changes to a stacked overlay stay out of the one below it.
+    let mut template = UtxoOverlay::new(&package);
+    template.connect_transaction(&child, None).unwrap();
+    let err = child
+        .validate_in(&template, HEIGHT, None, &[], true)
+        .unwrap_err();
+    assert!(err.contains("not a valid UTXO"), "{}", err);
+    assert!(package.coin(&(parent.id(), 0)).unwrap().is_some());
+}
+
+#[test]
+fn templates_validate_transactions_spending_their_mempool_parents() {
+    let (_, funding) = fund("overlay-template", 1);
+    let mut parent = spend(funding.id(), 0);
+    parent.outputs[0].value -= 10;
+    let mut child = spend(parent.id(), 0);
+    child.outputs[0].value = parent.outputs[0].value - 30;
+    // Spends the parent's input again, taking its own child down with it.
+    let mut rival = spend(funding.id(), 0);
+    rival.date = get_current_timestamp() + chrono::Duration::seconds(1);
+    let rival_child = spend(rival.id(), 0);
+
+    let snapshot = MiningSnapshot {
+        mempool: Vec::new(),
+        previous_hash: [0; 32],
+        height: HEIGHT,
+        median_time_past: None,
+        target: U256::MAX,
+        version: BlockHeader::INITIAL_VERSION,
+        receive_addr: "miner".to_string(),
+    };
+    let (txs, fees) = validate_template(
+        vec![parent.clone(), child.clone(), rival, rival_child],
+        &snapshot,
+    );
+    let ids: Vec<_> = txs.iter().map(|tx| tx.id()).collect();
+    assert_eq!(ids, vec![parent.id(), child.id()]);
+    assert_eq!(fees, 40);
+}
+
+#[test]
+fn block_transactions_spend_outputs_of_those_before_them() {
+    let (_, funding) = fund("in-block-chain", 1);
+    let parent = spend(funding.id(), 0);
+    let child = spend(parent.id(), 0);
+
+    let block = block_with(vec![parent.clone(), child.clone()]);
+    assert_eq!(block.validate_in(&LedgerView, HEIGHT, None, true), Ok(()));
+
+    let reversed = block_with(vec![child, parent]);
+    let err = reversed
+        .validate_in(&LedgerView, HEIGHT, None, true)
+        .unwrap_err();
+    assert!(err.contains("Unable to find transaction"), "{}", err);
+}
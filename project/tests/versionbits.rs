@@ -0,0 +1,126 @@
+use chrono::{DateTime, Duration};
+use primitive_types::U256;
+
+use project::model::versionbits::{
+    Deployment, ThresholdState, VERSIONBITS_TOP_BITS, compute_block_version, deployment_status,
+};
+use project::model::{Block, block::BlockHeader};
+
+const BASE_TIME: i64 = 1_800_000_000;
+const WINDOW: usize = 10;
+const THRESHOLD: usize = 8;
+
+fn deployment(start_time: i64, timeout: i64) -> Deployment {
+    Deployment::new("testdummy", 5, start_time, timeout).unwrap()
+}
+
+/// A chain whose block `i` has version `versions[i]` and is mined a minute after block `i - 1`.
+fn chain(versions: &[u32]) -> Vec<Block> {
+    let base = DateTime::from_timestamp(BASE_TIME, 0).unwrap().naive_utc();
+    versions
+        .iter()
+        .enumerate()
+        .map(|(i, &version)| Block {
+            header: BlockHeader {
+                version,
+                prev_block_hash: [0; 32],
+                merkle_root: [0; 32],
+                nonce: 0,
+                timestamp: base + Duration::minutes(i as i64),
+                target: U256::MAX,
+            },
+            transactions: Vec::new(),
+        })
+        .collect()
+}
+
+fn signalling(deployment: &Deployment) -> u32 {
+    VERSIONBITS_TOP_BITS | deployment.mask()
+}
+
+fn state_at(chain: &[Block], height: usize, deployment: &Deployment) -> ThresholdState {
+    deployment_status(chain, height, deployment, WINDOW, THRESHOLD).state
+}
+
+#[test]
+fn deployment_activates_after_a_signalling_period() {
+    let deployment = deployment(BASE_TIME, i64::MAX);
+    let mut versions = vec![BlockHeader::INITIAL_VERSION; WINDOW];
+    versions.extend(vec![signalling(&deployment); WINDOW]);
+    versions.extend(vec![VERSIONBITS_TOP_BITS; 2 * WINDOW]);
+    let chain = chain(&versions);
+
+    assert_eq!(state_at(&chain, 5, &deployment), ThresholdState::Defined);
+    assert_eq!(state_at(&chain, 10, &deployment), ThresholdState::Started);
+    assert_eq!(state_at(&chain, 19, &deployment), ThresholdState::Started);
+    assert_eq!(state_at(&chain, 20, &deployment), ThresholdState::LockedIn);
+    assert_eq!(state_at(&chain, 30, &deployment), ThresholdState::Active);
+
+    let status = deployment_status(&chain, 35, &deployment, WINDOW, THRESHOLD);
+    assert_eq!(status.state, ThresholdState::Active);
+    assert_eq!(status.since_height, 30);
+    assert_eq!(status.period_start, 30);
+    assert_eq!(status.elapsed, 5);
+    assert_eq!(status.signalled, 0);
+}
+
+#[test]
+fn lock_in_needs_the_threshold_within_one_period() {
+    let deployment = deployment(BASE_TIME, i64::MAX);
+    let mut versions = vec![BlockHeader::INITIAL_VERSION; WINDOW];
+    versions.extend(vec![signalling(&deployment); THRESHOLD - 1]);
+    versions.extend(vec![VERSIONBITS_TOP_BITS; WINDOW - THRESHOLD + 1]);
+    // Right bit without the version-bits top bits does not count.
+    versions.extend(vec![deployment.mask(); WINDOW]);
+    let chain = chain(&versions);
+
+    assert_eq!(state_at(&chain, 20, &deployment), ThresholdState::Started);
+    assert_eq!(state_at(&chain, 30, &deployment), ThresholdState::Started);
+    let status = deployment_status(&chain, 17, &deployment, WINDOW, THRESHOLD);
+    assert_eq!(status.signalled, (THRESHOLD - 1) as u64);
+    assert_eq!(status.elapsed, 7);
+}
+
+#[test]
+fn deployment_fails_once_timed_out() {
+    // Median-time-past reaches the timeout at the second boundary.
+    let timeout = BASE_TIME + 10 * 60;
+    let deployment = deployment(BASE_TIME, timeout);
+    let mut versions = vec![BlockHeader::INITIAL_VERSION; WINDOW];
+    versions.extend(vec![VERSIONBITS_TOP_BITS; WINDOW]);
+    versions.extend(vec![signalling(&deployment); WINDOW]);
+    let chain = chain(&versions);
+
+    assert_eq!(state_at(&chain, 10, &deployment), ThresholdState::Started);
+    assert_eq!(state_at(&chain, 20, &deployment), ThresholdState::Failed);
+    assert_eq!(state_at(&chain, 30, &deployment), ThresholdState::Failed);
+}
+
+#[test]
+fn block_version_signals_pending_deployments() {
+    let deployment = deployment(BASE_TIME, i64::MAX);
+    let later = Deployment::new("later", 7, i64::MAX - 1, i64::MAX).unwrap();
+    let deployments = [deployment.clone(), later];
+    let mut versions = vec![BlockHeader::INITIAL_VERSION; WINDOW];
+    versions.extend(vec![signalling(&deployment); 2 * WINDOW]);
+    let chain = chain(&versions);
+    let version_at =
+        |height| compute_block_version(&chain, height, &deployments, WINDOW, THRESHOLD);
+
+    assert_eq!(version_at(5), VERSIONBITS_TOP_BITS);
+    assert_eq!(version_at(10), signalling(&deployment));
+    assert_eq!(version_at(20), signalling(&deployment));
+    assert_eq!(version_at(30), VERSIONBITS_TOP_BITS);
+}
+
+#[test]
+fn deployments_parse_from_config() {
+    assert_eq!(
+        Deployment::parse("taproot:2:100:200").unwrap(),
+        Deployment::new("taproot", 2, 100, 200).unwrap()
+    );
+    assert!(Deployment::parse("taproot:2:100").is_err());
+    assert!(Deployment::parse("taproot:29:100:200").is_err());
+    assert!(Deployment::parse("taproot:2:200:100").is_err());
+    assert!(Deployment::parse(":2:100:200").is_err());
+}